@@ -0,0 +1,182 @@
+//! Whole-disk data distribution map
+//! Splits a device into equal regions and samples each one, recording how
+//! much of it is zero, its Shannon entropy and any file signatures seen. A
+//! map taken after an erase shows whether the whole surface was covered.
+
+use std::path::Path;
+
+use crate::analysis::{AnalysisError, forensics::FILE_SIGNATURES, sectors::SectorReader};
+
+const SECTOR_SIZE: u64 = 512;
+
+/// How the map is sampled
+#[derive(Debug, Clone, Copy)]
+pub struct MapConfig {
+    pub regions: usize,
+    /// Samples read per region, spread evenly across it
+    pub samples_per_region: usize,
+    /// Bytes per sample; rounded up to whole sectors
+    pub sample_size: usize,
+}
+
+impl Default for MapConfig {
+    fn default() -> Self {
+        MapConfig { regions: 256, samples_per_region: 4, sample_size: 16 * 1024 }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RegionClass {
+    Zero,
+    // One repeated byte value other than zero, e.g. an 0xFF fill
+    Uniform,
+    LowEntropy,
+    Data,
+    // Random, encrypted or compressed
+    HighEntropy,
+}
+
+impl std::fmt::Display for RegionClass {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RegionClass::Zero => write!(f, "Zero"),
+            RegionClass::Uniform => write!(f, "Uniform fill"),
+            RegionClass::LowEntropy => write!(f, "Low entropy"),
+            RegionClass::Data => write!(f, "Data"),
+            RegionClass::HighEntropy => write!(f, "High entropy"),
+        }
+    }
+}
+
+/// Statistics over the samples of one region
+#[derive(Debug, Clone)]
+pub struct RegionStats {
+    pub offset: u64,
+    pub len: u64,
+    pub bytes_sampled: u64,
+    /// Share of sampled bytes that are zero
+    pub zero_ratio: f32,
+    /// Shannon entropy in bits per byte, 0 to 8
+    pub entropy: f32,
+    /// The byte value when every sampled byte is the same
+    pub fill: Option<u8>,
+    /// File types whose headers start a sampled sector
+    pub signatures: Vec<&'static str>,
+}
+
+impl RegionStats {
+    pub fn class(&self) -> RegionClass {
+        match self.fill {
+            Some(0) => RegionClass::Zero,
+            Some(_) => RegionClass::Uniform,
+            None if self.entropy < 3.0 => RegionClass::LowEntropy,
+            None if self.entropy < 7.5 => RegionClass::Data,
+            None => RegionClass::HighEntropy,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct DataMap {
+    pub size: u64,
+    pub config: MapConfig,
+    pub regions: Vec<RegionStats>,
+}
+
+impl DataMap {
+    /// Number of regions in `class`
+    pub fn count(&self, class: RegionClass) -> usize {
+        self.regions.iter().filter(|region| region.class() == class).count()
+    }
+
+    pub fn regions_with_signatures(&self) -> usize {
+        self.regions.iter().filter(|region| !region.signatures.is_empty()).count()
+    }
+
+    pub fn bytes_sampled(&self) -> u64 {
+        self.regions.iter().map(|region| region.bytes_sampled).sum()
+    }
+}
+
+/// Samples the device region by region. `progress` gets the number of
+/// regions done and the total after each one.
+pub fn scan_data_map(
+    path: &Path,
+    config: MapConfig,
+    progress: &mut dyn FnMut(usize, usize),
+) -> Result<DataMap, AnalysisError> {
+    // Read past the page cache, so a map taken after an erase shows what the
+    // device returns and not what was cached before it
+    let reader = SectorReader::open(path, SECTOR_SIZE)?;
+    let total_sectors = reader.total_sectors();
+    if total_sectors == 0 {
+        return Err(AnalysisError::TooSmall(0));
+    }
+    let regions = config.regions.clamp(1, total_sectors as usize);
+    let sample_sectors = (config.sample_size as u64).div_ceil(SECTOR_SIZE).max(1);
+    let samples = config.samples_per_region.max(1) as u64;
+
+    let mut stats = Vec::with_capacity(regions);
+    for index in 0..regions {
+        let first = total_sectors * index as u64 / regions as u64;
+        let end = total_sectors * (index as u64 + 1) / regions as u64;
+        let sectors = end - first;
+
+        let mut histogram = [0u64; 256];
+        let mut signatures: Vec<&'static str> = Vec::new();
+        let mut sampled = 0;
+        // First and last sample touch the region's ends, the rest spread
+        // between; regions too small for that are read once
+        let span = sectors.saturating_sub(sample_sectors);
+        let mut last_lba = None;
+        for sample in 0..samples {
+            let lba = first + if samples > 1 { span * sample / (samples - 1) } else { 0 };
+            if last_lba == Some(lba) {
+                continue;
+            }
+            last_lba = Some(lba);
+            let page = reader.read_page(lba, sample_sectors.min(end - lba))?;
+            for &byte in &page.data {
+                histogram[byte as usize] += 1;
+            }
+            for sector in page.data.chunks(SECTOR_SIZE as usize) {
+                for signature in FILE_SIGNATURES {
+                    let magic = sector.get(signature.offset..signature.offset + signature.magic.len());
+                    if magic == Some(signature.magic) && !signatures.contains(&signature.name) {
+                        signatures.push(signature.name);
+                    }
+                }
+            }
+            sampled += page.data.len() as u64;
+        }
+
+        stats.push(RegionStats {
+            offset: first * SECTOR_SIZE,
+            len: sectors * SECTOR_SIZE,
+            bytes_sampled: sampled,
+            zero_ratio: histogram[0] as f32 / sampled.max(1) as f32,
+            entropy: entropy(&histogram, sampled),
+            fill: histogram.iter().position(|&count| count == sampled).map(|value| value as u8),
+            signatures,
+        });
+        progress(index + 1, regions);
+    }
+
+    Ok(DataMap { size: total_sectors * SECTOR_SIZE, config, regions: stats })
+}
+
+fn entropy(histogram: &[u64; 256], total: u64) -> f32 {
+    if total == 0 {
+        return 0.0;
+    }
+    let total = total as f64;
+    let bits: f64 = histogram
+        .iter()
+        .filter(|&&count| count > 0)
+        .map(|&count| {
+            let p = count as f64 / total;
+            -p * p.log2()
+        })
+        .sum();
+    bits as f32
+}
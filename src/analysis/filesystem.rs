@@ -0,0 +1,446 @@
+//! Filesystem detection
+//! Identifies what each partition holds by reading its superblock directly,
+//! and pulls out what the format records about it: label, UUID, space use
+//! and when it was last mounted.
+
+use std::{
+    fs::File,
+    io::{Read, Seek, SeekFrom},
+    path::Path,
+};
+
+use serde::Serialize;
+use time::OffsetDateTime;
+
+use crate::analysis::{
+    AnalysisError,
+    partition::{PartitionLayout, PartitionRole},
+};
+
+// Enough of a partition to reach every superblock below; Btrfs keeps its
+// first copy at 64 KiB
+const PROBE_SIZE: usize = 0x10000 + 4096;
+
+// Filesystem and volume signatures relative to the start of a partition:
+// (label, magic offset, magic, structure offset, structure length)
+pub(crate) const SIGNATURES: &[(&str, u64, &[u8], u64, u64)] = &[
+    ("ext2/3/4 superblock", 1080, &[0x53, 0xef], 1024, 1024),
+    ("NTFS boot sector", 3, b"NTFS    ", 0, 512),
+    ("exFAT boot sector", 3, b"EXFAT   ", 0, 512),
+    ("FAT32 boot sector", 82, b"FAT32   ", 0, 512),
+    ("FAT12/16 boot sector", 54, b"FAT1", 0, 512),
+    ("XFS superblock", 0, b"XFSB", 0, 512),
+    ("Btrfs superblock", 65600, b"_BHRfS_M", 65536, 4096),
+    ("APFS container superblock", 32, b"NXSB", 0, 4096),
+    ("HFS+ volume header", 1024, b"H+", 1024, 512),
+    ("LUKS header", 0, b"LUKS\xba\xbe", 0, 512),
+    ("LVM2 label", 536, b"LVM2 001", 512, 512),
+    ("Linux swap signature", 4086, b"SWAPSPACE2", 4086, 10),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum FilesystemKind {
+    Ext2,
+    Ext3,
+    Ext4,
+    Xfs,
+    Btrfs,
+    Ntfs,
+    Fat12,
+    Fat16,
+    Fat32,
+    ExFat,
+    Apfs,
+    HfsPlus,
+    Luks,
+    LvmPhysicalVolume,
+    Swap,
+}
+
+impl std::fmt::Display for FilesystemKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FilesystemKind::Ext2 => write!(f, "ext2"),
+            FilesystemKind::Ext3 => write!(f, "ext3"),
+            FilesystemKind::Ext4 => write!(f, "ext4"),
+            FilesystemKind::Xfs => write!(f, "XFS"),
+            FilesystemKind::Btrfs => write!(f, "Btrfs"),
+            FilesystemKind::Ntfs => write!(f, "NTFS"),
+            FilesystemKind::Fat12 => write!(f, "FAT12"),
+            FilesystemKind::Fat16 => write!(f, "FAT16"),
+            FilesystemKind::Fat32 => write!(f, "FAT32"),
+            FilesystemKind::ExFat => write!(f, "exFAT"),
+            FilesystemKind::Apfs => write!(f, "APFS"),
+            FilesystemKind::HfsPlus => write!(f, "HFS+"),
+            FilesystemKind::Luks => write!(f, "LUKS"),
+            FilesystemKind::LvmPhysicalVolume => write!(f, "LVM physical volume"),
+            FilesystemKind::Swap => write!(f, "Linux swap"),
+        }
+    }
+}
+
+/// What a superblock says about its filesystem. Fields the format does not
+/// record, or that need more than the superblock to work out, are None.
+#[derive(Debug, Clone)]
+pub struct FilesystemInfo {
+    pub kind: FilesystemKind,
+    pub label: Option<String>,
+    pub uuid: Option<String>,
+    /// Bytes the filesystem spans
+    pub size: Option<u64>,
+    pub used: Option<u64>,
+    pub free: Option<u64>,
+    pub last_mounted: Option<OffsetDateTime>,
+    pub last_mount_point: Option<String>,
+}
+
+impl FilesystemInfo {
+    fn new(kind: FilesystemKind) -> Self {
+        FilesystemInfo {
+            kind,
+            label: None,
+            uuid: None,
+            size: None,
+            used: None,
+            free: None,
+            last_mounted: None,
+            last_mount_point: None,
+        }
+    }
+
+    // Fills whichever of used and free is missing from the other
+    fn with_space(mut self, size: u64, free: Option<u64>) -> Self {
+        self.size = Some(size);
+        self.free = free.map(|free| free.min(size));
+        self.used = self.free.map(|free| size - free);
+        self
+    }
+}
+
+/// A partition, or the whole disk when it has no partition table
+#[derive(Debug, Clone)]
+pub struct Volume {
+    /// None for the whole disk
+    pub partition: Option<u32>,
+    pub offset: u64,
+    pub size: u64,
+    pub filesystem: Option<FilesystemInfo>,
+}
+
+/// Identifies the filesystem on every partition in `layout`, or on the
+/// whole device when there are none. Extended partition containers are
+/// skipped; their logical partitions are listed instead.
+pub fn scan_filesystems(path: &Path, layout: &PartitionLayout) -> Result<Vec<Volume>, AnalysisError> {
+    let mut file = File::open(path)?;
+    let disk_size = layout.total_sectors * layout.sector_size;
+    let mut volumes: Vec<Volume> = layout
+        .partitions
+        .iter()
+        .filter(|partition| partition.role != PartitionRole::Extended)
+        .map(|partition| Volume {
+            partition: Some(partition.number),
+            offset: partition.first_lba * layout.sector_size,
+            size: partition.sectors() * layout.sector_size,
+            filesystem: None,
+        })
+        .collect();
+    if volumes.is_empty() {
+        volumes.push(Volume { partition: None, offset: 0, size: disk_size, filesystem: None });
+    }
+    for volume in &mut volumes {
+        // A partition past the end of the disk is reported by the layout
+        if volume.offset < disk_size {
+            let size = volume.size.min(disk_size - volume.offset);
+            volume.filesystem = detect_filesystem(&mut file, volume.offset, size)?;
+        }
+    }
+    Ok(volumes)
+}
+
+/// Identifies the filesystem in the `size` bytes at `offset`
+pub fn detect_filesystem<R: Read + Seek>(
+    reader: &mut R,
+    offset: u64,
+    size: u64,
+) -> Result<Option<FilesystemInfo>, AnalysisError> {
+    let mut data = vec![0u8; PROBE_SIZE.min(size as usize)];
+    reader.seek(SeekFrom::Start(offset))?;
+    reader.read_exact(&mut data)?;
+    // Short volumes leave the later probes reading zeros
+    data.resize(PROBE_SIZE, 0);
+    let probe = Probe(&data);
+
+    // Containers first: their payload may look like anything
+    let parsers: [fn(Probe) -> Option<FilesystemInfo>; 12] = [
+        luks, lvm, swap, btrfs, xfs, apfs, ext, hfs_plus, ntfs, exfat, fat, fat_legacy,
+    ];
+    Ok(parsers.iter().find_map(|parse| parse(probe)))
+}
+
+// Little- and big-endian field access to a buffer read from disk
+#[derive(Clone, Copy)]
+pub(crate) struct Probe<'a>(pub(crate) &'a [u8]);
+
+impl Probe<'_> {
+    pub(crate) fn bytes(&self, offset: usize, len: usize) -> &[u8] {
+        &self.0[offset..offset + len]
+    }
+
+    pub(crate) fn le16(&self, offset: usize) -> u16 {
+        u16::from_le_bytes(self.bytes(offset, 2).try_into().unwrap())
+    }
+
+    pub(crate) fn le32(&self, offset: usize) -> u32 {
+        u32::from_le_bytes(self.bytes(offset, 4).try_into().unwrap())
+    }
+
+    pub(crate) fn le64(&self, offset: usize) -> u64 {
+        u64::from_le_bytes(self.bytes(offset, 8).try_into().unwrap())
+    }
+
+    pub(crate) fn be16(&self, offset: usize) -> u16 {
+        u16::from_be_bytes(self.bytes(offset, 2).try_into().unwrap())
+    }
+
+    pub(crate) fn be32(&self, offset: usize) -> u32 {
+        u32::from_be_bytes(self.bytes(offset, 4).try_into().unwrap())
+    }
+
+    fn be64(&self, offset: usize) -> u64 {
+        u64::from_be_bytes(self.bytes(offset, 8).try_into().unwrap())
+    }
+
+    // NUL- or space-padded string, None when empty
+    fn text(&self, offset: usize, len: usize) -> Option<String> {
+        let bytes = self.bytes(offset, len);
+        let end = bytes.iter().position(|&b| b == 0).unwrap_or(len);
+        let text = String::from_utf8_lossy(&bytes[..end]).trim().to_string();
+        (!text.is_empty()).then_some(text)
+    }
+
+    // Standard 8-4-4-4-12 form of 16 bytes stored in order
+    fn uuid(&self, offset: usize) -> Option<String> {
+        let b = self.bytes(offset, 16);
+        if b.iter().all(|&b| b == 0) {
+            return None;
+        }
+        Some(format!(
+            "{}-{}-{}-{}-{}",
+            hex::encode(&b[0..4]),
+            hex::encode(&b[4..6]),
+            hex::encode(&b[6..8]),
+            hex::encode(&b[8..10]),
+            hex::encode(&b[10..16])
+        ))
+    }
+}
+
+fn luks(p: Probe) -> Option<FilesystemInfo> {
+    if p.bytes(0, 6) != b"LUKS\xba\xbe" {
+        return None;
+    }
+    let mut info = FilesystemInfo::new(FilesystemKind::Luks);
+    info.uuid = p.text(168, 40);
+    // Only LUKS2 has a label
+    if p.be16(6) == 2 {
+        info.label = p.text(24, 48);
+    }
+    Some(info)
+}
+
+fn lvm(p: Probe) -> Option<FilesystemInfo> {
+    if p.bytes(512, 8) != b"LABELONE" || p.bytes(536, 8) != b"LVM2 001" {
+        return None;
+    }
+    let header = 512 + p.le32(532) as usize;
+    let mut info = FilesystemInfo::new(FilesystemKind::LvmPhysicalVolume);
+    if header + 40 <= PROBE_SIZE {
+        // Printed the way pvdisplay does: 6-4-4-4-4-4-6
+        let id = String::from_utf8_lossy(p.bytes(header, 32)).to_string();
+        let mut parts = Vec::new();
+        let mut rest = id.as_str();
+        for len in [6, 4, 4, 4, 4, 4, 6] {
+            let (part, tail) = rest.split_at(len.min(rest.len()));
+            parts.push(part);
+            rest = tail;
+        }
+        info.uuid = Some(parts.join("-"));
+        info.size = Some(p.le64(header + 32));
+    }
+    Some(info)
+}
+
+fn swap(p: Probe) -> Option<FilesystemInfo> {
+    if p.bytes(4086, 10) != b"SWAPSPACE2" {
+        return None;
+    }
+    let mut info = FilesystemInfo::new(FilesystemKind::Swap);
+    info.uuid = p.uuid(1036);
+    info.label = p.text(1052, 16);
+    info.size = Some((p.le32(1028) as u64 + 1) * 4096);
+    Some(info)
+}
+
+fn btrfs(p: Probe) -> Option<FilesystemInfo> {
+    let sb = 0x10000;
+    if p.bytes(sb + 0x40, 8) != b"_BHRfS_M" {
+        return None;
+    }
+    let total = p.le64(sb + 0x70);
+    let used = p.le64(sb + 0x78);
+    let mut info = FilesystemInfo::new(FilesystemKind::Btrfs).with_space(total, Some(total.saturating_sub(used)));
+    info.uuid = p.uuid(sb + 0x20);
+    info.label = p.text(sb + 0x12b, 256);
+    Some(info)
+}
+
+fn xfs(p: Probe) -> Option<FilesystemInfo> {
+    if p.bytes(0, 4) != b"XFSB" {
+        return None;
+    }
+    let block_size = p.be32(4) as u64;
+    let blocks = p.be64(8);
+    let free = p.be64(144);
+    let mut info = FilesystemInfo::new(FilesystemKind::Xfs).with_space(blocks.saturating_mul(block_size), Some(free.saturating_mul(block_size)));
+    info.uuid = p.uuid(32);
+    info.label = p.text(108, 12);
+    Some(info)
+}
+
+fn apfs(p: Probe) -> Option<FilesystemInfo> {
+    if p.bytes(32, 4) != b"NXSB" {
+        return None;
+    }
+    let mut info = FilesystemInfo::new(FilesystemKind::Apfs);
+    info.size = Some((p.le32(36) as u64).saturating_mul(p.le64(40)));
+    info.uuid = p.uuid(72);
+    Some(info)
+}
+
+fn ext(p: Probe) -> Option<FilesystemInfo> {
+    const COMPAT_HAS_JOURNAL: u32 = 0x4;
+    const INCOMPAT_EXTENTS: u32 = 0x40;
+    const INCOMPAT_64BIT: u32 = 0x80;
+    const INCOMPAT_FLEX_BG: u32 = 0x200;
+
+    let sb = 1024;
+    if p.le16(sb + 0x38) != 0xef53 {
+        return None;
+    }
+    let compat = p.le32(sb + 0x5c);
+    let incompat = p.le32(sb + 0x60);
+    let kind = if incompat & (INCOMPAT_EXTENTS | INCOMPAT_64BIT | INCOMPAT_FLEX_BG) != 0 {
+        FilesystemKind::Ext4
+    } else if compat & COMPAT_HAS_JOURNAL != 0 {
+        FilesystemKind::Ext3
+    } else {
+        FilesystemKind::Ext2
+    };
+
+    let block_size = 1024u64 << p.le32(sb + 0x18).min(16);
+    let mut blocks = p.le32(sb + 0x04) as u64;
+    let mut free = p.le32(sb + 0x0c) as u64;
+    if incompat & INCOMPAT_64BIT != 0 {
+        blocks |= (p.le32(sb + 0x150) as u64) << 32;
+        free |= (p.le32(sb + 0x158) as u64) << 32;
+    }
+    let mut info = FilesystemInfo::new(kind).with_space(blocks.saturating_mul(block_size), Some(free.saturating_mul(block_size)));
+    info.uuid = p.uuid(sb + 0x68);
+    info.label = p.text(sb + 0x78, 16);
+    info.last_mount_point = p.text(sb + 0x88, 64);
+    let mounted = p.le32(sb + 0x2c);
+    info.last_mounted = (mounted != 0)
+        .then(|| OffsetDateTime::from_unix_timestamp(mounted as i64).ok())
+        .flatten();
+    Some(info)
+}
+
+fn hfs_plus(p: Probe) -> Option<FilesystemInfo> {
+    let header = 1024;
+    if !matches!(p.bytes(header, 2), b"H+" | b"HX") {
+        return None;
+    }
+    let block_size = p.be32(header + 40) as u64;
+    let blocks = p.be32(header + 44) as u64;
+    let free = p.be32(header + 48) as u64;
+    Some(FilesystemInfo::new(FilesystemKind::HfsPlus).with_space(blocks * block_size, Some(free * block_size)))
+}
+
+fn ntfs(p: Probe) -> Option<FilesystemInfo> {
+    if p.bytes(3, 8) != b"NTFS    " {
+        return None;
+    }
+    // Label and free space live in the MFT, not the boot sector
+    let mut info = FilesystemInfo::new(FilesystemKind::Ntfs);
+    info.size = Some(p.le64(0x28).saturating_mul(p.le16(0x0b) as u64));
+    info.uuid = Some(format!("{:016X}", p.le64(0x48)));
+    Some(info)
+}
+
+fn exfat(p: Probe) -> Option<FilesystemInfo> {
+    if p.bytes(3, 8) != b"EXFAT   " {
+        return None;
+    }
+    let sector_size = 1u64 << p.0[0x6c].min(12);
+    let cluster_size = sector_size << p.0[0x6d].min(25);
+    let heap = (p.le32(0x5c) as u64).saturating_mul(cluster_size);
+    // Percent of the cluster heap in use; FFh when unknown
+    let percent = p.0[0x70];
+    let free = (percent <= 100).then(|| heap - heap / 100 * percent as u64);
+    let mut info = FilesystemInfo::new(FilesystemKind::ExFat).with_space(p.le64(0x48).saturating_mul(sector_size), free);
+    info.uuid = Some(fat_serial(p.le32(0x64)));
+    Some(info)
+}
+
+fn fat(p: Probe) -> Option<FilesystemInfo> {
+    if p.bytes(82, 5) != b"FAT32" || p.bytes(510, 2) != [0x55, 0xaa] {
+        return None;
+    }
+    let sector_size = p.le16(0x0b) as u64;
+    let cluster_size = sector_size * p.0[0x0d] as u64;
+    // FSInfo keeps the last known free cluster count, FFFFFFFFh when unknown
+    let fs_info = p.le16(0x30) as usize * sector_size as usize;
+    let free = (fs_info > 0
+        && fs_info + 512 <= PROBE_SIZE
+        && p.le32(fs_info) == 0x4161_5252
+        && p.le32(fs_info + 0x1e4) == 0x6141_7272)
+        .then(|| p.le32(fs_info + 0x1e8))
+        .filter(|&clusters| clusters != u32::MAX)
+        .map(|clusters| clusters as u64 * cluster_size);
+
+    let mut info = FilesystemInfo::new(FilesystemKind::Fat32).with_space(p.le32(0x20) as u64 * sector_size, free);
+    info.uuid = Some(fat_serial(p.le32(0x43)));
+    info.label = p.text(0x47, 11).filter(|label| label != "NO NAME");
+    Some(info)
+}
+
+// FAT12 and FAT16 share a layout and are told apart by cluster count
+fn fat_legacy(p: Probe) -> Option<FilesystemInfo> {
+    if p.bytes(54, 4) != b"FAT1" || p.bytes(510, 2) != [0x55, 0xaa] {
+        return None;
+    }
+    let sector_size = p.le16(0x0b) as u64;
+    let per_cluster = p.0[0x0d] as u64;
+    if sector_size == 0 || per_cluster == 0 {
+        return None;
+    }
+    let sectors = match p.le16(0x13) {
+        0 => p.le32(0x20) as u64,
+        small => small as u64,
+    };
+    let root_sectors = (p.le16(0x11) as u64 * 32).div_ceil(sector_size);
+    let overhead = p.le16(0x0e) as u64 + p.0[0x10] as u64 * p.le16(0x16) as u64 + root_sectors;
+    let clusters = sectors.saturating_sub(overhead) / per_cluster;
+    let kind = if clusters < 4085 { FilesystemKind::Fat12 } else { FilesystemKind::Fat16 };
+
+    let mut info = FilesystemInfo::new(kind);
+    info.size = Some(sectors * sector_size);
+    info.uuid = Some(fat_serial(p.le32(0x27)));
+    info.label = p.text(0x2b, 11).filter(|label| label != "NO NAME");
+    Some(info)
+}
+
+// FAT and exFAT print the volume serial as XXXX-XXXX
+fn fat_serial(serial: u32) -> String {
+    format!("{:04X}-{:04X}", serial >> 16, serial & 0xffff)
+}
@@ -0,0 +1,805 @@
+//! Forensic preview of recoverable data
+//! Shows what an erase takes away, and checks that one left nothing: files
+//! carved by their header and footer signatures from space no filesystem
+//! claims, and deleted entries still present in ext2/3/4 and NTFS metadata.
+//! Nothing is written, and image files work as well as devices.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::Path,
+};
+
+use serde::Serialize;
+
+use crate::analysis::{
+    AnalysisError,
+    filesystem::{FilesystemKind, Probe, Volume, scan_filesystems},
+    partition::{PartitionLayout, PartitionRole, RegionKind, read_partition_table},
+    sectors::SectorReader,
+};
+
+const SECTOR_SIZE: u64 = 512;
+// Bytes read at a time while carving and walking the MFT
+const CHUNK_SIZE: u64 = 1024 * 1024;
+// Deleted names kept per volume as examples
+const MAX_SAMPLES: usize = 25;
+// Carved files listed one by one; the rest are only counted
+const MAX_CARVED: usize = 10_000;
+// Guards against corrupt block maps claiming huge directories
+const MAX_DIR_BLOCKS: usize = 65_536;
+
+/// How the end of a carved file is found
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum FileEnd {
+    /// A trailer, and the number of bytes that follow it
+    Footer(&'static [u8], u64),
+    /// Chunk size in the RIFF header
+    Riff,
+    /// Page size times page count from the database header
+    Sqlite,
+    /// Start header pointing past the end header
+    SevenZip,
+    /// Nothing marks the end; carving runs to the next header
+    Unknown,
+}
+
+/// A file type recognised by the header at the start of a sector
+pub(crate) struct FileSignature {
+    pub name: &'static str,
+    /// Where `magic` sits in the file
+    pub offset: usize,
+    pub magic: &'static [u8],
+    pub end: FileEnd,
+    /// Carving stops here when no end is found
+    pub max_len: u64,
+}
+
+const MB: u64 = 1024 * 1024;
+
+const fn signature(
+    name: &'static str,
+    offset: usize,
+    magic: &'static [u8],
+    end: FileEnd,
+    max_len: u64,
+) -> FileSignature {
+    FileSignature { name, offset, magic, end, max_len }
+}
+
+pub(crate) const FILE_SIGNATURES: &[FileSignature] = &[
+    signature("JPEG", 0, b"\xff\xd8\xff", FileEnd::Footer(b"\xff\xd9", 0), 32 * MB),
+    signature("PNG", 0, b"\x89PNG\r\n\x1a\n", FileEnd::Footer(b"IEND\xae\x42\x60\x82", 0), 32 * MB),
+    signature("GIF", 0, b"GIF8", FileEnd::Footer(b"\x00\x3b", 0), 16 * MB),
+    signature("PDF", 0, b"%PDF-", FileEnd::Footer(b"%%EOF", 0), 256 * MB),
+    // The end of central directory record is 22 bytes, without a comment
+    signature("ZIP/Office", 0, b"PK\x03\x04", FileEnd::Footer(b"PK\x05\x06", 18), 256 * MB),
+    signature("GZIP", 0, b"\x1f\x8b\x08", FileEnd::Unknown, 64 * MB),
+    signature("7-Zip", 0, b"7z\xbc\xaf\x27\x1c", FileEnd::SevenZip, 1024 * MB),
+    signature("RAR", 0, b"Rar!\x1a\x07", FileEnd::Unknown, 64 * MB),
+    signature("XZ", 0, b"\xfd7zXZ\x00", FileEnd::Footer(b"YZ", 0), 64 * MB),
+    signature("SQLite", 0, b"SQLite format 3\x00", FileEnd::Sqlite, 1024 * MB),
+    signature("ELF", 0, b"\x7fELF", FileEnd::Unknown, 64 * MB),
+    signature("RIFF (WAV/AVI)", 0, b"RIFF", FileEnd::Riff, 1024 * MB),
+    signature("Ogg", 0, b"OggS", FileEnd::Unknown, 64 * MB),
+    signature("MP4/MOV", 4, b"ftyp", FileEnd::Unknown, 256 * MB),
+];
+
+/// A file found by its signature outside any filesystem
+#[derive(Debug, Clone, Serialize)]
+pub struct CarvedFile {
+    pub kind: &'static str,
+    pub offset: u64,
+    pub len: u64,
+    /// Whether the file's end was found, rather than guessed
+    pub complete: bool,
+}
+
+/// A deleted file or directory whose name is still on disk
+#[derive(Debug, Clone, Serialize)]
+pub struct DeletedEntry {
+    pub name: String,
+    pub directory: bool,
+    /// Where the filesystem still records it
+    pub size: Option<u64>,
+}
+
+/// Deleted entries found in one filesystem's metadata
+#[derive(Debug, Clone, Serialize)]
+pub struct DeletedFiles {
+    /// None for a filesystem on the whole disk
+    pub partition: Option<u32>,
+    pub filesystem: FilesystemKind,
+    /// Deleted inodes or MFT records of files and of directories
+    pub files: u64,
+    pub directories: u64,
+    /// Deleted entries whose name survives
+    pub names: u64,
+    /// Named deleted files by extension
+    pub by_type: BTreeMap<String, u64>,
+    pub samples: Vec<DeletedEntry>,
+}
+
+impl DeletedFiles {
+    fn new(volume: &Volume, filesystem: FilesystemKind) -> Self {
+        DeletedFiles {
+            partition: volume.partition,
+            filesystem,
+            files: 0,
+            directories: 0,
+            names: 0,
+            by_type: BTreeMap::new(),
+            samples: Vec::new(),
+        }
+    }
+
+    fn add_name(&mut self, name: String, directory: bool, size: Option<u64>) {
+        self.names += 1;
+        if !directory {
+            *self.by_type.entry(extension(&name)).or_default() += 1;
+        }
+        if self.samples.len() < MAX_SAMPLES {
+            self.samples.push(DeletedEntry { name, directory, size });
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.files + self.directories + self.names == 0
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ForensicReport {
+    /// Bytes not claimed by a filesystem, all of which were carved
+    pub unallocated_bytes: u64,
+    /// Every carved file by type, including those not listed
+    pub carved_by_type: BTreeMap<&'static str, u64>,
+    pub carved: Vec<CarvedFile>,
+    pub deleted: Vec<DeletedFiles>,
+}
+
+impl ForensicReport {
+    pub fn carved_count(&self) -> u64 {
+        self.carved_by_type.values().sum()
+    }
+
+    /// Whether nothing recoverable was found
+    pub fn is_clean(&self) -> bool {
+        self.carved_count() == 0 && self.deleted.iter().all(DeletedFiles::is_empty)
+    }
+
+    fn add_carved(&mut self, signature: &FileSignature, offset: u64, len: u64, complete: bool) {
+        *self.carved_by_type.entry(signature.name).or_default() += 1;
+        if self.carved.len() < MAX_CARVED {
+            self.carved.push(CarvedFile { kind: signature.name, offset, len, complete });
+        }
+    }
+}
+
+/// Looks for deleted entries in every ext2/3/4 and NTFS filesystem, then
+/// carves files from space outside them. `progress` gets the bytes carved
+/// so far and the total to carve.
+pub fn scan_forensics(
+    path: &Path,
+    progress: &mut dyn FnMut(u64, u64),
+) -> Result<ForensicReport, AnalysisError> {
+    let layout = read_partition_table(path)?;
+    let volumes = scan_filesystems(path, &layout)?;
+    let reader = SectorReader::open(path, SECTOR_SIZE)?;
+    let mut report = ForensicReport::default();
+
+    for volume in &volumes {
+        if let Some(deleted) = find_deleted(&reader, volume)? {
+            report.deleted.push(deleted);
+        }
+    }
+
+    let ranges = unallocated(&layout, &volumes);
+    let total = ranges.iter().map(|(start, end)| end - start).sum();
+    report.unallocated_bytes = total;
+    let mut done = 0;
+    for (start, end) in ranges {
+        carve(&reader, start, end, &mut report, &mut |len| {
+            done += len;
+            progress(done, total);
+        })?;
+    }
+    Ok(report)
+}
+
+/// Deleted entries in the filesystem on `volume`, for the formats whose
+/// metadata is understood
+pub fn find_deleted(reader: &SectorReader, volume: &Volume) -> Result<Option<DeletedFiles>, AnalysisError> {
+    let Some(info) = &volume.filesystem else {
+        return Ok(None);
+    };
+    let mut deleted = DeletedFiles::new(volume, info.kind);
+    let found = match info.kind {
+        FilesystemKind::Ext2 | FilesystemKind::Ext3 | FilesystemKind::Ext4 => {
+            Ext::open(reader, volume)?.map(|ext| ext.find_deleted(&mut deleted)).transpose()?
+        }
+        FilesystemKind::Ntfs => ntfs_deleted(reader, volume, &mut deleted)?,
+        _ => None,
+    };
+    Ok(found.map(|()| deleted))
+}
+
+// Byte ranges outside every filesystem: free space, the unused part of
+// extended partitions, and partitions holding nothing recognised or swap
+fn unallocated(layout: &PartitionLayout, volumes: &[Volume]) -> Vec<(u64, u64)> {
+    let disk_size = layout.total_sectors * layout.sector_size;
+    let mut ranges: Vec<(u64, u64)> = Vec::new();
+    // Without partitions the single volume covers the disk
+    if !layout.partitions.is_empty() {
+        ranges.extend(
+            layout
+                .regions
+                .iter()
+                .filter(|region| match region.kind {
+                    RegionKind::Free => true,
+                    RegionKind::Partition(index) => layout.partitions[index].role == PartitionRole::Extended,
+                    RegionKind::Metadata => false,
+                })
+                .map(|region| (region.first_lba * layout.sector_size, (region.last_lba + 1) * layout.sector_size)),
+        );
+    }
+    ranges.extend(
+        volumes
+            .iter()
+            .filter(|volume| volume.filesystem.as_ref().is_none_or(|info| info.kind == FilesystemKind::Swap))
+            .map(|volume| (volume.offset, volume.offset.saturating_add(volume.size))),
+    );
+
+    let mut merged: Vec<(u64, u64)> = Vec::new();
+    ranges.sort();
+    for (start, end) in ranges {
+        let end = end.min(disk_size);
+        if start >= end {
+            continue;
+        }
+        match merged.last_mut() {
+            Some(last) if start <= last.1 => last.1 = last.1.max(end),
+            _ => merged.push((start, end)),
+        }
+    }
+    merged
+}
+
+// Reads whole sectors, padded with zeros past the end of the device
+fn read(reader: &SectorReader, offset: u64, len: u64) -> Result<Vec<u8>, AnalysisError> {
+    let lba = offset / SECTOR_SIZE;
+    if lba >= reader.total_sectors() {
+        return Ok(vec![0; len as usize]);
+    }
+    let mut data = reader.read_page(lba, len.div_ceil(SECTOR_SIZE))?.data;
+    data.resize(len as usize, 0);
+    Ok(data)
+}
+
+fn match_header(sector: &[u8]) -> Option<&'static FileSignature> {
+    FILE_SIGNATURES
+        .iter()
+        .find(|signature| sector.get(signature.offset..).is_some_and(|rest| rest.starts_with(signature.magic)))
+}
+
+// Length of a file whose header records it
+fn header_len(signature: &FileSignature, sector: &[u8]) -> Option<u64> {
+    let p = Probe(sector);
+    let len = match signature.end {
+        FileEnd::Riff => p.le32(4) as u64 + 8,
+        FileEnd::Sqlite => {
+            let page_size = match p.be16(16) {
+                1 => 65536,
+                size => size as u64,
+            };
+            page_size * p.be32(28) as u64
+        }
+        FileEnd::SevenZip => 32u64.saturating_add(p.le64(12)).saturating_add(p.le64(20)),
+        _ => return None,
+    };
+    (len > 0).then_some(len)
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|window| window == needle)
+}
+
+// Carves [start, end) sector by sector. Files are assumed to start on a
+// sector boundary, as every filesystem places them; one is carved at a time,
+// and a new header closes the one before it.
+fn carve(
+    reader: &SectorReader,
+    start: u64,
+    end: u64,
+    report: &mut ForensicReport,
+    progress: &mut dyn FnMut(u64),
+) -> Result<(), AnalysisError> {
+    let mut open: Option<(&'static FileSignature, u64)> = None;
+    // Last bytes searched, so a footer split across sectors is still found
+    let mut tail: Vec<u8> = Vec::new();
+    // End of a file whose length came from its header
+    let mut skip_to = start;
+
+    let mut chunk = start;
+    while chunk < end {
+        let len = CHUNK_SIZE.min(end - chunk);
+        let data = read(reader, chunk, len)?;
+        for (index, sector) in data.chunks(SECTOR_SIZE as usize).enumerate() {
+            let offset = chunk + index as u64 * SECTOR_SIZE;
+            if offset < skip_to {
+                continue;
+            }
+
+            if let Some(signature) = match_header(sector) {
+                if let Some((previous, file_start)) = open.take() {
+                    report.add_carved(previous, file_start, offset - file_start, false);
+                }
+                tail.clear();
+                if let Some(len) = header_len(signature, sector) {
+                    let complete = len <= signature.max_len && len <= end - offset;
+                    let len = len.min(signature.max_len).min(end - offset);
+                    report.add_carved(signature, offset, len, complete);
+                    skip_to = offset + len;
+                    continue;
+                }
+                open = Some((signature, offset));
+            }
+
+            let Some((signature, file_start)) = open else {
+                continue;
+            };
+            if let FileEnd::Footer(footer, trailing) = signature.end {
+                let window_start = offset - tail.len() as u64;
+                let mut window = std::mem::take(&mut tail);
+                window.extend_from_slice(sector);
+                // Past the magic, which could contain the footer bytes
+                let from = if file_start == offset { signature.offset + signature.magic.len() } else { 0 };
+                if let Some(position) = find(&window[from..], footer) {
+                    let file_end = (window_start + (from + position + footer.len()) as u64 + trailing).min(end);
+                    report.add_carved(signature, file_start, file_end - file_start, true);
+                    open = None;
+                    skip_to = file_end;
+                    continue;
+                }
+                tail = window[window.len() + 1 - footer.len()..].to_vec();
+            }
+            if offset + SECTOR_SIZE - file_start >= signature.max_len {
+                report.add_carved(signature, file_start, signature.max_len, false);
+                open = None;
+                tail.clear();
+            }
+        }
+        chunk += len;
+        progress(len);
+    }
+    if let Some((signature, file_start)) = open {
+        report.add_carved(signature, file_start, end - file_start, false);
+    }
+    Ok(())
+}
+
+// "jpg" from "IMG_0001.JPG", "(none)" without an extension
+fn extension(name: &str) -> String {
+    match name.rsplit_once('.') {
+        Some((stem, extension)) if !stem.is_empty() && !extension.is_empty() && extension.len() <= 8 => {
+            extension.to_lowercase()
+        }
+        _ => "(none)".to_string(),
+    }
+}
+
+// ext2/3/4 metadata. Deleted inodes keep their mode and a deletion time;
+// deleted directory entries stay in their block, either absorbed into the
+// previous entry's record length or, at the start of a block, with a zero
+// inode number.
+struct Ext<'a> {
+    reader: &'a SectorReader,
+    offset: u64,
+    size: u64,
+    block_size: u64,
+    inodes_count: u32,
+    inodes_per_group: u32,
+    inode_size: u64,
+    first_data_block: u64,
+    desc_size: u64,
+    // Group descriptors carry unused inode counts
+    checksummed: bool,
+}
+
+impl<'a> Ext<'a> {
+    fn open(reader: &'a SectorReader, volume: &Volume) -> Result<Option<Self>, AnalysisError> {
+        let data = read(reader, volume.offset + 1024, 1024)?;
+        let sb = Probe(&data);
+        if sb.le16(0x38) != 0xef53 || sb.le32(0x18) > 6 {
+            return Ok(None);
+        }
+        let block_size = 1024u64 << sb.le32(0x18);
+        let inode_size = if sb.le32(0x4c) >= 1 { sb.le16(0x58) as u64 } else { 128 };
+        let desc_size = if sb.le32(0x60) & 0x80 != 0 { (sb.le16(0xfe) as u64).max(32) } else { 32 };
+        let ext = Ext {
+            reader,
+            offset: volume.offset,
+            size: volume.size,
+            block_size,
+            inodes_count: sb.le32(0x00),
+            inodes_per_group: sb.le32(0x28),
+            inode_size,
+            first_data_block: sb.le32(0x14) as u64,
+            desc_size,
+            checksummed: sb.le32(0x64) & (0x10 | 0x400) != 0,
+        };
+        let sane = ext.inodes_per_group > 0 && (128..=block_size).contains(&inode_size) && desc_size <= block_size;
+        Ok(sane.then_some(ext))
+    }
+
+    // Blocks past the end of the volume read as zeros
+    fn read_blocks(&self, block: u64, count: u64) -> Result<Vec<u8>, AnalysisError> {
+        let offset = block.saturating_mul(self.block_size);
+        let len = count * self.block_size;
+        if offset.saturating_add(len) > self.size {
+            return Ok(vec![0; len as usize]);
+        }
+        read(self.reader, self.offset + offset, len)
+    }
+
+    fn find_deleted(&self, deleted: &mut DeletedFiles) -> Result<(), AnalysisError> {
+        let groups = self.inodes_count.div_ceil(self.inodes_per_group) as u64;
+        let table_blocks = (groups * self.desc_size).div_ceil(self.block_size);
+        let descriptors = self.read_blocks(self.first_data_block + 1, table_blocks)?;
+
+        // Sizes of deleted inodes, for the names that point at them
+        let mut sizes: HashMap<u32, u64> = HashMap::new();
+        let mut directories: Vec<Vec<u8>> = Vec::new();
+        for group in 0..groups {
+            let start = (group * self.desc_size) as usize;
+            let d = Probe(&descriptors[start..start + self.desc_size as usize]);
+            // Never-initialised inode tables hold nothing
+            if self.checksummed && d.le16(0x12) & 0x1 != 0 {
+                continue;
+            }
+            let mut table = d.le32(0x08) as u64;
+            let mut unused = if self.checksummed { d.le16(0x1c) as u64 } else { 0 };
+            if self.desc_size >= 64 {
+                table |= (d.le32(0x28) as u64) << 32;
+                if self.checksummed {
+                    unused |= (d.le16(0x32) as u64) << 16;
+                }
+            }
+            let used = (self.inodes_per_group as u64).saturating_sub(unused);
+            let bytes = self.read_blocks(table, (used * self.inode_size).div_ceil(self.block_size))?;
+
+            for (index, raw) in bytes.chunks_exact(self.inode_size as usize).take(used as usize).enumerate() {
+                let number = (group * self.inodes_per_group as u64 + index as u64 + 1) as u32;
+                let inode = Probe(raw);
+                let mode = inode.le16(0x00);
+                let links = inode.le16(0x1a);
+                let kind = mode & 0xf000;
+                if links == 0 && inode.le32(0x14) != 0 && mode != 0 {
+                    match kind {
+                        0x4000 => deleted.directories += 1,
+                        0x8000 => deleted.files += 1,
+                        _ => continue,
+                    }
+                    let size = inode.le32(0x04) as u64 | (inode.le32(0x6c) as u64) << 32;
+                    sizes.insert(number, size);
+                } else if links > 0 && kind == 0x4000 {
+                    directories.push(raw.to_vec());
+                }
+            }
+        }
+
+        for raw in &directories {
+            // Indexed directories keep their tree root after ".." in block 0
+            let indexed = Probe(raw).le32(0x20) & 0x1000 != 0;
+            for (index, block) in self.file_blocks(raw)?.into_iter().enumerate() {
+                let data = self.read_blocks(block, 1)?;
+                self.scan_directory_block(&data, indexed && index == 0, &sizes, deleted);
+            }
+        }
+        Ok(())
+    }
+
+    // Physical blocks of a file in order. Block-mapped files are followed
+    // through the single indirect block, enough for any but huge directories.
+    fn file_blocks(&self, inode: &[u8]) -> Result<Vec<u64>, AnalysisError> {
+        let p = Probe(inode);
+        let mut blocks = Vec::new();
+        if p.le32(0x20) & 0x80000 != 0 {
+            self.extent_blocks(&inode[0x28..0x28 + 60], 5, &mut blocks)?;
+        } else {
+            blocks.extend((0..12).map(|slot| p.le32(0x28 + slot * 4) as u64).filter(|&block| block != 0));
+            let indirect = p.le32(0x28 + 12 * 4) as u64;
+            if indirect != 0 {
+                let data = self.read_blocks(indirect, 1)?;
+                let pointers = Probe(&data);
+                let pointed = (0..data.len() / 4).map(|slot| pointers.le32(slot * 4) as u64);
+                blocks.extend(pointed.filter(|&block| block != 0));
+            }
+        }
+        blocks.truncate(MAX_DIR_BLOCKS);
+        Ok(blocks)
+    }
+
+    fn extent_blocks(&self, node: &[u8], levels: u32, blocks: &mut Vec<u64>) -> Result<(), AnalysisError> {
+        let p = Probe(node);
+        if p.le16(0) != 0xf30a {
+            return Ok(());
+        }
+        let entries = p.le16(2) as usize;
+        let depth = p.le16(6);
+        for entry in 0..entries {
+            let at = 12 + entry * 12;
+            if at + 12 > node.len() || blocks.len() >= MAX_DIR_BLOCKS {
+                break;
+            }
+            if depth == 0 {
+                // Lengths over 32768 mark uninitialised extents, which read as zeros
+                let len = p.le16(at + 4) as u64;
+                if len > 32768 {
+                    continue;
+                }
+                let first = (p.le16(at + 6) as u64) << 32 | p.le32(at + 8) as u64;
+                blocks.extend(first..first + len);
+            } else if levels > 0 {
+                let leaf = (p.le16(at + 8) as u64) << 32 | p.le32(at + 4) as u64;
+                let data = self.read_blocks(leaf, 1)?;
+                self.extent_blocks(&data, levels - 1, blocks)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn scan_directory_block(
+        &self,
+        block: &[u8],
+        index_root: bool,
+        sizes: &HashMap<u32, u64>,
+        deleted: &mut DeletedFiles,
+    ) {
+        let p = Probe(block);
+        let mut at = 0;
+        let mut first = true;
+        while at + 8 <= block.len() {
+            let inode = p.le32(at);
+            let rec_len = p.le16(at + 4) as usize;
+            let name_len = block[at + 6] as usize;
+            if rec_len < 8 || !rec_len.is_multiple_of(4) || at + rec_len > block.len() {
+                break;
+            }
+            if inode == 0 && first && let Some(entry) = self.dirent(block, at, at + rec_len, true) {
+                deleted.add_name(entry.0, entry.1, None);
+            }
+            // Index nodes and checksum tails are entries without a name;
+            // their slack is not directory entries
+            let skip_slack = (inode == 0 && name_len == 0) || (index_root && at == 12);
+            if !skip_slack {
+                let mut slack = at + (8 + name_len).next_multiple_of(4);
+                while slack + 8 <= at + rec_len {
+                    match self.dirent(block, slack, at + rec_len, false) {
+                        Some((name, directory, number, len)) => {
+                            deleted.add_name(name, directory, sizes.get(&number).copied());
+                            slack += len;
+                        }
+                        None => slack += 4,
+                    }
+                }
+            }
+            at += rec_len;
+            first = false;
+        }
+    }
+
+    // A plausible directory entry at `at` that fits before `end`:
+    // (name, is a directory, inode, length)
+    fn dirent(
+        &self,
+        block: &[u8],
+        at: usize,
+        end: usize,
+        zero_inode: bool,
+    ) -> Option<(String, bool, u32, usize)> {
+        let p = Probe(block);
+        let inode = p.le32(at);
+        let rec_len = p.le16(at + 4) as usize;
+        let name_len = block[at + 6] as usize;
+        let file_type = block[at + 7];
+        let inode_ok = if zero_inode { inode == 0 } else { inode != 0 && inode <= self.inodes_count };
+        if !inode_ok
+            || name_len == 0
+            || !(1..=7).contains(&file_type)
+            || rec_len < 8 + name_len
+            || !rec_len.is_multiple_of(4)
+            || at + 8 + name_len > end
+        {
+            return None;
+        }
+        let name = &block[at + 8..at + 8 + name_len];
+        if name.iter().any(|&b| b < 0x20 || b == b'/' || b == 0x7f) || name == b"." || name == b".." {
+            return None;
+        }
+        let len = (8 + name_len).next_multiple_of(4);
+        Some((String::from_utf8_lossy(name).to_string(), file_type == 2, inode, len))
+    }
+}
+
+// An MFT record reduced to what matters here
+struct MftRecord {
+    in_use: bool,
+    directory: bool,
+    // Non-zero for extension records of another file
+    base: u64,
+    name: Option<String>,
+    size: Option<u64>,
+    runs: Vec<(u64, u64)>,
+}
+
+// NTFS marks a deleted file's MFT record as not in use and leaves the rest,
+// names and sizes included, until the record is reused
+fn ntfs_deleted(
+    reader: &SectorReader,
+    volume: &Volume,
+    deleted: &mut DeletedFiles,
+) -> Result<Option<()>, AnalysisError> {
+    let boot = read(reader, volume.offset, SECTOR_SIZE)?;
+    let p = Probe(&boot);
+    if p.bytes(3, 8) != b"NTFS    " {
+        return Ok(None);
+    }
+    let sectors_per_cluster = match boot[0x0d] {
+        raw if raw > 0x80 => 1u64 << (256 - raw as u32).min(16),
+        raw => raw as u64,
+    };
+    let cluster = p.le16(0x0b) as u64 * sectors_per_cluster;
+    let record_size = match boot[0x40] as i8 {
+        clusters if clusters > 0 => clusters as u64 * cluster,
+        shift => 1u64 << (-(shift as i32)).clamp(0, 20),
+    };
+    if cluster == 0 || !(512..=65536).contains(&record_size) || !record_size.is_power_of_two() {
+        return Ok(None);
+    }
+
+    // Record 0 describes the MFT itself
+    let mft = p.le64(0x30).saturating_mul(cluster);
+    let mut first = read(reader, volume.offset.saturating_add(mft), record_size)?;
+    let Some(mft_record) = parse_mft_record(&mut first) else {
+        return Ok(None);
+    };
+    let mut remaining = mft_record.size.unwrap_or(0) / record_size;
+
+    let mut number = 0u64;
+    for (lcn, clusters) in mft_record.runs {
+        let mut offset = volume.offset.saturating_add(lcn.saturating_mul(cluster));
+        let mut run_len = clusters.saturating_mul(cluster);
+        while run_len > 0 && remaining > 0 {
+            let len = CHUNK_SIZE.min(run_len).min(remaining * record_size);
+            let mut data = read(reader, offset, len)?;
+            for raw in data.chunks_exact_mut(record_size as usize) {
+                // The first 16 records are the filesystem's own files
+                if number >= 16
+                    && let Some(record) = parse_mft_record(raw)
+                    && !record.in_use
+                    && record.base == 0
+                {
+                    if record.directory {
+                        deleted.directories += 1;
+                    } else {
+                        deleted.files += 1;
+                    }
+                    if let Some(name) = record.name {
+                        deleted.add_name(name, record.directory, record.size);
+                    }
+                }
+                number += 1;
+                remaining -= 1;
+            }
+            offset += len;
+            run_len -= len;
+        }
+    }
+    Ok(Some(()))
+}
+
+// Applies the update sequence fixups and reads the file name, size and
+// data runs. None for anything that is not an intact record.
+fn parse_mft_record(raw: &mut [u8]) -> Option<MftRecord> {
+    if !raw.starts_with(b"FILE") {
+        return None;
+    }
+    let usa_offset = Probe(raw).le16(0x04) as usize;
+    let usa_count = Probe(raw).le16(0x06) as usize;
+    if usa_count == 0 || usa_offset + usa_count * 2 > raw.len() || (usa_count - 1) * 512 > raw.len() {
+        return None;
+    }
+    let usn = [raw[usa_offset], raw[usa_offset + 1]];
+    for stride in 1..usa_count {
+        let end = stride * 512;
+        if raw[end - 2..end] != usn {
+            return None;
+        }
+        let fixup = usa_offset + stride * 2;
+        raw.copy_within(fixup..fixup + 2, end - 2);
+    }
+
+    let p = Probe(raw);
+    let flags = p.le16(0x16);
+    let mut record = MftRecord {
+        in_use: flags & 0x1 != 0,
+        directory: flags & 0x2 != 0,
+        base: p.le64(0x20) & 0xffff_ffff_ffff,
+        name: None,
+        size: None,
+        runs: Vec::new(),
+    };
+    let used = (p.le32(0x18) as usize).min(raw.len());
+    let mut name_space = None;
+    let mut at = p.le16(0x14) as usize;
+    while at + 16 <= used {
+        let kind = p.le32(at);
+        let len = p.le32(at + 4) as usize;
+        if kind == 0xffff_ffff || len < 16 || at + len > used {
+            break;
+        }
+        let resident = raw[at + 8] == 0;
+        let unnamed = raw[at + 9] == 0;
+        match kind {
+            // $FILE_NAME; the DOS 8.3 name (namespace 2) only if nothing better
+            0x30 if resident => {
+                let value = at + p.le16(at + 0x14) as usize;
+                if value + 0x42 <= at + len {
+                    let chars = raw[value + 0x40] as usize;
+                    let space = raw[value + 0x41];
+                    let end = value + 0x42 + chars * 2;
+                    if end <= at + len && (name_space.is_none() || name_space == Some(2)) {
+                        let units: Vec<u16> = raw[value + 0x42..end]
+                            .chunks_exact(2)
+                            .map(|unit| u16::from_le_bytes([unit[0], unit[1]]))
+                            .collect();
+                        record.name = Some(String::from_utf16_lossy(&units));
+                        name_space = Some(space);
+                    }
+                }
+            }
+            // Unnamed $DATA
+            0x80 if unnamed && resident => record.size = Some(p.le32(at + 0x10) as u64),
+            0x80 if unnamed && len >= 0x40 => {
+                record.size = Some(p.le64(at + 0x30));
+                record.runs = data_runs(&raw[at..at + len], p.le16(at + 0x20) as usize);
+            }
+            _ => {}
+        }
+        at += len;
+    }
+    Some(record)
+}
+
+// Decodes a run list into (first cluster, clusters); sparse runs are left out
+fn data_runs(attribute: &[u8], start: usize) -> Vec<(u64, u64)> {
+    let mut runs = Vec::new();
+    let mut lcn: i64 = 0;
+    let mut at = start;
+    while let Some(&header) = attribute.get(at)
+        && header != 0
+    {
+        let len_size = (header & 0xf) as usize;
+        let offset_size = (header >> 4) as usize;
+        let fits = at + 1 + len_size + offset_size <= attribute.len();
+        if len_size == 0 || len_size > 8 || offset_size > 8 || !fits {
+            break;
+        }
+        let field = |from: usize, size: usize, signed: bool| {
+            let mut bytes = [0u8; 8];
+            bytes[..size].copy_from_slice(&attribute[from..from + size]);
+            // Offsets are relative to the previous run and may be negative
+            if signed && size > 0 && size < 8 && attribute[from + size - 1] & 0x80 != 0 {
+                bytes[size..].fill(0xff);
+            }
+            i64::from_le_bytes(bytes)
+        };
+        let clusters = field(at + 1, len_size, false) as u64;
+        if offset_size > 0 {
+            lcn += field(at + 1 + len_size, offset_size, true);
+            runs.push((lcn.max(0) as u64, clusters));
+        }
+        at += 1 + len_size + offset_size;
+    }
+    runs
+}
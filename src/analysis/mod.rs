@@ -0,0 +1,42 @@
+//! Read-only analysis of device contents
+//! Looks at what is on a disk before it is erased; nothing in here writes.
+
+pub mod datamap;
+pub mod filesystem;
+pub mod forensics;
+pub mod partition;
+pub mod sectors;
+
+#[derive(Debug)]
+pub enum AnalysisError {
+    IoError(std::io::Error),
+    // Smaller than the structures being looked for
+    TooSmall(u64),
+    // LBA past the end of the device
+    OutOfRange(u64),
+}
+
+impl std::fmt::Display for AnalysisError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AnalysisError::IoError(e) => write!(f, "I/O error: {}", e),
+            AnalysisError::TooSmall(size) => write!(f, "device is too small to analyze ({} bytes)", size),
+            AnalysisError::OutOfRange(lba) => write!(f, "LBA {} is past the end of the device", lba),
+        }
+    }
+}
+
+impl std::error::Error for AnalysisError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            AnalysisError::IoError(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for AnalysisError {
+    fn from(e: std::io::Error) -> Self {
+        AnalysisError::IoError(e)
+    }
+}
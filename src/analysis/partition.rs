@@ -0,0 +1,688 @@
+//! Partition table analysis
+//! Parses MBR tables including extended and logical partitions, protective
+//! MBRs and GPT with both headers, and maps the result onto the whole disk
+//! so gaps and overlapping partitions show up.
+
+use std::{
+    collections::HashSet,
+    fs::File,
+    io::{Read, Seek, SeekFrom},
+    path::Path,
+};
+
+use crate::analysis::AnalysisError;
+
+const MBR_SIGNATURE: [u8; 2] = [0x55, 0xaa];
+const MBR_TYPE_PROTECTIVE: u8 = 0xee;
+const MBR_TYPES_EXTENDED: [u8; 3] = [0x05, 0x0f, 0x85];
+const GPT_SIGNATURE: &[u8; 8] = b"EFI PART";
+// Legacy BIOS bootable attribute of a GPT entry
+const GPT_ATTR_BOOTABLE: u64 = 1 << 2;
+// Sector sizes probed for the GPT header; 4Kn drives put it at byte 4096
+const SECTOR_SIZES: [u64; 2] = [512, 4096];
+// Bounds on values read from disk, so a corrupt table cannot make the
+// analysis loop or allocate without limit
+const MAX_LOGICAL_PARTITIONS: usize = 128;
+const MAX_GPT_ENTRIES: u32 = 16384;
+const MAX_GPT_ENTRY_SIZE: u32 = 4096;
+
+/// GUID as stored on disk; the first three fields are little-endian
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Guid(pub [u8; 16]);
+
+impl Guid {
+    pub fn is_nil(&self) -> bool {
+        self.0 == [0; 16]
+    }
+}
+
+impl std::fmt::Display for Guid {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let b = &self.0;
+        write!(
+            f,
+            "{:08X}-{:04X}-{:04X}-{}-{}",
+            u32::from_le_bytes([b[0], b[1], b[2], b[3]]),
+            u16::from_le_bytes([b[4], b[5]]),
+            u16::from_le_bytes([b[6], b[7]]),
+            hex::encode_upper(&b[8..10]),
+            hex::encode_upper(&b[10..16])
+        )
+    }
+}
+
+// Partition type GUIDs worth naming
+const GPT_TYPES: &[(&str, &str)] = &[
+    ("C12A7328-F81F-11D2-BA4B-00A0C93EC93B", "EFI System"),
+    ("21686148-6449-6E6F-744E-656564454649", "BIOS boot"),
+    ("E3C9E316-0B5C-4DB8-817D-F92DF00215AE", "Microsoft reserved"),
+    ("EBD0A0A2-B9E5-4433-87C0-68B6B72699C7", "Microsoft basic data"),
+    ("DE94BBA4-06D1-4D40-A16A-BFD50179D6AC", "Windows recovery"),
+    ("5808C8AA-7E8F-42E0-85D2-E1E90434CFB3", "Windows LDM metadata"),
+    ("AF9B60A0-1431-4F62-BC68-3311714A69AD", "Windows LDM data"),
+    ("0FC63DAF-8483-4772-8E79-3D69D8477DE4", "Linux filesystem"),
+    ("4F68BCE3-E8CD-4DB1-96E7-FBCAF984B709", "Linux root (x86-64)"),
+    ("B921B045-1DF0-41C3-AF44-4C6F280D3FAE", "Linux root (ARM64)"),
+    ("933AC7E1-2EB4-4F13-B844-0E14E2AEF915", "Linux home"),
+    ("BC13C2FF-59E6-4262-A352-B275FD6F7172", "Linux extended boot"),
+    ("0657FD6D-A4AB-43C4-84E5-0933C84B4F4F", "Linux swap"),
+    ("E6D6D379-F507-44C2-A23C-238F2A3DF928", "Linux LVM"),
+    ("A19D880F-05FC-4D3B-A006-743F0F84911E", "Linux RAID"),
+    ("CA7D7CCB-63ED-4C53-861C-1742536059CC", "Linux LUKS"),
+    ("48465300-0000-11AA-AA11-00306543ECAC", "Apple HFS+"),
+    ("7C3457EF-0000-11AA-AA11-00306543ECAC", "Apple APFS"),
+    ("516E7CB4-6ECF-11D6-8FF8-00022D09712B", "FreeBSD UFS"),
+    ("6A898CC3-1DD2-11B2-99A6-080020736631", "ZFS"),
+];
+
+const MBR_TYPES: &[(u8, &str)] = &[
+    (0x01, "FAT12"),
+    (0x04, "FAT16 <32M"),
+    (0x05, "Extended"),
+    (0x06, "FAT16"),
+    (0x07, "NTFS/exFAT"),
+    (0x0b, "FAT32"),
+    (0x0c, "FAT32 (LBA)"),
+    (0x0e, "FAT16 (LBA)"),
+    (0x0f, "Extended (LBA)"),
+    (0x27, "Windows recovery"),
+    (0x82, "Linux swap"),
+    (0x83, "Linux"),
+    (0x85, "Linux extended"),
+    (0x8e, "Linux LVM"),
+    (0xa5, "FreeBSD"),
+    (0xaf, "Apple HFS+"),
+    (0xee, "GPT protective"),
+    (0xef, "EFI System"),
+    (0xfd, "Linux RAID"),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PartitionKind {
+    Mbr(u8),
+    Gpt(Guid),
+}
+
+impl PartitionKind {
+    pub fn description(&self) -> String {
+        match self {
+            PartitionKind::Mbr(kind) => MBR_TYPES
+                .iter()
+                .find(|(known, _)| known == kind)
+                .map(|(_, name)| name.to_string())
+                .unwrap_or_else(|| format!("Unknown (0x{:02x})", kind)),
+            PartitionKind::Gpt(guid) => {
+                let guid = guid.to_string();
+                GPT_TYPES
+                    .iter()
+                    .find(|(known, _)| *known == guid)
+                    .map(|(_, name)| name.to_string())
+                    .unwrap_or(guid)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PartitionRole {
+    Primary,
+    // MBR container for logical partitions
+    Extended,
+    Logical,
+}
+
+#[derive(Debug, Clone)]
+pub struct Partition {
+    /// Numbered the way Linux names the partition devices: MBR primaries
+    /// 1-4 and logicals from 5, GPT by entry slot
+    pub number: u32,
+    pub first_lba: u64,
+    /// Inclusive
+    pub last_lba: u64,
+    pub kind: PartitionKind,
+    pub role: PartitionRole,
+    pub bootable: bool,
+    /// GPT only
+    pub name: Option<String>,
+    /// GPT unique partition GUID
+    pub guid: Option<Guid>,
+}
+
+impl Partition {
+    pub fn sectors(&self) -> u64 {
+        self.last_lba - self.first_lba + 1
+    }
+}
+
+/// A GPT header and whether it and its entry array passed their CRC checks
+#[derive(Debug, Clone)]
+pub struct GptHeader {
+    /// Where the header was read from
+    pub lba: u64,
+    pub alternate_lba: u64,
+    pub first_usable_lba: u64,
+    pub last_usable_lba: u64,
+    pub disk_guid: Guid,
+    pub entries_lba: u64,
+    pub entry_count: u32,
+    pub entry_size: u32,
+    /// Header CRC matches and the header points at its own location
+    pub header_valid: bool,
+    pub entries_valid: bool,
+    entries_crc: u32,
+}
+
+impl GptHeader {
+    pub fn is_valid(&self) -> bool {
+        self.header_valid && self.entries_valid
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum PartitionScheme {
+    None,
+    Mbr {
+        disk_signature: u32,
+    },
+    Gpt {
+        disk_guid: Guid,
+        first_usable_lba: u64,
+        last_usable_lba: u64,
+        primary: Option<GptHeader>,
+        backup: Option<GptHeader>,
+        protective_mbr: bool,
+    },
+}
+
+impl std::fmt::Display for PartitionScheme {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PartitionScheme::None => write!(f, "No partition table"),
+            PartitionScheme::Mbr { .. } => write!(f, "MBR"),
+            PartitionScheme::Gpt { .. } => write!(f, "GPT"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RegionKind {
+    // MBR, GPT headers and entry arrays
+    Metadata,
+    /// Index into [`PartitionLayout::partitions`]
+    Partition(usize),
+    Free,
+}
+
+/// Consecutive sectors with the same use
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Region {
+    pub first_lba: u64,
+    /// Inclusive
+    pub last_lba: u64,
+    pub kind: RegionKind,
+}
+
+impl Region {
+    pub fn sectors(&self) -> u64 {
+        self.last_lba - self.first_lba + 1
+    }
+}
+
+/// Two partitions, by number, that share sectors
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Overlap {
+    pub first: u32,
+    pub second: u32,
+    pub sectors: u64,
+}
+
+#[derive(Debug, Clone)]
+pub struct PartitionLayout {
+    pub sector_size: u64,
+    pub total_sectors: u64,
+    pub scheme: PartitionScheme,
+    pub partitions: Vec<Partition>,
+    /// Covers the whole disk in order, without overlaps. Where partitions
+    /// overlap the region goes to the one listed first.
+    pub regions: Vec<Region>,
+    pub overlaps: Vec<Overlap>,
+    /// Corrupt or inconsistent structures, in the order they were found
+    pub problems: Vec<String>,
+}
+
+impl PartitionLayout {
+    /// Unallocated space
+    pub fn gaps(&self) -> impl Iterator<Item = &Region> {
+        self.regions.iter().filter(|region| region.kind == RegionKind::Free)
+    }
+
+    fn check(&mut self) {
+        let total = self.total_sectors;
+        let mut problems = Vec::new();
+        for partition in &self.partitions {
+            if partition.last_lba >= total {
+                problems.push(format!("partition {} extends past the end of the disk", partition.number));
+            }
+            if let PartitionScheme::Gpt { first_usable_lba, last_usable_lba, .. } = self.scheme
+                && (partition.first_lba < first_usable_lba || partition.last_lba > last_usable_lba)
+            {
+                problems.push(format!("partition {} lies outside the usable area", partition.number));
+            }
+        }
+        if let Some(extended) = self.partitions.iter().find(|p| p.role == PartitionRole::Extended) {
+            for logical in self.partitions.iter().filter(|p| p.role == PartitionRole::Logical) {
+                if logical.first_lba < extended.first_lba || logical.last_lba > extended.last_lba {
+                    problems.push(format!(
+                        "logical partition {} lies outside extended partition {}",
+                        logical.number, extended.number
+                    ));
+                }
+            }
+        }
+
+        for (i, a) in self.partitions.iter().enumerate() {
+            for b in &self.partitions[i + 1..] {
+                // Logical partitions live inside the extended one by design
+                let roles = [a.role, b.role];
+                if roles.contains(&PartitionRole::Extended) && roles.contains(&PartitionRole::Logical) {
+                    continue;
+                }
+                let first = a.first_lba.max(b.first_lba);
+                let last = a.last_lba.min(b.last_lba);
+                if first <= last {
+                    problems.push(format!("partitions {} and {} overlap", a.number, b.number));
+                    self.overlaps.push(Overlap { first: a.number, second: b.number, sectors: last - first + 1 });
+                }
+            }
+        }
+        self.problems.extend(problems);
+    }
+
+    fn map_regions(&mut self) {
+        let total = self.total_sectors;
+        // (first, end exclusive, priority, kind); the highest priority wins
+        let mut spans: Vec<(u64, u64, u8, RegionKind)> = Vec::new();
+        match self.scheme {
+            PartitionScheme::None => {}
+            PartitionScheme::Mbr { .. } => spans.push((0, 1, 3, RegionKind::Metadata)),
+            PartitionScheme::Gpt { first_usable_lba, last_usable_lba, .. } => {
+                spans.push((0, first_usable_lba, 3, RegionKind::Metadata));
+                spans.push((last_usable_lba.saturating_add(1), total, 3, RegionKind::Metadata));
+            }
+        }
+        for (index, partition) in self.partitions.iter().enumerate() {
+            // The extended container only shows where no logical partition is
+            let priority = if partition.role == PartitionRole::Extended { 1 } else { 2 };
+            spans.push((partition.first_lba, partition.last_lba + 1, priority, RegionKind::Partition(index)));
+        }
+        for span in &mut spans {
+            span.0 = span.0.min(total);
+            span.1 = span.1.min(total);
+        }
+
+        let mut bounds: Vec<u64> = spans.iter().flat_map(|span| [span.0, span.1]).collect();
+        bounds.extend([0, total]);
+        bounds.sort_unstable();
+        bounds.dedup();
+
+        let mut regions: Vec<Region> = Vec::new();
+        for window in bounds.windows(2) {
+            let (first, end) = (window[0], window[1]);
+            let mut kind = RegionKind::Free;
+            let mut best = 0;
+            for &(start, stop, priority, span_kind) in &spans {
+                if start <= first && first < stop && priority > best {
+                    kind = span_kind;
+                    best = priority;
+                }
+            }
+            match regions.last_mut() {
+                Some(last) if last.kind == kind => last.last_lba = end - 1,
+                _ => regions.push(Region { first_lba: first, last_lba: end - 1, kind }),
+            }
+        }
+        self.regions = regions;
+    }
+}
+
+/// Analyzes the partition table of a device or disk image. The sector size
+/// is taken from where the GPT header is found, 512 bytes otherwise.
+pub fn read_partition_table(path: &Path) -> Result<PartitionLayout, AnalysisError> {
+    let mut file = File::open(path)?;
+    let size = file.seek(SeekFrom::End(0))?;
+    let sector_size = SECTOR_SIZES
+        .into_iter()
+        .find(|&sector_size| {
+            read_at(&mut file, sector_size, GPT_SIGNATURE.len()).is_ok_and(|data| data == GPT_SIGNATURE)
+        })
+        .unwrap_or(SECTOR_SIZES[0]);
+    analyze(&mut file, size, sector_size)
+}
+
+/// Analyzes a partition table read from `reader`, which holds `size` bytes
+pub fn analyze<R: Read + Seek>(reader: &mut R, size: u64, sector_size: u64) -> Result<PartitionLayout, AnalysisError> {
+    let total_sectors = size / sector_size;
+    if total_sectors < 2 {
+        return Err(AnalysisError::TooSmall(size));
+    }
+    let mut layout = PartitionLayout {
+        sector_size,
+        total_sectors,
+        scheme: PartitionScheme::None,
+        partitions: Vec::new(),
+        regions: Vec::new(),
+        overlaps: Vec::new(),
+        problems: Vec::new(),
+    };
+
+    let mbr = read_at(reader, 0, 512)?;
+    let entries = mbr_entries(&mbr);
+    let protective_mbr = entries
+        .as_ref()
+        .is_some_and(|entries| entries.iter().any(|entry| entry.kind == MBR_TYPE_PROTECTIVE));
+
+    if read_gpt(reader, &mut layout, protective_mbr) {
+        if !protective_mbr {
+            layout.problems.push("GPT without a protective MBR".to_string());
+        } else if entries.as_ref().is_some_and(|entries| entries.len() > 1) {
+            layout.problems.push("hybrid MBR: the MBR lists partitions besides the GPT".to_string());
+        }
+    } else if let Some(entries) = entries {
+        if protective_mbr {
+            layout.problems.push("protective MBR but no readable GPT".to_string());
+        }
+        layout.scheme = PartitionScheme::Mbr {
+            disk_signature: u32::from_le_bytes(mbr[440..444].try_into().unwrap()),
+        };
+        read_mbr(reader, &entries, &mut layout);
+    }
+
+    layout.check();
+    layout.map_regions();
+    Ok(layout)
+}
+
+struct MbrEntry {
+    slot: usize,
+    bootable: bool,
+    kind: u8,
+    first_lba: u64,
+    sectors: u64,
+}
+
+// Used entries of an MBR or EBR. None without the boot signature or with
+// boot flags other than 00h/80h, e.g. a FAT boot sector on an unpartitioned
+// disk.
+fn mbr_entries(sector: &[u8]) -> Option<Vec<MbrEntry>> {
+    if sector[510..512] != MBR_SIGNATURE {
+        return None;
+    }
+    let mut entries = Vec::new();
+    for slot in 0..4 {
+        let entry = &sector[446 + slot * 16..462 + slot * 16];
+        if entry[0] & 0x7f != 0 {
+            return None;
+        }
+        let sectors = u32::from_le_bytes(entry[12..16].try_into().unwrap()) as u64;
+        if entry[4] == 0 || sectors == 0 {
+            continue;
+        }
+        entries.push(MbrEntry {
+            slot,
+            bootable: entry[0] == 0x80,
+            kind: entry[4],
+            first_lba: u32::from_le_bytes(entry[8..12].try_into().unwrap()) as u64,
+            sectors,
+        });
+    }
+    Some(entries)
+}
+
+fn read_mbr<R: Read + Seek>(reader: &mut R, entries: &[MbrEntry], layout: &mut PartitionLayout) {
+    let mut extended = None;
+    for entry in entries {
+        let is_extended = MBR_TYPES_EXTENDED.contains(&entry.kind);
+        layout.partitions.push(Partition {
+            number: entry.slot as u32 + 1,
+            first_lba: entry.first_lba,
+            last_lba: entry.first_lba + entry.sectors - 1,
+            kind: PartitionKind::Mbr(entry.kind),
+            role: if is_extended { PartitionRole::Extended } else { PartitionRole::Primary },
+            bootable: entry.bootable,
+            name: None,
+            guid: None,
+        });
+        if is_extended {
+            if extended.is_some() {
+                layout.problems.push("more than one extended partition".to_string());
+            } else {
+                extended = Some(entry.first_lba);
+            }
+        }
+    }
+    if let Some(start) = extended {
+        read_logical(reader, start, layout);
+    }
+}
+
+// Follows the chain of extended boot records. Each EBR describes one logical
+// partition relative to itself and links the next EBR relative to the start
+// of the extended partition.
+fn read_logical<R: Read + Seek>(reader: &mut R, extended_start: u64, layout: &mut PartitionLayout) {
+    let mut ebr_lba = extended_start;
+    let mut seen = HashSet::new();
+    let mut number = 5;
+    loop {
+        if !seen.insert(ebr_lba) {
+            layout.problems.push(format!("EBR chain loops back to LBA {}", ebr_lba));
+            return;
+        }
+        if seen.len() > MAX_LOGICAL_PARTITIONS {
+            layout.problems.push(format!("more than {} logical partitions", MAX_LOGICAL_PARTITIONS));
+            return;
+        }
+        let Some(entries) = read_at(reader, ebr_lba * layout.sector_size, 512)
+            .ok()
+            .and_then(|sector| mbr_entries(&sector))
+        else {
+            layout.problems.push(format!("no valid EBR at LBA {}", ebr_lba));
+            return;
+        };
+
+        let mut next = None;
+        for entry in entries {
+            if MBR_TYPES_EXTENDED.contains(&entry.kind) {
+                next = Some(extended_start + entry.first_lba);
+            } else if entry.slot == 0 {
+                let first_lba = ebr_lba + entry.first_lba;
+                layout.partitions.push(Partition {
+                    number,
+                    first_lba,
+                    last_lba: first_lba + entry.sectors - 1,
+                    kind: PartitionKind::Mbr(entry.kind),
+                    role: PartitionRole::Logical,
+                    bootable: entry.bootable,
+                    name: None,
+                    guid: None,
+                });
+                number += 1;
+            }
+        }
+        match next {
+            Some(lba) => ebr_lba = lba,
+            None => return,
+        }
+    }
+}
+
+// Reads both GPT headers and takes the partitions from the first copy that
+// passes its checks. Returns false when there is no GPT at all.
+fn read_gpt<R: Read + Seek>(reader: &mut R, layout: &mut PartitionLayout, protective_mbr: bool) -> bool {
+    let last_lba = layout.total_sectors - 1;
+    let primary = read_gpt_header(reader, 1, layout);
+
+    // The primary says where the backup is; look in the last sector too in
+    // case the primary is damaged or the disk was resized
+    let mut candidates = Vec::new();
+    if let Some((header, _)) = &primary
+        && header.header_valid
+        && header.alternate_lba <= last_lba
+    {
+        candidates.push(header.alternate_lba);
+    }
+    if !candidates.contains(&last_lba) {
+        candidates.push(last_lba);
+    }
+    let backup = candidates.into_iter().find_map(|lba| read_gpt_header(reader, lba, layout));
+
+    if primary.is_none() && backup.is_none() {
+        return false;
+    }
+
+    for (label, copy) in [("primary", &primary), ("backup", &backup)] {
+        match copy {
+            None => layout.problems.push(format!("{} GPT header is missing", label)),
+            Some((header, _)) if !header.header_valid => {
+                layout.problems.push(format!("{} GPT header at LBA {} is corrupt", label, header.lba))
+            }
+            Some((header, _)) if !header.entries_valid => {
+                layout.problems.push(format!("{} GPT partition entries are corrupt", label))
+            }
+            _ => {}
+        }
+    }
+    if let Some((header, _)) = &backup
+        && header.lba != last_lba
+    {
+        layout.problems.push(format!(
+            "backup GPT header is at LBA {} instead of the last sector {}",
+            header.lba, last_lba
+        ));
+    }
+    if let (Some((a, _)), Some((b, _))) = (&primary, &backup)
+        && a.is_valid()
+        && b.is_valid()
+        && (a.entries_crc != b.entries_crc || a.disk_guid != b.disk_guid)
+    {
+        layout.problems.push("primary and backup GPT differ".to_string());
+    }
+
+    // Fall back to a damaged copy rather than showing nothing
+    let (header, entries) = [&primary, &backup]
+        .into_iter()
+        .flatten()
+        .find(|(header, _)| header.is_valid())
+        .or_else(|| [&primary, &backup].into_iter().flatten().find(|(_, entries)| entries.is_some()))
+        .or(primary.as_ref())
+        .or(backup.as_ref())
+        .unwrap();
+
+    if let Some(entries) = entries {
+        for (slot, entry) in entries.chunks_exact(header.entry_size as usize).enumerate() {
+            let kind = Guid(entry[0..16].try_into().unwrap());
+            if kind.is_nil() {
+                continue;
+            }
+            let number = slot as u32 + 1;
+            let first_lba = u64::from_le_bytes(entry[32..40].try_into().unwrap());
+            let last_lba = u64::from_le_bytes(entry[40..48].try_into().unwrap());
+            if last_lba < first_lba {
+                layout.problems.push(format!("partition {} ends before it starts", number));
+                continue;
+            }
+            let attributes = u64::from_le_bytes(entry[48..56].try_into().unwrap());
+            let name: Vec<u16> = entry[56..128]
+                .chunks_exact(2)
+                .map(|c| u16::from_le_bytes([c[0], c[1]]))
+                .take_while(|&c| c != 0)
+                .collect();
+            let name = String::from_utf16_lossy(&name);
+            layout.partitions.push(Partition {
+                number,
+                first_lba,
+                last_lba,
+                kind: PartitionKind::Gpt(kind),
+                role: PartitionRole::Primary,
+                bootable: attributes & GPT_ATTR_BOOTABLE != 0,
+                name: (!name.is_empty()).then_some(name),
+                guid: Some(Guid(entry[16..32].try_into().unwrap())),
+            });
+        }
+    }
+
+    layout.scheme = PartitionScheme::Gpt {
+        disk_guid: header.disk_guid,
+        first_usable_lba: header.first_usable_lba,
+        last_usable_lba: header.last_usable_lba,
+        primary: primary.as_ref().map(|(header, _)| header.clone()),
+        backup: backup.as_ref().map(|(header, _)| header.clone()),
+        protective_mbr,
+    };
+    true
+}
+
+// A header with the GPT signature at `lba` and its entry array, if readable
+fn read_gpt_header<R: Read + Seek>(
+    reader: &mut R,
+    lba: u64,
+    layout: &PartitionLayout,
+) -> Option<(GptHeader, Option<Vec<u8>>)> {
+    let sector = read_at(reader, lba * layout.sector_size, layout.sector_size as usize).ok()?;
+    if &sector[0..8] != GPT_SIGNATURE {
+        return None;
+    }
+    let dword = |offset: usize| u32::from_le_bytes(sector[offset..offset + 4].try_into().unwrap());
+    let qword = |offset: usize| u64::from_le_bytes(sector[offset..offset + 8].try_into().unwrap());
+
+    let header_size = dword(12) as usize;
+    let header_crc_ok = (92..=sector.len()).contains(&header_size) && {
+        let mut header = sector[..header_size].to_vec();
+        header[16..20].fill(0);
+        crc32(&header) == dword(16)
+    };
+    let mut header = GptHeader {
+        lba,
+        alternate_lba: qword(32),
+        first_usable_lba: qword(40),
+        last_usable_lba: qword(48),
+        disk_guid: Guid(sector[56..72].try_into().unwrap()),
+        entries_lba: qword(72),
+        entry_count: dword(80),
+        entry_size: dword(84),
+        header_valid: header_crc_ok && qword(24) == lba,
+        entries_valid: false,
+        entries_crc: dword(88),
+    };
+
+    let sane = header.entry_count <= MAX_GPT_ENTRIES
+        && (128..=MAX_GPT_ENTRY_SIZE).contains(&header.entry_size)
+        && header.entry_size.is_multiple_of(8)
+        && header.entries_lba < layout.total_sectors;
+    let entries = sane
+        .then(|| {
+            let len = header.entry_count as usize * header.entry_size as usize;
+            read_at(reader, header.entries_lba * layout.sector_size, len).ok()
+        })
+        .flatten();
+    header.entries_valid = entries.as_ref().is_some_and(|entries| crc32(entries) == header.entries_crc);
+    Some((header, entries))
+}
+
+fn read_at<R: Read + Seek>(reader: &mut R, offset: u64, len: usize) -> std::io::Result<Vec<u8>> {
+    let mut data = vec![0u8; len];
+    reader.seek(SeekFrom::Start(offset))?;
+    reader.read_exact(&mut data)?;
+    Ok(data)
+}
+
+// CRC-32 (IEEE) as used by GPT
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ 0xedb8_8320 } else { crc >> 1 };
+        }
+    }
+    !crc
+}
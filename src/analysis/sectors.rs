@@ -0,0 +1,210 @@
+//! Raw sector reads for the hex viewer
+//! Reads bypass the page cache with O_DIRECT where the target allows it, so
+//! what is shown is what the device returns rather than cached data. Known
+//! on-disk structures in the sectors read are located for highlighting.
+
+use std::{
+    fs::File,
+    io::{Seek, SeekFrom},
+    os::{fd::AsRawFd, unix::fs::FileExt},
+    path::Path,
+};
+
+use crate::analysis::{
+    AnalysisError,
+    filesystem::SIGNATURES,
+    partition::{PartitionLayout, PartitionScheme},
+};
+
+// Satisfies the O_DIRECT alignment of any logical block size up to 4K
+const DIRECT_ALIGNMENT: u64 = 4096;
+
+/// Read-only access to the sectors of a device or image
+pub struct SectorReader {
+    file: File,
+    direct: bool,
+    size: u64,
+    sector_size: u64,
+}
+
+/// Sectors read from a device
+#[derive(Debug, Clone)]
+pub struct SectorPage {
+    pub lba: u64,
+    pub sector_size: u64,
+    pub total_sectors: u64,
+    /// Whether the read bypassed the page cache
+    pub direct: bool,
+    pub data: Vec<u8>,
+}
+
+impl SectorPage {
+    /// Byte offset of the page on the device
+    pub fn offset(&self) -> u64 {
+        self.lba * self.sector_size
+    }
+}
+
+impl SectorReader {
+    /// Falls back to buffered reads where O_DIRECT is refused, e.g. for
+    /// image files on tmpfs; the cached pages are dropped first then.
+    pub fn open(path: &Path, sector_size: u64) -> Result<Self, AnalysisError> {
+        #[cfg(target_os = "linux")]
+        let direct = crate::platform::linux::open_direct(path).ok();
+        #[cfg(not(target_os = "linux"))]
+        let direct: Option<File> = None;
+
+        let (mut file, direct) = match direct {
+            Some(file) => (file, true),
+            None => {
+                let file = File::open(path)?;
+                unsafe {
+                    libc::posix_fadvise(file.as_raw_fd(), 0, 0, libc::POSIX_FADV_DONTNEED);
+                }
+                (file, false)
+            }
+        };
+        let size = file.seek(SeekFrom::End(0))?;
+        Ok(SectorReader { file, direct, size, sector_size })
+    }
+
+    pub fn total_sectors(&self) -> u64 {
+        self.size / self.sector_size
+    }
+
+    /// Reads up to `count` sectors from `lba`, fewer at the end of the device
+    pub fn read_page(&self, lba: u64, count: u64) -> Result<SectorPage, AnalysisError> {
+        let total_sectors = self.total_sectors();
+        if lba >= total_sectors {
+            return Err(AnalysisError::OutOfRange(lba));
+        }
+        let count = count.min(total_sectors - lba);
+        let offset = lba * self.sector_size;
+        let len = (count * self.sector_size) as usize;
+        let data = if self.direct {
+            self.read_direct(offset, len)?
+        } else {
+            let mut data = vec![0u8; len];
+            self.file.read_exact_at(&mut data, offset)?;
+            data
+        };
+        Ok(SectorPage {
+            lba,
+            sector_size: self.sector_size,
+            total_sectors,
+            direct: self.direct,
+            data,
+        })
+    }
+
+    // Widens the read to aligned boundaries and copies out the part asked for
+    fn read_direct(&self, offset: u64, len: usize) -> Result<Vec<u8>, AnalysisError> {
+        let start = offset / DIRECT_ALIGNMENT * DIRECT_ALIGNMENT;
+        let end = (offset + len as u64).div_ceil(DIRECT_ALIGNMENT) * DIRECT_ALIGNMENT;
+        let aligned_len = (end - start) as usize;
+        let mut buffer = vec![0u8; aligned_len + DIRECT_ALIGNMENT as usize];
+        let pad = buffer.as_ptr().align_offset(DIRECT_ALIGNMENT as usize);
+        let aligned = &mut buffer[pad..pad + aligned_len];
+
+        // The device may end before the aligned end; short reads stop there
+        let needed = (offset - start) as usize + len;
+        let mut filled = 0;
+        while filled < needed {
+            let read = self.file.read_at(&mut aligned[filled..], start + filled as u64)?;
+            if read == 0 {
+                return Err(std::io::Error::from(std::io::ErrorKind::UnexpectedEof).into());
+            }
+            filled += read;
+        }
+        let skip = (offset - start) as usize;
+        Ok(aligned[skip..skip + len].to_vec())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StructureKind {
+    PartitionTable,
+    BootSignature,
+    GptHeader,
+    GptEntries,
+    Filesystem,
+}
+
+/// A known structure found in a page, in device byte offsets
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Highlight {
+    pub offset: u64,
+    pub len: u64,
+    pub kind: StructureKind,
+    pub label: String,
+}
+
+impl Highlight {
+    pub fn contains(&self, offset: u64) -> bool {
+        offset >= self.offset && offset < self.offset + self.len
+    }
+}
+
+/// Locates partition tables, GPT headers and entries, and filesystem
+/// superblocks in `page`. The partition layout, when known, tells where GPT
+/// entries and partitions start; without it only the disk start is checked.
+pub fn find_structures(page: &SectorPage, layout: Option<&PartitionLayout>) -> Vec<Highlight> {
+    let start = page.offset();
+    let end = start + page.data.len() as u64;
+    let in_page = |offset: u64, len: u64| offset >= start && offset + len <= end;
+    let bytes = |offset: u64, len: u64| &page.data[(offset - start) as usize..(offset - start + len) as usize];
+    let mut found = Vec::new();
+    let mut add = |offset: u64, len: u64, kind: StructureKind, label: String| {
+        // Clipped to the page so highlights never point outside the data
+        let first = offset.max(start);
+        let last = (offset + len).min(end);
+        if first < last {
+            found.push(Highlight { offset: first, len: last - first, kind, label });
+        }
+    };
+
+    // An unpartitioned disk may start with a filesystem boot sector instead
+    let has_table = layout.is_none_or(|layout| !matches!(layout.scheme, PartitionScheme::None));
+    // Every sector that ends in 55 AA: MBR, EBRs and boot sectors
+    for (index, sector) in page.data.chunks_exact(page.sector_size as usize).enumerate() {
+        let sector_start = start + index as u64 * page.sector_size;
+        if sector[510..512] != [0x55, 0xaa] {
+            continue;
+        }
+        if sector_start == 0 && has_table {
+            add(440, 4, StructureKind::PartitionTable, "MBR disk signature".to_string());
+            add(446, 64, StructureKind::PartitionTable, "MBR partition table".to_string());
+        }
+        add(sector_start + 510, 2, StructureKind::BootSignature, "Boot signature (55 AA)".to_string());
+    }
+
+    for (index, sector) in page.data.chunks_exact(page.sector_size as usize).enumerate() {
+        if sector.starts_with(b"EFI PART") {
+            let header_size = u32::from_le_bytes(sector[12..16].try_into().unwrap()).clamp(92, 512) as u64;
+            let offset = start + index as u64 * page.sector_size;
+            add(offset, header_size, StructureKind::GptHeader, "GPT header".to_string());
+        }
+    }
+
+    let mut partition_starts = vec![0];
+    if let Some(layout) = layout {
+        if let PartitionScheme::Gpt { primary, backup, .. } = &layout.scheme {
+            for (label, header) in [("primary", primary), ("backup", backup)] {
+                let Some(header) = header else { continue };
+                let entries = header.entries_lba * layout.sector_size;
+                let len = header.entry_count as u64 * header.entry_size as u64;
+                add(entries, len, StructureKind::GptEntries, format!("GPT {} partition entries", label));
+            }
+        }
+        partition_starts.extend(layout.partitions.iter().map(|p| p.first_lba * layout.sector_size));
+    }
+    for partition_start in partition_starts {
+        for &(label, magic_offset, magic, offset, len) in SIGNATURES {
+            let magic_at = partition_start + magic_offset;
+            if in_page(magic_at, magic.len() as u64) && bytes(magic_at, magic.len() as u64) == magic {
+                add(partition_start + offset, len, StructureKind::Filesystem, label.to_string());
+            }
+        }
+    }
+    found
+}
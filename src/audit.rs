@@ -0,0 +1,349 @@
+//! Audit trail module
+//! Append-only log of erase operations where every entry carries the hash of
+//! the previous one, so edits, reordering and truncation can be detected
+
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{BufRead, BufReader, Read, Seek, SeekFrom, Write},
+    os::fd::AsRawFd,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::cert::now_rfc3339;
+
+/// prev_hash of the first entry in a log
+pub const GENESIS_HASH: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+#[derive(Debug)]
+pub enum AuditError {
+    IoError(std::io::Error),
+    Json(serde_json::Error),
+    Tampered { line: usize, reason: String },
+    Truncated { expected_seq: u64, found_seq: u64 },
+}
+
+impl std::fmt::Display for AuditError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AuditError::IoError(e) => write!(f, "I/O error: {}", e),
+            AuditError::Json(e) => write!(f, "JSON error: {}", e),
+            AuditError::Tampered { line, reason } => {
+                write!(f, "audit log modified at line {}: {}", line, reason)
+            }
+            AuditError::Truncated { expected_seq, found_seq } => write!(
+                f,
+                "audit log truncated: last entry is #{} but #{} was recorded",
+                found_seq, expected_seq
+            ),
+        }
+    }
+}
+
+impl std::error::Error for AuditError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            AuditError::IoError(e) => Some(e),
+            AuditError::Json(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for AuditError {
+    fn from(e: std::io::Error) -> Self {
+        AuditError::IoError(e)
+    }
+}
+
+impl From<serde_json::Error> for AuditError {
+    fn from(e: serde_json::Error) -> Self {
+        AuditError::Json(e)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum AuditEvent {
+    DeviceDetected {
+        path: String,
+        model: Option<String>,
+        serial: Option<String>,
+    },
+    MethodChosen {
+        path: String,
+        method: String,
+    },
+    EraseStarted {
+        path: String,
+        method: String,
+    },
+    EraseFinished {
+        path: String,
+        method: String,
+    },
+    EraseFailed {
+        path: String,
+        method: String,
+        error: String,
+    },
+    VerificationResult {
+        path: String,
+        passed: bool,
+        mismatches: u64,
+    },
+    CertificateIssued {
+        certificate_id: String,
+        paths: Vec<String>,
+    },
+    CertificateRevoked {
+        certificate_id: String,
+        reason: String,
+    },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditEntry {
+    pub seq: u64,
+    pub timestamp: String,
+    pub host: String,
+    pub user: String,
+    pub event: AuditEvent,
+    pub prev_hash: String,
+    pub hash: String,
+}
+
+// Everything but the hash itself, in the order it is hashed
+#[derive(Serialize)]
+struct HashedFields<'a> {
+    seq: u64,
+    timestamp: &'a str,
+    host: &'a str,
+    user: &'a str,
+    event: &'a AuditEvent,
+    prev_hash: &'a str,
+}
+
+impl AuditEntry {
+    fn compute_hash(&self) -> Result<String, AuditError> {
+        let fields = HashedFields {
+            seq: self.seq,
+            timestamp: &self.timestamp,
+            host: &self.host,
+            user: &self.user,
+            event: &self.event,
+            prev_hash: &self.prev_hash,
+        };
+        Ok(hex::encode(Sha256::digest(serde_json::to_vec(&fields)?)))
+    }
+}
+
+// Records the newest entry next to the log, so cutting entries off the end
+// is detected even though the remaining chain is still consistent
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct AuditHead {
+    seq: u64,
+    hash: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct LogVerification {
+    pub entries: u64,
+    pub last_hash: String,
+}
+
+pub struct AuditLog {
+    path: PathBuf,
+}
+
+impl AuditLog {
+    pub fn open(path: &Path) -> Result<Self, AuditError> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(AuditLog { path: path.to_path_buf() })
+    }
+
+    pub fn open_default() -> Result<Self, AuditError> {
+        Self::open(&default_log_path())
+    }
+
+    pub fn append(&self, event: AuditEvent) -> Result<AuditEntry, AuditError> {
+        let mut file = OpenOptions::new().read(true).append(true).open(&self.path)?;
+
+        // Other processes (GUI and CLI) may append concurrently, so the tail
+        // is re-read under an exclusive lock, released when `file` is closed
+        lock_exclusive(&file)?;
+        let (seq, prev_hash) = match read_last_entry(&mut file)? {
+            Some(last) => (last.seq + 1, last.hash),
+            None => (0, GENESIS_HASH.to_string()),
+        };
+
+        let mut entry = AuditEntry {
+            seq,
+            timestamp: now_rfc3339(),
+            host: hostname(),
+            user: current_user(),
+            event,
+            prev_hash,
+            hash: String::new(),
+        };
+        entry.hash = entry.compute_hash()?;
+
+        let mut line = serde_json::to_vec(&entry)?;
+        line.push(b'\n');
+        file.write_all(&line)?;
+        file.sync_data()?;
+
+        // Replaced atomically and made durable like a checkpoint. A crash
+        // before this completes leaves the head one entry behind the log,
+        // which verify_log accepts.
+        let head = AuditHead { seq: entry.seq, hash: entry.hash.clone() };
+        let head_path = head_path(&self.path);
+        let temp_path = head_path.with_extension("head.tmp");
+        let mut temp = File::create(&temp_path)?;
+        temp.write_all(&serde_json::to_vec(&head)?)?;
+        temp.sync_all()?;
+        fs::rename(&temp_path, &head_path)?;
+        if let Some(parent) = head_path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            File::open(parent)?.sync_all()?;
+        }
+
+        Ok(entry)
+    }
+
+    pub fn verify(&self) -> Result<LogVerification, AuditError> {
+        verify_log(&self.path)
+    }
+}
+
+/// Walks the whole log checking sequence numbers, hashes and the chain links
+pub fn verify_log(path: &Path) -> Result<LogVerification, AuditError> {
+    let reader = BufReader::new(File::open(path)?);
+    let mut expected_seq = 0;
+    let mut prev_hash = GENESIS_HASH.to_string();
+    let mut before_last = GENESIS_HASH.to_string();
+
+    for (index, line) in reader.lines().enumerate() {
+        let line_number = index + 1;
+        let line = line?;
+        let tampered = |reason: &str| AuditError::Tampered {
+            line: line_number,
+            reason: reason.to_string(),
+        };
+
+        let entry: AuditEntry =
+            serde_json::from_str(&line).map_err(|_| tampered("entry is not valid JSON"))?;
+        if entry.seq != expected_seq {
+            return Err(tampered(&format!(
+                "expected entry #{} but found #{}",
+                expected_seq, entry.seq
+            )));
+        }
+        if entry.prev_hash != prev_hash {
+            return Err(tampered("chain link does not match the previous entry"));
+        }
+        if entry.compute_hash()? != entry.hash {
+            return Err(tampered("entry contents do not match its hash"));
+        }
+
+        before_last = std::mem::replace(&mut prev_hash, entry.hash);
+        expected_seq += 1;
+    }
+
+    // Every append records the head, so a log with entries must have one.
+    // The first append can crash before its head is written; that one case
+    // is indistinguishable from a removed head and is reported too.
+    let head = match fs::read(head_path(path)) {
+        Ok(head) => Some(head),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound && expected_seq > 0 => {
+            return Err(AuditError::Tampered {
+                line: expected_seq as usize,
+                reason: "the record of the last entry is missing, entries may have been cut off".to_string(),
+            });
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+        Err(e) => return Err(e.into()),
+    };
+    if let Some(head) = head {
+        let head: AuditHead = serde_json::from_slice(&head)?;
+        let found_seq = expected_seq.saturating_sub(1);
+        // The log is synced before the head is replaced, so after a crash in
+        // between the head names the entry before the last one
+        let (line, recorded) = match expected_seq {
+            0 => return Err(AuditError::Truncated { expected_seq: head.seq, found_seq }),
+            _ if head.seq == found_seq => (expected_seq, &prev_hash),
+            2.. if head.seq + 1 == found_seq => (expected_seq - 1, &before_last),
+            _ => return Err(AuditError::Truncated { expected_seq: head.seq, found_seq }),
+        };
+        if head.hash != *recorded {
+            return Err(AuditError::Tampered {
+                line: line as usize,
+                reason: "entry does not match the recorded head".to_string(),
+            });
+        }
+    }
+
+    Ok(LogVerification { entries: expected_seq, last_hash: prev_hash })
+}
+
+pub fn default_log_path() -> PathBuf {
+    crate::libs::data_dir().join("audit.log")
+}
+
+fn head_path(path: &Path) -> PathBuf {
+    path.with_extension("head")
+}
+
+// Reads backwards from the end of the file to the start of the last line
+fn read_last_entry(file: &mut File) -> Result<Option<AuditEntry>, AuditError> {
+    const CHUNK: u64 = 4096;
+    let length = file.seek(SeekFrom::End(0))?;
+    let mut tail = Vec::new();
+    let mut position = length;
+
+    while position > 0 {
+        let start = position.saturating_sub(CHUNK);
+        let mut chunk = vec![0u8; (position - start) as usize];
+        file.seek(SeekFrom::Start(start))?;
+        file.read_exact(&mut chunk)?;
+        chunk.extend_from_slice(&tail);
+        tail = chunk;
+        position = start;
+
+        let trimmed = tail.trim_ascii_end();
+        if let Some(newline) = trimmed.iter().rposition(|&b| b == b'\n') {
+            tail = trimmed[newline + 1..].to_vec();
+            break;
+        }
+    }
+
+    let line = tail.trim_ascii();
+    if line.is_empty() {
+        return Ok(None);
+    }
+    Ok(Some(serde_json::from_slice(line)?))
+}
+
+fn hostname() -> String {
+    fs::read_to_string("/proc/sys/kernel/hostname")
+        .map(|h| h.trim().to_string())
+        .unwrap_or_default()
+}
+
+fn current_user() -> String {
+    ["SUDO_USER", "USER", "LOGNAME"]
+        .iter()
+        .find_map(|var| std::env::var(var).ok().filter(|v| !v.is_empty()))
+        .unwrap_or_default()
+}
+
+fn lock_exclusive(file: &File) -> Result<(), AuditError> {
+    if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX) } != 0 {
+        return Err(AuditError::IoError(std::io::Error::last_os_error()));
+    }
+    Ok(())
+}
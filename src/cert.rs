@@ -1,15 +0,0 @@
-// Certificate generation module
-// Creates compliance certificates in JSON and PDF formats
-
-// TODO: Add certificate data structures
-// TODO: Add JSON certificate generation
-// TODO: Add PDF certificate generation
-// TODO: Add digital signature support
-// TODO: Add timestamp integration
-// TODO: Add compliance standard templates
-// TODO: Add custom certificate templates
-// TODO: Add certificate validation
-// TODO: Add metadata inclusion (device info, method used, etc.)
-// TODO: Add QR code generation for certificates
-// TODO: Add certificate storage and retrieval
-// TODO: Add audit trail functionality
\ No newline at end of file
@@ -0,0 +1,512 @@
+//! CMS/PKCS#7 signatures for enterprise PKI
+//! Signs certificates with a PEM key chained to a corporate CA, and verifies
+//! detached signatures and signed PDFs against a trust bundle
+
+use std::{fs, path::Path, time::{Duration, SystemTime, UNIX_EPOCH}};
+
+use cms::{
+    builder::{SignedDataBuilder, SignerInfoBuilder, create_signing_time_attribute},
+    cert::{CertificateChoices, IssuerAndSerialNumber},
+    content_info::ContentInfo,
+    signed_data::{EncapsulatedContentInfo, SignedData, SignerIdentifier, SignerInfo},
+};
+use const_oid::{ObjectIdentifier, db::{rfc5911, rfc5912}};
+use der::{Decode, Encode, SliceReader, asn1::OctetString};
+use p256::pkcs8::DecodePrivateKey;
+use rsa::pkcs1::DecodeRsaPrivateKey;
+use sha2::{Digest, Sha256, Sha384, Sha512};
+use signature::{Verifier, hazmat::PrehashVerifier};
+use spki::{AlgorithmIdentifierOwned, DecodePublicKey, EncodePublicKey, SubjectPublicKeyInfoOwned};
+use x509_cert::{Certificate as X509Certificate, ext::pkix::BasicConstraints};
+
+use crate::cert::{CertError, Certificate, pdf, template::CertificateTemplate};
+
+// Space reserved in a signed PDF for the DER signature, chain included
+const PDF_SIGNATURE_RESERVE: usize = 16 * 1024;
+const MAX_CHAIN_DEPTH: usize = 8;
+
+enum CmsKey {
+    Rsa(Box<rsa::pkcs1v15::SigningKey<Sha256>>),
+    EcP256(p256::ecdsa::SigningKey),
+}
+
+pub struct CmsSigner {
+    key: CmsKey,
+    // Signer certificate first, followed by any intermediates
+    chain: Vec<X509Certificate>,
+}
+
+#[derive(Debug, Clone)]
+pub struct CmsVerification {
+    pub signer: String,
+    pub chain: Vec<String>,
+    pub signing_time: Option<String>,
+}
+
+impl CmsSigner {
+    pub fn load(key_path: &Path, chain_path: &Path) -> Result<Self, CertError> {
+        let key = load_private_key(&fs::read_to_string(key_path)?)?;
+        let chain = load_certificates(chain_path)?;
+        let leaf = chain
+            .first()
+            .ok_or_else(|| CertError::Chain("certificate chain file is empty".to_string()))?;
+
+        let public_key = match &key {
+            CmsKey::Rsa(key) => rsa::RsaPublicKey::from(key.as_ref().as_ref()).to_public_key_der(),
+            CmsKey::EcP256(key) => key.verifying_key().to_public_key_der(),
+        }
+        .map_err(|e| CertError::Key(e.to_string()))?;
+        let leaf_key = leaf
+            .tbs_certificate
+            .subject_public_key_info
+            .to_der()
+            .map_err(cms_error)?;
+        if public_key.as_bytes() != leaf_key.as_slice() {
+            return Err(CertError::Key(
+                "private key does not match the first certificate in the chain".to_string(),
+            ));
+        }
+
+        Ok(CmsSigner { key, chain })
+    }
+
+    pub fn subject(&self) -> String {
+        self.chain[0].tbs_certificate.subject.to_string()
+    }
+
+    /// Produces a DER-encoded detached SignedData over `data`
+    pub fn sign_detached(&self, data: &[u8]) -> Result<Vec<u8>, CertError> {
+        let leaf = &self.chain[0];
+        let content = EncapsulatedContentInfo { econtent_type: rfc5911::ID_DATA, econtent: None };
+        let digest = Sha256::digest(data);
+        let digest_algorithm = AlgorithmIdentifierOwned { oid: rfc5912::ID_SHA_256, parameters: None };
+        let sid = SignerIdentifier::IssuerAndSerialNumber(IssuerAndSerialNumber {
+            issuer: leaf.tbs_certificate.issuer.clone(),
+            serial_number: leaf.tbs_certificate.serial_number.clone(),
+        });
+
+        let mut builder = SignedDataBuilder::new(&content);
+        builder.add_digest_algorithm(digest_algorithm.clone()).map_err(cms_error)?;
+        for certificate in &self.chain {
+            builder
+                .add_certificate(CertificateChoices::Certificate(certificate.clone()))
+                .map_err(cms_error)?;
+        }
+
+        let signing_time = create_signing_time_attribute().map_err(cms_error)?;
+        match &self.key {
+            CmsKey::Rsa(key) => {
+                let mut signer_info =
+                    SignerInfoBuilder::new(key.as_ref(), sid, digest_algorithm, &content, Some(&digest))
+                        .map_err(cms_error)?;
+                signer_info.add_signed_attribute(signing_time).map_err(cms_error)?;
+                builder
+                    .add_signer_info::<_, rsa::pkcs1v15::Signature>(signer_info)
+                    .map_err(cms_error)?;
+            }
+            CmsKey::EcP256(key) => {
+                let mut signer_info =
+                    SignerInfoBuilder::new(key, sid, digest_algorithm, &content, Some(&digest))
+                        .map_err(cms_error)?;
+                signer_info.add_signed_attribute(signing_time).map_err(cms_error)?;
+                builder
+                    .add_signer_info::<_, p256::ecdsa::DerSignature>(signer_info)
+                    .map_err(cms_error)?;
+            }
+        }
+
+        builder.build().map_err(cms_error)?.to_der().map_err(cms_error)
+    }
+
+    /// Renders the certificate as a PDF carrying an embedded adbe.pkcs7.detached signature
+    pub fn sign_pdf(
+        &self,
+        cert: &Certificate,
+        template: &CertificateTemplate,
+    ) -> Result<Vec<u8>, CertError> {
+        let mut document = pdf::render_pdf_for_signing(cert, template, &self.subject(), PDF_SIGNATURE_RESERVE)?;
+        let slot = find_signature_slot(&document)?;
+
+        let byte_range = [
+            0,
+            slot.contents_start,
+            slot.contents_end,
+            document.len() - slot.contents_end,
+        ];
+        let formatted = format!(
+            "[{} {} {} {}]",
+            byte_range[0], byte_range[1], byte_range[2], byte_range[3]
+        );
+        if formatted.len() > slot.byte_range_len {
+            return Err(CertError::Cms("byte range does not fit its placeholder".to_string()));
+        }
+        let mut padded = formatted.into_bytes();
+        padded.resize(slot.byte_range_len - 1, b' ');
+        padded.push(b']');
+        document[slot.byte_range_start..slot.byte_range_start + slot.byte_range_len]
+            .copy_from_slice(&padded);
+
+        let mut signed_bytes = document[..slot.contents_start].to_vec();
+        signed_bytes.extend_from_slice(&document[slot.contents_end..]);
+        let signature = self.sign_detached(&signed_bytes)?;
+
+        // Hex digits between the angle brackets, zero padded to the reserved size
+        let capacity = slot.contents_end - slot.contents_start - 2;
+        let encoded = hex::encode_upper(&signature);
+        if encoded.len() > capacity {
+            return Err(CertError::Cms("signature exceeds the space reserved in the PDF".to_string()));
+        }
+        let digits = &mut document[slot.contents_start + 1..slot.contents_end - 1];
+        digits.fill(b'0');
+        digits[..encoded.len()].copy_from_slice(encoded.as_bytes());
+
+        Ok(document)
+    }
+}
+
+pub fn load_certificates(path: &Path) -> Result<Vec<X509Certificate>, CertError> {
+    let bytes = fs::read(path)?;
+    if bytes.starts_with(b"-----") || bytes.windows(11).any(|w| w == b"-----BEGIN ") {
+        X509Certificate::load_pem_chain(&bytes).map_err(cms_error)
+    } else {
+        Ok(vec![X509Certificate::from_der(&bytes).map_err(cms_error)?])
+    }
+}
+
+/// Verifies a detached signature over `data` and chains the signer to `trust`
+pub fn verify_detached(
+    signature: &[u8],
+    data: &[u8],
+    trust: &[X509Certificate],
+) -> Result<CmsVerification, CertError> {
+    let mut reader = SliceReader::new(signature).map_err(cms_error)?;
+    let content_info = ContentInfo::decode(&mut reader).map_err(cms_error)?;
+    if content_info.content_type != rfc5911::ID_SIGNED_DATA {
+        return Err(CertError::Cms("not a CMS SignedData structure".to_string()));
+    }
+    let signed_data: SignedData = content_info.content.decode_as().map_err(cms_error)?;
+
+    let embedded: Vec<X509Certificate> = signed_data
+        .certificates
+        .iter()
+        .flat_map(|set| set.0.iter())
+        .filter_map(|choice| match choice {
+            CertificateChoices::Certificate(certificate) => Some(certificate.clone()),
+            _ => None,
+        })
+        .collect();
+
+    let signer_info = signed_data
+        .signer_infos
+        .0
+        .iter()
+        .next()
+        .ok_or_else(|| CertError::Cms("signature has no signer".to_string()))?;
+    let signer = find_signer_certificate(signer_info, &embedded)?;
+    let signing_time = verify_signer_info(signer_info, signer, data)?;
+    let chain = verify_chain(signer, &embedded, trust)?;
+
+    Ok(CmsVerification {
+        signer: signer.tbs_certificate.subject.to_string(),
+        chain,
+        signing_time,
+    })
+}
+
+/// Verifies the embedded signature of a PDF produced by `CmsSigner::sign_pdf`
+pub fn verify_pdf(document: &[u8], trust: &[X509Certificate]) -> Result<CmsVerification, CertError> {
+    let slot = find_signature_slot(document)?;
+    let range = &document[slot.byte_range_start..slot.byte_range_start + slot.byte_range_len];
+    let numbers: Vec<usize> = String::from_utf8_lossy(range)
+        .trim_matches(|c| c == '[' || c == ']' || c == ' ')
+        .split_whitespace()
+        .map(|n| n.parse::<usize>())
+        .collect::<Result<_, _>>()
+        .map_err(|_| CertError::Cms("malformed /ByteRange".to_string()))?;
+    let [start, first_len, second_start, second_len] = numbers[..] else {
+        return Err(CertError::Cms("malformed /ByteRange".to_string()));
+    };
+    if start != 0
+        || first_len != slot.contents_start
+        || second_start != slot.contents_end
+        || second_start + second_len != document.len()
+    {
+        return Err(CertError::Cms("/ByteRange does not cover the whole document".to_string()));
+    }
+
+    let mut signed_bytes = document[..first_len].to_vec();
+    signed_bytes.extend_from_slice(&document[second_start..]);
+    let signature = hex::decode(&document[slot.contents_start + 1..slot.contents_end - 1])
+        .map_err(|e| CertError::Cms(e.to_string()))?;
+
+    verify_detached(&signature, &signed_bytes, trust)
+}
+
+fn load_private_key(pem: &str) -> Result<CmsKey, CertError> {
+    if pem.contains("BEGIN RSA PRIVATE KEY") {
+        let key = rsa::RsaPrivateKey::from_pkcs1_pem(pem).map_err(|e| CertError::Key(e.to_string()))?;
+        return Ok(CmsKey::Rsa(Box::new(rsa::pkcs1v15::SigningKey::new(key))));
+    }
+    if pem.contains("BEGIN EC PRIVATE KEY") {
+        let key = p256::SecretKey::from_sec1_pem(pem).map_err(|e| CertError::Key(e.to_string()))?;
+        return Ok(CmsKey::EcP256(key.into()));
+    }
+    if let Ok(key) = rsa::RsaPrivateKey::from_pkcs8_pem(pem) {
+        return Ok(CmsKey::Rsa(Box::new(rsa::pkcs1v15::SigningKey::new(key))));
+    }
+    p256::ecdsa::SigningKey::from_pkcs8_pem(pem)
+        .map(CmsKey::EcP256)
+        .map_err(|_| CertError::Key("unsupported private key, expected RSA or P-256 PEM".to_string()))
+}
+
+fn find_signer_certificate<'a>(
+    signer_info: &SignerInfo,
+    certificates: &'a [X509Certificate],
+) -> Result<&'a X509Certificate, CertError> {
+    certificates
+        .iter()
+        .find(|certificate| match &signer_info.sid {
+            SignerIdentifier::IssuerAndSerialNumber(id) => {
+                certificate.tbs_certificate.issuer == id.issuer
+                    && certificate.tbs_certificate.serial_number == id.serial_number
+            }
+            SignerIdentifier::SubjectKeyIdentifier(ski) => certificate
+                .tbs_certificate
+                .get::<x509_cert::ext::pkix::SubjectKeyIdentifier>()
+                .ok()
+                .flatten()
+                .is_some_and(|(_, ext)| ext == *ski),
+        })
+        .ok_or_else(|| CertError::Chain("signer certificate is not included in the signature".to_string()))
+}
+
+// Checks the message digest and the signature over the signed attributes,
+// returning the signing time if one was recorded
+fn verify_signer_info(
+    signer_info: &SignerInfo,
+    signer: &X509Certificate,
+    data: &[u8],
+) -> Result<Option<String>, CertError> {
+    let attributes = signer_info
+        .signed_attrs
+        .as_ref()
+        .ok_or_else(|| CertError::Cms("signature has no signed attributes".to_string()))?;
+
+    let digest = match signer_info.digest_alg.oid {
+        rfc5912::ID_SHA_256 => Sha256::digest(data).to_vec(),
+        rfc5912::ID_SHA_384 => Sha384::digest(data).to_vec(),
+        rfc5912::ID_SHA_512 => Sha512::digest(data).to_vec(),
+        oid => return Err(CertError::Cms(format!("unsupported digest algorithm {}", oid))),
+    };
+
+    let mut digest_matches = false;
+    let mut signing_time = None;
+    for attribute in attributes.iter() {
+        let Some(value) = attribute.values.iter().next() else {
+            continue;
+        };
+        if attribute.oid == rfc5911::ID_MESSAGE_DIGEST {
+            let value: OctetString = value.decode_as().map_err(cms_error)?;
+            digest_matches = value.as_bytes() == digest.as_slice();
+        } else if attribute.oid == rfc5911::ID_SIGNING_TIME {
+            let time = x509_cert::time::Time::from_der(&value.to_der().map_err(cms_error)?)
+                .map_err(cms_error)?;
+            signing_time = Some(time.to_string());
+        }
+    }
+    if !digest_matches {
+        return Err(CertError::InvalidSignature);
+    }
+
+    let message = attributes.to_der().map_err(cms_error)?;
+    let algorithm = signature_digest(&signer_info.signature_algorithm, &signer_info.digest_alg.oid)?;
+    verify_signature(
+        &signer.tbs_certificate.subject_public_key_info,
+        algorithm,
+        &message,
+        signer_info.signature.as_bytes(),
+    )?;
+    Ok(signing_time)
+}
+
+// Walks from `leaf` through `intermediates` until a certificate in `trust` vouches
+// for it. Returns the subjects of the chain, leaf first.
+fn verify_chain(
+    leaf: &X509Certificate,
+    intermediates: &[X509Certificate],
+    trust: &[X509Certificate],
+) -> Result<Vec<String>, CertError> {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+    let mut current = leaf;
+    let mut subjects = Vec::new();
+
+    for _ in 0..MAX_CHAIN_DEPTH {
+        check_validity(current, now)?;
+        subjects.push(current.tbs_certificate.subject.to_string());
+
+        if trust.iter().any(|anchor| anchor == current) {
+            return Ok(subjects);
+        }
+        if let Some(anchor) = trust.iter().find(|anchor| is_issuer(anchor, current)) {
+            check_validity(anchor, now)?;
+            subjects.push(anchor.tbs_certificate.subject.to_string());
+            return Ok(subjects);
+        }
+
+        current = intermediates
+            .iter()
+            .find(|candidate| *candidate != current && is_issuer(candidate, current))
+            .ok_or_else(|| {
+                CertError::Chain(format!(
+                    "no trusted issuer found for {}",
+                    current.tbs_certificate.subject
+                ))
+            })?;
+    }
+
+    Err(CertError::Chain("certificate chain is too long".to_string()))
+}
+
+fn is_issuer(issuer: &X509Certificate, subject: &X509Certificate) -> bool {
+    if issuer.tbs_certificate.subject != subject.tbs_certificate.issuer {
+        return false;
+    }
+    let is_ca = issuer
+        .tbs_certificate
+        .get::<BasicConstraints>()
+        .ok()
+        .flatten()
+        .is_some_and(|(_, constraints)| constraints.ca);
+    if !is_ca {
+        return false;
+    }
+    let Ok(tbs) = subject.tbs_certificate.to_der() else {
+        return false;
+    };
+    let Ok(algorithm) = signature_digest(&subject.signature_algorithm, &rfc5912::ID_SHA_256) else {
+        return false;
+    };
+    verify_signature(
+        &issuer.tbs_certificate.subject_public_key_info,
+        algorithm,
+        &tbs,
+        subject.signature.raw_bytes(),
+    )
+    .is_ok()
+}
+
+fn check_validity(certificate: &X509Certificate, now: Duration) -> Result<(), CertError> {
+    let validity = &certificate.tbs_certificate.validity;
+    if now < validity.not_before.to_unix_duration() || now > validity.not_after.to_unix_duration() {
+        return Err(CertError::Chain(format!(
+            "certificate {} is outside its validity period",
+            certificate.tbs_certificate.subject
+        )));
+    }
+    Ok(())
+}
+
+#[derive(Clone, Copy)]
+enum SignatureDigest {
+    Sha256,
+    Sha384,
+    Sha512,
+}
+
+// Resolves the hash to use for a signature algorithm. Plain rsaEncryption
+// defers to the separately declared digest algorithm, as CMS allows.
+fn signature_digest(
+    algorithm: &AlgorithmIdentifierOwned,
+    digest: &ObjectIdentifier,
+) -> Result<SignatureDigest, CertError> {
+    match algorithm.oid {
+        rfc5912::SHA_256_WITH_RSA_ENCRYPTION | rfc5912::ECDSA_WITH_SHA_256 => Ok(SignatureDigest::Sha256),
+        rfc5912::SHA_384_WITH_RSA_ENCRYPTION | rfc5912::ECDSA_WITH_SHA_384 => Ok(SignatureDigest::Sha384),
+        rfc5912::SHA_512_WITH_RSA_ENCRYPTION | rfc5912::ECDSA_WITH_SHA_512 => Ok(SignatureDigest::Sha512),
+        rfc5912::RSA_ENCRYPTION => match *digest {
+            rfc5912::ID_SHA_256 => Ok(SignatureDigest::Sha256),
+            rfc5912::ID_SHA_384 => Ok(SignatureDigest::Sha384),
+            rfc5912::ID_SHA_512 => Ok(SignatureDigest::Sha512),
+            oid => Err(CertError::Cms(format!("unsupported digest algorithm {}", oid))),
+        },
+        oid => Err(CertError::Cms(format!("unsupported signature algorithm {}", oid))),
+    }
+}
+
+fn verify_signature(
+    public_key: &SubjectPublicKeyInfoOwned,
+    digest: SignatureDigest,
+    message: &[u8],
+    signature: &[u8],
+) -> Result<(), CertError> {
+    let key_der = public_key.to_der().map_err(cms_error)?;
+    match public_key.algorithm.oid {
+        rfc5912::RSA_ENCRYPTION => {
+            let key = rsa::RsaPublicKey::from_public_key_der(&key_der)
+                .map_err(|e| CertError::Key(e.to_string()))?;
+            let signature = rsa::pkcs1v15::Signature::try_from(signature)
+                .map_err(|_| CertError::InvalidSignature)?;
+            let result = match digest {
+                SignatureDigest::Sha256 => rsa::pkcs1v15::VerifyingKey::<Sha256>::new(key).verify(message, &signature),
+                SignatureDigest::Sha384 => rsa::pkcs1v15::VerifyingKey::<Sha384>::new(key).verify(message, &signature),
+                SignatureDigest::Sha512 => rsa::pkcs1v15::VerifyingKey::<Sha512>::new(key).verify(message, &signature),
+            };
+            result.map_err(|_| CertError::InvalidSignature)
+        }
+        rfc5912::ID_EC_PUBLIC_KEY => {
+            let key = p256::ecdsa::VerifyingKey::from_public_key_der(&key_der)
+                .map_err(|e| CertError::Key(format!("only P-256 EC keys are supported: {}", e)))?;
+            let signature = p256::ecdsa::Signature::from_der(signature)
+                .map_err(|_| CertError::InvalidSignature)?;
+            let result = match digest {
+                SignatureDigest::Sha256 => key.verify(message, &signature),
+                SignatureDigest::Sha384 => key.verify_prehash(&Sha384::digest(message), &signature),
+                SignatureDigest::Sha512 => key.verify_prehash(&Sha512::digest(message), &signature),
+            };
+            result.map_err(|_| CertError::InvalidSignature)
+        }
+        oid => Err(CertError::Key(format!("unsupported public key algorithm {}", oid))),
+    }
+}
+
+struct SignatureSlot {
+    byte_range_start: usize,
+    byte_range_len: usize,
+    // Offsets of the hex string in /Contents, including its angle brackets
+    contents_start: usize,
+    contents_end: usize,
+}
+
+// The signature dictionary is the last object written and ends with its
+// placeholders, so they are searched for from the end. Page content before it
+// may contain the same tokens in certificate text.
+fn find_signature_slot(document: &[u8]) -> Result<SignatureSlot, CertError> {
+    let missing = || CertError::Cms("PDF has no signature dictionary".to_string());
+
+    let contents_key = rfind_bytes(document, b"/Contents <").ok_or_else(missing)?;
+    let byte_range_key = rfind_bytes(&document[..contents_key], b"/ByteRange ").ok_or_else(missing)?;
+    let byte_range_start = byte_range_key + b"/ByteRange ".len();
+    let byte_range_len = document[byte_range_start..]
+        .iter()
+        .position(|&b| b == b']')
+        .ok_or_else(missing)?
+        + 1;
+
+    let contents_start = contents_key + b"/Contents ".len();
+    let contents_end = document[contents_start..]
+        .iter()
+        .position(|&b| b == b'>')
+        .ok_or_else(missing)?
+        + contents_start
+        + 1;
+
+    Ok(SignatureSlot { byte_range_start, byte_range_len, contents_start, contents_end })
+}
+
+fn rfind_bytes(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).rposition(|window| window == needle)
+}
+
+fn cms_error<E: std::fmt::Display>(e: E) -> CertError {
+    CertError::Cms(e.to_string())
+}
@@ -0,0 +1,516 @@
+//! Certificate generation module
+//! Creates compliance certificates in JSON and PDF formats
+
+pub mod cms;
+pub mod pdf;
+pub mod store;
+pub mod template;
+
+use std::{fs, path::{Path, PathBuf}};
+
+use base64::{Engine, engine::general_purpose::{STANDARD, URL_SAFE_NO_PAD}};
+use ed25519_dalek::{
+    Signature, Signer, SigningKey, Verifier, VerifyingKey,
+    pkcs8::{DecodePrivateKey, EncodePrivateKey, spki::der::pem::LineEnding},
+};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use time::{OffsetDateTime, format_description::well_known::Rfc3339};
+
+use crate::{
+    erasure::errormap::ErrorMap,
+    platform::{BlockGeometry, StorageDevice},
+};
+
+/// Prefix of the compact payload encoded in the QR code, bumped on format changes
+pub const COMPACT_PREFIX: &str = "SECERT1";
+pub const SIGNATURE_ALGORITHM: &str = "Ed25519";
+
+#[derive(Debug)]
+pub enum CertError {
+    IoError(std::io::Error),
+    Json(serde_json::Error),
+    Key(String),
+    Unsigned,
+    InvalidSignature,
+    UntrustedSigner(String),
+    MalformedPayload(String),
+    QrCode(String),
+    Cms(String),
+    Chain(String),
+    Store(String),
+}
+
+impl std::fmt::Display for CertError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CertError::IoError(e) => write!(f, "I/O error: {}", e),
+            CertError::Json(e) => write!(f, "JSON error: {}", e),
+            CertError::Key(e) => write!(f, "signing key error: {}", e),
+            CertError::Unsigned => write!(f, "certificate is not signed"),
+            CertError::InvalidSignature => write!(f, "certificate signature is invalid"),
+            CertError::UntrustedSigner(fp) => write!(f, "certificate signed by untrusted key {}", fp),
+            CertError::MalformedPayload(e) => write!(f, "malformed compact payload: {}", e),
+            CertError::QrCode(e) => write!(f, "QR code error: {}", e),
+            CertError::Cms(e) => write!(f, "CMS error: {}", e),
+            CertError::Chain(e) => write!(f, "certificate chain error: {}", e),
+            CertError::Store(e) => write!(f, "certificate store error: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for CertError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            CertError::IoError(e) => Some(e),
+            CertError::Json(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for CertError {
+    fn from(e: std::io::Error) -> Self {
+        CertError::IoError(e)
+    }
+}
+
+impl From<serde_json::Error> for CertError {
+    fn from(e: serde_json::Error) -> Self {
+        CertError::Json(e)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CertificateResult {
+    Passed,
+    Failed,
+}
+
+impl std::fmt::Display for CertificateResult {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CertificateResult::Passed => write!(f, "Passed"),
+            CertificateResult::Failed => write!(f, "Failed"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeviceRecord {
+    pub path: String,
+    pub model: Option<String>,
+    pub serial_number: Option<String>,
+    pub vendor: Option<String>,
+    pub size: Option<u64>,
+    // Fields below are absent from certificates issued before they were recorded
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub geometry: Option<BlockGeometry>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub firmware: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub wwn: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub transport: Option<String>,
+    /// Stable `/dev/disk/by-id` name
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub by_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub by_path: Option<String>,
+    pub device_type: String,
+}
+
+impl From<&StorageDevice> for DeviceRecord {
+    fn from(device: &StorageDevice) -> Self {
+        DeviceRecord {
+            path: device.path.clone(),
+            model: device.model.clone(),
+            serial_number: device.serial_number.clone(),
+            vendor: device.vendor.clone(),
+            size: device.size,
+            geometry: device.geometry,
+            firmware: device.firmware.clone(),
+            wwn: device.wwn.clone(),
+            transport: Some(device.transport.to_string()),
+            by_id: device.stable_id().map(str::to_string),
+            by_path: device.by_path.clone(),
+            device_type: format!("{:?}", device.device_type),
+        }
+    }
+}
+
+impl DeviceRecord {
+    /// Text the operator types to confirm erasing this device: the last four
+    /// characters of the serial number, or the device name without one
+    pub fn confirmation_code(&self) -> String {
+        match self.serial_number.as_deref().map(str::trim).filter(|s| !s.is_empty()) {
+            Some(serial) => {
+                let chars: Vec<char> = serial.chars().collect();
+                chars[chars.len().saturating_sub(4)..].iter().collect()
+            }
+            None => Path::new(&self.path)
+                .file_name()
+                .map_or_else(|| self.path.clone(), |name| name.to_string_lossy().to_string()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct VerificationSummary {
+    pub method: String,
+    pub bytes_verified: u64,
+    pub mismatches: u64,
+    pub passed: bool,
+    // Error fields are absent from certificates issued before they were recorded
+    /// Sectors the overwrite could not write
+    #[serde(default, skip_serializing_if = "ErrorMap::is_empty")]
+    pub write_errors: ErrorMap,
+    /// Sectors verification could not read back
+    #[serde(default, skip_serializing_if = "ErrorMap::is_empty")]
+    pub read_errors: ErrorMap,
+    /// Policy that judged the errors, when there were any
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error_policy: Option<String>,
+}
+
+/// Drive health as recorded in a certificate, see [`crate::health`]
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HealthSummary {
+    pub source: String,
+    pub passed: Option<bool>,
+    pub reallocated_sectors: Option<u64>,
+    pub pending_sectors: Option<u64>,
+    pub media_errors: Option<u64>,
+    pub power_on_hours: Option<u64>,
+    pub percent_used: Option<u8>,
+    pub temperature_celsius: Option<i16>,
+    pub warnings: Vec<String>,
+}
+
+/// An interrupted overwrite carrying on from its checkpoint, see
+/// [`crate::erasure::checkpoint`]
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ResumeRecord {
+    pub resumed_at: String,
+    /// Pass that was in progress, counted from 1
+    pub pass: usize,
+    pub passes: usize,
+    /// Bytes of that pass already written
+    pub offset: u64,
+}
+
+impl std::fmt::Display for ResumeRecord {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.pass > self.passes {
+            return write!(f, "{}, all {} passes written", self.resumed_at, self.passes);
+        }
+        write!(f, "{}, pass {} of {} from byte {}", self.resumed_at, self.pass, self.passes, self.offset)
+    }
+}
+
+/// The signed part of a certificate. Field order is the serialization order,
+/// which is what the signature covers, so do not reorder fields.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CertificateBody {
+    pub id: String,
+    pub issued_at: String,
+    pub operator: String,
+    pub organization: Option<String>,
+    pub devices: Vec<DeviceRecord>,
+    pub method: String,
+    pub standard: Option<String>,
+    pub started_at: Option<String>,
+    pub finished_at: Option<String>,
+    pub verification: VerificationSummary,
+    pub result: CertificateResult,
+    /// Whether the erase was read back; see [`CertificateBody::is_verified`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub verified: Option<bool>,
+    // Absent from certificates issued before health was recorded
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub health_before: Option<HealthSummary>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub health_after: Option<HealthSummary>,
+    /// Times the erase was interrupted and resumed
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub resumes: Vec<ResumeRecord>,
+}
+
+impl CertificateBody {
+    pub fn new(operator: &str, devices: Vec<DeviceRecord>, method: &str) -> Self {
+        CertificateBody {
+            id: new_certificate_id(),
+            issued_at: now_rfc3339(),
+            operator: operator.to_string(),
+            organization: None,
+            devices,
+            method: method.to_string(),
+            standard: None,
+            started_at: None,
+            finished_at: None,
+            verification: VerificationSummary::default(),
+            result: CertificateResult::Failed,
+            verified: Some(false),
+            health_before: None,
+            health_after: None,
+            resumes: Vec::new(),
+        }
+    }
+
+    /// A passed erase that was not read back, e.g. with verification off or a
+    /// firmware erase, is complete but unverified. Certificates issued before
+    /// this was recorded only carry a verification method when one ran.
+    pub fn is_verified(&self) -> bool {
+        self.verified.unwrap_or(!self.verification.method.is_empty())
+    }
+
+    pub fn to_signing_bytes(&self) -> Result<Vec<u8>, CertError> {
+        Ok(serde_json::to_vec(self)?)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CertificateSignature {
+    pub algorithm: String,
+    pub public_key: String,
+    pub fingerprint: String,
+    pub value: String,
+    pub compact: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Certificate {
+    pub body: CertificateBody,
+    pub signature: Option<CertificateSignature>,
+}
+
+impl Certificate {
+    pub fn unsigned(body: CertificateBody) -> Self {
+        Certificate { body, signature: None }
+    }
+
+    pub fn to_json(&self) -> Result<String, CertError> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, CertError> {
+        Ok(serde_json::from_str(json)?)
+    }
+
+    pub fn load(path: &Path) -> Result<Self, CertError> {
+        Self::from_json(&fs::read_to_string(path)?)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), CertError> {
+        fs::write(path, self.to_json()?)?;
+        Ok(())
+    }
+
+    /// Checks the embedded signature and compact payload. When a trusted
+    /// fingerprint is given, the signing key must also match it; without one
+    /// this only shows the certificate is consistent with its own embedded
+    /// key, which anyone can produce.
+    pub fn verify(&self, trusted_fingerprint: Option<&str>) -> Result<(), CertError> {
+        let signature = self.signature.as_ref().ok_or(CertError::Unsigned)?;
+        let key = decode_verifying_key(&signature.public_key)?;
+
+        if key_fingerprint(&key) != signature.fingerprint {
+            return Err(CertError::InvalidSignature);
+        }
+        if let Some(trusted) = trusted_fingerprint
+            && !trusted.eq_ignore_ascii_case(&signature.fingerprint)
+        {
+            return Err(CertError::UntrustedSigner(signature.fingerprint.clone()));
+        }
+
+        let value = decode_signature(&STANDARD, &signature.value)?;
+        key.verify(&self.body.to_signing_bytes()?, &value)
+            .map_err(|_| CertError::InvalidSignature)?;
+
+        let compact = verify_compact(&signature.compact, &key)?;
+        if compact.id != self.body.id || compact.h != body_digest(&self.body)? {
+            return Err(CertError::InvalidSignature);
+        }
+        Ok(())
+    }
+}
+
+/// Short form of a certificate that fits in a QR code. `h` is the SHA-256 of the
+/// full signed body, so a scanned code can be matched against the JSON document.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CompactPayload {
+    pub id: String,
+    pub iat: String,
+    pub sn: Vec<String>,
+    pub m: String,
+    pub ok: bool,
+    pub fp: String,
+    pub h: String,
+}
+
+pub struct CertificateSigner {
+    key: SigningKey,
+}
+
+impl CertificateSigner {
+    pub fn generate() -> Result<Self, CertError> {
+        let mut seed = [0u8; 32];
+        getrandom::fill(&mut seed).map_err(|e| CertError::Key(e.to_string()))?;
+        Ok(CertificateSigner { key: SigningKey::from_bytes(&seed) })
+    }
+
+    pub fn load(path: &Path) -> Result<Self, CertError> {
+        let pem = fs::read_to_string(path)?;
+        let key = SigningKey::from_pkcs8_pem(&pem).map_err(|e| CertError::Key(e.to_string()))?;
+        Ok(CertificateSigner { key })
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), CertError> {
+        let pem = self
+            .key
+            .to_pkcs8_pem(LineEnding::LF)
+            .map_err(|e| CertError::Key(e.to_string()))?;
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, pem.as_bytes())?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(path, fs::Permissions::from_mode(0o600))?;
+        }
+        Ok(())
+    }
+
+    /// Loads the key at `path`, creating a fresh one on first use
+    pub fn load_or_create(path: &Path) -> Result<Self, CertError> {
+        if path.exists() {
+            return Self::load(path);
+        }
+        let signer = Self::generate()?;
+        signer.save(path)?;
+        Ok(signer)
+    }
+
+    pub fn verifying_key(&self) -> VerifyingKey {
+        self.key.verifying_key()
+    }
+
+    pub fn fingerprint(&self) -> String {
+        key_fingerprint(&self.verifying_key())
+    }
+
+    pub fn sign(&self, body: CertificateBody) -> Result<Certificate, CertError> {
+        let value = self.key.sign(&body.to_signing_bytes()?);
+        let compact = self.sign_compact(&body)?;
+        Ok(Certificate {
+            signature: Some(CertificateSignature {
+                algorithm: SIGNATURE_ALGORITHM.to_string(),
+                public_key: STANDARD.encode(self.verifying_key().to_bytes()),
+                fingerprint: self.fingerprint(),
+                value: STANDARD.encode(value.to_bytes()),
+                compact,
+            }),
+            body,
+        })
+    }
+
+    fn sign_compact(&self, body: &CertificateBody) -> Result<String, CertError> {
+        let payload = CompactPayload {
+            id: body.id.clone(),
+            iat: body.issued_at.clone(),
+            sn: body
+                .devices
+                .iter()
+                .map(|d| d.serial_number.clone().unwrap_or_default())
+                .collect(),
+            m: body.method.clone(),
+            ok: body.result == CertificateResult::Passed,
+            fp: self.fingerprint(),
+            h: body_digest(body)?,
+        };
+        let json = serde_json::to_vec(&payload)?;
+        let signature = self.key.sign(&json);
+        Ok(format!(
+            "{}.{}.{}",
+            COMPACT_PREFIX,
+            URL_SAFE_NO_PAD.encode(&json),
+            URL_SAFE_NO_PAD.encode(signature.to_bytes())
+        ))
+    }
+}
+
+/// Verifies a scanned compact payload against a known public key
+pub fn verify_compact(compact: &str, key: &VerifyingKey) -> Result<CompactPayload, CertError> {
+    let mut parts = compact.trim().split('.');
+    let (Some(prefix), Some(payload), Some(signature), None) =
+        (parts.next(), parts.next(), parts.next(), parts.next())
+    else {
+        return Err(CertError::MalformedPayload("expected three dot-separated parts".to_string()));
+    };
+    if prefix != COMPACT_PREFIX {
+        return Err(CertError::MalformedPayload(format!("unknown prefix {}", prefix)));
+    }
+
+    let json = URL_SAFE_NO_PAD
+        .decode(payload)
+        .map_err(|e| CertError::MalformedPayload(e.to_string()))?;
+    let signature = decode_signature(&URL_SAFE_NO_PAD, signature)?;
+    key.verify(&json, &signature).map_err(|_| CertError::InvalidSignature)?;
+
+    let payload: CompactPayload = serde_json::from_slice(&json)?;
+    if payload.fp != key_fingerprint(key) {
+        return Err(CertError::UntrustedSigner(payload.fp));
+    }
+    Ok(payload)
+}
+
+pub fn key_fingerprint(key: &VerifyingKey) -> String {
+    hex::encode(Sha256::digest(key.to_bytes()))
+}
+
+pub fn decode_verifying_key(encoded: &str) -> Result<VerifyingKey, CertError> {
+    let bytes = STANDARD.decode(encoded).map_err(|e| CertError::Key(e.to_string()))?;
+    let bytes: [u8; 32] = bytes
+        .try_into()
+        .map_err(|_| CertError::Key("public key must be 32 bytes".to_string()))?;
+    VerifyingKey::from_bytes(&bytes).map_err(|e| CertError::Key(e.to_string()))
+}
+
+/// Default location of the signing key, under the user's data directory
+pub fn default_key_path() -> PathBuf {
+    crate::libs::data_dir().join("signing-key.pem")
+}
+
+pub fn now_rfc3339() -> String {
+    OffsetDateTime::now_utc()
+        .format(&Rfc3339)
+        .unwrap_or_else(|_| "1970-01-01T00:00:00Z".to_string())
+}
+
+fn new_certificate_id() -> String {
+    let mut bytes = [0u8; 16];
+    if getrandom::fill(&mut bytes).is_err() {
+        let nanos = OffsetDateTime::now_utc().unix_timestamp_nanos();
+        bytes = nanos.to_be_bytes();
+    }
+    // RFC 4122 version 4 layout
+    bytes[6] = (bytes[6] & 0x0f) | 0x40;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+    let hex = hex::encode(bytes);
+    format!("{}-{}-{}-{}-{}", &hex[0..8], &hex[8..12], &hex[12..16], &hex[16..20], &hex[20..32])
+}
+
+fn body_digest(body: &CertificateBody) -> Result<String, CertError> {
+    Ok(URL_SAFE_NO_PAD.encode(Sha256::digest(body.to_signing_bytes()?)))
+}
+
+fn decode_signature<E: Engine>(engine: &E, encoded: &str) -> Result<Signature, CertError> {
+    let bytes = engine.decode(encoded).map_err(|_| CertError::InvalidSignature)?;
+    let bytes: [u8; 64] = bytes.try_into().map_err(|_| CertError::InvalidSignature)?;
+    Ok(Signature::from_bytes(&bytes))
+}
+
+// TODO: Add timestamp integration
+// TODO: Add compliance standard templates
@@ -0,0 +1,722 @@
+//! PDF certificate rendering
+//! Lays out a certificate from a template and embeds the compact payload as a QR code
+
+use pdf_writer::{
+    Content, Date, Finish, Name, Pdf, Rect, Ref, Str, TextStr,
+    types::{AnnotationFlags, FieldType, SigFlags},
+};
+use qrcode::{Color, EcLevel, QrCode};
+
+use crate::cert::{
+    CertError, Certificate, CertificateResult, HealthSummary,
+    store::Revocation,
+    template::{CertificateTemplate, TemplateSection},
+};
+use crate::libs::StorageDriver;
+
+const REGULAR: Name = Name(b"F1");
+const BOLD: Name = Name(b"F2");
+
+const FOOTER_HEIGHT: f32 = 24.0;
+const ROW_HEIGHT: f32 = 16.0;
+const LABEL_WIDTH: f32 = 130.0;
+
+const PASSED_COLOR: [u8; 3] = [0x2e, 0x7d, 0x32];
+const FAILED_COLOR: [u8; 3] = [0xc6, 0x28, 0x28];
+const WARNING_COLOR: [u8; 3] = [0xef, 0x6c, 0x00];
+const MUTED_COLOR: [u8; 3] = [0x70, 0x70, 0x70];
+const ROW_SHADE: [u8; 3] = [0xf0, 0xf2, 0xf5];
+const WHITE: [u8; 3] = [0xff, 0xff, 0xff];
+const WATERMARK_COLOR: [u8; 3] = [0xef, 0x9a, 0x9a];
+
+// Helvetica advance widths for ASCII 32..=126, in 1/1000 em
+const HELVETICA_WIDTHS: [u16; 95] = [
+    278, 278, 355, 556, 556, 889, 667, 191, 333, 333, 389, 584, 278, 333, 278, 278,
+    556, 556, 556, 556, 556, 556, 556, 556, 556, 556, 278, 278, 584, 584, 584, 556,
+    1015, 667, 667, 722, 722, 667, 611, 778, 722, 278, 500, 667, 556, 833, 722, 778,
+    667, 778, 722, 667, 611, 722, 667, 944, 667, 667, 611, 278, 278, 278, 469, 556,
+    333, 556, 556, 500, 556, 556, 278, 556, 556, 222, 222, 500, 222, 833, 556, 556,
+    556, 556, 333, 500, 278, 556, 500, 722, 500, 500, 500, 334, 260, 334, 584,
+];
+
+pub fn render_pdf(cert: &Certificate, template: &CertificateTemplate) -> Result<Vec<u8>, CertError> {
+    Ok(layout_certificate(cert, template, None)?.into_pdf(cert, None))
+}
+
+/// Renders a revoked certificate with the revocation in its summary and a
+/// watermark across every page
+pub fn render_revoked_pdf(
+    cert: &Certificate,
+    template: &CertificateTemplate,
+    revocation: &Revocation,
+) -> Result<Vec<u8>, CertError> {
+    Ok(layout_certificate(cert, template, Some(revocation))?.into_pdf(cert, None))
+}
+
+/// Renders the certificate with an empty signature dictionary whose /ByteRange
+/// and /Contents placeholders are filled in afterwards by the CMS signer
+pub(crate) fn render_pdf_for_signing(
+    cert: &Certificate,
+    template: &CertificateTemplate,
+    signer: &str,
+    reserve: usize,
+) -> Result<Vec<u8>, CertError> {
+    let field = SignatureField { signer, reserve };
+    Ok(layout_certificate(cert, template, None)?.into_pdf(cert, Some(field)))
+}
+
+struct SignatureField<'a> {
+    signer: &'a str,
+    reserve: usize,
+}
+
+fn layout_certificate<'a>(
+    cert: &Certificate,
+    template: &'a CertificateTemplate,
+    revocation: Option<&'a Revocation>,
+) -> Result<Layout<'a>, CertError> {
+    let mut layout = Layout::new(template, revocation);
+
+    for section in &template.sections {
+        match section {
+            TemplateSection::Header => layout.header(cert),
+            TemplateSection::Summary => layout.summary(cert),
+            TemplateSection::Devices => layout.devices(cert),
+            TemplateSection::Method => layout.method(cert),
+            TemplateSection::Verification => layout.verification(cert),
+            TemplateSection::Health => layout.health(cert),
+            TemplateSection::Signature => layout.signature(cert),
+            TemplateSection::OperatorSignature => layout.operator_signature(cert),
+            TemplateSection::QrCode => layout.qr_code(cert)?,
+        }
+    }
+    layout.footers();
+
+    Ok(layout)
+}
+
+struct Layout<'a> {
+    template: &'a CertificateTemplate,
+    revocation: Option<&'a Revocation>,
+    pages: Vec<Content>,
+    y: f32,
+}
+
+impl<'a> Layout<'a> {
+    fn new(template: &'a CertificateTemplate, revocation: Option<&'a Revocation>) -> Self {
+        Layout {
+            template,
+            revocation,
+            pages: vec![new_page(template, revocation)],
+            y: template.page_height - template.margin,
+        }
+    }
+
+    fn left(&self) -> f32 {
+        self.template.margin
+    }
+
+    fn right(&self) -> f32 {
+        self.template.page_width - self.template.margin
+    }
+
+    fn width(&self) -> f32 {
+        self.right() - self.left()
+    }
+
+    fn size(&self) -> f32 {
+        self.template.body_size
+    }
+
+    // Starts a new page when fewer than `height` points remain on the current one
+    fn ensure(&mut self, height: f32) {
+        if self.y - height < self.template.margin + FOOTER_HEIGHT {
+            self.pages.push(new_page(self.template, self.revocation));
+            self.y = self.template.page_height - self.template.margin;
+        }
+    }
+
+    fn content(&mut self) -> &mut Content {
+        self.pages.last_mut().expect("layout always has a page")
+    }
+
+    fn text(&mut self, x: f32, y: f32, font: Name, size: f32, color: [u8; 3], text: &str) {
+        draw_text(self.content(), x, y, font, size, color, text);
+    }
+
+    fn fill_rect(&mut self, x: f32, y: f32, width: f32, height: f32, color: [u8; 3]) {
+        let content = self.content();
+        set_fill(content, color);
+        content.rect(x, y, width, height);
+        content.fill_nonzero();
+    }
+
+    fn line(&mut self, x1: f32, y1: f32, x2: f32, y2: f32, width: f32, color: [u8; 3]) {
+        draw_line(self.content(), (x1, y1), (x2, y2), width, color);
+    }
+
+    fn heading(&mut self, title: &str) {
+        let size = self.size() + 3.0;
+        self.ensure(size + ROW_HEIGHT * 2.0);
+        self.y -= size + 8.0;
+        let (left, right, accent) = (self.left(), self.right(), self.template.accent_color);
+        self.text(left, self.y, BOLD, size, accent, title);
+        self.y -= 5.0;
+        self.line(left, self.y, right, self.y, 0.75, accent);
+        self.y -= 4.0;
+    }
+
+    fn field(&mut self, label: &str, value: &str) {
+        let size = self.size();
+        let max_width = self.width() - LABEL_WIDTH;
+        let lines = wrap_text(value, size, max_width);
+        self.ensure(ROW_HEIGHT * lines.len() as f32);
+
+        let (left, color) = (self.left(), self.template.text_color);
+        self.y -= ROW_HEIGHT;
+        self.text(left, self.y + 4.0, BOLD, size, color, label);
+        for (index, line) in lines.iter().enumerate() {
+            if index > 0 {
+                self.y -= ROW_HEIGHT;
+            }
+            self.text(left + LABEL_WIDTH, self.y + 4.0, REGULAR, size, color, line);
+        }
+    }
+
+    fn header(&mut self, cert: &Certificate) {
+        let template = self.template;
+        let (left, width) = (self.left(), self.width());
+        let top = template.page_height - template.margin;
+        self.fill_rect(left, top - 6.0, width, 6.0, template.accent_color);
+
+        self.y = top - 36.0;
+        self.text(left, self.y, BOLD, 22.0, template.text_color, &template.title);
+
+        let organization = cert
+            .body
+            .organization
+            .clone()
+            .filter(|o| !o.is_empty())
+            .unwrap_or_else(|| template.organization.clone());
+        if !organization.is_empty() {
+            self.y -= 18.0;
+            self.text(left, self.y, BOLD, 12.0, template.accent_color, &organization);
+        }
+        if !template.subtitle.is_empty() {
+            self.y -= 15.0;
+            self.text(left, self.y, REGULAR, self.size(), MUTED_COLOR, &template.subtitle);
+        }
+
+        self.y -= 4.0;
+        self.field("Certificate ID", &cert.body.id);
+        self.field("Issued", &cert.body.issued_at);
+    }
+
+    fn summary(&mut self, cert: &Certificate) {
+        let height = 40.0;
+        self.ensure(height + 12.0);
+        self.y -= height + 12.0;
+
+        let (color, headline) = match (self.revocation, cert.body.result) {
+            (Some(_), _) => (FAILED_COLOR, "CERTIFICATE REVOKED"),
+            (None, CertificateResult::Passed) if !cert.body.is_verified() => {
+                (WARNING_COLOR, "ERASURE COMPLETED (NOT VERIFIED)")
+            }
+            (None, CertificateResult::Passed) => (PASSED_COLOR, "ERASURE COMPLETED AND VERIFIED"),
+            (None, CertificateResult::Failed) => (FAILED_COLOR, "ERASURE FAILED OR NOT VERIFIED"),
+        };
+        let (left, width) = (self.left(), self.width());
+        self.fill_rect(left, self.y, width, height, color);
+        self.text(left + 12.0, self.y + 23.0, BOLD, 13.0, WHITE, headline);
+
+        let detail = match self.revocation {
+            Some(revocation) => format!("Revoked {}: {}", revocation.revoked_at, revocation.reason),
+            None => format!("{} device(s) | {}", cert.body.devices.len(), cert.body.method),
+        };
+        let detail = truncate_text(&detail, self.size(), width - 24.0);
+        self.text(left + 12.0, self.y + 9.0, REGULAR, self.size(), WHITE, &detail);
+    }
+
+    fn devices(&mut self, cert: &Certificate) {
+        self.heading("Devices");
+
+        // Column widths as fractions of the printable width
+        let columns: [(&str, f32); 5] = [
+            ("Path", 0.17),
+            ("Model", 0.29),
+            ("Serial", 0.26),
+            ("Size", 0.14),
+            ("Type", 0.14),
+        ];
+        let size = self.size() - 1.0;
+
+        self.table_header(&columns, size);
+        for (index, device) in cert.body.devices.iter().enumerate() {
+            if self.y - ROW_HEIGHT * 2.0 < self.template.margin + FOOTER_HEIGHT {
+                self.ensure(ROW_HEIGHT * 2.0);
+                self.table_header(&columns, size);
+            }
+            self.y -= ROW_HEIGHT;
+
+            let (left, width) = (self.left(), self.width());
+            if index % 2 == 1 {
+                self.fill_rect(left, self.y, width, ROW_HEIGHT, ROW_SHADE);
+            }
+
+            let cells = [
+                device.path.clone(),
+                device.model.clone().unwrap_or_else(|| "Unknown".to_string()),
+                device.serial_number.clone().unwrap_or_else(|| "Unknown".to_string()),
+                match (device.size, device.geometry) {
+                    (Some(size), Some(geometry)) => {
+                        format!("{} ({})", StorageDriver::format_size(size), geometry.format())
+                    }
+                    (Some(size), None) => StorageDriver::format_size(size),
+                    (None, _) => "Unknown".to_string(),
+                },
+                device.device_type.clone(),
+            ];
+
+            let mut x = left;
+            for ((_, fraction), cell) in columns.iter().zip(cells.iter()) {
+                let column_width = width * fraction;
+                let cell = truncate_text(cell, size, column_width - 8.0);
+                self.text(x + 4.0, self.y + 4.5, REGULAR, size, self.template.text_color, &cell);
+                x += column_width;
+            }
+
+            // Identifiers that survive a reboot, under the row they belong to
+            let details: Vec<String> = [
+                ("Transport", &device.transport),
+                ("Firmware", &device.firmware),
+                ("WWN", &device.wwn),
+                ("ID", &device.by_id),
+                ("Port", &device.by_path),
+            ]
+            .into_iter()
+            .filter_map(|(label, value)| value.as_ref().map(|value| format!("{}: {}", label, value)))
+            .collect();
+            if !details.is_empty() {
+                self.y -= ROW_HEIGHT;
+                if index % 2 == 1 {
+                    self.fill_rect(left, self.y, width, ROW_HEIGHT, ROW_SHADE);
+                }
+                let line = truncate_text(&details.join("   "), size - 1.0, width - 8.0);
+                self.text(left + 4.0, self.y + 4.5, REGULAR, size - 1.0, MUTED_COLOR, &line);
+            }
+        }
+
+        if cert.body.devices.is_empty() {
+            self.field("Devices", "None recorded");
+        }
+    }
+
+    fn table_header(&mut self, columns: &[(&str, f32)], size: f32) {
+        self.ensure(ROW_HEIGHT * 2.0);
+        self.y -= ROW_HEIGHT;
+        let (left, width) = (self.left(), self.width());
+        self.fill_rect(left, self.y, width, ROW_HEIGHT, self.template.accent_color);
+
+        let mut x = left;
+        for (title, fraction) in columns {
+            self.text(x + 4.0, self.y + 4.5, BOLD, size, WHITE, title);
+            x += width * fraction;
+        }
+    }
+
+    fn method(&mut self, cert: &Certificate) {
+        self.heading("Erasure Method");
+        self.field("Method", &cert.body.method);
+        self.field("Standard", cert.body.standard.as_deref().unwrap_or("Not specified"));
+        self.field("Started", cert.body.started_at.as_deref().unwrap_or("Not recorded"));
+        self.field("Finished", cert.body.finished_at.as_deref().unwrap_or("Not recorded"));
+        for resume in &cert.body.resumes {
+            self.field("Resumed", &resume.to_string());
+        }
+    }
+
+    fn verification(&mut self, cert: &Certificate) {
+        let verification = &cert.body.verification;
+        self.heading("Verification");
+        self.field(
+            "Method",
+            if verification.method.is_empty() { "Not performed" } else { &verification.method },
+        );
+        self.field(
+            "Data verified",
+            &format!(
+                "{} ({} bytes)",
+                StorageDriver::format_size(verification.bytes_verified),
+                verification.bytes_verified
+            ),
+        );
+        self.field("Mismatches", &verification.mismatches.to_string());
+        for (label, errors) in [("Unwritable LBAs", &verification.write_errors), ("Unreadable LBAs", &verification.read_errors)] {
+            if !errors.is_empty() {
+                let map = format!("{} sectors of {} bytes: {}", errors.sectors(), errors.sector_size, errors);
+                self.field(label, &map);
+            }
+        }
+        if let Some(policy) = &verification.error_policy {
+            self.field("Error policy", policy);
+        }
+        self.field("Outcome", if verification.passed { "Passed" } else { "Failed" });
+    }
+
+    fn health(&mut self, cert: &Certificate) {
+        let (before, after) = (&cert.body.health_before, &cert.body.health_after);
+        if before.is_none() && after.is_none() {
+            return;
+        }
+        self.heading("Drive Health");
+        let describe = |health: &HealthSummary| {
+            let count = |value: Option<u64>| value.map_or_else(|| "n/a".to_string(), |v| v.to_string());
+            let assessment = match health.passed {
+                Some(true) => "passed",
+                Some(false) => "FAILING",
+                None => "no assessment",
+            };
+            format!(
+                "{}: {}, {} reallocated, {} pending, {} media errors, {} power-on hours{}{}",
+                health.source,
+                assessment,
+                count(health.reallocated_sectors),
+                count(health.pending_sectors),
+                count(health.media_errors),
+                count(health.power_on_hours),
+                health.percent_used.map_or_else(String::new, |p| format!(", {}% worn", p)),
+                health.temperature_celsius.map_or_else(String::new, |t| format!(", {} °C", t)),
+            )
+        };
+        for (label, health) in [("Before erase", before), ("After erase", after)] {
+            match health {
+                Some(health) => {
+                    self.field(label, &describe(health));
+                    for warning in &health.warnings {
+                        self.field("Warning", warning);
+                    }
+                }
+                None => self.field(label, "Not reported"),
+            }
+        }
+    }
+
+    fn signature(&mut self, cert: &Certificate) {
+        self.heading("Digital Signature");
+        match &cert.signature {
+            Some(signature) => {
+                self.field("Algorithm", &signature.algorithm);
+                self.field("Key fingerprint", &group_hex(&signature.fingerprint));
+            }
+            None => self.field("Status", "This certificate is not digitally signed"),
+        }
+    }
+
+    fn operator_signature(&mut self, cert: &Certificate) {
+        self.heading("Operator");
+        self.field("Name", &cert.body.operator);
+
+        self.ensure(48.0);
+        self.y -= 40.0;
+        let (left, right) = (self.left(), self.right());
+        let split = left + self.width() * 0.6;
+        let color = self.template.text_color;
+        self.line(left, self.y, split - 24.0, self.y, 0.5, color);
+        self.line(split, self.y, right, self.y, 0.5, color);
+        self.y -= 11.0;
+        self.text(left, self.y, REGULAR, 8.0, MUTED_COLOR, "Operator signature");
+        self.text(split, self.y, REGULAR, 8.0, MUTED_COLOR, "Date");
+    }
+
+    fn qr_code(&mut self, cert: &Certificate) -> Result<(), CertError> {
+        let Some(signature) = &cert.signature else {
+            return Ok(());
+        };
+
+        let code = QrCode::with_error_correction_level(signature.compact.as_bytes(), EcLevel::M)
+            .map_err(|e| CertError::QrCode(e.to_string()))?;
+        let modules = code.width();
+        let colors = code.to_colors();
+
+        let size = self.template.qr_size;
+        self.ensure(size + 16.0);
+        self.y -= size + 16.0;
+
+        // Four-module quiet zone on every side
+        let module = size / (modules + 8) as f32;
+        let origin_x = self.left() + module * 4.0;
+        let origin_y = self.y + module * 4.0;
+        let content = self.content();
+        content.set_fill_rgb(0.0, 0.0, 0.0);
+        for (index, color) in colors.iter().enumerate() {
+            if *color == Color::Dark {
+                let (col, row) = (index % modules, index / modules);
+                let x = origin_x + col as f32 * module;
+                let y = origin_y + (modules - 1 - row) as f32 * module;
+                content.rect(x, y, module, module);
+            }
+        }
+        content.fill_nonzero();
+
+        let x = self.left() + size + 12.0;
+        let (text_color, body) = (self.template.text_color, self.size());
+        self.text(x, self.y + size - 24.0, BOLD, body, text_color, "Offline verification");
+        self.text(
+            x,
+            self.y + size - 40.0,
+            REGULAR,
+            body - 1.0,
+            MUTED_COLOR,
+            "Scan this code to check the signed certificate summary",
+        );
+        self.text(
+            x,
+            self.y + size - 53.0,
+            REGULAR,
+            body - 1.0,
+            MUTED_COLOR,
+            "against the issuer's public key without network access.",
+        );
+        Ok(())
+    }
+
+    fn footers(&mut self) {
+        let template = self.template;
+        let total = self.pages.len();
+        let (left, right) = (self.left(), self.right());
+        let y = template.margin;
+
+        for (index, content) in self.pages.iter_mut().enumerate() {
+            let page_label = format!("Page {} of {}", index + 1, total);
+            let label_width = text_width(&page_label, 8.0);
+            draw_line(content, (left, y + 12.0), (right, y + 12.0), 0.5, MUTED_COLOR);
+            draw_text(content, left, y, REGULAR, 8.0, MUTED_COLOR, &template.footer);
+            draw_text(content, right - label_width, y, REGULAR, 8.0, MUTED_COLOR, &page_label);
+        }
+    }
+
+    fn into_pdf(self, cert: &Certificate, signature: Option<SignatureField>) -> Vec<u8> {
+        let template = self.template;
+        let catalog_id = Ref::new(1);
+        let page_tree_id = Ref::new(2);
+        let regular_id = Ref::new(3);
+        let bold_id = Ref::new(4);
+        let info_id = Ref::new(5);
+        let first_page = 6;
+
+        let page_ids: Vec<Ref> = (0..self.pages.len())
+            .map(|i| Ref::new(first_page + 2 * i as i32))
+            .collect();
+
+        let field_id = Ref::new(first_page + 2 * page_ids.len() as i32);
+        let signature_id = Ref::new(field_id.get() + 1);
+
+        let mut pdf = Pdf::new();
+        let mut catalog = pdf.catalog(catalog_id);
+        catalog.pages(page_tree_id);
+        if signature.is_some() {
+            catalog
+                .form()
+                .fields([field_id])
+                .sig_flags(SigFlags::SIGNATURES_EXIST | SigFlags::APPEND_ONLY);
+        }
+        catalog.finish();
+        pdf.pages(page_tree_id)
+            .kids(page_ids.iter().copied())
+            .count(page_ids.len() as i32);
+
+        pdf.type1_font(regular_id)
+            .base_font(Name(template.font.as_bytes()))
+            .encoding_predefined(Name(b"WinAnsiEncoding"));
+        pdf.type1_font(bold_id)
+            .base_font(Name(template.bold_font.as_bytes()))
+            .encoding_predefined(Name(b"WinAnsiEncoding"));
+
+        let title = format!("{} {}", template.title, cert.body.id);
+        pdf.document_info(info_id)
+            .title(TextStr(&title))
+            .creator(TextStr("Secure Erase"))
+            .producer(TextStr("Secure Erase"));
+
+        for (index, (page_id, content)) in page_ids.iter().zip(self.pages).enumerate() {
+            let content_id = Ref::new(page_id.get() + 1);
+            let mut page = pdf.page(*page_id);
+            page.media_box(Rect::new(0.0, 0.0, template.page_width, template.page_height));
+            page.parent(page_tree_id);
+            page.contents(content_id);
+            if index == 0 && signature.is_some() {
+                page.annotations([field_id]);
+            }
+            let mut resources = page.resources();
+            resources.fonts().pair(REGULAR, regular_id).pair(BOLD, bold_id);
+            resources.finish();
+            page.finish();
+
+            pdf.stream(content_id, &content.finish());
+        }
+
+        if let Some(signature) = signature {
+            let mut field = pdf.form_field(field_id);
+            field.field_type(FieldType::Signature);
+            field.partial_name(TextStr("ErasureCertificateSignature"));
+            field.pair(Name(b"V"), signature_id);
+            let mut widget = field.into_annotation();
+            widget.rect(Rect::new(0.0, 0.0, 0.0, 0.0));
+            widget.flags(AnnotationFlags::PRINT);
+            widget.page(page_ids[0]);
+            widget.finish();
+
+            // Placeholders are rewritten in place, so their encoded width is
+            // fixed. They come last in the last object written, which is
+            // where the signer looks for them.
+            let mut dict = pdf.indirect(signature_id).dict();
+            dict.pair(Name(b"Type"), Name(b"Sig"));
+            dict.pair(Name(b"Filter"), Name(b"Adobe.PPKLite"));
+            dict.pair(Name(b"SubFilter"), Name(b"adbe.pkcs7.detached"));
+            dict.pair(Name(b"Name"), TextStr(signature.signer));
+            dict.pair(Name(b"Reason"), TextStr("Certificate of data erasure"));
+            dict.pair(Name(b"M"), pdf_now());
+            dict.insert(Name(b"ByteRange"))
+                .array()
+                .items([0, i32::MAX, i32::MAX, i32::MAX]);
+            dict.pair(Name(b"Contents"), Str(&vec![0xff; signature.reserve]));
+            dict.finish();
+        }
+
+        pdf.finish()
+    }
+}
+
+fn pdf_now() -> Date {
+    let now = time::OffsetDateTime::now_utc();
+    Date::new(now.year() as u16)
+        .month(now.month() as u8)
+        .day(now.day())
+        .hour(now.hour())
+        .minute(now.minute())
+        .second(now.second())
+        .utc_offset_hour(0)
+}
+
+fn set_fill(content: &mut Content, color: [u8; 3]) {
+    let [r, g, b] = color.map(|c| c as f32 / 255.0);
+    content.set_fill_rgb(r, g, b);
+}
+
+fn draw_text(content: &mut Content, x: f32, y: f32, font: Name, size: f32, color: [u8; 3], text: &str) {
+    set_fill(content, color);
+    content.begin_text();
+    content.set_font(font, size);
+    content.next_line(x, y);
+    content.show(Str(&encode_text(text)));
+    content.end_text();
+}
+
+// A revoked certificate's pages start with a watermark, so the rest is
+// drawn over it
+fn new_page(template: &CertificateTemplate, revocation: Option<&Revocation>) -> Content {
+    let mut content = Content::new();
+    if revocation.is_some() {
+        draw_watermark(&mut content, template, "REVOKED");
+    }
+    content
+}
+
+// Large text diagonally across the middle of the page
+fn draw_watermark(content: &mut Content, template: &CertificateTemplate, text: &str) {
+    let size = template.page_width / 6.0;
+    let angle = (template.page_height / template.page_width).atan();
+    let (sin, cos) = angle.sin_cos();
+    let half_width = text_width(text, size) / 2.0;
+    let x = template.page_width / 2.0 - half_width * cos + size / 3.0 * sin;
+    let y = template.page_height / 2.0 - half_width * sin - size / 3.0 * cos;
+
+    set_fill(content, WATERMARK_COLOR);
+    content.begin_text();
+    content.set_font(BOLD, size);
+    content.set_text_matrix([cos, sin, -sin, cos, x, y]);
+    content.show(Str(&encode_text(text)));
+    content.end_text();
+}
+
+fn draw_line(content: &mut Content, from: (f32, f32), to: (f32, f32), width: f32, color: [u8; 3]) {
+    let [r, g, b] = color.map(|c| c as f32 / 255.0);
+    content.set_stroke_rgb(r, g, b);
+    content.set_line_width(width);
+    content.move_to(from.0, from.1);
+    content.line_to(to.0, to.1);
+    content.stroke();
+}
+
+// Maps text onto WinAnsi, which matches Latin-1 for the printable range
+fn encode_text(text: &str) -> Vec<u8> {
+    text.chars()
+        .map(|c| match c as u32 {
+            0x20..=0x7e | 0xa0..=0xff => c as u8,
+            _ => b'?',
+        })
+        .collect()
+}
+
+fn text_width(text: &str, size: f32) -> f32 {
+    let units: u32 = text
+        .chars()
+        .map(|c| match c as u32 {
+            code @ 32..=126 => HELVETICA_WIDTHS[(code - 32) as usize] as u32,
+            _ => 556,
+        })
+        .sum();
+    units as f32 * size / 1000.0
+}
+
+fn truncate_text(text: &str, size: f32, max_width: f32) -> String {
+    if text_width(text, size) <= max_width {
+        return text.to_string();
+    }
+    let mut truncated = text.to_string();
+    while !truncated.is_empty() && text_width(&format!("{}...", truncated), size) > max_width {
+        truncated.pop();
+    }
+    format!("{}...", truncated)
+}
+
+fn wrap_text(text: &str, size: f32, max_width: f32) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut current = String::new();
+
+    for word in text.split_whitespace() {
+        let candidate = if current.is_empty() {
+            word.to_string()
+        } else {
+            format!("{} {}", current, word)
+        };
+        if text_width(&candidate, size) <= max_width {
+            current = candidate;
+            continue;
+        }
+        if !current.is_empty() {
+            lines.push(std::mem::take(&mut current));
+        }
+        // Hard-break words that do not fit on a line of their own
+        for c in word.chars() {
+            current.push(c);
+            if text_width(&current, size) > max_width {
+                current.pop();
+                lines.push(std::mem::take(&mut current));
+                current.push(c);
+            }
+        }
+    }
+    if !current.is_empty() || lines.is_empty() {
+        lines.push(current);
+    }
+    lines
+}
+
+fn group_hex(hex: &str) -> String {
+    hex.as_bytes()
+        .chunks(4)
+        .map(|chunk| String::from_utf8_lossy(chunk).into_owned())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
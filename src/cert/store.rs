@@ -0,0 +1,384 @@
+//! Certificate store
+//! Keeps issued certificates in an indexed directory so they can be searched,
+//! re-exported and revoked long after the erase
+
+use std::{
+    fs::{self, OpenOptions},
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+use time::{Date, OffsetDateTime, format_description::well_known::Rfc3339, macros::format_description};
+
+use crate::{
+    audit::{AuditEvent, AuditLog},
+    cert::{
+        CertError, Certificate, CertificateResult,
+        cms::CmsSigner,
+        now_rfc3339,
+        pdf::{render_pdf, render_revoked_pdf},
+        template::CertificateTemplate,
+    },
+};
+
+const CERTIFICATES_DIR: &str = "certificates";
+const INDEX_FILE: &str = "index.jsonl";
+const REVOCATIONS_FILE: &str = "revocations.jsonl";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IndexedDevice {
+    pub path: String,
+    pub model: Option<String>,
+    pub serial_number: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IndexEntry {
+    pub id: String,
+    pub issued_at: String,
+    pub operator: String,
+    pub method: String,
+    pub result: CertificateResult,
+    pub devices: Vec<IndexedDevice>,
+}
+
+impl From<&Certificate> for IndexEntry {
+    fn from(cert: &Certificate) -> Self {
+        IndexEntry {
+            id: cert.body.id.clone(),
+            issued_at: cert.body.issued_at.clone(),
+            operator: cert.body.operator.clone(),
+            method: cert.body.method.clone(),
+            result: cert.body.result,
+            devices: cert
+                .body
+                .devices
+                .iter()
+                .map(|d| IndexedDevice {
+                    path: d.path.clone(),
+                    model: d.model.clone(),
+                    serial_number: d.serial_number.clone(),
+                })
+                .collect(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Revocation {
+    pub id: String,
+    pub revoked_at: String,
+    pub reason: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct StoredCertificate {
+    pub entry: IndexEntry,
+    pub revocation: Option<Revocation>,
+}
+
+/// All set fields must match. Text fields compare case-insensitively; model
+/// and operator match on substrings, serial numbers must match exactly.
+#[derive(Debug, Clone, Default)]
+pub struct CertificateQuery {
+    pub serial: Option<String>,
+    pub model: Option<String>,
+    pub operator: Option<String>,
+    pub result: Option<CertificateResult>,
+    pub issued_from: Option<OffsetDateTime>,
+    pub issued_to: Option<OffsetDateTime>,
+    pub include_revoked: bool,
+}
+
+impl CertificateQuery {
+    fn matches(&self, stored: &StoredCertificate) -> bool {
+        let entry = &stored.entry;
+        if stored.revocation.is_some() && !self.include_revoked {
+            return false;
+        }
+        if let Some(serial) = &self.serial
+            && !entry.devices.iter().any(|d| {
+                d.serial_number
+                    .as_deref()
+                    .is_some_and(|s| s.trim().eq_ignore_ascii_case(serial.trim()))
+            })
+        {
+            return false;
+        }
+        if let Some(model) = &self.model
+            && !entry
+                .devices
+                .iter()
+                .any(|d| d.model.as_deref().is_some_and(|m| contains_ignore_case(m, model)))
+        {
+            return false;
+        }
+        if let Some(operator) = &self.operator
+            && !contains_ignore_case(&entry.operator, operator)
+        {
+            return false;
+        }
+        if self.result.is_some_and(|result| result != entry.result) {
+            return false;
+        }
+        if self.issued_from.is_some() || self.issued_to.is_some() {
+            let Ok(issued) = OffsetDateTime::parse(&entry.issued_at, &Rfc3339) else {
+                return false;
+            };
+            if self.issued_from.is_some_and(|from| issued < from)
+                || self.issued_to.is_some_and(|to| issued > to)
+            {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+pub struct CertificateStore {
+    root: PathBuf,
+    entries: Vec<StoredCertificate>,
+    template: CertificateTemplate,
+    cms: Option<CmsSigner>,
+    audit: Option<AuditLog>,
+}
+
+impl CertificateStore {
+    pub fn open(root: &Path) -> Result<Self, CertError> {
+        fs::create_dir_all(root.join(CERTIFICATES_DIR))?;
+        let mut store = CertificateStore {
+            root: root.to_path_buf(),
+            entries: Vec::new(),
+            template: CertificateTemplate::default(),
+            cms: None,
+            audit: None,
+        };
+        store.load()?;
+        Ok(store)
+    }
+
+    pub fn open_default() -> Result<Self, CertError> {
+        Self::open(&default_store_path())
+    }
+
+    /// Template of the PDFs written alongside newly stored certificates
+    pub fn set_template(&mut self, template: CertificateTemplate) {
+        self.template = template;
+    }
+
+    /// Signs newly stored certificates with an enterprise key as well: the
+    /// JSON gets a detached signature and the PDF an embedded one
+    pub fn set_cms_signer(&mut self, signer: CmsSigner) {
+        self.cms = Some(signer);
+    }
+
+    /// Log that revocations are recorded in
+    pub fn set_audit_log(&mut self, log: AuditLog) {
+        self.audit = Some(log);
+    }
+
+    /// Stores the certificate as JSON, with a PDF rendering next to it
+    pub fn insert(&mut self, cert: &Certificate) -> Result<(), CertError> {
+        let path = self.certificate_path(&cert.body.id)?;
+        if path.exists() {
+            return Err(CertError::Store(format!("certificate {} is already stored", cert.body.id)));
+        }
+        let json = cert.to_json()?;
+        let (pdf, signature) = match &self.cms {
+            Some(cms) => (cms.sign_pdf(cert, &self.template)?, Some(cms.sign_detached(json.as_bytes())?)),
+            None => (render_pdf(cert, &self.template)?, None),
+        };
+        fs::write(&path, json)?;
+        fs::write(path.with_extension("pdf"), pdf)?;
+        if let Some(signature) = signature {
+            fs::write(path.with_extension("p7s"), signature)?;
+        }
+
+        let entry = IndexEntry::from(cert);
+        append_line(&self.root.join(INDEX_FILE), &entry)?;
+        self.entries.push(StoredCertificate { entry, revocation: None });
+        Ok(())
+    }
+
+    pub fn get(&self, id: &str) -> Result<Certificate, CertError> {
+        let path = self.certificate_path(id)?;
+        if !path.exists() {
+            return Err(CertError::Store(format!("no certificate with id {}", id)));
+        }
+        Certificate::load(&path)
+    }
+
+    /// The certificate as stored, the bytes its CMS signature covers
+    pub fn json_path(&self, id: &str) -> Result<PathBuf, CertError> {
+        self.certificate_path(id)
+    }
+
+    /// The PDF written when the certificate was stored
+    pub fn pdf_path(&self, id: &str) -> Result<PathBuf, CertError> {
+        Ok(self.certificate_path(id)?.with_extension("pdf"))
+    }
+
+    /// Detached CMS signature of the JSON, present when a CMS signer was set
+    pub fn cms_signature_path(&self, id: &str) -> Result<PathBuf, CertError> {
+        Ok(self.certificate_path(id)?.with_extension("p7s"))
+    }
+
+    pub fn status(&self, id: &str) -> Option<&StoredCertificate> {
+        self.entries.iter().find(|stored| stored.entry.id == id)
+    }
+
+    /// Newest first
+    pub fn query(&self, query: &CertificateQuery) -> Vec<&StoredCertificate> {
+        let mut results: Vec<&StoredCertificate> =
+            self.entries.iter().filter(|stored| query.matches(stored)).collect();
+        results.sort_by_key(|stored| {
+            std::cmp::Reverse(OffsetDateTime::parse(&stored.entry.issued_at, &Rfc3339).ok())
+        });
+        results
+    }
+
+    pub fn revoke(&mut self, id: &str, reason: &str) -> Result<Revocation, CertError> {
+        let stored = self
+            .entries
+            .iter_mut()
+            .find(|stored| stored.entry.id == id)
+            .ok_or_else(|| CertError::Store(format!("no certificate with id {}", id)))?;
+        if let Some(existing) = &stored.revocation {
+            return Err(CertError::Store(format!(
+                "certificate {} was already revoked at {}",
+                id, existing.revoked_at
+            )));
+        }
+
+        let revocation = Revocation {
+            id: id.to_string(),
+            revoked_at: now_rfc3339(),
+            reason: reason.to_string(),
+        };
+        // Logged first, so nothing is revoked without a trace in the audit log
+        if let Some(log) = &self.audit {
+            log.append(AuditEvent::CertificateRevoked {
+                certificate_id: id.to_string(),
+                reason: reason.to_string(),
+            })
+            .map_err(|e| CertError::IoError(std::io::Error::other(format!("audit log: {}", e))))?;
+        }
+        append_line(&self.root.join(REVOCATIONS_FILE), &revocation)?;
+        stored.revocation = Some(revocation.clone());
+        Ok(revocation)
+    }
+
+    pub fn export_json(&self, id: &str, destination: &Path) -> Result<(), CertError> {
+        self.get(id)?.save(destination)
+    }
+
+    /// Revoked certificates are marked as such on every page
+    pub fn export_pdf(
+        &self,
+        id: &str,
+        template: &CertificateTemplate,
+        destination: &Path,
+    ) -> Result<(), CertError> {
+        let cert = self.get(id)?;
+        let pdf = match self.status(id).and_then(|stored| stored.revocation.as_ref()) {
+            Some(revocation) => render_revoked_pdf(&cert, template, revocation)?,
+            None => render_pdf(&cert, template)?,
+        };
+        fs::write(destination, pdf)?;
+        Ok(())
+    }
+
+    /// Recreates the index from the stored certificate files
+    pub fn rebuild_index(&mut self) -> Result<(), CertError> {
+        let mut index = Vec::new();
+        for entry in fs::read_dir(self.root.join(CERTIFICATES_DIR))?.flatten() {
+            let path = entry.path();
+            if path.extension().is_some_and(|ext| ext == "json") {
+                let cert = Certificate::load(&path)?;
+                index.push(IndexEntry::from(&cert));
+            }
+        }
+
+        let mut contents = Vec::new();
+        for entry in &index {
+            serde_json::to_writer(&mut contents, entry)?;
+            contents.push(b'\n');
+        }
+        let index_path = self.root.join(INDEX_FILE);
+        let temp_path = index_path.with_extension("jsonl.tmp");
+        fs::write(&temp_path, contents)?;
+        fs::rename(&temp_path, &index_path)?;
+
+        self.load()
+    }
+
+    fn load(&mut self) -> Result<(), CertError> {
+        let index: Vec<IndexEntry> = read_lines(&self.root.join(INDEX_FILE))?;
+        let revocations: Vec<Revocation> = read_lines(&self.root.join(REVOCATIONS_FILE))?;
+
+        self.entries = index
+            .into_iter()
+            .map(|entry| StoredCertificate {
+                revocation: revocations.iter().find(|r| r.id == entry.id).cloned(),
+                entry,
+            })
+            .collect();
+        Ok(())
+    }
+
+    fn certificate_path(&self, id: &str) -> Result<PathBuf, CertError> {
+        // Ids become file names, so anything but a plain UUID is rejected
+        if id.is_empty() || !id.chars().all(|c| c.is_ascii_hexdigit() || c == '-') {
+            return Err(CertError::Store(format!("invalid certificate id {:?}", id)));
+        }
+        Ok(self.root.join(CERTIFICATES_DIR).join(format!("{}.json", id)))
+    }
+}
+
+pub fn default_store_path() -> PathBuf {
+    crate::libs::data_dir().join("store")
+}
+
+/// Accepts a full RFC 3339 timestamp or a plain YYYY-MM-DD date. Plain dates
+/// mean the start of that day, or its end when `end_of_day` is set.
+pub fn parse_query_date(value: &str, end_of_day: bool) -> Result<OffsetDateTime, CertError> {
+    if let Ok(timestamp) = OffsetDateTime::parse(value, &Rfc3339) {
+        return Ok(timestamp);
+    }
+    let date = Date::parse(value, format_description!("[year]-[month]-[day]"))
+        .map_err(|_| CertError::Store(format!("invalid date {:?}, expected YYYY-MM-DD", value)))?;
+    let datetime = if end_of_day {
+        date.with_hms(23, 59, 59)
+    } else {
+        date.with_hms(0, 0, 0)
+    }
+    .map_err(|e| CertError::Store(e.to_string()))?;
+    Ok(datetime.assume_utc())
+}
+
+fn contains_ignore_case(haystack: &str, needle: &str) -> bool {
+    haystack.to_lowercase().contains(&needle.to_lowercase())
+}
+
+fn append_line<T: Serialize>(path: &Path, value: &T) -> Result<(), CertError> {
+    let mut line = serde_json::to_vec(value)?;
+    line.push(b'\n');
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    file.write_all(&line)?;
+    file.sync_data()?;
+    Ok(())
+}
+
+fn read_lines<T: for<'de> Deserialize<'de>>(path: &Path) -> Result<Vec<T>, CertError> {
+    let contents = match fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e.into()),
+    };
+    contents
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| serde_json::from_str(line).map_err(CertError::from))
+        .collect()
+}
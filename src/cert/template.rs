@@ -0,0 +1,83 @@
+//! Certificate layout templates
+//! Controls branding and section order of rendered PDF certificates
+
+use std::{fs, path::Path};
+
+use serde::{Deserialize, Serialize};
+
+use crate::cert::CertError;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TemplateSection {
+    Header,
+    Summary,
+    Devices,
+    Method,
+    Verification,
+    // Left out of certificates without health data
+    Health,
+    Signature,
+    OperatorSignature,
+    QrCode,
+}
+
+/// Page geometry is in PDF points (1/72 inch). Fonts must be one of the
+/// standard 14 PDF fonts since nothing is embedded.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CertificateTemplate {
+    pub title: String,
+    pub subtitle: String,
+    pub organization: String,
+    pub footer: String,
+    pub accent_color: [u8; 3],
+    pub text_color: [u8; 3],
+    pub page_width: f32,
+    pub page_height: f32,
+    pub margin: f32,
+    pub font: String,
+    pub bold_font: String,
+    pub body_size: f32,
+    pub qr_size: f32,
+    pub sections: Vec<TemplateSection>,
+}
+
+impl Default for CertificateTemplate {
+    fn default() -> Self {
+        CertificateTemplate {
+            title: "Certificate of Data Erasure".to_string(),
+            subtitle: "Storage media sanitization record".to_string(),
+            organization: String::new(),
+            footer: "Generated by Secure Erase".to_string(),
+            accent_color: [0x1f, 0x4e, 0x79],
+            text_color: [0x20, 0x20, 0x20],
+            // A4 portrait
+            page_width: 595.0,
+            page_height: 842.0,
+            margin: 48.0,
+            font: "Helvetica".to_string(),
+            bold_font: "Helvetica-Bold".to_string(),
+            body_size: 10.0,
+            qr_size: 120.0,
+            sections: vec![
+                TemplateSection::Header,
+                TemplateSection::Summary,
+                TemplateSection::Devices,
+                TemplateSection::Method,
+                TemplateSection::Verification,
+                TemplateSection::Health,
+                TemplateSection::Signature,
+                TemplateSection::OperatorSignature,
+                TemplateSection::QrCode,
+            ],
+        }
+    }
+}
+
+impl CertificateTemplate {
+    pub fn load(path: &Path) -> Result<Self, CertError> {
+        let json = fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&json)?)
+    }
+}
@@ -0,0 +1,1562 @@
+// Command line front-end
+// Headless access to detection, erasure, verification and certificates for
+// SSH sessions and PXE-booted machines without a display
+
+use std::{
+    io::{self, BufRead, IsTerminal, Write},
+    path::{Path, PathBuf},
+    process::ExitCode,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicBool, Ordering},
+    },
+    thread,
+    time::{Duration, SystemTime},
+};
+
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use serde::Serialize;
+
+use secure_erase::{
+    analysis::forensics::scan_forensics,
+    audit::{AuditError, AuditEvent, AuditLog, default_log_path, verify_log},
+    cert::{
+        CertError, Certificate, CertificateSigner, DeviceRecord,
+        cms::{CmsSigner, CmsVerification, load_certificates, verify_detached, verify_pdf},
+        CertificateResult, default_key_path,
+        pdf::{render_pdf, render_revoked_pdf},
+        store::{CertificateQuery, CertificateStore, parse_query_date},
+        template::CertificateTemplate,
+    },
+    erasure::{
+        EraseError, check_target,
+        batch::{BatchConfig, BatchControl, BatchDevice, BatchEvent, BatchServices, JobOutcome,
+            default_report_dir, start_batch,
+        },
+        checkpoint::{CheckpointStore, default_checkpoint_dir},
+        engine::{IoBackend, IoConfig, IoOp, benchmark},
+        errormap::ErrorPolicy,
+        overwrite::{OverwriteStandard, Pattern, PatternGenerator, Seed},
+        progress::{ProgressTracker, format_duration, format_rate},
+        throttle::{IoLimits, IoPriority, PauseWindow, Throttle, ThrottleSettings},
+    },
+    health::{HealthError, read_health},
+    libs::StorageDriver,
+    platform::{DeviceIdentity, StorageDevice, WipeMethod},
+    verify::{VerifyMode, verify_pattern},
+};
+
+#[derive(Parser)]
+#[command(name = "secure-erase", version, about = "Detect, analyze, and securely wipe storage devices")]
+pub struct Cli {
+    /// Print machine-readable JSON on stdout instead of text
+    #[arg(long, global = true)]
+    json: bool,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// List detected storage devices
+    List,
+    /// Show details of one device
+    Info { device: String },
+    /// Erase one or more devices in parallel
+    Erase(EraseArgs),
+    /// List overwrites interrupted by a crash or power cut, or carry one on
+    Resume(ResumeArgs),
+    /// Check that a device contains only the given pattern
+    Verify(VerifyArgs),
+    /// Measure read or write throughput of a device with the erase I/O engine
+    Bench(BenchArgs),
+    /// Look for recoverable files on a device or disk image, without writing
+    Forensics { target: String },
+    /// Show SMART or log page health of a drive
+    Health { device: String },
+    /// Inspect and verify erasure certificates
+    #[command(subcommand)]
+    Cert(CertCommand),
+    /// Check the audit log for edited, reordered or missing entries
+    VerifyLog {
+        /// Log to check instead of the default one
+        #[arg(long)]
+        log: Option<PathBuf>,
+    },
+    /// Launch the graphical interface (default)
+    Gui,
+}
+
+#[derive(Args)]
+pub struct EraseArgs {
+    #[arg(required = true)]
+    devices: Vec<String>,
+    #[arg(long, value_enum, default_value_t = MethodArg::Overwrite)]
+    method: MethodArg,
+    #[arg(long, value_enum, default_value_t = StandardArg::Zero)]
+    standard: StandardArg,
+    #[arg(long, value_enum, default_value_t = VerifyArg::Sampled)]
+    verify: VerifyArg,
+    /// Percentage of the device read back in sampled verification
+    #[arg(long, default_value_t = 10, value_parser = clap::value_parser!(u8).range(1..=100))]
+    sample_percent: u8,
+    /// What sectors that cannot be written do to the job
+    #[arg(long, value_enum, default_value_t = BadSectorArg::Fail)]
+    on_bad_sectors: BadSectorArg,
+    /// Bad sectors a job may have and still pass with --on-bad-sectors tolerate
+    #[arg(long, default_value_t = 8)]
+    max_bad_sectors: u64,
+    /// Name recorded in the certificates
+    #[arg(long)]
+    operator: Option<String>,
+    #[arg(long, default_value_t = 2)]
+    jobs_per_controller: usize,
+    #[command(flatten)]
+    io: IoArgs,
+    #[command(flatten)]
+    schedule: ScheduleArgs,
+    #[command(flatten)]
+    cms: CmsArgs,
+    /// Skip the interactive confirmation
+    #[arg(long)]
+    yes: bool,
+    /// Allow erasing the disk the running system was booted from
+    #[arg(long)]
+    allow_system_disk: bool,
+}
+
+#[derive(Args)]
+pub struct ResumeArgs {
+    /// Drive to carry on erasing; lists every checkpoint without one
+    device: Option<String>,
+    /// Delete the drive's checkpoint instead of resuming
+    #[arg(long)]
+    discard: bool,
+    /// Name recorded in the certificate
+    #[arg(long)]
+    operator: Option<String>,
+    #[command(flatten)]
+    io: IoArgs,
+    #[command(flatten)]
+    schedule: ScheduleArgs,
+    #[command(flatten)]
+    cms: CmsArgs,
+    /// Skip the interactive confirmation
+    #[arg(long)]
+    yes: bool,
+    /// Allow erasing the disk the running system was booted from
+    #[arg(long)]
+    allow_system_disk: bool,
+}
+
+#[derive(Args)]
+pub struct VerifyArgs {
+    device: String,
+    /// Expected bytes in hex, repeated across the device (e.g. 00, ff, 924924)
+    #[arg(long, default_value = "00")]
+    pattern: String,
+    #[arg(long, value_enum, default_value_t = VerifyArg::Full)]
+    mode: VerifyArg,
+    #[arg(long, default_value_t = 10, value_parser = clap::value_parser!(u8).range(1..=100))]
+    sample_percent: u8,
+    #[command(flatten)]
+    io: IoArgs,
+}
+
+#[derive(Args)]
+pub struct BenchArgs {
+    device: String,
+    /// Overwrite the start of the device with zeros instead of reading it
+    #[arg(long)]
+    write: bool,
+    /// Bytes to transfer, e.g. 512M or 4G
+    #[arg(long, default_value = "1G", value_parser = parse_size)]
+    size: u64,
+    #[command(flatten)]
+    io: IoArgs,
+    /// Skip the interactive confirmation of a write benchmark
+    #[arg(long)]
+    yes: bool,
+    /// Allow writing to the disk the running system was booted from
+    #[arg(long)]
+    allow_system_disk: bool,
+}
+
+#[derive(Args)]
+pub struct IoArgs {
+    /// How overwrite and verify requests are kept in flight
+    #[arg(long, value_enum, default_value_t = IoBackendArg::Auto)]
+    io_backend: IoBackendArg,
+    /// Requests in flight at once
+    #[arg(long, default_value_t = 4, value_parser = clap::value_parser!(u16).range(1..=256))]
+    queue_depth: u16,
+    /// Bytes per request, a multiple of 4K, e.g. 256K or 4M
+    #[arg(long, default_value = "4M", value_parser = parse_block_size)]
+    block_size: u64,
+    /// Cap on each job's transfer rate per second, e.g. 200M
+    #[arg(long, value_parser = parse_size)]
+    max_rate: Option<u64>,
+    /// Cap on each job's requests per second
+    #[arg(long)]
+    max_iops: Option<u32>,
+    /// I/O scheduling class of the requests
+    #[arg(long, value_enum, default_value_t = IoniceArg::Normal)]
+    ionice: IoniceArg,
+    /// Level within --ionice best-effort, 0 (highest) to 7 (lowest)
+    #[arg(long, default_value_t = 4, value_parser = clap::value_parser!(u8).range(0..=7))]
+    ionice_level: u8,
+}
+
+#[derive(Args)]
+pub struct ScheduleArgs {
+    /// Pause jobs every day during this local time window, e.g. 08:00-18:00;
+    /// may be given more than once
+    #[arg(long, value_name = "HH:MM-HH:MM")]
+    pause_during: Vec<PauseWindow>,
+    /// JSON throttle settings, re-read whenever the file changes while jobs
+    /// run; they replace the limits and windows of the options above
+    #[arg(long)]
+    throttle_file: Option<PathBuf>,
+}
+
+#[derive(Args)]
+pub struct CmsArgs {
+    /// PEM private key, RSA or P-256, that also signs certificates with CMS
+    /// for an enterprise PKI
+    #[arg(long, requires = "cms_chain")]
+    cms_key: Option<PathBuf>,
+    /// PEM certificate chain of --cms-key, its own certificate first
+    #[arg(long, requires = "cms_key")]
+    cms_chain: Option<PathBuf>,
+}
+
+impl CmsArgs {
+    fn signer(&self) -> Result<Option<CmsSigner>, CliError> {
+        match (&self.cms_key, &self.cms_chain) {
+            (Some(key), Some(chain)) => Ok(Some(CmsSigner::load(key, chain)?)),
+            _ => Ok(None),
+        }
+    }
+}
+
+#[derive(Subcommand)]
+pub enum CertCommand {
+    /// Print a certificate, given a file or a certificate id from the store
+    Show { certificate: String },
+    /// Check a certificate's signature and revocation status, or the CMS
+    /// signature of a signed PDF
+    Verify {
+        certificate: String,
+        /// Require the signing key to have this SHA-256 fingerprint; by
+        /// default the key this machine signs certificates with
+        #[arg(long)]
+        fingerprint: Option<String>,
+        /// PEM or DER bundle of trusted CA certificates to check the CMS
+        /// signature against
+        #[arg(long)]
+        trust: Option<PathBuf>,
+        /// Detached CMS signature of a JSON certificate, by default the .p7s
+        /// file next to it
+        #[arg(long, requires = "trust")]
+        signature: Option<PathBuf>,
+    },
+    /// Render a certificate as PDF
+    Pdf {
+        certificate: String,
+        /// JSON layout template; the built-in layout when not given
+        #[arg(long)]
+        template: Option<PathBuf>,
+        /// Defaults to <certificate id>.pdf in the current directory
+        #[arg(long, short)]
+        output: Option<PathBuf>,
+        #[command(flatten)]
+        cms: CmsArgs,
+    },
+    /// Search the certificate store, newest first
+    Search(SearchArgs),
+    /// Copy a stored certificate out as JSON or PDF
+    Export(ExportArgs),
+    /// Mark a stored certificate as no longer valid
+    Revoke {
+        id: String,
+        /// Recorded with the revocation and in the audit log
+        #[arg(long)]
+        reason: String,
+    },
+}
+
+#[derive(Args)]
+pub struct ExportArgs {
+    id: String,
+    #[arg(long, value_enum, default_value_t = ExportFormat::Json)]
+    format: ExportFormat,
+    /// Defaults to <certificate id>.json or .pdf in the current directory
+    #[arg(long, short)]
+    output: Option<PathBuf>,
+    /// JSON layout template for PDFs
+    #[arg(long)]
+    template: Option<PathBuf>,
+}
+
+#[derive(Args)]
+pub struct SearchArgs {
+    /// Serial number, matched exactly
+    #[arg(long)]
+    serial: Option<String>,
+    /// Part of the device model
+    #[arg(long)]
+    model: Option<String>,
+    /// Part of the operator name
+    #[arg(long)]
+    operator: Option<String>,
+    #[arg(long, value_enum)]
+    result: Option<ResultArg>,
+    /// Issued on or after, YYYY-MM-DD or an RFC 3339 timestamp
+    #[arg(long)]
+    from: Option<String>,
+    /// Issued on or before, YYYY-MM-DD or an RFC 3339 timestamp
+    #[arg(long)]
+    to: Option<String>,
+    /// List revoked certificates too
+    #[arg(long)]
+    include_revoked: bool,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum ResultArg {
+    Passed,
+    Failed,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum ExportFormat {
+    Json,
+    Pdf,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum MethodArg {
+    Overwrite,
+    SecureErase,
+    CryptoErase,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum StandardArg {
+    Zero,
+    Random,
+    Dod3,
+    Dod7,
+    Gutmann,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum VerifyArg {
+    None,
+    Sampled,
+    Full,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum IoBackendArg {
+    Auto,
+    Uring,
+    Threads,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum IoniceArg {
+    Normal,
+    BestEffort,
+    Idle,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum BadSectorArg {
+    Abort,
+    Fail,
+    Tolerate,
+}
+
+impl From<MethodArg> for WipeMethod {
+    fn from(method: MethodArg) -> Self {
+        match method {
+            MethodArg::Overwrite => WipeMethod::Overwrite,
+            MethodArg::SecureErase => WipeMethod::SecureErase,
+            MethodArg::CryptoErase => WipeMethod::CryptoErase,
+        }
+    }
+}
+
+impl From<StandardArg> for OverwriteStandard {
+    fn from(standard: StandardArg) -> Self {
+        match standard {
+            StandardArg::Zero => OverwriteStandard::Zero,
+            StandardArg::Random => OverwriteStandard::Random,
+            StandardArg::Dod3 => OverwriteStandard::Dod3Pass,
+            StandardArg::Dod7 => OverwriteStandard::Dod7Pass,
+            StandardArg::Gutmann => OverwriteStandard::Gutmann,
+        }
+    }
+}
+
+impl VerifyArg {
+    fn mode(self, percent: u8) -> VerifyMode {
+        match self {
+            VerifyArg::None => VerifyMode::None,
+            VerifyArg::Sampled => VerifyMode::Sampled(percent),
+            VerifyArg::Full => VerifyMode::Full,
+        }
+    }
+}
+
+impl BadSectorArg {
+    fn policy(self, max: u64) -> ErrorPolicy {
+        match self {
+            BadSectorArg::Abort => ErrorPolicy::Abort,
+            BadSectorArg::Fail => ErrorPolicy::Fail,
+            BadSectorArg::Tolerate => ErrorPolicy::Tolerate(max),
+        }
+    }
+}
+
+impl IoArgs {
+    fn config(&self) -> IoConfig {
+        let backend = match self.io_backend {
+            IoBackendArg::Auto => IoBackend::Auto,
+            IoBackendArg::Uring => IoBackend::IoUring,
+            IoBackendArg::Threads => IoBackend::Threads,
+        };
+        let limits = IoLimits {
+            bytes_per_second: self.max_rate,
+            iops: self.max_iops,
+            priority: match self.ionice {
+                IoniceArg::Normal => IoPriority::Normal,
+                IoniceArg::BestEffort => IoPriority::BestEffort(self.ionice_level),
+                IoniceArg::Idle => IoPriority::Idle,
+            },
+        };
+        IoConfig {
+            backend,
+            queue_depth: self.queue_depth as usize,
+            block_size: self.block_size as usize,
+            throttle: Throttle::new(ThrottleSettings { limits, pause_windows: Vec::new() }),
+        }
+    }
+}
+
+impl ScheduleArgs {
+    // Adds the pause windows to the throttle of `io`
+    fn apply(&self, io: &IoConfig) {
+        let mut settings = io.throttle.settings();
+        settings.pause_windows = self.pause_during.clone();
+        io.throttle.set(settings);
+    }
+}
+
+// Byte count with an optional K, M, G or T suffix (powers of 1024)
+fn parse_size(value: &str) -> Result<u64, String> {
+    let value = value.trim();
+    let (number, shift) = match value.char_indices().last() {
+        Some((index, 'K' | 'k')) => (&value[..index], 10),
+        Some((index, 'M' | 'm')) => (&value[..index], 20),
+        Some((index, 'G' | 'g')) => (&value[..index], 30),
+        Some((index, 'T' | 't')) => (&value[..index], 40),
+        _ => (value, 0),
+    };
+    let number: u64 = number.parse().map_err(|_| format!("invalid size {:?}", value))?;
+    number.checked_mul(1 << shift).ok_or_else(|| format!("size {:?} is too large", value))
+}
+
+fn parse_block_size(value: &str) -> Result<u64, String> {
+    match parse_size(value)? {
+        size if size == 0 || size % 4096 != 0 => Err("block size must be a non-zero multiple of 4K".to_string()),
+        size if size > 1 << 30 => Err("block size must be at most 1G".to_string()),
+        size => Ok(size),
+    }
+}
+
+// Exit status for each class of failure. Usage errors share 2 with clap.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FailureClass {
+    Internal,
+    Usage,
+    NotFound,
+    Refused,
+    Io,
+    EraseFailed,
+    VerificationFailed,
+    Cancelled,
+    CertificateInvalid,
+    Unsupported,
+}
+
+impl FailureClass {
+    pub fn code(self) -> u8 {
+        match self {
+            FailureClass::Internal => 1,
+            FailureClass::Usage => 2,
+            FailureClass::NotFound => 3,
+            FailureClass::Refused => 4,
+            FailureClass::Io => 5,
+            FailureClass::EraseFailed => 6,
+            FailureClass::VerificationFailed => 7,
+            FailureClass::Cancelled => 8,
+            FailureClass::CertificateInvalid => 9,
+            FailureClass::Unsupported => 10,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct CliError {
+    pub class: FailureClass,
+    pub message: String,
+}
+
+impl CliError {
+    fn new(class: FailureClass, message: impl Into<String>) -> Self {
+        CliError { class, message: message.into() }
+    }
+}
+
+impl From<EraseError> for CliError {
+    fn from(e: EraseError) -> Self {
+        let class = match &e {
+            EraseError::IoError(_) => FailureClass::Io,
+            EraseError::InvalidTarget(_) => FailureClass::NotFound,
+            EraseError::Unsupported(_) => FailureClass::Unsupported,
+            EraseError::CommandFailed(_) => FailureClass::EraseFailed,
+            EraseError::IdentityChanged(_) | EraseError::InUse(_) | EraseError::Checkpoint(_) => {
+                FailureClass::Refused
+            }
+            EraseError::Cancelled => FailureClass::Cancelled,
+        };
+        CliError::new(class, e.to_string())
+    }
+}
+
+impl From<CertError> for CliError {
+    fn from(e: CertError) -> Self {
+        let class = match &e {
+            CertError::IoError(_) => FailureClass::Io,
+            CertError::Store(_) => FailureClass::NotFound,
+            _ => FailureClass::CertificateInvalid,
+        };
+        CliError::new(class, e.to_string())
+    }
+}
+
+impl From<AuditError> for CliError {
+    fn from(e: AuditError) -> Self {
+        let class = match &e {
+            AuditError::IoError(_) => FailureClass::Io,
+            AuditError::Json(_) | AuditError::Tampered { .. } | AuditError::Truncated { .. } => {
+                FailureClass::VerificationFailed
+            }
+        };
+        CliError::new(class, e.to_string())
+    }
+}
+
+impl From<HealthError> for CliError {
+    fn from(e: HealthError) -> Self {
+        let class = match &e {
+            HealthError::IoError(_) => FailureClass::Io,
+            HealthError::Unsupported(_) | HealthError::CommandFailed(_) => FailureClass::Unsupported,
+        };
+        CliError::new(class, e.to_string())
+    }
+}
+
+#[derive(Serialize)]
+struct DeviceInfo {
+    #[serde(flatten)]
+    record: DeviceRecord,
+    name: String,
+    status: String,
+    controller: Option<String>,
+    host_controller: Option<String>,
+    /// Every by-id name, where the record keeps only the most descriptive
+    #[serde(rename = "by_id_all")]
+    by_id: Vec<String>,
+    in_use: Vec<String>,
+    system_disk: bool,
+}
+
+impl From<&StorageDevice> for DeviceInfo {
+    fn from(device: &StorageDevice) -> Self {
+        DeviceInfo {
+            record: DeviceRecord::from(device),
+            name: device.name.clone(),
+            status: format!("{:?}", device.status),
+            controller: StorageDriver::controller_id(&device.path),
+            host_controller: device.host_controller.clone(),
+            by_id: device.by_id.clone(),
+            in_use: device.usage.reasons.iter().map(|r| r.to_string()).collect(),
+            system_disk: device.usage.is_system_disk(),
+        }
+    }
+}
+
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+extern "C" fn on_interrupt(_: libc::c_int) {
+    INTERRUPTED.store(true, Ordering::Relaxed);
+}
+
+pub fn run(cli: Cli) -> ExitCode {
+    let json = cli.json;
+    let result = match cli.command {
+        Some(Command::List) => list(json),
+        Some(Command::Info { device }) => info(&device, json),
+        Some(Command::Erase(args)) => erase(args, json),
+        Some(Command::Resume(args)) => resume(args, json),
+        Some(Command::Verify(args)) => verify(args, json),
+        Some(Command::Bench(args)) => bench(args, json),
+        Some(Command::Forensics { target }) => forensics(&target, json),
+        Some(Command::Health { device }) => health(&device, json),
+        Some(Command::Cert(CertCommand::Show { certificate })) => cert_show(&certificate, json),
+        Some(Command::Cert(CertCommand::Verify { certificate, fingerprint, trust, signature })) => {
+            cert_verify(&certificate, fingerprint.as_deref(), trust.as_deref(), signature.as_deref(), json)
+        }
+        Some(Command::Cert(CertCommand::Pdf { certificate, template, output, cms })) => {
+            cert_pdf(&certificate, template.as_deref(), output, &cms, json)
+        }
+        Some(Command::Cert(CertCommand::Search(args))) => cert_search(args, json),
+        Some(Command::Cert(CertCommand::Export(args))) => cert_export(args, json),
+        Some(Command::Cert(CertCommand::Revoke { id, reason })) => cert_revoke(&id, &reason, json),
+        Some(Command::VerifyLog { log }) => verify_audit_log(log, json),
+        Some(Command::Gui) | None => {
+            Err(CliError::new(FailureClass::Internal, "the GUI is launched from main"))
+        }
+    };
+
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            if json {
+                print_json(&serde_json::json!({ "error": e }));
+            } else {
+                eprintln!("error: {}", e.message);
+            }
+            ExitCode::from(e.class.code())
+        }
+    }
+}
+
+fn list(json: bool) -> Result<(), CliError> {
+    let devices = StorageDriver::detect_storage_devices();
+    audit_events(devices.iter().map(|device| AuditEvent::DeviceDetected {
+        path: device.path.clone(),
+        model: device.model.clone(),
+        serial: device.serial_number.clone(),
+    }));
+
+    if json {
+        let infos: Vec<DeviceInfo> = devices.iter().map(DeviceInfo::from).collect();
+        print_json(&infos);
+        return Ok(());
+    }
+    println!(
+        "{:<16} {:<10} {:<7} {:<28} {:<24} STATUS",
+        "DEVICE", "SIZE", "TYPE", "MODEL", "SERIAL"
+    );
+    for device in &devices {
+        println!(
+            "{:<16} {:<10} {:<7} {:<28} {:<24} {:?}",
+            device.path,
+            device.size.map(StorageDriver::format_size).unwrap_or_else(|| "-".to_string()),
+            format!("{:?}", device.device_type),
+            device.model.as_deref().unwrap_or("-"),
+            device.serial_number.as_deref().unwrap_or("-"),
+            device.status
+        );
+    }
+    Ok(())
+}
+
+fn info(path: &str, json: bool) -> Result<(), CliError> {
+    let devices = StorageDriver::detect_storage_devices();
+    let device = find_device(&devices, path)
+        .ok_or_else(|| CliError::new(FailureClass::NotFound, format!("no device {}", path)))?;
+    let info = DeviceInfo::from(device);
+
+    if json {
+        print_json(&info);
+        return Ok(());
+    }
+    let unknown = || "Unknown".to_string();
+    println!("Path:       {}", info.record.path);
+    println!("Name:       {}", info.name);
+    println!("Model:      {}", info.record.model.clone().unwrap_or_else(unknown));
+    println!("Serial:     {}", info.record.serial_number.clone().unwrap_or_else(unknown));
+    println!("Vendor:     {}", info.record.vendor.clone().unwrap_or_else(unknown));
+    println!("Firmware:   {}", info.record.firmware.clone().unwrap_or_else(unknown));
+    println!("WWN:        {}", info.record.wwn.clone().unwrap_or_else(unknown));
+    println!("Transport:  {}", info.record.transport.clone().unwrap_or_else(unknown));
+    println!(
+        "Size:       {}",
+        info.record.size.map(StorageDriver::format_size).unwrap_or_else(unknown)
+    );
+    println!("Type:       {}", info.record.device_type);
+    if let Some(geometry) = &info.record.geometry {
+        println!(
+            "Sectors:    {} ({} logical, {} physical)",
+            geometry.format(),
+            geometry.logical_block_size,
+            geometry.physical_block_size
+        );
+        println!("Optimal IO: {}", geometry.optimal_io_size);
+        println!("Alignment:  {}", geometry.alignment_offset);
+        println!("Max xfer:   {}", geometry.max_transfer);
+        println!("Discard:    {}", geometry.discard_granularity);
+    }
+    println!("Status:     {}", info.status);
+    println!("Controller: {}", info.host_controller.or(info.controller).unwrap_or_else(unknown));
+    for by_id in &info.by_id {
+        println!("By ID:      /dev/disk/by-id/{}", by_id);
+    }
+    if let Some(by_path) = &info.record.by_path {
+        println!("By path:    /dev/disk/by-path/{}", by_path);
+    }
+    for reason in &info.in_use {
+        println!("In use:     {}", reason);
+    }
+    Ok(())
+}
+
+fn erase(args: EraseArgs, json: bool) -> Result<(), CliError> {
+    let detected = StorageDriver::detect_storage_devices();
+    let mut devices = Vec::new();
+    for path in &args.devices {
+        let (record, identity) = erase_target(&detected, path, args.allow_system_disk)?;
+        devices.push(BatchDevice::new(record).with_identity(identity));
+    }
+
+    let operator = operator_name(args.operator)?;
+    let cms = args.cms.signer()?;
+    if !args.yes {
+        confirm(&devices, &detected)?;
+    }
+
+    let config = BatchConfig {
+        operator,
+        method: args.method.into(),
+        standard: args.standard.into(),
+        verify: args.verify.mode(args.sample_percent),
+        error_policy: args.on_bad_sectors.policy(args.max_bad_sectors),
+        max_jobs_per_controller: args.jobs_per_controller,
+        allow_system_disk: args.allow_system_disk,
+        report_dir: default_report_dir(),
+        checkpoint_dir: Some(default_checkpoint_dir()),
+        io: args.io.config(),
+    };
+    args.schedule.apply(&config.io);
+    run_batch(devices, config, args.devices, args.schedule.throttle_file, cms, json)
+}
+
+fn resume(args: ResumeArgs, json: bool) -> Result<(), CliError> {
+    let store = CheckpointStore::open_default().map_err(|e| CliError::new(FailureClass::Io, e.to_string()))?;
+    let Some(path) = args.device else {
+        let checkpoints = store.list();
+        if json {
+            print_json(&checkpoints);
+            return Ok(());
+        }
+        if checkpoints.is_empty() {
+            println!("No interrupted erases");
+        }
+        for checkpoint in &checkpoints {
+            println!(
+                "{:<16} {:<24} {:<32} {:5.1}%  saved {}",
+                checkpoint.path,
+                checkpoint.identity.serial.as_deref().unwrap_or("-"),
+                checkpoint.standard,
+                checkpoint.fraction() * 100.0,
+                checkpoint.saved_at
+            );
+        }
+        return Ok(());
+    };
+
+    let detected = StorageDriver::detect_storage_devices();
+    let (record, identity) = erase_target(&detected, &path, args.allow_system_disk)?;
+    let checkpoint = store
+        .find(&path, &identity)
+        .ok_or_else(|| CliError::new(FailureClass::NotFound, format!("no checkpoint for {}", path)))?;
+    if args.discard {
+        return store.remove(&checkpoint).map_err(|e| CliError::new(FailureClass::Io, e.to_string()));
+    }
+
+    let operator = operator_name(args.operator)?;
+    let cms = args.cms.signer()?;
+    let devices = vec![BatchDevice::new(record).with_identity(identity).with_resume(checkpoint.clone())];
+    if !args.yes {
+        eprintln!(
+            "Resuming {} at {:.1}%, started {}",
+            checkpoint.standard,
+            checkpoint.fraction() * 100.0,
+            checkpoint.started_at
+        );
+        confirm(&devices, &detected)?;
+    }
+
+    let config = BatchConfig {
+        operator,
+        method: WipeMethod::Overwrite,
+        standard: checkpoint.standard,
+        verify: checkpoint.verify,
+        error_policy: checkpoint.error_policy,
+        max_jobs_per_controller: 1,
+        allow_system_disk: args.allow_system_disk,
+        report_dir: default_report_dir(),
+        checkpoint_dir: Some(default_checkpoint_dir()),
+        io: args.io.config(),
+    };
+    args.schedule.apply(&config.io);
+    run_batch(devices, config, vec![path], args.schedule.throttle_file, cms, json)
+}
+
+fn operator_name(operator: Option<String>) -> Result<String, CliError> {
+    operator
+        .or_else(|| std::env::var("SUDO_USER").ok())
+        .or_else(|| std::env::var("USER").ok())
+        .filter(|operator| !operator.trim().is_empty())
+        .ok_or_else(|| CliError::new(FailureClass::Usage, "no operator name, pass --operator"))
+}
+
+// Runs the jobs to completion, printing progress and the batch summary
+fn run_batch(
+    devices: Vec<BatchDevice>,
+    config: BatchConfig,
+    paths: Vec<String>,
+    throttle_file: Option<PathBuf>,
+    cms: Option<CmsSigner>,
+    json: bool,
+) -> Result<(), CliError> {
+    let mut store = CertificateStore::open_default()?;
+    if let Some(cms) = cms {
+        store.set_cms_signer(cms);
+    }
+    let services = BatchServices {
+        signer: Some(Arc::new(CertificateSigner::load_or_create(&default_key_path())?)),
+        store: Some(Arc::new(Mutex::new(store))),
+        audit: AuditLog::open_default().ok().map(Arc::new),
+    };
+
+    unsafe {
+        libc::signal(libc::SIGINT, on_interrupt as *const () as libc::sighandler_t);
+        libc::signal(libc::SIGTERM, on_interrupt as *const () as libc::sighandler_t);
+    }
+
+    let handle = start_batch(devices, config, services);
+    let show_progress = io::stderr().is_terminal();
+    let mut trackers = vec![ProgressTracker::new(); paths.len()];
+    let mut throttle_modified = None;
+    loop {
+        if INTERRUPTED.swap(false, Ordering::Relaxed) {
+            eprintln!("\ninterrupted, cancelling all jobs");
+            handle.cancel_all();
+        }
+        if let Some(path) = &throttle_file {
+            reload_throttle(path, &mut throttle_modified, &handle.control());
+        }
+        for event in handle.poll() {
+            match event {
+                BatchEvent::JobStarted { job } => {
+                    trackers[job] = ProgressTracker::new();
+                    eprintln!("{}: started", paths[job]);
+                }
+                BatchEvent::Progress { job, progress } => {
+                    trackers[job].update(progress);
+                    if show_progress {
+                        eprint!("\r{}: {}   ", paths[job], progress_line(&trackers[job]));
+                    }
+                }
+                // Only pause windows hold jobs started from the CLI
+                BatchEvent::JobPaused { job, window } => {
+                    let window = window.map(|w| format!(" during {}", w)).unwrap_or_default();
+                    eprintln!("\r{}: paused{}", paths[job], window);
+                }
+                BatchEvent::JobResumed { job } => eprintln!("\r{}: resumed", paths[job]),
+                BatchEvent::JobFinished { job, result } => {
+                    let error = result.error.map(|e| format!(": {}", e)).unwrap_or_default();
+                    eprintln!("\r{}: {}{}", paths[job], result.outcome, error);
+                }
+                BatchEvent::BatchFinished { summary, report } => {
+                    if json {
+                        print_json(&summary);
+                    } else {
+                        print!("{}", summary.to_text());
+                    }
+                    if let Err(e) = report {
+                        eprintln!("warning: failed to save batch report: {}", e);
+                    }
+
+                    let outcomes: Vec<&str> =
+                        summary.jobs.iter().filter_map(|job| job.error.as_deref()).collect();
+                    return if summary.count(JobOutcome::Cancelled) > 0 {
+                        Err(CliError::new(FailureClass::Cancelled, "one or more jobs were cancelled"))
+                    } else if summary.count(JobOutcome::Failed) > 0 && outcomes.is_empty() {
+                        Err(CliError::new(FailureClass::VerificationFailed, "verification failed"))
+                    } else if summary.count(JobOutcome::Failed) > 0 {
+                        Err(CliError::new(FailureClass::EraseFailed, outcomes.join("; ")))
+                    } else {
+                        Ok(())
+                    };
+                }
+            }
+        }
+        thread::sleep(Duration::from_millis(100));
+    }
+}
+
+// Applies the settings in `path` to the running jobs whenever the file changes
+fn reload_throttle(path: &Path, modified: &mut Option<SystemTime>, control: &BatchControl) {
+    let Ok(changed) = std::fs::metadata(path).and_then(|metadata| metadata.modified()) else {
+        return;
+    };
+    if *modified == Some(changed) {
+        return;
+    }
+    *modified = Some(changed);
+    let settings = std::fs::read(path)
+        .map_err(|e| e.to_string())
+        .and_then(|data| serde_json::from_slice::<ThrottleSettings>(&data).map_err(|e| e.to_string()));
+    match settings {
+        Ok(settings) => {
+            control.set_throttle(settings);
+            eprintln!("\rthrottle settings loaded from {}", path.display());
+        }
+        Err(e) => eprintln!("\rwarning: ignoring {}: {}", path.display(), e),
+    }
+}
+
+fn progress_line(tracker: &ProgressTracker) -> String {
+    let Some(progress) = tracker.latest() else {
+        return String::new();
+    };
+    let stats = tracker.stats();
+    let eta = stats.eta.map(format_duration).unwrap_or_else(|| "--".to_string());
+    if let Some(firmware) = &progress.firmware {
+        let done = firmware
+            .reported
+            .map(|done| format!("{:5.1}%", done * 100.0))
+            .unwrap_or_else(|| "running".to_string());
+        return format!("{} {}, elapsed {}, ETA {}", firmware.operation, done, format_duration(stats.elapsed), eta);
+    }
+    format!(
+        "{} pass {}/{} ({}) {:5.1}%, {}, ETA {}",
+        progress.phase,
+        progress.pass,
+        progress.passes,
+        progress.pattern,
+        progress.overall_fraction() * 100.0,
+        format_rate(stats.current_rate),
+        eta
+    )
+}
+
+fn verify(args: VerifyArgs, json: bool) -> Result<(), CliError> {
+    let bytes = hex::decode(&args.pattern)
+        .ok()
+        .filter(|bytes| !bytes.is_empty())
+        .ok_or_else(|| CliError::new(FailureClass::Usage, format!("invalid pattern {:?}", args.pattern)))?;
+    let path = Path::new(&args.device);
+    let size = std::fs::File::open(path)
+        .and_then(|mut file| io::Seek::seek(&mut file, io::SeekFrom::End(0)))
+        .map_err(|e| CliError::new(FailureClass::NotFound, format!("{}: {}", args.device, e)))?;
+
+    let generator = PatternGenerator::new(Pattern::Fixed(bytes), Seed::default());
+    let mode = args.mode.mode(args.sample_percent);
+    let geometry = StorageDriver::block_geometry(&args.device).unwrap_or_default();
+    let io = args.io.config().aligned_to(&geometry);
+    let report = verify_pattern(path, &generator, size, mode, &io, &mut |_| {}, &AtomicBool::new(false))?;
+    audit_events([AuditEvent::VerificationResult {
+        path: args.device.clone(),
+        passed: report.passed,
+        mismatches: report.mismatched_sectors,
+    }]);
+
+    if json {
+        print_json(&report);
+    } else {
+        println!("Verification: {}", report.mode);
+        println!("Bytes read:   {}", report.bytes_verified);
+        println!("Mismatched sectors: {}", report.mismatched_sectors);
+        if let Some(offset) = report.first_mismatch {
+            println!("First mismatch at byte {}", offset);
+        }
+        if !report.unreadable.is_empty() {
+            println!("Unreadable sectors: {} ({})", report.unreadable.sectors(), report.unreadable);
+        }
+        println!("Result:       {}", if report.passed { "Passed" } else { "Failed" });
+    }
+    if !report.passed {
+        return Err(CliError::new(FailureClass::VerificationFailed, "device does not match the pattern"));
+    }
+    if !report.unreadable.is_empty() {
+        return Err(CliError::new(FailureClass::VerificationFailed, "some sectors could not be read"));
+    }
+    Ok(())
+}
+
+fn bench(args: BenchArgs, json: bool) -> Result<(), CliError> {
+    let op = if args.write { IoOp::Write } else { IoOp::Read };
+    let mut expected = None;
+    if args.write {
+        let detected = StorageDriver::detect_storage_devices();
+        let (record, identity) = erase_target(&detected, &args.device, args.allow_system_disk)?;
+        if !args.yes {
+            eprintln!("The write benchmark overwrites the first {}", StorageDriver::format_size(args.size));
+            confirm(&[BatchDevice::new(record).with_identity(identity.clone())], &detected)?;
+        }
+        // Same checks as an erase, since the device may have changed while
+        // the operator was confirming
+        check_target(&args.device, Some(&identity), args.allow_system_disk)?;
+        expected = Some(identity);
+    }
+
+    unsafe {
+        libc::signal(libc::SIGINT, on_interrupt as *const () as libc::sighandler_t);
+    }
+    let geometry = StorageDriver::block_geometry(&args.device).unwrap_or_default();
+    let io = args.io.config().aligned_to(&geometry);
+    let report = benchmark(Path::new(&args.device), op, args.size, &io, expected.as_ref(), &INTERRUPTED)?;
+
+    if json {
+        print_json(&report);
+    } else {
+        println!("Operation:   {}", report.op);
+        println!("Backend:     {}", report.backend);
+        println!("Queue depth: {}", report.queue_depth);
+        println!("Block size:  {}", StorageDriver::format_size(report.block_size as u64));
+        println!("Transferred: {}", StorageDriver::format_size(report.bytes));
+        println!("Elapsed:     {:.2}s", report.seconds);
+        println!("Throughput:  {}", format_rate(report.throughput));
+    }
+    Ok(())
+}
+
+fn forensics(target: &str, json: bool) -> Result<(), CliError> {
+    let report = scan_forensics(Path::new(target), &mut |_, _| {})
+        .map_err(|e| CliError::new(FailureClass::Io, format!("{}: {}", target, e)))?;
+
+    if json {
+        print_json(&report);
+    } else {
+        println!("Searched:     {} outside any filesystem", StorageDriver::format_size(report.unallocated_bytes));
+        println!("Carved files: {}", report.carved_count());
+        for (kind, count) in &report.carved_by_type {
+            println!("  {:<16} {}", kind, count);
+        }
+        for deleted in &report.deleted {
+            let volume = deleted.partition.map_or("whole disk".to_string(), |number| format!("partition {}", number));
+            println!(
+                "Deleted in {} ({}): {} files, {} directories, {} names",
+                volume, deleted.filesystem, deleted.files, deleted.directories, deleted.names
+            );
+            for entry in &deleted.samples {
+                let size = entry.size.map(StorageDriver::format_size).unwrap_or_default();
+                println!("  {}{} {}", entry.name, if entry.directory { "/" } else { "" }, size);
+            }
+        }
+        println!("Result:       {}", if report.is_clean() { "Nothing recoverable" } else { "Recoverable data found" });
+    }
+    if !report.is_clean() {
+        return Err(CliError::new(FailureClass::VerificationFailed, "recoverable data found"));
+    }
+    Ok(())
+}
+
+fn health(path: &str, json: bool) -> Result<(), CliError> {
+    let report = read_health(Path::new(path))?;
+    if json {
+        print_json(&report);
+        return Ok(());
+    }
+    let unknown = |value: Option<u64>| value.map_or_else(|| "-".to_string(), |v| v.to_string());
+    println!("Source:        {}", report.source);
+    println!(
+        "Assessment:    {}",
+        match report.passed {
+            Some(true) => "Passed",
+            Some(false) => "Failure predicted",
+            None => "Not reported",
+        }
+    );
+    println!("Reallocated:   {}", unknown(report.reallocated_sectors));
+    println!("Pending:       {}", unknown(report.pending_sectors));
+    println!("Media errors:  {}", unknown(report.media_errors));
+    println!("Power-on time: {} hours", unknown(report.power_on_hours));
+    println!("Wear:          {}", report.percent_used.map_or("-".to_string(), |p| format!("{}% used", p)));
+    if let Some(spare) = report.available_spare {
+        println!("Spare:         {}%", spare);
+    }
+    println!("Temperature:   {}", report.temperature_celsius.map_or("-".to_string(), |t| format!("{} °C", t)));
+    if !report.attributes.is_empty() {
+        println!("{:>3} {:<26} {:>5} {:>5} {:>6} {:>14}", "ID", "ATTRIBUTE", "VALUE", "WORST", "THRESH", "RAW");
+        for attribute in &report.attributes {
+            println!(
+                "{:>3} {:<26} {:>5} {:>5} {:>6} {:>14}{}",
+                attribute.id,
+                attribute.name,
+                attribute.current,
+                attribute.worst,
+                attribute.threshold,
+                attribute.raw,
+                if attribute.failing() { "  FAILING" } else { "" }
+            );
+        }
+    }
+    for warning in report.warnings() {
+        println!("Warning:       {}", warning);
+    }
+    Ok(())
+}
+
+fn verify_audit_log(log: Option<PathBuf>, json: bool) -> Result<(), CliError> {
+    let path = log.unwrap_or_else(default_log_path);
+    let verification = verify_log(&path)?;
+    if json {
+        print_json(&serde_json::json!({
+            "path": path,
+            "entries": verification.entries,
+            "last_hash": verification.last_hash,
+        }));
+    } else {
+        println!("{}: {} entries intact", path.display(), verification.entries);
+        println!("Last hash: {}", verification.last_hash);
+    }
+    Ok(())
+}
+
+fn cert_show(certificate: &str, json: bool) -> Result<(), CliError> {
+    let cert = load_certificate(certificate)?;
+    if json {
+        print_json(&cert);
+        return Ok(());
+    }
+    let body = &cert.body;
+    println!("Certificate: {}", body.id);
+    println!("Issued:      {}", body.issued_at);
+    println!("Operator:    {}", body.operator);
+    println!("Method:      {}", body.method);
+    if let Some(standard) = &body.standard {
+        println!("Standard:    {}", standard);
+    }
+    if body.result == CertificateResult::Passed && !body.is_verified() {
+        println!("Result:      {} (not verified)", body.result);
+    } else {
+        println!("Result:      {}", body.result);
+    }
+    for device in &body.devices {
+        println!(
+            "Device:      {} {} (serial {})",
+            device.path,
+            device.model.as_deref().unwrap_or("-"),
+            device.serial_number.as_deref().unwrap_or("-")
+        );
+    }
+    let verification = &body.verification;
+    for (label, errors) in [("Write errors:", &verification.write_errors), ("Read errors: ", &verification.read_errors)] {
+        if !errors.is_empty() {
+            println!("{} {} sectors of {} bytes: {}", label, errors.sectors(), errors.sector_size, errors);
+        }
+    }
+    if let Some(policy) = &verification.error_policy {
+        println!("Bad sectors: {}", policy);
+    }
+    for (label, health) in [("Health pre:  ", &body.health_before), ("Health post: ", &body.health_after)] {
+        if let Some(health) = health {
+            let assessment = match health.passed {
+                Some(false) => "failure predicted",
+                _ if health.warnings.is_empty() => "no warnings",
+                _ => "warnings",
+            };
+            println!("{}{} ({})", label, health.source, assessment);
+            for warning in &health.warnings {
+                println!("             {}", warning);
+            }
+        }
+    }
+    for resume in &body.resumes {
+        println!("Resumed:     {}", resume);
+    }
+    if let Some(signature) = &cert.signature {
+        println!("Signed by:   {}", signature.fingerprint);
+    }
+    Ok(())
+}
+
+fn cert_verify(
+    certificate: &str,
+    fingerprint: Option<&str>,
+    trust: Option<&Path>,
+    signature_path: Option<&Path>,
+    json: bool,
+) -> Result<(), CliError> {
+    let path = certificate_file(certificate)?;
+    let document = std::fs::read(&path).map_err(CertError::from)?;
+    let trust = trust.map(load_certificates).transpose()?;
+    if document.starts_with(b"%PDF-") {
+        let trust = trust.ok_or_else(|| {
+            CliError::new(FailureClass::Usage, "signed PDFs are verified against a CA bundle, pass --trust")
+        })?;
+        let cms = verify_pdf(&document, &trust);
+        if json {
+            print_json(&serde_json::json!({ "path": path, "cms": cms_json(&cms) }));
+        } else {
+            print_cms(&path.display().to_string(), &cms);
+        }
+        cms?;
+        return Ok(());
+    }
+
+    let cert = Certificate::from_json(&String::from_utf8_lossy(&document))?;
+    // A signature only proves something when the key is known; without a
+    // fingerprint the key this machine signs with is the one trusted
+    let anchor = match fingerprint {
+        Some(fingerprint) => Some(fingerprint.to_string()),
+        None => CertificateSigner::load(&default_key_path()).ok().map(|signer| signer.fingerprint()),
+    };
+    let signature = cert.verify(anchor.as_deref());
+    // A CA-verified CMS signature over the document vouches for it as well
+    let trusted = anchor.is_some() || trust.is_some();
+    let revocation = CertificateStore::open_default()
+        .ok()
+        .and_then(|store| store.status(&cert.body.id).and_then(|s| s.revocation.clone()));
+    // The detached signature covers the JSON exactly as stored
+    let cms = trust.map(|trust| {
+        let signature_path = signature_path.map_or_else(|| path.with_extension("p7s"), Path::to_path_buf);
+        std::fs::read(&signature_path)
+            .map_err(CertError::from)
+            .and_then(|cms_signature| verify_detached(&cms_signature, &document, &trust))
+    });
+
+    if json {
+        print_json(&serde_json::json!({
+            "id": cert.body.id,
+            "signature_valid": signature.is_ok(),
+            "signature_error": signature.as_ref().err().map(|e| e.to_string()),
+            "signer_trusted": signature.is_ok() && trusted,
+            "cms": cms.as_ref().map(cms_json),
+            "revoked": revocation.is_some(),
+            "revocation": revocation,
+        }));
+    } else {
+        match &signature {
+            Ok(()) if !trusted => {
+                println!("{}: signature is internally consistent but the signer is NOT trusted", cert.body.id)
+            }
+            Ok(()) => println!("{}: signature valid", cert.body.id),
+            Err(e) => println!("{}: {}", cert.body.id, e),
+        }
+        if let Some(cms) = &cms {
+            print_cms(&cert.body.id, cms);
+        }
+        if let Some(revocation) = &revocation {
+            println!("revoked at {}: {}", revocation.revoked_at, revocation.reason);
+        }
+    }
+
+    signature?;
+    cms.transpose()?;
+    if !trusted {
+        return Err(CliError::new(
+            FailureClass::CertificateInvalid,
+            "signer is not trusted, pass --fingerprint or --trust",
+        ));
+    }
+    if revocation.is_some() {
+        return Err(CliError::new(FailureClass::CertificateInvalid, "certificate has been revoked"));
+    }
+    Ok(())
+}
+
+fn cms_json(cms: &Result<CmsVerification, CertError>) -> serde_json::Value {
+    match cms {
+        Ok(cms) => serde_json::json!({
+            "valid": true,
+            "signer": cms.signer,
+            "chain": cms.chain,
+            "signing_time": cms.signing_time,
+        }),
+        Err(e) => serde_json::json!({ "valid": false, "error": e.to_string() }),
+    }
+}
+
+fn print_cms(name: &str, cms: &Result<CmsVerification, CertError>) {
+    match cms {
+        Ok(cms) => {
+            println!("{}: CMS signature valid, signed by {}", name, cms.signer);
+            println!("chain: {}", cms.chain.join(" <- "));
+            if let Some(time) = &cms.signing_time {
+                println!("signed at {}", time);
+            }
+        }
+        Err(e) => println!("{}: CMS signature: {}", name, e),
+    }
+}
+
+fn cert_pdf(
+    certificate: &str,
+    template: Option<&Path>,
+    output: Option<PathBuf>,
+    cms: &CmsArgs,
+    json: bool,
+) -> Result<(), CliError> {
+    let cert = load_certificate(certificate)?;
+    let cms = cms.signer()?;
+    let template = load_template(template)?;
+    let output = output.unwrap_or_else(|| PathBuf::from(format!("{}.pdf", cert.body.id)));
+    let revocation = CertificateStore::open_default()
+        .ok()
+        .and_then(|store| store.status(&cert.body.id).and_then(|s| s.revocation.clone()));
+    let document = match (&revocation, &cms) {
+        (Some(_), Some(_)) => {
+            return Err(CliError::new(FailureClass::CertificateInvalid, "certificate has been revoked"));
+        }
+        (Some(revocation), None) => render_revoked_pdf(&cert, &template, revocation)?,
+        (None, Some(cms)) => cms.sign_pdf(&cert, &template)?,
+        (None, None) => render_pdf(&cert, &template)?,
+    };
+    std::fs::write(&output, document)
+        .map_err(|e| CliError::new(FailureClass::Io, format!("{}: {}", output.display(), e)))?;
+
+    if json {
+        print_json(&serde_json::json!({ "id": cert.body.id, "path": output }));
+    } else {
+        println!("{}", output.display());
+    }
+    Ok(())
+}
+
+fn cert_search(args: SearchArgs, json: bool) -> Result<(), CliError> {
+    let query = CertificateQuery {
+        serial: args.serial,
+        model: args.model,
+        operator: args.operator,
+        result: args.result.map(|result| match result {
+            ResultArg::Passed => CertificateResult::Passed,
+            ResultArg::Failed => CertificateResult::Failed,
+        }),
+        issued_from: args.from.as_deref().map(|date| parse_query_date(date, false)).transpose().map_err(usage)?,
+        issued_to: args.to.as_deref().map(|date| parse_query_date(date, true)).transpose().map_err(usage)?,
+        include_revoked: args.include_revoked,
+    };
+    let store = CertificateStore::open_default()?;
+    let found = store.query(&query);
+
+    if json {
+        let found: Vec<_> = found
+            .iter()
+            .map(|stored| serde_json::json!({ "certificate": stored.entry, "revocation": stored.revocation }))
+            .collect();
+        print_json(&found);
+        return Ok(());
+    }
+    if found.is_empty() {
+        println!("No matching certificates");
+    }
+    for stored in found {
+        let entry = &stored.entry;
+        let serials: Vec<&str> =
+            entry.devices.iter().map(|d| d.serial_number.as_deref().unwrap_or("-")).collect();
+        let result = if stored.revocation.is_some() { "Revoked".to_string() } else { entry.result.to_string() };
+        println!(
+            "{:<36} {:<25} {:<16} {:<8} {}",
+            entry.id,
+            entry.issued_at,
+            entry.operator,
+            result,
+            serials.join(", ")
+        );
+    }
+    Ok(())
+}
+
+fn cert_export(args: ExportArgs, json: bool) -> Result<(), CliError> {
+    let store = CertificateStore::open_default()?;
+    let extension = match args.format {
+        ExportFormat::Json => "json",
+        ExportFormat::Pdf => "pdf",
+    };
+    let output = args.output.unwrap_or_else(|| PathBuf::from(format!("{}.{}", args.id, extension)));
+    match args.format {
+        ExportFormat::Json => store.export_json(&args.id, &output)?,
+        ExportFormat::Pdf => store.export_pdf(&args.id, &load_template(args.template.as_deref())?, &output)?,
+    }
+
+    if json {
+        print_json(&serde_json::json!({ "id": args.id, "path": output }));
+    } else {
+        println!("{}", output.display());
+    }
+    Ok(())
+}
+
+fn cert_revoke(id: &str, reason: &str, json: bool) -> Result<(), CliError> {
+    if reason.trim().is_empty() {
+        return Err(CliError::new(FailureClass::Usage, "a revocation needs a reason"));
+    }
+    let mut store = CertificateStore::open_default()?;
+    store.set_audit_log(AuditLog::open_default().map_err(|e| CliError::new(FailureClass::Io, e.to_string()))?);
+    let revocation = store.revoke(id, reason.trim())?;
+
+    if json {
+        print_json(&revocation);
+    } else {
+        println!("{} revoked at {}", revocation.id, revocation.revoked_at);
+    }
+    Ok(())
+}
+
+fn load_template(path: Option<&Path>) -> Result<CertificateTemplate, CliError> {
+    match path {
+        Some(path) => Ok(CertificateTemplate::load(path)?),
+        None => Ok(CertificateTemplate::default()),
+    }
+}
+
+fn usage(e: CertError) -> CliError {
+    CliError::new(FailureClass::Usage, e.to_string())
+}
+
+// A file on disk, or otherwise a certificate id in the default store
+fn load_certificate(certificate: &str) -> Result<Certificate, CliError> {
+    Ok(Certificate::load(&certificate_file(certificate)?)?)
+}
+
+fn certificate_file(certificate: &str) -> Result<PathBuf, CliError> {
+    let path = PathBuf::from(certificate);
+    if path.exists() {
+        return Ok(path);
+    }
+    let path = CertificateStore::open_default()?.json_path(certificate)?;
+    if !path.exists() {
+        return Err(CliError::new(FailureClass::NotFound, format!("no certificate with id {}", certificate)));
+    }
+    Ok(path)
+}
+
+fn find_device<'a>(devices: &'a [StorageDevice], path: &str) -> Option<&'a StorageDevice> {
+    let name = path.trim_start_matches("/dev/");
+    devices
+        .iter()
+        .find(|device| device.path == path || device.path.trim_start_matches("/dev/") == name)
+}
+
+// Detected block devices, or plain image files which are useful for testing
+// The identity is taken from the same scan the operator is shown, and is
+// checked again by the worker right before writing
+fn erase_target(
+    devices: &[StorageDevice],
+    path: &str,
+    allow_system_disk: bool,
+) -> Result<(DeviceRecord, DeviceIdentity), CliError> {
+    if let Some(device) = find_device(devices, path) {
+        if device.usage.blocks_erase(allow_system_disk) {
+            let hint = if device.usage.is_in_use() { "" } else { ", pass --allow-system-disk to override" };
+            return Err(CliError::new(
+                FailureClass::Refused,
+                format!("refusing to erase {}: {}{}", device.path, device.usage.describe(), hint),
+            ));
+        }
+        return Ok((DeviceRecord::from(device), DeviceIdentity::from(device)));
+    }
+    match std::fs::metadata(path) {
+        Ok(metadata) if metadata.is_file() => Ok((
+            DeviceRecord {
+                path: path.to_string(),
+                model: None,
+                serial_number: None,
+                vendor: None,
+                size: Some(metadata.len()),
+                geometry: None,
+                firmware: None,
+                wwn: None,
+                transport: None,
+                by_id: None,
+                by_path: None,
+                device_type: "Image".to_string(),
+            },
+            DeviceIdentity { size: Some(metadata.len()), ..Default::default() },
+        )),
+        _ => Err(CliError::new(FailureClass::NotFound, format!("no device {}", path))),
+    }
+}
+
+// Shows what is about to be destroyed and has the operator type each
+// device's confirmation code, so a typo in a path cannot wipe the wrong disk
+fn confirm(devices: &[BatchDevice], detected: &[StorageDevice]) -> Result<(), CliError> {
+    if !io::stdin().is_terminal() {
+        return Err(CliError::new(
+            FailureClass::Refused,
+            "refusing to erase without confirmation, pass --yes",
+        ));
+    }
+    eprintln!("All data on these devices will be destroyed:");
+    for device in devices {
+        let record = &device.record;
+        let status = find_device(detected, &record.path)
+            .map_or_else(|| "Image file".to_string(), |d| format!("{:?}", d.status));
+        eprintln!("  {}", record.path);
+        eprintln!("    Model:  {}", record.model.as_deref().unwrap_or("Unknown"));
+        eprintln!("    Serial: {}", record.serial_number.as_deref().unwrap_or("Unknown"));
+        if let Some(wwn) = &record.wwn {
+            eprintln!("    WWN:    {}", wwn);
+        }
+        if let Some(by_id) = &record.by_id {
+            eprintln!("    ID:     {}", by_id);
+        }
+        eprintln!("    Size:   {}", record.size.map(StorageDriver::format_size).unwrap_or_else(|| "Unknown".to_string()));
+        eprintln!("    Status: {}", status);
+    }
+
+    for device in devices {
+        let code = device.record.confirmation_code();
+        let what = if device.record.serial_number.is_some() {
+            "the last characters of the serial"
+        } else {
+            "the device name"
+        };
+        eprint!("Type {} of {} ({} characters): ", what, device.record.path, code.chars().count());
+        let _ = io::stderr().flush();
+
+        let mut answer = String::new();
+        io::stdin()
+            .lock()
+            .read_line(&mut answer)
+            .map_err(|e| CliError::new(FailureClass::Io, e.to_string()))?;
+        if answer.trim() != code {
+            return Err(CliError::new(
+                FailureClass::Refused,
+                format!("erase of {} not confirmed", device.record.path),
+            ));
+        }
+    }
+    Ok(())
+}
+
+fn audit_events(events: impl IntoIterator<Item = AuditEvent>) {
+    let Ok(log) = AuditLog::open_default() else {
+        return;
+    };
+    for event in events {
+        if let Err(e) = log.append(event) {
+            eprintln!("warning: failed to write audit log: {}", e);
+            return;
+        }
+    }
+}
+
+fn print_json<T: Serialize>(value: &T) {
+    match serde_json::to_string_pretty(value) {
+        Ok(json) => println!("{}", json),
+        Err(e) => eprintln!("error: {}", e),
+    }
+}
@@ -1,11 +1,40 @@
-use std::{fs::{self, OpenOptions}, os::fd::AsRawFd};
+use std::{
+    fs::{self, File, OpenOptions},
+    os::fd::{AsRawFd, RawFd},
+    path::Path,
+    thread,
+    time::Duration,
+};
 
-use crate::platform::{StorageInterface};
+use crate::{
+    erasure::{EraseError, ErasePhase, EraseProgress, FirmwareProgress},
+    platform::StorageInterface,
+};
+
+pub const ATA_IDENTIFY_DEVICE: u8 = 0xec;
+pub const ATA_SANITIZE_DEVICE: u8 = 0xb4;
+pub const ATA_SECURITY_SET_PASSWORD: u8 = 0xf1;
+pub const ATA_SECURITY_ERASE_PREPARE: u8 = 0xf3;
+pub const ATA_SECURITY_ERASE_UNIT: u8 = 0xf4;
+pub const ATA_SECURITY_DISABLE_PASSWORD: u8 = 0xf6;
+
+// SANITIZE DEVICE subcommands (feature) and the signatures they require (LBA)
+const SANITIZE_STATUS_EXT: u16 = 0x0000;
+const CRYPTO_SCRAMBLE_EXT: u16 = 0x0011;
+const BLOCK_ERASE_EXT: u16 = 0x0012;
+const CRYPTO_SCRAMBLE_SIGNATURE: u64 = 0x4372_7970;
+const BLOCK_ERASE_SIGNATURE: u64 = 0x426b_4572;
+
+// Temporary user password set for SECURITY ERASE UNIT; the drive clears it
+// again when the erase completes
+const ERASE_PASSWORD: &[u8] = b"secure-erase";
 
 pub struct AtaDeviceInfo {
     pub model: String,
     pub serial: String,
     pub firmware: String,
+    /// World wide name in the kernel's `naa.` notation
+    pub wwn: Option<String>,
     pub sector_size: u16,
     pub sector_count: u64,
     pub security_supported: bool,
@@ -13,6 +42,96 @@ pub struct AtaDeviceInfo {
     pub security_locked: bool,
     pub security_frozen: bool,
     pub enhanced_erase_supported: bool,
+    pub sanitize_supported: bool,
+    pub sanitize_block_erase: bool,
+    pub sanitize_crypto_scramble: bool,
+    /// Time the drive estimates for SECURITY ERASE UNIT
+    pub erase_time: Option<Duration>,
+    pub enhanced_erase_time: Option<Duration>,
+}
+
+impl AtaDeviceInfo {
+    /// Decodes the 512-byte IDENTIFY DEVICE data
+    pub fn from_identify(data: &[u8; 512]) -> Self {
+        let word = |index: usize| u16::from_le_bytes([data[index * 2], data[index * 2 + 1]]);
+        // Strings are stored with the bytes of every word swapped
+        let string = |start: usize, end: usize| {
+            let bytes: Vec<u8> = (start..end).flat_map(|i| word(i).to_be_bytes()).collect();
+            String::from_utf8_lossy(&bytes).trim().to_string()
+        };
+        // Erase times are in units of two minutes; 0 means not reported
+        let erase_time = |value: u16| {
+            let minutes = if value & 0x8000 != 0 { value & 0x7fff } else { value & 0x00ff };
+            (minutes != 0).then(|| Duration::from_secs(minutes as u64 * 120))
+        };
+
+        let sector_count = if word(83) & 0x0400 != 0 {
+            (100..104).rev().fold(0u64, |count, i| (count << 16) | word(i) as u64)
+        } else {
+            (word(61) as u64) << 16 | word(60) as u64
+        };
+        let sector_size = if word(106) & 0xc000 == 0x4000 && word(106) & 0x1000 != 0 {
+            (((word(118) as u32) << 16 | word(117) as u32) * 2).min(u16::MAX as u32) as u16
+        } else {
+            512
+        };
+        let security = word(128);
+        let wwn = (108..112).fold(0u64, |wwn, i| (wwn << 16) | word(i) as u64);
+
+        AtaDeviceInfo {
+            model: string(27, 47),
+            serial: string(10, 20),
+            firmware: string(23, 27),
+            wwn: (word(87) & 0x0100 != 0 && wwn != 0).then(|| format!("naa.{:016x}", wwn)),
+            sector_size,
+            sector_count,
+            security_supported: word(82) & 0x0002 != 0 && security & 0x0001 != 0,
+            security_enabled: security & 0x0002 != 0,
+            security_locked: security & 0x0004 != 0,
+            security_frozen: security & 0x0008 != 0,
+            enhanced_erase_supported: security & 0x0020 != 0,
+            sanitize_supported: word(59) & 0x1000 != 0,
+            sanitize_block_erase: word(59) & 0x8000 != 0,
+            sanitize_crypto_scramble: word(59) & 0x2000 != 0,
+            erase_time: erase_time(word(89)),
+            enhanced_erase_time: erase_time(word(90)),
+        }
+    }
+}
+
+/// Registers written for an ATA command
+#[derive(Debug, Clone, Copy, Default)]
+pub struct AtaTaskfile {
+    pub command: u8,
+    pub feature: u16,
+    pub count: u16,
+    pub lba: u64,
+    pub device: u8,
+    /// 48-bit command
+    pub extended: bool,
+}
+
+/// Registers returned by the drive after a command
+#[derive(Debug, Clone, Copy, Default)]
+pub struct AtaStatus {
+    pub status: u8,
+    pub error: u8,
+    pub count: u16,
+    pub lba: u64,
+}
+
+pub enum AtaData<'a> {
+    None,
+    In(&'a mut [u8]),
+    Out(&'a [u8]),
+}
+
+/// Result of SANITIZE STATUS EXT
+#[derive(Debug, Clone, Copy)]
+pub struct AtaSanitizeStatus {
+    pub in_progress: bool,
+    pub completed: bool,
+    pub progress: f32,
 }
 
 #[derive(Debug)]
@@ -24,34 +143,70 @@ pub enum AtaError {
     CommandFailed(String),
 }
 
+impl std::fmt::Display for AtaError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AtaError::IoError(e) => write!(f, "I/O error: {}", e),
+            AtaError::Unsupported => write!(f, "operation not supported"),
+            AtaError::DeviceNotFound => write!(f, "device not found"),
+            AtaError::PermissionDenied => write!(f, "permission denied"),
+            AtaError::CommandFailed(e) => write!(f, "ATA command failed: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for AtaError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            AtaError::IoError(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for AtaError {
+    fn from(e: std::io::Error) -> Self {
+        match e.kind() {
+            std::io::ErrorKind::NotFound => AtaError::DeviceNotFound,
+            std::io::ErrorKind::PermissionDenied => AtaError::PermissionDenied,
+            _ => AtaError::IoError(e),
+        }
+    }
+}
+
 pub struct AtaStorageDriver {
-	// TODO: Add fields for device path, file descriptor, etc.
     device_path: String,
-    fd: i32,
+    file: File,
 }
 
 impl AtaStorageDriver {
-	pub fn new(device_path: &str) -> Self {
+    pub fn new(device_path: &str) -> Result<Self, AtaError> {
         let file = OpenOptions::new()
             .read(true)
             .write(true)
-            .open(device_path)
-            .expect("Failed to open device");
+            .open(device_path)?;
 
-        let fd = file.as_raw_fd();
-        AtaStorageDriver {
+        Ok(AtaStorageDriver {
             device_path: device_path.to_string(),
-            fd,
-        }
+            file,
+        })
+    }
+
+    pub fn device_path(&self) -> &str {
+        &self.device_path
+    }
+
+    pub fn fd(&self) -> RawFd {
+        self.file.as_raw_fd()
     }
 
     pub fn is_ata_device(&self) -> bool {
-        // Check if the device is an ATA device
-        let device_path = format!("/sys/block/{}/device", self.device_path);
-        if let Ok(content) = fs::read_to_string(&device_path) {
-            return content.contains("ata");
-        }
-        false
+        // ATA disks sit below an ataN port in sysfs
+        let Some(name) = Path::new(&self.device_path).file_name() else {
+            return false;
+        };
+        fs::canonicalize(Path::new("/sys/block").join(name))
+            .is_ok_and(|path| path.components().any(|c| c.as_os_str().to_string_lossy().starts_with("ata")))
     }
 
     pub fn identify_ata_device(&self) -> Result<AtaDeviceInfo, AtaError> {
@@ -64,15 +219,193 @@ impl AtaStorageDriver {
             Err(AtaError::Unsupported)
         }
     }
+
+    pub fn execute(&self, taskfile: &AtaTaskfile, data: AtaData, timeout: Duration) -> Result<AtaStatus, AtaError> {
+        #[cfg(target_os = "linux")]
+        {
+            crate::platform::linux::ata_pass_through(self.fd(), taskfile, data, timeout)
+        }
+        #[cfg(not(target_os = "linux"))]
+        {
+            let _ = (taskfile, data, timeout);
+            Err(AtaError::Unsupported)
+        }
+    }
+
+    /// Starts a SANITIZE block erase or crypto scramble. The command returns
+    /// immediately; poll [`Self::sanitize_status`] until it completes.
+    pub fn sanitize(&self, crypto: bool) -> Result<(), AtaError> {
+        let (feature, lba) = if crypto {
+            (CRYPTO_SCRAMBLE_EXT, CRYPTO_SCRAMBLE_SIGNATURE)
+        } else {
+            (BLOCK_ERASE_EXT, BLOCK_ERASE_SIGNATURE)
+        };
+        let taskfile = AtaTaskfile {
+            command: ATA_SANITIZE_DEVICE,
+            feature,
+            lba,
+            device: 0x40,
+            extended: true,
+            ..Default::default()
+        };
+        self.execute(&taskfile, AtaData::None, Duration::from_secs(30))?;
+        Ok(())
+    }
+
+    pub fn sanitize_status(&self) -> Result<AtaSanitizeStatus, AtaError> {
+        let taskfile = AtaTaskfile {
+            command: ATA_SANITIZE_DEVICE,
+            feature: SANITIZE_STATUS_EXT,
+            device: 0x40,
+            extended: true,
+            ..Default::default()
+        };
+        let status = self.execute(&taskfile, AtaData::None, Duration::from_secs(30))?;
+        Ok(AtaSanitizeStatus {
+            completed: status.count & 0x8000 != 0,
+            in_progress: status.count & 0x4000 != 0,
+            progress: (status.lba & 0xffff) as f32 / 65536.0,
+        })
+    }
+
+    /// Runs SECURITY ERASE UNIT with a temporary user password. Blocks until
+    /// the drive finishes, which can take hours. If the erase fails the
+    /// password is disabled again so the drive is not left locked.
+    pub fn security_erase(&self, enhanced: bool, timeout: Duration) -> Result<(), AtaError> {
+        let mut block = [0u8; 512];
+        block[2..2 + ERASE_PASSWORD.len()].copy_from_slice(ERASE_PASSWORD);
+        let command = |command: u8| AtaTaskfile {
+            command,
+            count: 1,
+            ..Default::default()
+        };
+
+        self.execute(&command(ATA_SECURITY_SET_PASSWORD), AtaData::Out(&block), Duration::from_secs(10))?;
+        // Word 0: bit 0 selects the user password, bit 1 the enhanced erase
+        let mut erase_block = block;
+        erase_block[0] = if enhanced { 0x02 } else { 0x00 };
+        let erased = self
+            .execute(&command(ATA_SECURITY_ERASE_PREPARE), AtaData::None, Duration::from_secs(10))
+            .and_then(|_| self.execute(&command(ATA_SECURITY_ERASE_UNIT), AtaData::Out(&erase_block), timeout));
+
+        // A successful erase clears the password itself. Otherwise the drive
+        // would lock at the next power cycle, so the password is removed again.
+        let Err(error) = erased else {
+            return Ok(());
+        };
+        let disabled =
+            self.execute(&command(ATA_SECURITY_DISABLE_PASSWORD), AtaData::Out(&block), Duration::from_secs(10));
+        match disabled {
+            Ok(_) => Err(error),
+            Err(disable) => Err(AtaError::CommandFailed(format!(
+                "{}; removing the security password also failed ({}), so the drive will lock \
+                 at the next power cycle; unlock it with the user password \"{}\"",
+                error,
+                disable,
+                String::from_utf8_lossy(ERASE_PASSWORD)
+            ))),
+        }
+    }
+}
+
+impl From<AtaError> for EraseError {
+    fn from(e: AtaError) -> Self {
+        match e {
+            AtaError::IoError(e) => EraseError::IoError(e),
+            AtaError::Unsupported => EraseError::Unsupported("ATA command not supported".to_string()),
+            other => EraseError::CommandFailed(other.to_string()),
+        }
+    }
+}
+
+/// Erases an ATA drive with SANITIZE when supported, falling back to
+/// SECURITY ERASE UNIT for block erases. Progress comes from the sanitize
+/// status or, for security erase, a countdown from the IDENTIFY estimate.
+pub fn firmware_erase(
+    path: &Path,
+    crypto: bool,
+    progress: &mut dyn FnMut(EraseProgress),
+) -> Result<(), EraseError> {
+    let driver = AtaStorageDriver::new(&path.to_string_lossy())?;
+    if !driver.is_ata_device() {
+        return Err(EraseError::Unsupported(format!("{} is not an ATA drive", path.display())));
+    }
+    let info = driver.identify_ata_device()?;
+    let report = |progress: &mut dyn FnMut(EraseProgress), firmware: FirmwareProgress| {
+        progress(EraseProgress {
+            phase: ErasePhase::Firmware,
+            pass: 1,
+            passes: 1,
+            pattern: firmware.operation.clone(),
+            firmware: Some(firmware),
+            ..Default::default()
+        })
+    };
+
+    let sanitize = info.sanitize_supported
+        && if crypto { info.sanitize_crypto_scramble } else { info.sanitize_block_erase };
+    if sanitize {
+        let operation = if crypto { "Sanitize Crypto Scramble" } else { "Sanitize Block Erase" };
+        driver.sanitize(crypto)?;
+        loop {
+            thread::sleep(Duration::from_secs(1));
+            let status = driver.sanitize_status()?;
+            if !status.in_progress {
+                if !status.completed {
+                    return Err(EraseError::CommandFailed(format!("{} did not complete", operation)));
+                }
+                report(progress, FirmwareProgress {
+                    operation: operation.to_string(),
+                    reported: Some(1.0),
+                    estimated: None,
+                });
+                return Ok(());
+            }
+            report(progress, FirmwareProgress {
+                operation: operation.to_string(),
+                reported: Some(status.progress),
+                estimated: None,
+            });
+        }
+    }
+
+    if crypto {
+        return Err(EraseError::Unsupported("drive does not support sanitize crypto scramble".to_string()));
+    }
+    if !info.security_supported {
+        return Err(EraseError::Unsupported("drive supports neither sanitize nor security erase".to_string()));
+    }
+    if info.security_frozen {
+        return Err(EraseError::CommandFailed(
+            "drive security is frozen, suspend and resume the machine to unfreeze it".to_string(),
+        ));
+    }
+    if info.security_locked || info.security_enabled {
+        return Err(EraseError::CommandFailed("drive already has a security password set".to_string()));
+    }
+
+    let enhanced = info.enhanced_erase_supported;
+    let (operation, estimated) = if enhanced {
+        ("Enhanced Security Erase", info.enhanced_erase_time)
+    } else {
+        ("Security Erase", info.erase_time)
+    };
+    // SECURITY ERASE UNIT reports nothing while it runs, so it is issued on
+    // a helper thread while the estimate counts down here
+    let timeout = estimated.map_or(Duration::from_secs(24 * 3600), |e| e * 2 + Duration::from_secs(600));
+    thread::scope(|scope| {
+        let erase = scope.spawn(|| driver.security_erase(enhanced, timeout));
+        while !erase.is_finished() {
+            report(progress, FirmwareProgress {
+                operation: operation.to_string(),
+                reported: None,
+                estimated,
+            });
+            thread::sleep(Duration::from_millis(500));
+        }
+        erase.join().unwrap_or(Err(AtaError::CommandFailed("erase thread panicked".to_string())))
+    })?;
+    Ok(())
 }
 
-// TODO: Add ATA command interface
-// TODO: Add hdparm wrapper functions
-// TODO: Add direct ioctl implementation as fallback
-// TODO: Add ATA device identification
-// TODO: Add secure erase unit command
-// TODO: Add enhanced secure erase command
-// TODO: Add time estimation for erase operations
-// TODO: Add password handling for frozen drives
-// TODO: Add error handling for ATA-specific errors
-// TODO: Add progress monitoring for long operations
\ No newline at end of file
+// TODO: Add password handling for frozen drives
\ No newline at end of file
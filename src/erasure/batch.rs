@@ -0,0 +1,654 @@
+//! Batch erasure
+//! Runs one worker thread per device so a rack of drives can be wiped at once,
+//! while limiting how many jobs share a controller at the same time
+
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    sync::{
+        Arc, Condvar, Mutex,
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, Receiver, Sender},
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use serde::{Deserialize, Serialize};
+use time::{OffsetDateTime, macros::format_description};
+
+use crate::{
+    audit::{AuditEvent, AuditLog},
+    cert::{
+        CertificateBody, CertificateResult, CertificateSigner, DeviceRecord, HealthSummary,
+        VerificationSummary, now_rfc3339, store::CertificateStore,
+    },
+    erasure::{
+        EraseError, EraseProgress, EraseRequest,
+        checkpoint::Checkpoint,
+        engine::IoConfig,
+        erase_device,
+        errormap::ErrorPolicy,
+        overwrite::OverwriteStandard,
+        throttle::{PauseWindow, Throttle, ThrottleSettings},
+    },
+    health::{HealthReport, HealthWarning},
+    libs::StorageDriver,
+    platform::{DeviceIdentity, WipeMethod},
+    verify::VerifyMode,
+};
+
+/// Used when the controller of a device cannot be determined
+pub const UNKNOWN_CONTROLLER: &str = "unknown";
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(100);
+
+#[derive(Debug, Clone)]
+pub struct BatchDevice {
+    pub record: DeviceRecord,
+    pub controller: String,
+    /// Checked against the device right before it is written to
+    pub identity: Option<DeviceIdentity>,
+    /// Carry on an interrupted overwrite instead of starting over
+    pub resume: Option<Checkpoint>,
+}
+
+impl BatchDevice {
+    pub fn new(record: DeviceRecord) -> Self {
+        let controller = StorageDriver::controller_id(&record.path)
+            .unwrap_or_else(|| UNKNOWN_CONTROLLER.to_string());
+        BatchDevice { record, controller, identity: None, resume: None }
+    }
+
+    pub fn with_identity(mut self, identity: DeviceIdentity) -> Self {
+        self.identity = Some(identity);
+        self
+    }
+
+    pub fn with_resume(mut self, checkpoint: Checkpoint) -> Self {
+        self.resume = Some(checkpoint);
+        self
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct BatchConfig {
+    pub operator: String,
+    pub method: WipeMethod,
+    pub standard: OverwriteStandard,
+    pub verify: VerifyMode,
+    pub error_policy: ErrorPolicy,
+    pub max_jobs_per_controller: usize,
+    /// Passed on to every [`EraseRequest`]
+    pub allow_system_disk: bool,
+    /// Where the batch summary report is written, see [`default_report_dir`]
+    pub report_dir: PathBuf,
+    /// Where overwrites save checkpoints, see
+    /// [`super::checkpoint::default_checkpoint_dir`]
+    pub checkpoint_dir: Option<PathBuf>,
+    /// Its throttle is shared by every job and adjusted through [`BatchControl`]
+    pub io: IoConfig,
+}
+
+/// Shared services the workers report to. Any of them may be missing, in which
+/// case that step is skipped and noted in the job's warnings.
+#[derive(Clone, Default)]
+pub struct BatchServices {
+    pub signer: Option<Arc<CertificateSigner>>,
+    pub store: Option<Arc<Mutex<CertificateStore>>>,
+    pub audit: Option<Arc<AuditLog>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum JobOutcome {
+    Passed,
+    Failed,
+    Cancelled,
+}
+
+impl std::fmt::Display for JobOutcome {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            JobOutcome::Passed => write!(f, "Passed"),
+            JobOutcome::Failed => write!(f, "Failed"),
+            JobOutcome::Cancelled => write!(f, "Cancelled"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JobResult {
+    pub device: DeviceRecord,
+    pub controller: String,
+    pub outcome: JobOutcome,
+    pub error: Option<String>,
+    pub started_at: Option<String>,
+    pub finished_at: String,
+    pub bytes_written: u64,
+    pub verification: Option<VerificationSummary>,
+    #[serde(default)]
+    pub health_before: Option<HealthSummary>,
+    #[serde(default)]
+    pub health_after: Option<HealthSummary>,
+    pub certificate_id: Option<String>,
+    pub warnings: Vec<String>,
+}
+
+#[derive(Debug)]
+pub enum BatchEvent {
+    JobStarted { job: usize },
+    Progress { job: usize, progress: EraseProgress },
+    /// Held by the operator, or by a pause window until it ends
+    JobPaused { job: usize, window: Option<PauseWindow> },
+    JobResumed { job: usize },
+    JobFinished { job: usize, result: Box<JobResult> },
+    BatchFinished { summary: Box<BatchSummary>, report: Result<PathBuf, String> },
+}
+
+pub struct BatchHandle {
+    events: Receiver<BatchEvent>,
+    control: BatchControl,
+}
+
+impl BatchHandle {
+    pub fn poll(&self) -> Vec<BatchEvent> {
+        self.events.try_iter().collect()
+    }
+
+    /// Blocks until the next event; None once the batch has finished
+    pub fn recv(&self) -> Option<BatchEvent> {
+        self.events.recv().ok()
+    }
+
+    /// Control that can be kept on another thread than the event receiver
+    pub fn control(&self) -> BatchControl {
+        self.control.clone()
+    }
+
+    pub fn cancel(&self, job: usize) {
+        self.control.cancel(job);
+    }
+
+    pub fn cancel_all(&self) {
+        self.control.cancel_all();
+    }
+}
+
+/// Cancels, pauses and resumes the jobs of a running batch, and adjusts
+/// their throttling
+#[derive(Clone)]
+pub struct BatchControl {
+    cancels: Vec<Arc<AtomicBool>>,
+    pauses: Vec<Arc<AtomicBool>>,
+    throttle: Throttle,
+}
+
+impl BatchControl {
+    pub fn cancel(&self, job: usize) {
+        if let Some(cancel) = self.cancels.get(job) {
+            cancel.store(true, Ordering::Relaxed);
+        }
+    }
+
+    pub fn cancel_all(&self) {
+        for cancel in &self.cancels {
+            cancel.store(true, Ordering::Relaxed);
+        }
+    }
+
+    /// Holds an overwrite or verification at the next chunk boundary. A
+    /// firmware erase keeps running inside the drive regardless.
+    pub fn pause(&self, job: usize) {
+        if let Some(pause) = self.pauses.get(job) {
+            pause.store(true, Ordering::Relaxed);
+        }
+    }
+
+    pub fn resume(&self, job: usize) {
+        if let Some(pause) = self.pauses.get(job) {
+            pause.store(false, Ordering::Relaxed);
+        }
+    }
+
+    /// Rate limits and pause windows of every job, see [`Throttle`]
+    pub fn throttle(&self) -> ThrottleSettings {
+        self.throttle.settings()
+    }
+
+    /// Applies from each job's next request
+    pub fn set_throttle(&self, settings: ThrottleSettings) {
+        self.throttle.set(settings);
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BatchSummary {
+    pub batch_id: String,
+    pub operator: String,
+    pub method: String,
+    pub standard: Option<String>,
+    pub verification: String,
+    pub started_at: String,
+    pub finished_at: String,
+    pub jobs: Vec<JobResult>,
+}
+
+impl BatchSummary {
+    pub fn count(&self, outcome: JobOutcome) -> usize {
+        self.jobs.iter().filter(|job| job.outcome == outcome).count()
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut text = format!(
+            "Batch {}\nOperator: {}\nMethod: {}\n",
+            self.batch_id, self.operator, self.method
+        );
+        if let Some(standard) = &self.standard {
+            text += &format!("Standard: {}\n", standard);
+        }
+        text += &format!(
+            "Verification: {}\nStarted: {}\nFinished: {}\nResult: {} passed, {} failed, {} cancelled\n\n",
+            self.verification,
+            self.started_at,
+            self.finished_at,
+            self.count(JobOutcome::Passed),
+            self.count(JobOutcome::Failed),
+            self.count(JobOutcome::Cancelled)
+        );
+        text += &format!(
+            "{:<14} {:<24} {:<22} {:<10} {:<36} {}\n",
+            "Device", "Model", "Serial", "Result", "Certificate", "Notes"
+        );
+        for job in &self.jobs {
+            let notes: Vec<&str> = job
+                .error
+                .iter()
+                .chain(job.warnings.iter())
+                .map(String::as_str)
+                .collect();
+            text += &format!(
+                "{:<14} {:<24} {:<22} {:<10} {:<36} {}\n",
+                job.device.path,
+                job.device.model.as_deref().unwrap_or("-"),
+                job.device.serial_number.as_deref().unwrap_or("-"),
+                job.outcome,
+                job.certificate_id.as_deref().unwrap_or("-"),
+                notes.join("; ")
+            );
+        }
+        text
+    }
+
+    /// Writes <batch_id>.json and <batch_id>.txt, returning the JSON path
+    pub fn save(&self, directory: &Path) -> std::io::Result<PathBuf> {
+        fs::create_dir_all(directory)?;
+        let json_path = directory.join(format!("{}.json", self.batch_id));
+        fs::write(&json_path, serde_json::to_vec_pretty(self)?)?;
+        fs::write(directory.join(format!("{}.txt", self.batch_id)), self.to_text())?;
+        Ok(json_path)
+    }
+}
+
+pub fn default_report_dir() -> PathBuf {
+    crate::libs::data_dir().join("batches")
+}
+
+/// Starts every job immediately; jobs beyond the per-controller limit wait
+/// in the queue until a slot on their controller frees up
+pub fn start_batch(
+    devices: Vec<BatchDevice>,
+    config: BatchConfig,
+    services: BatchServices,
+) -> BatchHandle {
+    let (sender, events) = mpsc::channel();
+    let flags = || devices.iter().map(|_| Arc::new(AtomicBool::new(false))).collect();
+    let control = BatchControl { cancels: flags(), pauses: flags(), throttle: config.io.throttle.clone() };
+    let slots = Arc::new(ControllerSlots::new(config.max_jobs_per_controller));
+    let config = Arc::new(config);
+    let started_at = now_rfc3339();
+
+    let workers: Vec<_> = devices
+        .into_iter()
+        .enumerate()
+        .map(|(job, device)| {
+            let worker = Worker {
+                job,
+                config: Arc::clone(&config),
+                services: services.clone(),
+                slots: Arc::clone(&slots),
+                cancel: Arc::clone(&control.cancels[job]),
+                pause: Arc::clone(&control.pauses[job]),
+                events: sender.clone(),
+            };
+            let fallback = device.clone();
+            (fallback, thread::spawn(move || worker.run(device)))
+        })
+        .collect();
+
+    thread::spawn(move || {
+        let jobs = workers
+            .into_iter()
+            .map(|(device, handle)| {
+                handle.join().unwrap_or_else(|_| {
+                    failed_result(device, None, "worker thread panicked".to_string())
+                })
+            })
+            .collect();
+
+        let summary = BatchSummary {
+            batch_id: batch_id(),
+            operator: config.operator.clone(),
+            method: config.method.to_string(),
+            standard: (config.method == WipeMethod::Overwrite)
+                .then(|| config.standard.to_string()),
+            verification: config.verify.to_string(),
+            started_at,
+            finished_at: now_rfc3339(),
+            jobs,
+        };
+        let report = summary.save(&config.report_dir).map_err(|e| e.to_string());
+        let _ = sender.send(BatchEvent::BatchFinished { summary: Box::new(summary), report });
+    });
+
+    BatchHandle { events, control }
+}
+
+struct Worker {
+    job: usize,
+    config: Arc<BatchConfig>,
+    services: BatchServices,
+    slots: Arc<ControllerSlots>,
+    cancel: Arc<AtomicBool>,
+    pause: Arc<AtomicBool>,
+    events: Sender<BatchEvent>,
+}
+
+impl Worker {
+    fn run(self, device: BatchDevice) -> JobResult {
+        let Some(_slot) = self.slots.acquire(&device.controller, &self.cancel) else {
+            let result = JobResult {
+                outcome: JobOutcome::Cancelled,
+                ..failed_result(device, None, EraseError::Cancelled.to_string())
+            };
+            return self.finish(result);
+        };
+
+        // Report the result before the slot is freed on return, so a queued
+        // job on the same controller never appears to start before this one
+        // ended. The guard also frees it if the erase panics.
+        self.finish(self.erase(device))
+    }
+
+    // Called between chunks; blocking here holds the erase where it is. Jobs
+    // also hold during the pause windows of the batch's throttle.
+    fn wait_while_paused(&self) {
+        let throttle = &self.config.io.throttle;
+        let held = || self.pause.load(Ordering::Relaxed) || throttle.pause_window().is_some();
+        if !held() {
+            return;
+        }
+        let window = throttle.pause_window();
+        let _ = self.events.send(BatchEvent::JobPaused { job: self.job, window });
+        while held() && !self.cancel.load(Ordering::Relaxed) {
+            thread::sleep(Duration::from_millis(100));
+        }
+        let _ = self.events.send(BatchEvent::JobResumed { job: self.job });
+    }
+
+    fn finish(&self, result: JobResult) -> JobResult {
+        let _ = self.events.send(BatchEvent::JobFinished {
+            job: self.job,
+            result: Box::new(result.clone()),
+        });
+        result
+    }
+
+    fn erase(&self, device: BatchDevice) -> JobResult {
+        let _ = self.events.send(BatchEvent::JobStarted { job: self.job });
+        // A job due to start inside a pause window waits for it to end
+        self.wait_while_paused();
+        let mut warnings = Vec::new();
+        let path = device.record.path.clone();
+        let method = self.config.method.to_string();
+        self.audit(
+            AuditEvent::EraseStarted { path: path.clone(), method: method.clone() },
+            &mut warnings,
+        );
+
+        let request = EraseRequest {
+            path: path.clone(),
+            method: self.config.method.clone(),
+            standard: self.config.standard,
+            verify: self.config.verify,
+            error_policy: self.config.error_policy,
+            expected: device.identity.clone(),
+            allow_system_disk: self.config.allow_system_disk,
+            checkpoint_dir: self.config.checkpoint_dir.clone(),
+            resume: device.resume.clone(),
+            io: self.config.io.clone(),
+        };
+        let mut last_sent: Option<Instant> = None;
+        let mut forward = |progress: EraseProgress| {
+            let done = progress.bytes_done == progress.bytes_total;
+            if done || last_sent.is_none_or(|sent| sent.elapsed() >= PROGRESS_INTERVAL) {
+                last_sent = Some(Instant::now());
+                let _ = self.events.send(BatchEvent::Progress { job: self.job, progress });
+            }
+            self.wait_while_paused();
+        };
+        let started_at = now_rfc3339();
+
+        let report = match erase_device(&request, &mut forward, &self.cancel) {
+            Ok(report) => report,
+            Err(EraseError::Cancelled) => {
+                self.audit(
+                    AuditEvent::EraseFailed {
+                        path,
+                        method,
+                        error: EraseError::Cancelled.to_string(),
+                    },
+                    &mut warnings,
+                );
+                return JobResult {
+                    outcome: JobOutcome::Cancelled,
+                    warnings,
+                    ..failed_result(device, Some(started_at), EraseError::Cancelled.to_string())
+                };
+            }
+            Err(e) => {
+                self.audit(
+                    AuditEvent::EraseFailed { path, method, error: e.to_string() },
+                    &mut warnings,
+                );
+                let mut result = failed_result(device, Some(started_at), e.to_string());
+                result.warnings = warnings;
+                return result;
+            }
+        };
+
+        self.audit(AuditEvent::EraseFinished { path: path.clone(), method }, &mut warnings);
+        if let Some(error) = &report.checkpoint_error {
+            warnings.push(format!("checkpoint not saved: {}", error));
+        }
+        let verification = report.verification_summary();
+        let errors = report.errors();
+        if !errors.is_empty() {
+            warnings.push(format!(
+                "{} sectors of {} bytes failed ({}), LBAs {}",
+                errors.sectors(),
+                errors.sector_size,
+                self.config.error_policy,
+                errors
+            ));
+        }
+        if let Some(v) = &report.verification {
+            self.audit(
+                AuditEvent::VerificationResult {
+                    path: path.clone(),
+                    passed: v.passed,
+                    mismatches: v.mismatched_sectors,
+                },
+                &mut warnings,
+            );
+        }
+
+        // Retired sectors keep whatever they held; only firmware erase covers them
+        if self.config.method == WipeMethod::Overwrite
+            && let Some(health) = report.health_before.as_ref().filter(|h| h.has_remapped_sectors())
+        {
+            warnings.extend(
+                health
+                    .warnings()
+                    .iter()
+                    .filter(|w| matches!(w, HealthWarning::Reallocated(_) | HealthWarning::SpareUsed(_)))
+                    .map(|w| w.to_string()),
+            );
+        }
+        let health_before = report.health_before.as_ref().map(HealthReport::summary);
+        let health_after = report.health_after.as_ref().map(HealthReport::summary);
+
+        let outcome = if report.passed() { JobOutcome::Passed } else { JobOutcome::Failed };
+        let mut body = CertificateBody::new(
+            &self.config.operator,
+            vec![device.record.clone()],
+            &self.config.method.to_string(),
+        );
+        body.standard = report.overwrite.as_ref().map(|o| o.standard.to_string());
+        body.started_at = Some(report.started_at.clone());
+        body.finished_at = Some(report.finished_at.clone());
+        body.verification = verification.clone().unwrap_or_default();
+        body.verified = Some(report.verification.is_some());
+        body.health_before = health_before.clone();
+        body.health_after = health_after.clone();
+        body.resumes = report.resumes.clone();
+        body.result = match outcome {
+            JobOutcome::Passed => CertificateResult::Passed,
+            _ => CertificateResult::Failed,
+        };
+        let certificate_id = match self.issue_certificate(body) {
+            Ok(id) => {
+                self.audit(
+                    AuditEvent::CertificateIssued { certificate_id: id.clone(), paths: vec![path] },
+                    &mut warnings,
+                );
+                Some(id)
+            }
+            Err(e) => {
+                warnings.push(e);
+                None
+            }
+        };
+
+        JobResult {
+            device: device.record,
+            controller: device.controller,
+            outcome,
+            error: None,
+            started_at: Some(report.started_at),
+            finished_at: report.finished_at,
+            bytes_written: report.overwrite.map_or(0, |o| o.bytes_written),
+            verification,
+            health_before,
+            health_after,
+            certificate_id,
+            warnings,
+        }
+    }
+
+    fn issue_certificate(&self, body: CertificateBody) -> Result<String, String> {
+        let signer = self.services.signer.as_ref().ok_or("no signing key, certificate not issued")?;
+        let store = self.services.store.as_ref().ok_or("no certificate store, certificate not issued")?;
+        let cert = signer.sign(body).map_err(|e| format!("signing failed: {}", e))?;
+        store
+            .lock()
+            .map_err(|_| "certificate store lock poisoned".to_string())?
+            .insert(&cert)
+            .map_err(|e| format!("storing certificate failed: {}", e))?;
+        Ok(cert.body.id)
+    }
+
+    fn audit(&self, event: AuditEvent, warnings: &mut Vec<String>) {
+        if let Some(log) = &self.services.audit
+            && let Err(e) = log.append(event)
+        {
+            warnings.push(format!("audit log: {}", e));
+        }
+    }
+}
+
+fn failed_result(device: BatchDevice, started_at: Option<String>, error: String) -> JobResult {
+    JobResult {
+        device: device.record,
+        controller: device.controller,
+        outcome: JobOutcome::Failed,
+        error: Some(error),
+        started_at,
+        finished_at: now_rfc3339(),
+        bytes_written: 0,
+        verification: None,
+        health_before: None,
+        health_after: None,
+        certificate_id: None,
+        warnings: Vec::new(),
+    }
+}
+
+fn batch_id() -> String {
+    OffsetDateTime::now_utc()
+        .format(format_description!("batch-[year][month][day]-[hour][minute][second]-[subsecond digits:6]"))
+        .unwrap_or_else(|_| "batch".to_string())
+}
+
+// Counting semaphore per controller
+struct ControllerSlots {
+    limit: usize,
+    active: Mutex<HashMap<String, usize>>,
+    freed: Condvar,
+}
+
+impl ControllerSlots {
+    fn new(limit: usize) -> Self {
+        ControllerSlots { limit: limit.max(1), active: Mutex::new(HashMap::new()), freed: Condvar::new() }
+    }
+
+    // Waits for a free slot; returns None if the job was cancelled meanwhile
+    fn acquire<'a>(&'a self, controller: &str, cancel: &AtomicBool) -> Option<SlotGuard<'a>> {
+        let mut active = self.active.lock().unwrap_or_else(|e| e.into_inner());
+        loop {
+            if cancel.load(Ordering::Relaxed) {
+                return None;
+            }
+            let count = active.entry(controller.to_string()).or_insert(0);
+            if *count < self.limit {
+                *count += 1;
+                return Some(SlotGuard { slots: self, controller: controller.to_string() });
+            }
+            active = self
+                .freed
+                .wait_timeout(active, Duration::from_millis(200))
+                .unwrap_or_else(|e| e.into_inner())
+                .0;
+        }
+    }
+
+    fn release(&self, controller: &str) {
+        let mut active = self.active.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(count) = active.get_mut(controller) {
+            *count = count.saturating_sub(1);
+        }
+        self.freed.notify_all();
+    }
+}
+
+// Holds a controller slot until dropped
+struct SlotGuard<'a> {
+    slots: &'a ControllerSlots,
+    controller: String,
+}
+
+impl Drop for SlotGuard<'_> {
+    fn drop(&mut self) {
+        self.slots.release(&self.controller);
+    }
+}
@@ -0,0 +1,259 @@
+//! Checkpoints of long overwrites
+//! A multi-pass overwrite of a large drive runs for days. Its position is
+//! saved at intervals, so after a crash or power cut the job carries on from
+//! there instead of starting over.
+
+use std::{
+    fs::{self, File},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::{
+    cert::{ResumeRecord, now_rfc3339},
+    erasure::{
+        EraseError,
+        errormap::{ErrorMap, ErrorPolicy},
+        overwrite::{OverwriteStandard, Seed},
+    },
+    platform::{BlockGeometry, DeviceIdentity},
+    verify::VerifyMode,
+};
+
+/// How often a running overwrite saves its position
+pub const CHECKPOINT_INTERVAL: Duration = Duration::from_secs(60);
+
+/// How far an overwrite got. The device is synced before the state is saved,
+/// so everything before `offset` of pass `pass` is on the medium.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OverwriteState {
+    /// See [`recipe_hash`]
+    pub recipe: String,
+    pub device_size: u64,
+    /// Index of the pass in progress; the number of passes once all are written
+    pub pass: usize,
+    pub offset: u64,
+    /// Seed of each pass started so far, from which its data is regenerated
+    pub seeds: Vec<Seed>,
+    pub bytes_written: u64,
+    pub errors: ErrorMap,
+}
+
+/// Identifies what an overwrite writes: its passes, over how many bytes, in
+/// which block size. A checkpoint only applies to an overwrite with the same
+/// recipe.
+pub fn recipe_hash(standard: OverwriteStandard, device_size: u64, geometry: &BlockGeometry) -> String {
+    let mut hasher = Sha256::new();
+    for pattern in standard.passes() {
+        hasher.update(pattern.to_string().as_bytes());
+        hasher.update(b"\n");
+    }
+    hasher.update(device_size.to_le_bytes());
+    hasher.update(geometry.logical_block_size.to_le_bytes());
+    hex::encode(hasher.finalize())
+}
+
+/// An overwrite job as saved to the [`CheckpointStore`]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Checkpoint {
+    /// Device path when the job started; it may differ after a reboot
+    pub path: String,
+    pub identity: DeviceIdentity,
+    pub standard: OverwriteStandard,
+    pub verify: VerifyMode,
+    pub error_policy: ErrorPolicy,
+    pub started_at: String,
+    pub saved_at: String,
+    /// None until the overwrite has opened the device
+    pub overwrite: Option<OverwriteState>,
+    pub resumes: Vec<ResumeRecord>,
+}
+
+impl Checkpoint {
+    pub fn new(
+        path: &str,
+        identity: DeviceIdentity,
+        standard: OverwriteStandard,
+        verify: VerifyMode,
+        error_policy: ErrorPolicy,
+    ) -> Self {
+        let now = now_rfc3339();
+        Checkpoint {
+            path: path.to_string(),
+            identity,
+            standard,
+            verify,
+            error_policy,
+            started_at: now.clone(),
+            saved_at: now,
+            overwrite: None,
+            resumes: Vec::new(),
+        }
+    }
+
+    /// File name in the store. Keyed by the drive rather than its path where
+    /// possible, so a new job on the same drive replaces a stale checkpoint.
+    pub fn key(&self) -> String {
+        let id = self.identity.wwn.as_deref().or(self.identity.serial.as_deref()).unwrap_or(&self.path);
+        id.chars()
+            .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '.' { c } else { '_' })
+            .collect()
+    }
+
+    /// Refuses to resume on anything but the drive the checkpoint was taken
+    /// on. Drives without a serial or WWN must also still be at the same path.
+    pub fn check(&self, path: &str, current: &DeviceIdentity) -> Result<(), EraseError> {
+        let stable = self.identity.serial.is_some() || self.identity.wwn.is_some();
+        if !stable && self.path != path {
+            return Err(EraseError::IdentityChanged(format!(
+                "checkpoint was taken on {}, not {}",
+                self.path, path
+            )));
+        }
+        match self.identity.mismatch(current) {
+            Some(mismatch) => Err(EraseError::IdentityChanged(format!("{}: {}", path, mismatch))),
+            None => Ok(()),
+        }
+    }
+
+    pub fn matches(&self, path: &str, identity: &DeviceIdentity) -> bool {
+        self.check(path, identity).is_ok()
+    }
+
+    /// Completion of the overwrite, not counting verification
+    pub fn fraction(&self) -> f32 {
+        let passes = self.standard.passes().len();
+        match &self.overwrite {
+            Some(state) if state.device_size > 0 => {
+                let pass = state.offset as f64 / state.device_size as f64;
+                ((state.pass as f64 + pass) / passes as f64).min(1.0) as f32
+            }
+            _ => 0.0,
+        }
+    }
+
+    /// Where a resume carries on from, for the certificate
+    pub fn resume_record(&self) -> ResumeRecord {
+        let state = self.overwrite.as_ref();
+        ResumeRecord {
+            resumed_at: now_rfc3339(),
+            pass: state.map_or(0, |s| s.pass) + 1,
+            passes: self.standard.passes().len(),
+            offset: state.map_or(0, |s| s.offset),
+        }
+    }
+}
+
+pub fn default_checkpoint_dir() -> PathBuf {
+    crate::libs::data_dir().join("checkpoints")
+}
+
+/// Directory of checkpoint files, one per drive
+#[derive(Debug, Clone)]
+pub struct CheckpointStore {
+    root: PathBuf,
+}
+
+impl CheckpointStore {
+    pub fn open(root: &Path) -> io::Result<Self> {
+        fs::create_dir_all(root)?;
+        Ok(CheckpointStore { root: root.to_path_buf() })
+    }
+
+    pub fn open_default() -> io::Result<Self> {
+        Self::open(&default_checkpoint_dir())
+    }
+
+    pub fn save(&self, checkpoint: &Checkpoint) -> io::Result<()> {
+        // Written beside the old file and renamed over it, so a power cut
+        // leaves either the previous checkpoint or the new one
+        let path = self.path(checkpoint);
+        let temporary = path.with_extension("json.tmp");
+        let mut file = File::create(&temporary)?;
+        file.write_all(&serde_json::to_vec_pretty(checkpoint)?)?;
+        file.sync_all()?;
+        fs::rename(&temporary, &path)?;
+        File::open(&self.root)?.sync_all()
+    }
+
+    /// Every readable checkpoint, oldest job first
+    pub fn list(&self) -> Vec<Checkpoint> {
+        let Ok(entries) = fs::read_dir(&self.root) else {
+            return Vec::new();
+        };
+        let mut checkpoints: Vec<Checkpoint> = entries
+            .flatten()
+            .map(|entry| entry.path())
+            .filter(|path| path.extension().is_some_and(|extension| extension == "json"))
+            .filter_map(|path| serde_json::from_slice(&fs::read(path).ok()?).ok())
+            .collect();
+        checkpoints.sort_by(|a, b| a.started_at.cmp(&b.started_at));
+        checkpoints
+    }
+
+    /// The checkpoint of the drive now at `path`
+    pub fn find(&self, path: &str, identity: &DeviceIdentity) -> Option<Checkpoint> {
+        self.list().into_iter().find(|checkpoint| checkpoint.matches(path, identity))
+    }
+
+    pub fn remove(&self, checkpoint: &Checkpoint) -> io::Result<()> {
+        match fs::remove_file(self.path(checkpoint)) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        }
+    }
+
+    fn path(&self, checkpoint: &Checkpoint) -> PathBuf {
+        self.root.join(format!("{}.json", checkpoint.key()))
+    }
+}
+
+/// Saves the position of a running overwrite, see [`super::overwrite::overwrite_device`]
+pub struct Checkpointer {
+    store: Option<CheckpointStore>,
+    checkpoint: Checkpoint,
+    saved: Option<Instant>,
+    /// Why the last save failed; the erase carries on regardless
+    pub error: Option<String>,
+}
+
+impl Checkpointer {
+    /// Without a store the job can still be resumed from `checkpoint`, but
+    /// its progress is not saved again
+    pub fn new(store: Option<CheckpointStore>, checkpoint: Checkpoint) -> Self {
+        Checkpointer { store, checkpoint, saved: None, error: None }
+    }
+
+    pub fn checkpoint(&self) -> &Checkpoint {
+        &self.checkpoint
+    }
+
+    /// Whether the interval since the last save has passed
+    pub fn due(&self) -> bool {
+        self.saved.is_none_or(|saved| saved.elapsed() >= CHECKPOINT_INTERVAL)
+    }
+
+    /// Records `state`. Callers sync the device first, so the saved position
+    /// never runs ahead of the data.
+    pub fn save(&mut self, state: &OverwriteState) {
+        self.checkpoint.overwrite = Some(state.clone());
+        self.checkpoint.saved_at = now_rfc3339();
+        self.saved = Some(Instant::now());
+        if let Some(store) = &self.store
+            && let Err(e) = store.save(&self.checkpoint)
+        {
+            self.error = Some(e.to_string());
+        }
+    }
+
+    /// Drops the saved checkpoint once the job has finished
+    pub fn finish(&self) {
+        if let Some(store) = &self.store {
+            let _ = store.remove(&self.checkpoint);
+        }
+    }
+}
@@ -1,5 +1,5 @@
-// Cryptographic erase for Self-Encrypting Drives (SEDs)
-// Handles OPAL and other SED standards
+//! Cryptographic erase for Self-Encrypting Drives (SEDs)
+//! Handles OPAL and other SED standards
 
 // TODO: Add OPAL protocol implementation
 // TODO: Add SED device identification
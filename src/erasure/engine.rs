@@ -0,0 +1,558 @@
+//! I/O engine for the overwrite and verify loops
+//! Keeps several requests in flight through io_uring, or through a pool of
+//! threads doing pwrite/pread where io_uring is unavailable. Block devices
+//! are opened with O_DIRECT and served from aligned buffers, so data goes to
+//! the drive instead of the page cache. Nothing is known to be on the medium
+//! until [`IoEngine::flush`] returns; callers flush explicitly at the end of
+//! each pass and before recording a position.
+
+use std::{
+    collections::VecDeque,
+    fs::{self, File, OpenOptions},
+    io::{self, Seek, SeekFrom},
+    ops::{Deref, DerefMut},
+    os::unix::fs::{FileExt, FileTypeExt, OpenOptionsExt},
+    path::Path,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, Receiver, Sender},
+    },
+    thread::{self, JoinHandle},
+    time::Instant,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    erasure::{
+        EraseError, check_opened,
+        overwrite::CHUNK_SIZE,
+        throttle::{Pacer, Throttle, set_thread_ioprio},
+    },
+    platform::{BlockGeometry, DeviceIdentity},
+};
+
+// Satisfies the O_DIRECT alignment of any logical block size up to 4K
+const ALIGNMENT: usize = 4096;
+
+/// How requests are kept in flight
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum IoBackend {
+    /// io_uring, or the thread pool when the kernel or a seccomp filter refuses it
+    #[default]
+    Auto,
+    IoUring,
+    Threads,
+}
+
+impl std::fmt::Display for IoBackend {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            IoBackend::Auto => write!(f, "Automatic"),
+            IoBackend::IoUring => write!(f, "io_uring"),
+            IoBackend::Threads => write!(f, "Thread pool"),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct IoConfig {
+    pub backend: IoBackend,
+    /// Requests in flight at once
+    pub queue_depth: usize,
+    /// Bytes per request
+    pub block_size: usize,
+    /// Rate limits and I/O priority, read again before every request
+    pub throttle: Throttle,
+}
+
+impl Default for IoConfig {
+    fn default() -> Self {
+        IoConfig { backend: IoBackend::Auto, queue_depth: 4, block_size: CHUNK_SIZE, throttle: Throttle::default() }
+    }
+}
+
+impl IoConfig {
+    /// Rounds the block size to whole physical blocks of `geometry`
+    pub fn aligned_to(&self, geometry: &BlockGeometry) -> IoConfig {
+        IoConfig {
+            queue_depth: self.queue_depth.max(1),
+            block_size: geometry.write_size(self.block_size.max(ALIGNMENT)),
+            ..self.clone()
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum IoOp {
+    Read,
+    Write,
+}
+
+impl std::fmt::Display for IoOp {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            IoOp::Read => write!(f, "Read"),
+            IoOp::Write => write!(f, "Write"),
+        }
+    }
+}
+
+// Heap buffer starting on an ALIGNMENT boundary, as O_DIRECT requires
+struct AlignedBuffer {
+    storage: Vec<u8>,
+    start: usize,
+    len: usize,
+}
+
+impl AlignedBuffer {
+    fn new(len: usize) -> Self {
+        let storage = vec![0u8; len + ALIGNMENT];
+        let start = storage.as_ptr().align_offset(ALIGNMENT);
+        AlignedBuffer { storage, start, len }
+    }
+}
+
+impl Deref for AlignedBuffer {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.storage[self.start..self.start + self.len]
+    }
+}
+
+impl DerefMut for AlignedBuffer {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        &mut self.storage[self.start..self.start + self.len]
+    }
+}
+
+/// A finished request, handed back in offset order
+pub struct Completion<'a> {
+    pub offset: u64,
+    /// Data written, or read into
+    pub data: &'a mut [u8],
+    /// On error the caller may retry the range itself through `file`
+    pub result: io::Result<()>,
+    pub file: &'a File,
+}
+
+// A request submitted to the backend, identified by its buffer slot
+struct Request {
+    slot: usize,
+    offset: u64,
+    len: usize,
+    result: Option<io::Result<()>>,
+}
+
+pub struct IoEngine {
+    file: Arc<File>,
+    config: IoConfig,
+    backend: Backend,
+    buffers: Vec<Option<AlignedBuffer>>,
+    pacer: Pacer,
+}
+
+enum Backend {
+    #[cfg(target_os = "linux")]
+    Uring { ring: Box<io_uring::IoUring>, in_flight: usize },
+    Threads(ThreadPool),
+}
+
+impl IoEngine {
+    /// Opens `path` for `op`. Block devices are opened with O_DIRECT where
+    /// the kernel allows it; image files go through the page cache.
+    pub fn open(path: &Path, op: IoOp, config: &IoConfig) -> io::Result<Self> {
+        let file = Arc::new(open_direct(path, op)?);
+        let config = IoConfig { queue_depth: config.queue_depth.max(1), ..config.clone() };
+        let backend = match config.backend {
+            IoBackend::Threads => Backend::Threads(ThreadPool::new(&file, config.queue_depth)),
+            IoBackend::IoUring => uring(config.queue_depth)?,
+            IoBackend::Auto => uring(config.queue_depth)
+                .unwrap_or_else(|_| Backend::Threads(ThreadPool::new(&file, config.queue_depth))),
+        };
+        let buffers = (0..config.queue_depth).map(|_| Some(AlignedBuffer::new(config.block_size))).collect();
+        Ok(IoEngine { file, config, backend, buffers, pacer: Pacer::new() })
+    }
+
+    /// The backend in use, never [`IoBackend::Auto`]
+    pub fn backend(&self) -> IoBackend {
+        match self.backend {
+            #[cfg(target_os = "linux")]
+            Backend::Uring { .. } => IoBackend::IoUring,
+            Backend::Threads(_) => IoBackend::Threads,
+        }
+    }
+
+    pub fn config(&self) -> &IoConfig {
+        &self.config
+    }
+
+    pub fn file(&self) -> &File {
+        &self.file
+    }
+
+    /// Works for block devices as well as image files
+    pub fn size(&self) -> io::Result<u64> {
+        (&*self.file).seek(SeekFrom::End(0))
+    }
+
+    /// Flushes written data to the medium, including the drive's write cache
+    pub fn flush(&self) -> io::Result<()> {
+        self.file.sync_data()
+    }
+
+    /// Reads or writes each `(offset, length)` range in requests of at most
+    /// the block size, keeping up to the queue depth in flight within the
+    /// limits of the configured [`Throttle`]. For writes,
+    /// `fill` produces each request's data before it is submitted.
+    /// `complete` sees every request in offset order and returns false to
+    /// stop; requests already in flight are waited for, but not handed back.
+    /// Returns whether every range was covered.
+    pub fn run(
+        &mut self,
+        op: IoOp,
+        ranges: &[(u64, u64)],
+        fill: &mut dyn FnMut(u64, &mut [u8]),
+        complete: &mut dyn FnMut(Completion<'_>) -> Result<bool, EraseError>,
+    ) -> Result<bool, EraseError> {
+        let block = self.config.block_size as u64;
+        let mut pieces = ranges.iter().flat_map(|&(start, length)| {
+            (0..length.div_ceil(block)).map(move |index| {
+                let offset = start + index * block;
+                (offset, (start + length - offset).min(block) as usize)
+            })
+        });
+        let mut queue: VecDeque<Request> = VecDeque::new();
+        let mut free: Vec<usize> = (0..self.buffers.len()).rev().collect();
+        // Kept until in-flight requests are drained, since the kernel may
+        // still be using their buffers
+        let mut failure: Option<EraseError> = None;
+        let mut stopped = false;
+
+        loop {
+            while !stopped && failure.is_none() && !free.is_empty() {
+                let Some((offset, len)) = pieces.next() else {
+                    break;
+                };
+                let slot = free.pop().unwrap_or_default();
+                if op == IoOp::Write
+                    && let Some(buffer) = self.buffers[slot].as_mut()
+                {
+                    fill(offset, &mut buffer[..len]);
+                }
+                self.pacer.wait(&self.config.throttle, len);
+                match self.submit(op, slot, offset, len) {
+                    Ok(()) => queue.push_back(Request { slot, offset, len, result: None }),
+                    Err(e) => {
+                        free.push(slot);
+                        failure = Some(e.into());
+                    }
+                }
+            }
+            if queue.is_empty() {
+                break;
+            }
+
+            while queue.front().is_some_and(|request| request.result.is_none()) {
+                let finished = match self.wait() {
+                    Ok(finished) => finished,
+                    Err(e) => {
+                        // The ring is unusable; the buffers stay with it, see Drop
+                        return Err(e.into());
+                    }
+                };
+                for (slot, result) in finished {
+                    if let Some(request) = queue.iter_mut().find(|request| request.slot == slot) {
+                        request.result = Some(self.finish_short(op, request, result));
+                    }
+                }
+            }
+
+            while queue.front().is_some_and(|request| request.result.is_some()) {
+                let Some(request) = queue.pop_front() else {
+                    break;
+                };
+                free.push(request.slot);
+                if stopped || failure.is_some() {
+                    continue;
+                }
+                let Some(buffer) = self.buffers[request.slot].as_mut() else {
+                    continue;
+                };
+                let completion = Completion {
+                    offset: request.offset,
+                    data: &mut buffer[..request.len],
+                    result: request.result.unwrap_or(Ok(())),
+                    file: &self.file,
+                };
+                match complete(completion) {
+                    Ok(keep_going) => stopped = !keep_going,
+                    Err(e) => failure = Some(e),
+                }
+            }
+        }
+
+        match failure {
+            Some(e) => Err(e),
+            None => Ok(!stopped),
+        }
+    }
+
+    fn submit(&mut self, op: IoOp, slot: usize, offset: u64, len: usize) -> io::Result<()> {
+        let ioprio = self.config.throttle.limits().priority.ioprio();
+        match &mut self.backend {
+            #[cfg(target_os = "linux")]
+            Backend::Uring { ring, in_flight } => {
+                use io_uring::{opcode, types};
+                use std::os::fd::AsRawFd;
+
+                let buffer = self.buffers[slot].as_mut().ok_or_else(|| io::Error::other("buffer in use"))?;
+                let fd = types::Fd(self.file.as_raw_fd());
+                let entry = match op {
+                    IoOp::Write => {
+                        opcode::Write::new(fd, buffer.as_ptr(), len as u32).offset(offset).ioprio(ioprio).build()
+                    }
+                    IoOp::Read => {
+                        opcode::Read::new(fd, buffer.as_mut_ptr(), len as u32).offset(offset).ioprio(ioprio).build()
+                    }
+                };
+                // The buffer outlives the request: it is only dropped with
+                // the engine, which waits for the ring to drain first
+                unsafe { ring.submission().push(&entry.user_data(slot as u64)) }
+                    .map_err(|_| io::Error::other("io_uring submission queue is full"))?;
+                ring.submit()?;
+                *in_flight += 1;
+                Ok(())
+            }
+            Backend::Threads(pool) => {
+                let buffer = self.buffers[slot].take().ok_or_else(|| io::Error::other("buffer in use"))?;
+                pool.submit(Job { slot, op, offset, len, ioprio, buffer })
+            }
+        }
+    }
+
+    // Blocks until at least one request finished. Results are raw byte
+    // counts as the kernel returned them.
+    fn wait(&mut self) -> io::Result<Vec<(usize, io::Result<usize>)>> {
+        match &mut self.backend {
+            #[cfg(target_os = "linux")]
+            Backend::Uring { ring, in_flight } => {
+                loop {
+                    match ring.submit_and_wait(1) {
+                        Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                        result => result?,
+                    };
+                    break;
+                }
+                let finished: Vec<_> = ring
+                    .completion()
+                    .map(|entry| {
+                        let result = match entry.result() {
+                            errno if errno < 0 => Err(io::Error::from_raw_os_error(-errno)),
+                            count => Ok(count as usize),
+                        };
+                        (entry.user_data() as usize, result)
+                    })
+                    .collect();
+                *in_flight -= finished.len();
+                Ok(finished)
+            }
+            Backend::Threads(pool) => {
+                let (slot, buffer, result) = pool.done.recv().map_err(|_| io::Error::other("I/O threads exited"))?;
+                self.buffers[slot] = Some(buffer);
+                Ok(vec![(slot, result)])
+            }
+        }
+    }
+
+    // io_uring may transfer less than asked; the rest is done synchronously
+    fn finish_short(&mut self, op: IoOp, request: &Request, result: io::Result<usize>) -> io::Result<()> {
+        let done = result?;
+        if done >= request.len {
+            return Ok(());
+        }
+        let buffer = self.buffers[request.slot].as_mut().ok_or_else(|| io::Error::other("buffer in use"))?;
+        let rest = request.offset + done as u64;
+        match op {
+            IoOp::Write => self.file.write_all_at(&buffer[done..request.len], rest),
+            IoOp::Read => self.file.read_exact_at(&mut buffer[done..request.len], rest),
+        }
+    }
+}
+
+impl Drop for IoEngine {
+    fn drop(&mut self) {
+        #[cfg(target_os = "linux")]
+        if let Backend::Uring { ring, in_flight } = &mut self.backend {
+            while *in_flight > 0 {
+                if ring.submit_and_wait(1).is_err() {
+                    // The kernel may still write into the buffers; never free them
+                    std::mem::forget(std::mem::take(&mut self.buffers));
+                    return;
+                }
+                *in_flight -= ring.completion().count();
+            }
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn uring(queue_depth: usize) -> io::Result<Backend> {
+    let entries = (queue_depth as u32).next_power_of_two();
+    Ok(Backend::Uring { ring: Box::new(io_uring::IoUring::new(entries)?), in_flight: 0 })
+}
+
+#[cfg(not(target_os = "linux"))]
+fn uring(_queue_depth: usize) -> io::Result<Backend> {
+    Err(io::Error::new(io::ErrorKind::Unsupported, "io_uring needs Linux"))
+}
+
+fn open_direct(path: &Path, op: IoOp) -> io::Result<File> {
+    let mut options = OpenOptions::new();
+    match op {
+        IoOp::Read => options.read(true),
+        IoOp::Write => options.write(true),
+    };
+    if fs::metadata(path)?.file_type().is_block_device()
+        && let Ok(file) = options.clone().custom_flags(libc::O_DIRECT).open(path)
+    {
+        return Ok(file);
+    }
+    options.open(path)
+}
+
+struct Job {
+    slot: usize,
+    op: IoOp,
+    offset: u64,
+    len: usize,
+    ioprio: u16,
+    buffer: AlignedBuffer,
+}
+
+// One thread per request in flight, each doing blocking pread/pwrite
+struct ThreadPool {
+    jobs: Option<Sender<Job>>,
+    done: Receiver<(usize, AlignedBuffer, io::Result<usize>)>,
+    workers: Vec<JoinHandle<()>>,
+}
+
+impl ThreadPool {
+    fn new(file: &Arc<File>, threads: usize) -> Self {
+        let (jobs, queue) = mpsc::channel::<Job>();
+        let (finished, done) = mpsc::channel();
+        let queue = Arc::new(Mutex::new(queue));
+        let workers = (0..threads)
+            .map(|_| {
+                let (file, queue, finished) = (Arc::clone(file), Arc::clone(&queue), finished.clone());
+                thread::spawn(move || {
+                    let mut ioprio = 0;
+                    loop {
+                        let job = queue.lock().map_err(|_| ()).and_then(|queue| queue.recv().map_err(|_| ()));
+                        let Ok(mut job) = job else {
+                            return;
+                        };
+                        if job.ioprio != ioprio {
+                            ioprio = job.ioprio;
+                            set_thread_ioprio(ioprio);
+                        }
+                        let data = &mut job.buffer[..job.len];
+                        let result = match job.op {
+                            IoOp::Write => file.write_all_at(data, job.offset),
+                            IoOp::Read => file.read_exact_at(data, job.offset),
+                        };
+                        if finished.send((job.slot, job.buffer, result.map(|()| job.len))).is_err() {
+                            return;
+                        }
+                    }
+                })
+            })
+            .collect();
+        ThreadPool { jobs: Some(jobs), done, workers }
+    }
+
+    fn submit(&self, job: Job) -> io::Result<()> {
+        self.jobs
+            .as_ref()
+            .and_then(|jobs| jobs.send(job).ok())
+            .ok_or_else(|| io::Error::other("I/O threads exited"))
+    }
+}
+
+impl Drop for ThreadPool {
+    fn drop(&mut self) {
+        // Closing the queue ends each worker once its current job is done
+        self.jobs = None;
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+}
+
+/// Throughput reached by a [`benchmark`] run, within any limits of the
+/// configured throttle
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BenchmarkReport {
+    pub op: IoOp,
+    pub backend: IoBackend,
+    pub queue_depth: usize,
+    pub block_size: usize,
+    pub bytes: u64,
+    /// Including the final flush of written data
+    pub seconds: f64,
+    /// Bytes per second
+    pub throughput: f64,
+}
+
+/// Reads, or overwrites with zeros, the first `bytes` of `path` and reports
+/// the throughput. A write benchmark destroys the data it covers; with
+/// `expected`, the opened device is checked against it before any I/O.
+pub fn benchmark(
+    path: &Path,
+    op: IoOp,
+    bytes: u64,
+    config: &IoConfig,
+    expected: Option<&DeviceIdentity>,
+    cancel: &AtomicBool,
+) -> Result<BenchmarkReport, EraseError> {
+    let mut engine = IoEngine::open(path, op, config)?;
+    if let Some(expected) = expected {
+        check_opened(engine.file(), path, expected)?;
+    }
+    let bytes = bytes.min(engine.size()?);
+    if bytes == 0 {
+        return Err(EraseError::InvalidTarget(format!("{} has zero size", path.display())));
+    }
+
+    let started = Instant::now();
+    let mut failed = None;
+    let finished = engine.run(op, &[(0, bytes)], &mut |_, _| {}, &mut |completion| {
+        if let Err(e) = completion.result {
+            failed = Some(e);
+            return Ok(false);
+        }
+        Ok(!cancel.load(Ordering::Relaxed))
+    })?;
+    if let Some(e) = failed {
+        return Err(e.into());
+    }
+    if !finished {
+        return Err(EraseError::Cancelled);
+    }
+    if op == IoOp::Write {
+        engine.flush()?;
+    }
+    let seconds = started.elapsed().as_secs_f64().max(f64::EPSILON);
+
+    Ok(BenchmarkReport {
+        op,
+        backend: engine.backend(),
+        queue_depth: engine.config.queue_depth,
+        block_size: engine.config.block_size,
+        bytes,
+        seconds,
+        throughput: bytes as f64 / seconds,
+    })
+}
@@ -0,0 +1,134 @@
+//! Sectors that failed during an erase
+//! Failed writes are retried in smaller pieces down to a single sector, and
+//! the sectors that still fail are recorded here instead of aborting the job.
+
+use serde::{Deserialize, Serialize};
+
+/// A run of consecutive failed sectors
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LbaRange {
+    pub start: u64,
+    pub count: u64,
+}
+
+impl LbaRange {
+    pub fn end(&self) -> u64 {
+        self.start + self.count
+    }
+}
+
+impl std::fmt::Display for LbaRange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.count {
+            1 => write!(f, "{}", self.start),
+            count => write!(f, "{}-{}", self.start, self.start + count - 1),
+        }
+    }
+}
+
+/// Sorted, non-overlapping ranges of failed sectors
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ErrorMap {
+    pub sector_size: u64,
+    pub ranges: Vec<LbaRange>,
+}
+
+impl Default for ErrorMap {
+    fn default() -> Self {
+        ErrorMap::new(512)
+    }
+}
+
+impl std::fmt::Display for ErrorMap {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let ranges: Vec<String> = self.ranges.iter().map(|range| range.to_string()).collect();
+        write!(f, "{}", ranges.join(", "))
+    }
+}
+
+impl ErrorMap {
+    pub fn new(sector_size: u64) -> Self {
+        ErrorMap { sector_size: sector_size.max(1), ranges: Vec::new() }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ranges.is_empty()
+    }
+
+    pub fn sectors(&self) -> u64 {
+        self.ranges.iter().map(|range| range.count).sum()
+    }
+
+    /// Marks every sector touched by `length` bytes at byte `offset` as failed
+    pub fn add(&mut self, offset: u64, length: u64) {
+        if length == 0 {
+            return;
+        }
+        let start = offset / self.sector_size;
+        let end = (offset + length).div_ceil(self.sector_size);
+        self.insert(LbaRange { start, count: end - start });
+    }
+
+    /// Sectors failed in either map, counted in this map's sector size
+    pub fn union(&self, other: &ErrorMap) -> ErrorMap {
+        let mut union = self.clone();
+        for range in &other.ranges {
+            union.add(range.start * other.sector_size, range.count * other.sector_size);
+        }
+        union
+    }
+
+    fn insert(&mut self, mut range: LbaRange) {
+        // Ranges that overlap or touch the new one are merged into it
+        let first = self.ranges.partition_point(|r| r.end() < range.start);
+        let last = self.ranges.partition_point(|r| r.start <= range.end());
+        if first < last {
+            let start = range.start.min(self.ranges[first].start);
+            let end = range.end().max(self.ranges[last - 1].end());
+            range = LbaRange { start, count: end - start };
+        }
+        self.ranges.splice(first..last, [range]);
+    }
+}
+
+/// Decides what failed sectors do to an erase
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum ErrorPolicy {
+    /// Stop at the first sector that cannot be written
+    Abort,
+    /// Carry on to the end, then fail the job if any sector failed
+    #[default]
+    Fail,
+    /// Carry on, and pass the job with up to this many failed sectors
+    Tolerate(u64),
+}
+
+impl std::fmt::Display for ErrorPolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ErrorPolicy::Abort => write!(f, "Abort on first bad sector"),
+            ErrorPolicy::Fail => write!(f, "Continue, fail on bad sectors"),
+            ErrorPolicy::Tolerate(sectors) => write!(f, "Continue, tolerate up to {} bad sectors", sectors),
+        }
+    }
+}
+
+impl ErrorPolicy {
+    pub fn get_all_policies() -> Vec<ErrorPolicy> {
+        vec![ErrorPolicy::Abort, ErrorPolicy::Fail, ErrorPolicy::Tolerate(8)]
+    }
+
+    /// Whether a job with `failed` bad sectors passes
+    pub fn accepts(&self, failed: u64) -> bool {
+        match self {
+            ErrorPolicy::Abort | ErrorPolicy::Fail => failed == 0,
+            ErrorPolicy::Tolerate(limit) => failed <= *limit,
+        }
+    }
+}
+
+/// Whether `e` comes from the medium, as opposed to e.g. the device going
+/// away. Only these are worth retrying sector by sector.
+pub fn is_media_error(e: &std::io::Error) -> bool {
+    matches!(e.raw_os_error(), Some(libc::EIO | libc::ENODATA | libc::EREMOTEIO | libc::EILSEQ))
+}
@@ -1,13 +1,474 @@
-// Erasure module - Main interface for all erasure methods
-// This module coordinates between different erasure strategies
+//! Erasure module - Main interface for all erasure methods
+//! This module coordinates between different erasure strategies
 
 pub mod overwrite;
 pub mod ata;
+pub mod batch;
+pub mod checkpoint;
 pub mod nvme;
 pub mod crypto;
+pub mod engine;
+pub mod errormap;
+pub mod progress;
+pub mod throttle;
+
+use std::{
+    fs::File,
+    path::{Path, PathBuf},
+    sync::atomic::AtomicBool,
+    time::Duration,
+};
+
+use crate::{
+    cert::{ResumeRecord, VerificationSummary, now_rfc3339},
+    erasure::{
+        checkpoint::{Checkpoint, CheckpointStore, Checkpointer},
+        engine::IoConfig,
+        errormap::{ErrorMap, ErrorPolicy},
+        overwrite::{OverwriteOptions, OverwriteReport, OverwriteStandard, overwrite_device},
+    },
+    health::{HealthReport, read_health},
+    libs::StorageDriver,
+    platform::{DeviceIdentity, WipeMethod},
+    verify::{VerificationReport, VerifyMode, verify_pattern},
+};
+
+#[derive(Debug)]
+pub enum EraseError {
+    IoError(std::io::Error),
+    InvalidTarget(String),
+    Unsupported(String),
+    CommandFailed(String),
+    // The device at the path is no longer the one the operator confirmed
+    IdentityChanged(String),
+    // Mounted, held by another device or the system disk, see crate::safety
+    InUse(String),
+    // A saved checkpoint does not fit the overwrite it was meant to resume
+    Checkpoint(String),
+    Cancelled,
+}
+
+impl std::fmt::Display for EraseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EraseError::IoError(e) => write!(f, "I/O error: {}", e),
+            EraseError::InvalidTarget(e) => write!(f, "invalid target: {}", e),
+            EraseError::Unsupported(e) => write!(f, "not supported: {}", e),
+            EraseError::CommandFailed(e) => write!(f, "device command failed: {}", e),
+            EraseError::IdentityChanged(e) => write!(f, "device identity changed: {}", e),
+            EraseError::InUse(e) => write!(f, "device is in use: {}", e),
+            EraseError::Checkpoint(e) => write!(f, "cannot resume: {}", e),
+            EraseError::Cancelled => write!(f, "cancelled by operator"),
+        }
+    }
+}
+
+impl std::error::Error for EraseError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            EraseError::IoError(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for EraseError {
+    fn from(e: std::io::Error) -> Self {
+        EraseError::IoError(e)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ErasePhase {
+    #[default]
+    Erasing,
+    Verifying,
+    // A command running inside the drive, e.g. sanitize or secure erase
+    Firmware,
+}
+
+impl std::fmt::Display for ErasePhase {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ErasePhase::Erasing => write!(f, "Erasing"),
+            ErasePhase::Verifying => write!(f, "Verifying"),
+            ErasePhase::Firmware => write!(f, "Firmware Erase"),
+        }
+    }
+}
+
+/// Progress of a command the drive runs on its own
+#[derive(Debug, Clone, Default)]
+pub struct FirmwareProgress {
+    pub operation: String,
+    /// Completion reported by the drive (NVMe SPROG, ATA sanitize status)
+    pub reported: Option<f32>,
+    /// Duration the drive estimates for the whole operation
+    pub estimated: Option<Duration>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct EraseProgress {
+    pub phase: ErasePhase,
+    pub pass: usize,
+    pub passes: usize,
+    pub pattern: String,
+    /// Position within the current pass or verification
+    pub bytes_done: u64,
+    pub bytes_total: u64,
+    /// Totals over the whole erase, filled in by [`erase_device`]
+    pub bytes_written: u64,
+    pub bytes_verified: u64,
+    pub work_done: u64,
+    pub work_total: u64,
+    pub firmware: Option<FirmwareProgress>,
+}
+
+impl EraseProgress {
+    /// Completion of the current pass or phase
+    pub fn fraction(&self) -> f32 {
+        if let Some(reported) = self.firmware.as_ref().and_then(|f| f.reported) {
+            return reported;
+        }
+        if self.bytes_total == 0 {
+            return 0.0;
+        }
+        self.bytes_done as f32 / self.bytes_total as f32
+    }
+
+    /// Completion of the whole erase including verification
+    pub fn overall_fraction(&self) -> f32 {
+        if self.firmware.is_some() || self.work_total == 0 {
+            return self.fraction();
+        }
+        self.work_done as f32 / self.work_total as f32
+    }
+}
+
+/// Firmware erase support as reported by the drive itself
+#[derive(Debug, Clone, Default)]
+pub struct DriveCapabilities {
+    pub model: String,
+    pub serial: String,
+    pub firmware: String,
+    pub block_erase: bool,
+    pub crypto_erase: bool,
+    pub security_erase: bool,
+    pub security_frozen: bool,
+    /// Drive estimate for a firmware block erase
+    pub erase_time: Option<Duration>,
+}
+
+impl DriveCapabilities {
+    /// Whether `method` can run on this drive
+    pub fn supports(&self, method: &WipeMethod) -> bool {
+        match method {
+            WipeMethod::Overwrite => true,
+            WipeMethod::SecureErase => self.block_erase || (self.security_erase && !self.security_frozen),
+            WipeMethod::CryptoErase => self.crypto_erase,
+        }
+    }
+}
+
+/// Sends IDENTIFY to an ATA drive or Identify Controller to an NVMe drive
+pub fn identify_drive(path: &Path) -> Result<DriveCapabilities, EraseError> {
+    if is_nvme(path) {
+        let controller = nvme::NvmeController::open(path)?;
+        let info = controller.identify()?;
+        let status = controller.sanitize_status().ok();
+        return Ok(DriveCapabilities {
+            model: info.model,
+            serial: info.serial,
+            firmware: info.firmware,
+            block_erase: info.sanitize_block_erase,
+            crypto_erase: info.sanitize_crypto_erase,
+            erase_time: status.and_then(|s| s.block_erase_time),
+            ..Default::default()
+        });
+    }
+
+    let driver = ata::AtaStorageDriver::new(&path.to_string_lossy())?;
+    if !driver.is_ata_device() {
+        return Err(EraseError::Unsupported(format!("{} is not an ATA or NVMe drive", path.display())));
+    }
+    let info = driver.identify_ata_device()?;
+    Ok(DriveCapabilities {
+        block_erase: info.sanitize_supported && info.sanitize_block_erase,
+        crypto_erase: info.sanitize_supported && info.sanitize_crypto_scramble,
+        security_erase: info.security_supported,
+        security_frozen: info.security_frozen,
+        erase_time: if info.enhanced_erase_supported { info.enhanced_erase_time } else { info.erase_time },
+        model: info.model,
+        serial: info.serial,
+        firmware: info.firmware,
+    })
+}
+
+fn is_nvme(path: &Path) -> bool {
+    path.file_name()
+        .is_some_and(|name| name.to_string_lossy().starts_with("nvme"))
+}
+
+#[derive(Debug, Clone)]
+pub struct EraseRequest {
+    pub path: String,
+    pub method: WipeMethod,
+    pub standard: OverwriteStandard,
+    pub verify: VerifyMode,
+    /// What sectors that cannot be written do to the erase
+    pub error_policy: ErrorPolicy,
+    /// Identity the operator confirmed; checked again before the first write
+    pub expected: Option<DeviceIdentity>,
+    /// Erase the disk the running system was booted from
+    pub allow_system_disk: bool,
+    /// Where an overwrite saves checkpoints; none are saved without it
+    pub checkpoint_dir: Option<PathBuf>,
+    /// Carry on an overwrite interrupted at this checkpoint
+    pub resume: Option<Checkpoint>,
+    /// Backend, queue depth and block size of overwrite and verify I/O
+    pub io: IoConfig,
+}
+
+#[derive(Debug, Clone)]
+pub struct EraseReport {
+    pub started_at: String,
+    pub finished_at: String,
+    pub overwrite: Option<OverwriteReport>,
+    pub verification: Option<VerificationReport>,
+    /// Drive health right before the first write and after the erase; none
+    /// for image files and drives that do not report it
+    pub health_before: Option<HealthReport>,
+    pub health_after: Option<HealthReport>,
+    pub error_policy: ErrorPolicy,
+    /// Each time the overwrite was interrupted and carried on
+    pub resumes: Vec<ResumeRecord>,
+    /// Why checkpoints could not be saved, if they could not
+    pub checkpoint_error: Option<String>,
+}
+
+impl EraseReport {
+    pub fn passed(&self) -> bool {
+        self.verification.as_ref().is_none_or(|v| v.passed) && self.error_policy.accepts(self.errors().sectors())
+    }
+
+    /// Sectors that could not be written or read back
+    pub fn errors(&self) -> ErrorMap {
+        let written = self.overwrite.as_ref().map(|o| o.errors.clone()).unwrap_or_default();
+        match &self.verification {
+            Some(verification) => written.union(&verification.unreadable),
+            None => written,
+        }
+    }
+
+    /// Verification result with the sectors the overwrite could not write.
+    /// Present when the erase was verified or hit bad sectors.
+    pub fn verification_summary(&self) -> Option<VerificationSummary> {
+        let write_errors = self.overwrite.as_ref().map(|o| o.errors.clone()).unwrap_or_default();
+        let mut summary = match &self.verification {
+            Some(verification) => verification.summary(),
+            None if write_errors.is_empty() => return None,
+            None => VerificationSummary::default(),
+        };
+        summary.write_errors = write_errors;
+        if !summary.write_errors.is_empty() || !summary.read_errors.is_empty() {
+            summary.error_policy = Some(self.error_policy.to_string());
+        }
+        Some(summary)
+    }
+}
+
+/// Re-reads the identity and usage of `path` right before it is written to,
+/// refusing a device that changed or is in use. Anything that writes to a
+/// device the operator picked goes through this first.
+pub fn check_target(path: &str, expected: Option<&DeviceIdentity>, allow_system_disk: bool) -> Result<(), EraseError> {
+    if let Some(expected) = expected {
+        let current = StorageDriver::device_identity(path)
+            .ok_or_else(|| EraseError::IdentityChanged(format!("{} can no longer be identified", path)))?;
+        if let Some(mismatch) = expected.mismatch(&current) {
+            return Err(EraseError::IdentityChanged(format!("{}: {}", path, mismatch)));
+        }
+    }
+    let usage = StorageDriver::device_usage(path);
+    if usage.blocks_erase(allow_system_disk) {
+        return Err(EraseError::InUse(usage.describe()));
+    }
+    Ok(())
+}
+
+// Compares `expected` with the device `file` was opened on, so a drive that
+// replaced the one at `path` after the checks above is never written to
+pub(crate) fn check_opened(file: &File, path: &Path, expected: &DeviceIdentity) -> Result<(), EraseError> {
+    let current = StorageDriver::opened_identity(file).unwrap_or_default();
+    match expected.mismatch(&current) {
+        Some(mismatch) => Err(EraseError::IdentityChanged(format!("{}: {}", path.display(), mismatch))),
+        None => Ok(()),
+    }
+}
+
+/// Erases one device and verifies the result. Blocks until done, so callers
+/// run it on a worker thread.
+pub fn erase_device(
+    request: &EraseRequest,
+    progress: &mut dyn FnMut(EraseProgress),
+    cancel: &AtomicBool,
+) -> Result<EraseReport, EraseError> {
+    let path = Path::new(&request.path);
+
+    check_target(&request.path, request.expected.as_ref(), request.allow_system_disk)?;
+    if let Some(checkpoint) = &request.resume {
+        if request.method != WipeMethod::Overwrite || request.standard != checkpoint.standard {
+            return Err(EraseError::Checkpoint(format!(
+                "checkpoint is for a {} overwrite",
+                checkpoint.standard
+            )));
+        }
+        let current = StorageDriver::device_identity(&request.path).ok_or_else(|| {
+            EraseError::IdentityChanged(format!("{} can no longer be identified", request.path))
+        })?;
+        checkpoint.check(&request.path, &current)?;
+    }
+    let started_at = request.resume.as_ref().map_or_else(now_rfc3339, |c| c.started_at.clone());
+
+    let health_before = read_health(path).ok();
+
+    if request.method != WipeMethod::Overwrite {
+        firmware_erase(path, &request.method, progress, cancel)?;
+        return Ok(EraseReport {
+            started_at,
+            finished_at: now_rfc3339(),
+            overwrite: None,
+            verification: None,
+            health_before,
+            health_after: read_health(path).ok(),
+            error_policy: request.error_policy,
+            resumes: Vec::new(),
+            checkpoint_error: None,
+        });
+    }
+
+    // Overwrite and verification report their own phase; add up the totals
+    // across passes so callers can show overall progress and an ETA
+    let mut written = 0;
+    let mut written_total = 0;
+    let mut totals = |mut update: EraseProgress| {
+        match update.phase {
+            ErasePhase::Erasing => {
+                let size = update.bytes_total;
+                written = (update.pass as u64 - 1) * size + update.bytes_done;
+                written_total = update.passes as u64 * size;
+                update.work_total = written_total + request.verify.estimated_bytes(size);
+            }
+            ErasePhase::Verifying | ErasePhase::Firmware => {
+                update.bytes_verified = update.bytes_done;
+                update.work_total = written_total + update.bytes_total;
+            }
+        }
+        update.bytes_written = written;
+        update.work_done = written + update.bytes_verified;
+        progress(update);
+    };
+
+    let mut checkpointer = match (&request.resume, &request.checkpoint_dir) {
+        (None, None) => None,
+        (resume, dir) => {
+            let store = dir.as_deref().map(CheckpointStore::open).transpose()?;
+            let checkpoint = match resume {
+                Some(checkpoint) => {
+                    let mut checkpoint = checkpoint.clone();
+                    checkpoint.resumes.push(checkpoint.resume_record());
+                    checkpoint
+                }
+                None => Checkpoint::new(
+                    &request.path,
+                    StorageDriver::device_identity(&request.path)
+                        .or_else(|| request.expected.clone())
+                        .unwrap_or_default(),
+                    request.standard,
+                    request.verify,
+                    request.error_policy,
+                ),
+            };
+            Some(Checkpointer::new(store, checkpoint))
+        }
+    };
+
+    let options = OverwriteOptions {
+        geometry: StorageDriver::block_geometry(&request.path).unwrap_or_default(),
+        error_policy: request.error_policy,
+        io: request.io.clone(),
+        // The checks above went by path; this one goes by the opened device
+        expected: request.expected.clone().or_else(|| request.resume.as_ref().map(|c| c.identity.clone())),
+    };
+    let overwrite = overwrite_device(
+        path,
+        request.standard,
+        &options,
+        checkpointer.as_mut(),
+        &mut totals,
+        cancel,
+    )?;
+    let verification = match (request.verify, overwrite.final_pattern()) {
+        (VerifyMode::None, _) | (_, None) => None,
+        (mode, Some(generator)) => Some(verify_pattern(
+            path,
+            &generator,
+            overwrite.device_size,
+            mode,
+            &request.io.aligned_to(&options.geometry),
+            &mut totals,
+            cancel,
+        )?),
+    };
+
+    // Only an interrupted job is worth resuming
+    if let Some(checkpointer) = &checkpointer {
+        checkpointer.finish();
+    }
+
+    Ok(EraseReport {
+        started_at,
+        finished_at: now_rfc3339(),
+        overwrite: Some(overwrite),
+        verification,
+        health_before,
+        health_after: read_health(path).ok(),
+        error_policy: request.error_policy,
+        resumes: checkpointer.as_ref().map(|c| c.checkpoint().resumes.clone()).unwrap_or_default(),
+        checkpoint_error: checkpointer.and_then(|c| c.error),
+    })
+}
+
+// Hands the device to its own firmware. NVMe drives are sanitized, ATA drives
+// are sanitized or use SECURITY ERASE UNIT. Once the command is issued it can
+// no longer be cancelled.
+fn firmware_erase(
+    path: &Path,
+    method: &WipeMethod,
+    progress: &mut dyn FnMut(EraseProgress),
+    cancel: &AtomicBool,
+) -> Result<(), EraseError> {
+    let crypto = *method == WipeMethod::CryptoErase;
+    if !path.to_string_lossy().starts_with("/dev/") {
+        return Err(EraseError::InvalidTarget(format!(
+            "{} is not a device, firmware erase needs a drive",
+            path.display()
+        )));
+    }
+    if cancel.load(std::sync::atomic::Ordering::Relaxed) {
+        return Err(EraseError::Cancelled);
+    }
+
+    if is_nvme(path) {
+        let action = if crypto {
+            nvme::SanitizeAction::CryptoErase
+        } else {
+            nvme::SanitizeAction::BlockErase
+        };
+        nvme::sanitize_device(path, action, progress)
+    } else {
+        ata::firmware_erase(path, crypto, progress)
+    }
+}
 
-// TODO: Add main erasure coordinator struct
 // TODO: Add trait for erasure methods
-// TODO: Add device detection logic
 // TODO: Add erasure method selection logic
-// TODO: Add progress tracking interface
\ No newline at end of file
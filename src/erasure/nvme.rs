@@ -1,14 +1,270 @@
-// NVMe Secure Erase implementation
-// Wraps nvme-cli command or uses direct ioctl calls for NVMe drives
+//! NVMe Secure Erase implementation
+//! Issues Sanitize through the kernel's NVMe admin passthrough ioctl
+
+use std::{
+    fs::{File, OpenOptions},
+    os::fd::AsRawFd,
+    path::Path,
+    thread,
+    time::Duration,
+};
+
+use crate::erasure::{EraseError, ErasePhase, EraseProgress, FirmwareProgress};
+
+const NVME_ADMIN_GET_LOG_PAGE: u8 = 0x02;
+const NVME_ADMIN_IDENTIFY: u8 = 0x06;
+const NVME_ADMIN_SANITIZE: u8 = 0x84;
+const NVME_LOG_SANITIZE_STATUS: u32 = 0x81;
+const NVME_NSID_ALL: u32 = 0xffff_ffff;
+
+// Sanitize Status field of the sanitize status log
+const SANITIZE_STATUS_COMPLETED: u8 = 1;
+const SANITIZE_STATUS_IN_PROGRESS: u8 = 2;
+const SANITIZE_STATUS_FAILED: u8 = 3;
+const SANITIZE_STATUS_COMPLETED_NO_DEALLOC: u8 = 4;
+
+#[derive(Debug)]
+pub enum NvmeError {
+    IoError(std::io::Error),
+    Unsupported(String),
+    // NVMe status code returned by the controller
+    CommandFailed(u16),
+}
+
+impl std::fmt::Display for NvmeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            NvmeError::IoError(e) => write!(f, "I/O error: {}", e),
+            NvmeError::Unsupported(e) => write!(f, "not supported: {}", e),
+            NvmeError::CommandFailed(status) => write!(f, "NVMe command failed with status 0x{:03x}", status),
+        }
+    }
+}
+
+impl std::error::Error for NvmeError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            NvmeError::IoError(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for NvmeError {
+    fn from(e: std::io::Error) -> Self {
+        NvmeError::IoError(e)
+    }
+}
+
+impl From<NvmeError> for EraseError {
+    fn from(e: NvmeError) -> Self {
+        match e {
+            NvmeError::IoError(e) => EraseError::IoError(e),
+            NvmeError::Unsupported(e) => EraseError::Unsupported(e),
+            other => EraseError::CommandFailed(other.to_string()),
+        }
+    }
+}
+
+/// An admin command; data buffers are passed separately
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NvmeCommand {
+    pub opcode: u8,
+    pub nsid: u32,
+    pub cdw10: u32,
+    pub cdw11: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SanitizeAction {
+    BlockErase,
+    CryptoErase,
+}
+
+impl SanitizeAction {
+    fn sanact(&self) -> u32 {
+        match self {
+            SanitizeAction::BlockErase => 2,
+            SanitizeAction::CryptoErase => 4,
+        }
+    }
+}
+
+impl std::fmt::Display for SanitizeAction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SanitizeAction::BlockErase => write!(f, "Sanitize Block Erase"),
+            SanitizeAction::CryptoErase => write!(f, "Sanitize Crypto Erase"),
+        }
+    }
+}
+
+/// Fields of Identify Controller this crate uses
+#[derive(Debug, Clone)]
+pub struct NvmeControllerInfo {
+    pub serial: String,
+    pub model: String,
+    pub firmware: String,
+    pub sanitize_crypto_erase: bool,
+    pub sanitize_block_erase: bool,
+}
+
+/// Sanitize status log page (log identifier 81h)
+#[derive(Debug, Clone, Copy)]
+pub struct SanitizeStatus {
+    pub progress: f32,
+    pub status: u8,
+    pub block_erase_time: Option<Duration>,
+    pub crypto_erase_time: Option<Duration>,
+}
+
+pub struct NvmeController {
+    file: File,
+}
+
+impl NvmeController {
+    pub fn open(path: &Path) -> Result<Self, NvmeError> {
+        let file = OpenOptions::new().read(true).write(true).open(path)?;
+        Ok(NvmeController { file })
+    }
+
+    /// Enough for Identify and log pages. Closing a read-only descriptor does
+    /// not make udev announce a change the way a writable one does.
+    pub fn open_read_only(path: &Path) -> Result<Self, NvmeError> {
+        Ok(NvmeController { file: File::open(path)? })
+    }
+
+    pub fn admin_command(&self, command: &NvmeCommand, data: Option<&mut [u8]>) -> Result<u32, NvmeError> {
+        #[cfg(target_os = "linux")]
+        {
+            crate::platform::linux::nvme_admin_command(self.file.as_raw_fd(), command, data)
+        }
+        #[cfg(not(target_os = "linux"))]
+        {
+            let _ = (command, data);
+            Err(NvmeError::Unsupported("NVMe passthrough is only available on Linux".to_string()))
+        }
+    }
+
+    pub fn identify(&self) -> Result<NvmeControllerInfo, NvmeError> {
+        let mut data = vec![0u8; 4096];
+        let command = NvmeCommand {
+            opcode: NVME_ADMIN_IDENTIFY,
+            // CNS 01h: Identify Controller
+            cdw10: 1,
+            ..Default::default()
+        };
+        self.admin_command(&command, Some(&mut data))?;
+
+        let string = |range: std::ops::Range<usize>| String::from_utf8_lossy(&data[range]).trim().to_string();
+        let sanicap = u32::from_le_bytes(data[328..332].try_into().unwrap());
+        Ok(NvmeControllerInfo {
+            serial: string(4..24),
+            model: string(24..64),
+            firmware: string(64..72),
+            sanitize_crypto_erase: sanicap & 0x1 != 0,
+            sanitize_block_erase: sanicap & 0x2 != 0,
+        })
+    }
+
+    /// Reads `data.len()` bytes of log page `log`, which must be a multiple of four
+    pub fn get_log_page(&self, log: u32, data: &mut [u8]) -> Result<(), NvmeError> {
+        let dwords = (data.len() / 4 - 1) as u32;
+        let command = NvmeCommand {
+            opcode: NVME_ADMIN_GET_LOG_PAGE,
+            nsid: NVME_NSID_ALL,
+            cdw10: log | (dwords << 16),
+            ..Default::default()
+        };
+        self.admin_command(&command, Some(data))?;
+        Ok(())
+    }
+
+    pub fn sanitize_status(&self) -> Result<SanitizeStatus, NvmeError> {
+        let mut data = vec![0u8; 512];
+        self.get_log_page(NVME_LOG_SANITIZE_STATUS, &mut data)?;
+
+        let dword = |offset: usize| u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap());
+        // Estimates are in seconds; all ones means no estimate is given
+        let estimate = |seconds: u32| {
+            (seconds != 0 && seconds != u32::MAX).then(|| Duration::from_secs(seconds as u64))
+        };
+        Ok(SanitizeStatus {
+            progress: u16::from_le_bytes([data[0], data[1]]) as f32 / 65536.0,
+            status: data[2] & 0x07,
+            block_erase_time: estimate(dword(12)),
+            crypto_erase_time: estimate(dword(16)),
+        })
+    }
+
+    pub fn start_sanitize(&self, action: SanitizeAction) -> Result<(), NvmeError> {
+        let command = NvmeCommand {
+            opcode: NVME_ADMIN_SANITIZE,
+            cdw10: action.sanact(),
+            ..Default::default()
+        };
+        self.admin_command(&command, None)?;
+        Ok(())
+    }
+}
+
+/// Sanitizes the whole NVMe subsystem the namespace belongs to and reports
+/// SPROG until the operation completes. A started sanitize cannot be aborted;
+/// it even resumes after a power cycle.
+pub fn sanitize_device(
+    path: &Path,
+    action: SanitizeAction,
+    progress: &mut dyn FnMut(EraseProgress),
+) -> Result<(), EraseError> {
+    let controller = NvmeController::open(path)?;
+    let info = controller.identify()?;
+    let supported = match action {
+        SanitizeAction::BlockErase => info.sanitize_block_erase,
+        SanitizeAction::CryptoErase => info.sanitize_crypto_erase,
+    };
+    if !supported {
+        return Err(EraseError::Unsupported(format!("{} does not support {}", info.model, action)));
+    }
+
+    let before = controller.sanitize_status()?;
+    if before.status == SANITIZE_STATUS_IN_PROGRESS {
+        return Err(EraseError::CommandFailed("a sanitize operation is already running".to_string()));
+    }
+    let estimated = match action {
+        SanitizeAction::BlockErase => before.block_erase_time,
+        SanitizeAction::CryptoErase => before.crypto_erase_time,
+    };
+    controller.start_sanitize(action)?;
+
+    loop {
+        thread::sleep(Duration::from_secs(1));
+        let status = controller.sanitize_status()?;
+        let reported = match status.status {
+            SANITIZE_STATUS_IN_PROGRESS => status.progress,
+            SANITIZE_STATUS_COMPLETED | SANITIZE_STATUS_COMPLETED_NO_DEALLOC => 1.0,
+            SANITIZE_STATUS_FAILED => {
+                return Err(EraseError::CommandFailed(format!("{} failed", action)));
+            }
+            // The log may not reflect the new operation for the first moments
+            _ => 0.0,
+        };
+        progress(EraseProgress {
+            phase: ErasePhase::Firmware,
+            pass: 1,
+            passes: 1,
+            pattern: action.to_string(),
+            firmware: Some(FirmwareProgress {
+                operation: action.to_string(),
+                reported: Some(reported),
+                estimated,
+            }),
+            ..Default::default()
+        });
+        if reported >= 1.0 {
+            return Ok(());
+        }
+    }
+}
 
-// TODO: Add NVMe command interface
-// TODO: Add nvme-cli wrapper functions
-// TODO: Add direct ioctl implementation as fallback
-// TODO: Add NVMe device identification
 // TODO: Add format NVM command implementation
-// TODO: Add sanitize command implementation
-// TODO: Add crypto erase support for NVMe
-// TODO: Add secure erase settings configuration
 // TODO: Add namespace handling
-// TODO: Add progress monitoring
-// TODO: Add error handling for NVMe-specific errors
\ No newline at end of file
@@ -1,13 +1,403 @@
-// Overwrite methods for secure data erasure
-// Implements various overwrite patterns (DoD 5220.22-M, Gutmann, etc.)
-
-// TODO: Add overwrite pattern definitions
-// TODO: Add single-pass overwrite implementation
-// TODO: Add multi-pass overwrite implementation
-// TODO: Add DoD 5220.22-M method (3-pass)
-// TODO: Add Gutmann method (35-pass)
-// TODO: Add random overwrite method
-// TODO: Add zero overwrite method
-// TODO: Add progress callback mechanism
-// TODO: Add verification after overwrite
-// TODO: Add error handling for I/O operations
\ No newline at end of file
+//! Overwrite methods for secure data erasure
+//! Implements various overwrite patterns (DoD 5220.22-M, Gutmann, etc.)
+
+use std::{
+    fs::File,
+    os::unix::fs::FileExt,
+    path::Path,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use chacha20::{
+    ChaCha20,
+    cipher::{KeyIvInit, StreamCipher, StreamCipherSeek},
+};
+use rayon::prelude::*;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::{
+    erasure::{
+        EraseError, ErasePhase, EraseProgress, check_opened,
+        checkpoint::{Checkpointer, OverwriteState, recipe_hash},
+        engine::{IoConfig, IoEngine, IoOp},
+        errormap::{ErrorMap, ErrorPolicy, is_media_error},
+    },
+    platform::{BlockGeometry, DeviceIdentity},
+};
+
+/// Preferred size of each write, rounded to the device's block geometry
+pub const CHUNK_SIZE: usize = 4 * 1024 * 1024;
+// Random data is generated in pieces of this size, one per thread
+const RANDOM_PIECE: usize = 256 * 1024;
+// Keystream under one nonce, well within the 256 GiB the 32-bit ChaCha20
+// block counter can address
+const RANDOM_SEGMENT: u64 = 1 << 30;
+
+/// Sequence of passes written by an overwrite erase
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum OverwriteStandard {
+    Zero,
+    Random,
+    Dod3Pass,
+    Dod7Pass,
+    Gutmann,
+}
+
+impl std::fmt::Display for OverwriteStandard {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            OverwriteStandard::Zero => write!(f, "Single Pass Zeros (NIST 800-88 Clear)"),
+            OverwriteStandard::Random => write!(f, "Single Pass Random"),
+            OverwriteStandard::Dod3Pass => write!(f, "DoD 5220.22-M (3 Passes)"),
+            OverwriteStandard::Dod7Pass => write!(f, "DoD 5220.22-M ECE (7 Passes)"),
+            OverwriteStandard::Gutmann => write!(f, "Gutmann (35 Passes)"),
+        }
+    }
+}
+
+impl OverwriteStandard {
+    pub fn get_all_standards() -> Vec<OverwriteStandard> {
+        vec![
+            OverwriteStandard::Zero,
+            OverwriteStandard::Random,
+            OverwriteStandard::Dod3Pass,
+            OverwriteStandard::Dod7Pass,
+            OverwriteStandard::Gutmann,
+        ]
+    }
+
+    pub fn passes(&self) -> Vec<Pattern> {
+        let fixed = |bytes: &[u8]| Pattern::Fixed(bytes.to_vec());
+        match self {
+            OverwriteStandard::Zero => vec![fixed(&[0x00])],
+            OverwriteStandard::Random => vec![Pattern::Random],
+            OverwriteStandard::Dod3Pass => vec![fixed(&[0x00]), fixed(&[0xff]), Pattern::Random],
+            OverwriteStandard::Dod7Pass => vec![
+                fixed(&[0x00]),
+                fixed(&[0xff]),
+                Pattern::Random,
+                Pattern::Random,
+                fixed(&[0x00]),
+                fixed(&[0xff]),
+                Pattern::Random,
+            ],
+            OverwriteStandard::Gutmann => {
+                let mut passes = vec![Pattern::Random; 4];
+                passes.extend(
+                    [
+                        &[0x55][..], &[0xaa], &[0x92, 0x49, 0x24], &[0x49, 0x24, 0x92],
+                        &[0x24, 0x92, 0x49], &[0x00], &[0x11], &[0x22], &[0x33], &[0x44],
+                        &[0x55], &[0x66], &[0x77], &[0x88], &[0x99], &[0xaa], &[0xbb],
+                        &[0xcc], &[0xdd], &[0xee], &[0xff], &[0x92, 0x49, 0x24],
+                        &[0x49, 0x24, 0x92], &[0x24, 0x92, 0x49], &[0x6d, 0xb6, 0xdb],
+                        &[0xb6, 0xdb, 0x6d], &[0xdb, 0x6d, 0xb6],
+                    ]
+                    .iter()
+                    .map(|bytes| fixed(bytes)),
+                );
+                passes.extend(vec![Pattern::Random; 4]);
+                passes
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Pattern {
+    // Bytes repeated across the whole device
+    Fixed(Vec<u8>),
+    Random,
+}
+
+impl std::fmt::Display for Pattern {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Pattern::Fixed(bytes) => write!(f, "0x{}", hex::encode_upper(bytes)),
+            Pattern::Random => write!(f, "Random"),
+        }
+    }
+}
+
+/// Key of a random pass, drawn from the OS RNG. The pass's data is
+/// regenerated from it for verification instead of being stored.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Seed(pub [u8; 32]);
+
+impl Seed {
+    pub fn random() -> Result<Self, EraseError> {
+        let mut seed = [0u8; 32];
+        getrandom::fill(&mut seed)
+            .map_err(|e| EraseError::IoError(std::io::Error::other(e.to_string())))?;
+        Ok(Seed(seed))
+    }
+}
+
+impl std::fmt::Display for Seed {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", hex::encode(self.0))
+    }
+}
+
+impl Serialize for Seed {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+impl<'de> Deserialize<'de> for Seed {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let hex = String::deserialize(deserializer)?;
+        let bytes = hex::decode(&hex).map_err(serde::de::Error::custom)?;
+        let seed = bytes.try_into().map_err(|_| serde::de::Error::custom("seed is not 32 bytes"))?;
+        Ok(Seed(seed))
+    }
+}
+
+/// Produces the bytes of one pass. Random data is the ChaCha20 keystream
+/// keyed by the seed, with a fresh nonce for every GiB of the device, so any
+/// region can be regenerated on its own for verification. Large buffers
+/// are generated in parallel.
+#[derive(Debug, Clone)]
+pub struct PatternGenerator {
+    pattern: Pattern,
+    seed: Seed,
+}
+
+impl PatternGenerator {
+    pub fn new(pattern: Pattern, seed: Seed) -> Self {
+        PatternGenerator { pattern, seed }
+    }
+
+    pub fn fill(&self, offset: u64, buf: &mut [u8]) {
+        match &self.pattern {
+            Pattern::Fixed(bytes) if bytes.len() == 1 => buf.fill(bytes[0]),
+            Pattern::Fixed(bytes) => {
+                let period = bytes.len() as u64;
+                for (i, b) in buf.iter_mut().enumerate() {
+                    *b = bytes[((offset + i as u64) % period) as usize];
+                }
+            }
+            Pattern::Random => {
+                // Pieces start on RANDOM_PIECE boundaries of the device, so
+                // none crosses into the next segment
+                let head = (RANDOM_PIECE - (offset % RANDOM_PIECE as u64) as usize) % RANDOM_PIECE;
+                let (head, rest) = buf.split_at_mut(head.min(buf.len()));
+                keystream(&self.seed, offset, head);
+                let rest_offset = offset + head.len() as u64;
+                rest.par_chunks_mut(RANDOM_PIECE).enumerate().for_each(|(index, piece)| {
+                    keystream(&self.seed, rest_offset + (index * RANDOM_PIECE) as u64, piece);
+                });
+            }
+        }
+    }
+}
+
+// Writes the keystream found at byte `offset` of the pass into `buf`, which
+// must lie within one segment
+fn keystream(seed: &Seed, offset: u64, buf: &mut [u8]) {
+    if buf.is_empty() {
+        return;
+    }
+    let mut nonce = [0u8; 12];
+    nonce[..8].copy_from_slice(&(offset / RANDOM_SEGMENT).to_le_bytes());
+    let mut cipher = ChaCha20::new(&seed.0.into(), &nonce.into());
+    cipher.seek(offset % RANDOM_SEGMENT);
+    buf.fill(0);
+    cipher.apply_keystream(buf);
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PassRecord {
+    pub pattern: Pattern,
+    pub seed: Seed,
+}
+
+#[derive(Debug, Clone)]
+pub struct OverwriteReport {
+    pub standard: OverwriteStandard,
+    pub passes: Vec<PassRecord>,
+    pub bytes_written: u64,
+    pub device_size: u64,
+    pub geometry: BlockGeometry,
+    /// Sectors that could not be written in at least one pass
+    pub errors: ErrorMap,
+}
+
+impl OverwriteReport {
+    /// Generator for the data that should now be on the device
+    pub fn final_pattern(&self) -> Option<PatternGenerator> {
+        self.passes
+            .last()
+            .map(|pass| PatternGenerator::new(pass.pattern.clone(), pass.seed))
+    }
+}
+
+/// How an overwrite writes to the device
+#[derive(Debug, Clone, Default)]
+pub struct OverwriteOptions {
+    pub geometry: BlockGeometry,
+    pub error_policy: ErrorPolicy,
+    /// Block size is rounded to whole physical blocks of `geometry`
+    pub io: IoConfig,
+    /// Compared with the opened device before anything is written
+    pub expected: Option<DeviceIdentity>,
+}
+
+/// Writes every pass of `standard` over the whole device, syncing after each
+/// pass. Writes are whole physical blocks sized to suit the geometry, issued
+/// through an [`IoEngine`]. The seeds of random passes are returned so they
+/// can be verified.
+///
+/// Block devices are written with O_DIRECT so a bad sector fails the write
+/// that covers it. Unless the policy is [`ErrorPolicy::Abort`], such writes
+/// are retried in halves down to single sectors, and the sectors that still
+/// fail are recorded in the report while the pass carries on.
+///
+/// With a `checkpoint`, the position is saved after each pass, at intervals
+/// and on cancellation, and an overwrite the checkpoint already started
+/// carries on where it was saved.
+pub fn overwrite_device(
+    path: &Path,
+    standard: OverwriteStandard,
+    options: &OverwriteOptions,
+    mut checkpoint: Option<&mut Checkpointer>,
+    progress: &mut dyn FnMut(EraseProgress),
+    cancel: &AtomicBool,
+) -> Result<OverwriteReport, EraseError> {
+    let geometry = &options.geometry;
+    let policy = options.error_policy;
+    let mut engine = IoEngine::open(path, IoOp::Write, &options.io.aligned_to(geometry))?;
+    if let Some(expected) = &options.expected {
+        check_opened(engine.file(), path, expected)?;
+    }
+    let device_size = engine.size()?;
+    if device_size == 0 {
+        return Err(EraseError::InvalidTarget(format!("{} has zero size", path.display())));
+    }
+
+    let patterns = standard.passes();
+    let recipe = recipe_hash(standard, device_size, geometry);
+    let mut state = match checkpoint.as_ref().and_then(|c| c.checkpoint().overwrite.clone()) {
+        Some(state) if state.recipe != recipe => {
+            return Err(EraseError::Checkpoint(format!(
+                "{} no longer has the size or block size the checkpoint was taken with",
+                path.display()
+            )));
+        }
+        Some(state) if state.seeds.len() < state.pass.min(patterns.len()) => {
+            return Err(EraseError::Checkpoint("checkpoint is missing the seeds of written passes".to_string()));
+        }
+        Some(state) => state,
+        None => OverwriteState {
+            recipe,
+            device_size,
+            pass: 0,
+            offset: 0,
+            seeds: Vec::new(),
+            bytes_written: 0,
+            errors: ErrorMap::new(geometry.logical_block_size as u64),
+        },
+    };
+    // Also replaces a stale checkpoint left on this drive by an earlier job
+    save_checkpoint(engine.file(), checkpoint.as_deref_mut(), &state)?;
+
+    for (index, pattern) in patterns.iter().enumerate().skip(state.pass) {
+        if cancel.load(Ordering::Relaxed) {
+            save_checkpoint(engine.file(), checkpoint.as_deref_mut(), &state)?;
+            return Err(EraseError::Cancelled);
+        }
+        if state.seeds.len() <= index {
+            state.seeds.push(Seed::random()?);
+        }
+        let generator = PatternGenerator::new(pattern.clone(), state.seeds[index]);
+
+        let remaining = [(state.offset, device_size - state.offset)];
+        let finished = engine.run(
+            IoOp::Write,
+            &remaining,
+            &mut |offset, buffer| generator.fill(offset, buffer),
+            &mut |completion| {
+                let length = completion.data.len() as u64;
+                let failed = match completion.result {
+                    Ok(()) => 0,
+                    Err(e) if policy == ErrorPolicy::Abort || !is_media_error(&e) => return Err(e.into()),
+                    Err(_) => write_retrying(completion.file, completion.data, completion.offset, policy, &mut state.errors)?,
+                };
+                state.offset = completion.offset + length;
+                state.bytes_written += length - failed;
+
+                progress(EraseProgress {
+                    phase: ErasePhase::Erasing,
+                    pass: index + 1,
+                    passes: patterns.len(),
+                    pattern: pattern.to_string(),
+                    bytes_done: state.offset,
+                    bytes_total: device_size,
+                    ..Default::default()
+                });
+                if checkpoint.as_ref().is_some_and(|c| c.due()) {
+                    save_checkpoint(completion.file, checkpoint.as_deref_mut(), &state)?;
+                }
+                Ok(!cancel.load(Ordering::Relaxed))
+            },
+        )?;
+        if !finished {
+            save_checkpoint(engine.file(), checkpoint.as_deref_mut(), &state)?;
+            return Err(EraseError::Cancelled);
+        }
+
+        engine.flush()?;
+        state.pass = index + 1;
+        state.offset = 0;
+        save_checkpoint(engine.file(), checkpoint.as_deref_mut(), &state)?;
+    }
+
+    Ok(OverwriteReport {
+        standard,
+        passes: patterns
+            .into_iter()
+            .zip(&state.seeds)
+            .map(|(pattern, &seed)| PassRecord { pattern, seed })
+            .collect(),
+        bytes_written: state.bytes_written,
+        device_size,
+        geometry: *geometry,
+        errors: state.errors,
+    })
+}
+
+// Syncs what was written so far, then saves the position
+fn save_checkpoint(file: &File, checkpoint: Option<&mut Checkpointer>, state: &OverwriteState) -> Result<(), EraseError> {
+    if let Some(checkpoint) = checkpoint {
+        file.sync_data()?;
+        checkpoint.save(state);
+    }
+    Ok(())
+}
+
+// Writes `data` at `offset`. A write failing on the medium is split in halves
+// until single sectors fail; those are added to `errors` and their byte count
+// returned.
+fn write_retrying(
+    file: &File,
+    data: &[u8],
+    offset: u64,
+    policy: ErrorPolicy,
+    errors: &mut ErrorMap,
+) -> Result<u64, EraseError> {
+    match file.write_all_at(data, offset) {
+        Ok(()) => Ok(0),
+        Err(e) if policy == ErrorPolicy::Abort || !is_media_error(&e) => Err(e.into()),
+        Err(_) => {
+            let sector = errors.sector_size as usize;
+            if data.len() <= sector {
+                errors.add(offset, data.len() as u64);
+                return Ok(data.len() as u64);
+            }
+            let half = (data.len() / sector / 2).max(1) * sector;
+            let first = write_retrying(file, &data[..half], offset, policy, errors)?;
+            let second = write_retrying(file, &data[half..], offset + half as u64, policy, errors)?;
+            Ok(first + second)
+        }
+    }
+}
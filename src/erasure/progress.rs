@@ -0,0 +1,137 @@
+//! Throughput and ETA bookkeeping for erase progress updates
+
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+use crate::{erasure::EraseProgress, libs::StorageDriver};
+
+// Samples older than this are dropped from the instantaneous rate
+const RATE_WINDOW: Duration = Duration::from_secs(3);
+
+/// Rates and timings derived from a stream of progress updates
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ProgressStats {
+    pub elapsed: Duration,
+    /// Bytes per second over the last few seconds
+    pub current_rate: f64,
+    /// Bytes per second since the erase started
+    pub average_rate: f64,
+    pub eta: Option<Duration>,
+}
+
+/// Keeps the latest progress of one erase together with recent samples.
+/// Create it when the job starts and feed it every update.
+#[derive(Debug, Clone)]
+pub struct ProgressTracker {
+    started: Instant,
+    finished: Option<Instant>,
+    firmware_started: Option<Instant>,
+    samples: VecDeque<(Instant, u64)>,
+    latest: Option<EraseProgress>,
+}
+
+impl Default for ProgressTracker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ProgressTracker {
+    pub fn new() -> Self {
+        ProgressTracker {
+            started: Instant::now(),
+            finished: None,
+            firmware_started: None,
+            samples: VecDeque::new(),
+            latest: None,
+        }
+    }
+
+    pub fn update(&mut self, progress: EraseProgress) {
+        let now = Instant::now();
+        if progress.firmware.is_some() && self.firmware_started.is_none() {
+            self.firmware_started = Some(now);
+        }
+        self.samples.push_back((now, progress.work_done));
+        while self.samples.len() > 2 && now - self.samples[0].0 > RATE_WINDOW {
+            self.samples.pop_front();
+        }
+        self.latest = Some(progress);
+    }
+
+    /// Stops the clock, e.g. when the job finished or failed
+    pub fn finish(&mut self) {
+        self.finished.get_or_insert_with(Instant::now);
+    }
+
+    pub fn latest(&self) -> Option<&EraseProgress> {
+        self.latest.as_ref()
+    }
+
+    pub fn stats(&self) -> ProgressStats {
+        let now = self.finished.unwrap_or_else(Instant::now);
+        let elapsed = now - self.started;
+        let Some(latest) = &self.latest else {
+            return ProgressStats { elapsed, ..Default::default() };
+        };
+
+        let average_rate = rate(latest.work_done, elapsed);
+        let current_rate = match (self.samples.front(), self.samples.back()) {
+            (Some((first_at, first)), Some((last_at, last))) if last_at > first_at => {
+                rate(last - first, *last_at - *first_at)
+            }
+            _ => average_rate,
+        };
+
+        let eta = if self.finished.is_some() {
+            None
+        } else if let Some(firmware) = &latest.firmware {
+            // Prefer the drive's own progress, otherwise count down its estimate
+            let running = now - self.firmware_started.unwrap_or(self.started);
+            match (firmware.reported, firmware.estimated) {
+                (Some(done), _) if done > 0.01 && done < 1.0 => {
+                    Some(running.mul_f32((1.0 - done) / done))
+                }
+                (_, Some(estimated)) => Some(estimated.saturating_sub(running)),
+                _ => None,
+            }
+        } else {
+            let remaining = latest.work_total.saturating_sub(latest.work_done);
+            let speed = if current_rate > 0.0 { current_rate } else { average_rate };
+            (speed > 0.0).then(|| Duration::from_secs_f64(remaining as f64 / speed))
+        };
+
+        ProgressStats {
+            elapsed,
+            current_rate,
+            average_rate,
+            eta,
+        }
+    }
+}
+
+fn rate(bytes: u64, over: Duration) -> f64 {
+    if over.is_zero() {
+        return 0.0;
+    }
+    bytes as f64 / over.as_secs_f64()
+}
+
+/// Formats a duration as e.g. `1h 02m 03s`
+pub fn format_duration(duration: Duration) -> String {
+    let seconds = duration.as_secs();
+    let (hours, minutes, seconds) = (seconds / 3600, seconds / 60 % 60, seconds % 60);
+    if hours > 0 {
+        format!("{}h {:02}m {:02}s", hours, minutes, seconds)
+    } else if minutes > 0 {
+        format!("{}m {:02}s", minutes, seconds)
+    } else {
+        format!("{}s", seconds)
+    }
+}
+
+pub fn format_rate(bytes_per_second: f64) -> String {
+    format!("{}/s", StorageDriver::format_size(bytes_per_second as u64))
+}
@@ -0,0 +1,243 @@
+//! Bandwidth limits and pause windows
+//! Drives erased in the spare bays of a live server share its controllers
+//! with production I/O. Jobs can be held to a transfer rate, a request rate
+//! and an I/O priority, and pause on their own during set hours. All of it can
+//! be changed while jobs run, through the [`Throttle`] they share.
+
+use std::{
+    str::FromStr,
+    sync::{Arc, Mutex},
+    thread,
+    time::{Duration, Instant},
+};
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+// Longest a paced request sleeps before looking at the limits again
+const RECHECK: Duration = Duration::from_millis(100);
+
+/// I/O scheduling class of erase and verify requests, as set by ionice
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum IoPriority {
+    /// Whatever the process has
+    #[default]
+    Normal,
+    /// Best effort at a level from 0 (highest) to 7 (lowest)
+    BestEffort(u8),
+    /// Only served when no other process uses the disk
+    Idle,
+}
+
+impl std::fmt::Display for IoPriority {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            IoPriority::Normal => write!(f, "Normal"),
+            IoPriority::BestEffort(level) => write!(f, "Best effort, level {}", level),
+            IoPriority::Idle => write!(f, "Idle"),
+        }
+    }
+}
+
+impl IoPriority {
+    pub fn get_all_priorities() -> Vec<IoPriority> {
+        vec![IoPriority::Normal, IoPriority::BestEffort(7), IoPriority::Idle]
+    }
+
+    /// Value for ioprio_set and io_uring requests
+    pub fn ioprio(&self) -> u16 {
+        const CLASS_SHIFT: u16 = 13;
+        match self {
+            IoPriority::Normal => 0,
+            IoPriority::BestEffort(level) => (2 << CLASS_SHIFT) | (*level).min(7) as u16,
+            IoPriority::Idle => 3 << CLASS_SHIFT,
+        }
+    }
+}
+
+/// Applies `ioprio` to the I/O the calling thread issues itself
+pub(crate) fn set_thread_ioprio(ioprio: u16) {
+    const WHO_PROCESS: libc::c_long = 1;
+    unsafe {
+        libc::syscall(libc::SYS_ioprio_set, WHO_PROCESS, 0 as libc::c_long, ioprio as libc::c_long);
+    }
+}
+
+/// Caps on one job's I/O; None is unlimited
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct IoLimits {
+    pub bytes_per_second: Option<u64>,
+    pub iops: Option<u32>,
+    pub priority: IoPriority,
+}
+
+/// Daily stretch of local time during which jobs pause, e.g. office hours.
+/// Minutes after midnight; a window ending before it starts runs past
+/// midnight.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PauseWindow {
+    pub start: u16,
+    pub end: u16,
+}
+
+impl PauseWindow {
+    pub fn contains(&self, minute: u16) -> bool {
+        if self.start <= self.end {
+            (self.start..self.end).contains(&minute)
+        } else {
+            minute >= self.start || minute < self.end
+        }
+    }
+}
+
+impl std::fmt::Display for PauseWindow {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{:02}:{:02}-{:02}:{:02}",
+            self.start / 60,
+            self.start % 60,
+            self.end / 60,
+            self.end % 60
+        )
+    }
+}
+
+impl FromStr for PauseWindow {
+    type Err = String;
+
+    /// Parses `HH:MM-HH:MM`
+    fn from_str(value: &str) -> Result<Self, String> {
+        let minute = |time: &str| -> Option<u16> {
+            let (hours, minutes) = time.trim().split_once(':')?;
+            let (hours, minutes): (u16, u16) = (hours.parse().ok()?, minutes.parse().ok()?);
+            (hours < 24 && minutes < 60).then_some(hours * 60 + minutes)
+        };
+        let invalid = || format!("invalid window {:?}, expected e.g. 08:00-18:00", value);
+        let (start, end) = value.split_once('-').ok_or_else(invalid)?;
+        let window = PauseWindow { start: minute(start).ok_or_else(invalid)?, end: minute(end).ok_or_else(invalid)? };
+        if window.start == window.end {
+            return Err(format!("window {} is empty", window));
+        }
+        Ok(window)
+    }
+}
+
+/// Parses a comma separated list of windows; an empty list is no windows
+pub fn parse_windows(list: &str) -> Result<Vec<PauseWindow>, String> {
+    list.split(',').map(str::trim).filter(|window| !window.is_empty()).map(str::parse).collect()
+}
+
+impl Serialize for PauseWindow {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+impl<'de> Deserialize<'de> for PauseWindow {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct ThrottleSettings {
+    #[serde(default)]
+    pub limits: IoLimits,
+    #[serde(default)]
+    pub pause_windows: Vec<PauseWindow>,
+}
+
+impl ThrottleSettings {
+    /// The window the local time `minute` falls in
+    pub fn window_at(&self, minute: u16) -> Option<PauseWindow> {
+        self.pause_windows.iter().copied().find(|window| window.contains(minute))
+    }
+}
+
+/// Settings shared by running jobs and whatever adjusts them. Clones refer to
+/// the same settings; changes apply from the next request.
+#[derive(Debug, Clone, Default)]
+pub struct Throttle {
+    settings: Arc<Mutex<ThrottleSettings>>,
+}
+
+impl Throttle {
+    pub fn new(settings: ThrottleSettings) -> Self {
+        Throttle { settings: Arc::new(Mutex::new(settings)) }
+    }
+
+    pub fn settings(&self) -> ThrottleSettings {
+        self.settings.lock().map(|settings| settings.clone()).unwrap_or_default()
+    }
+
+    pub fn set(&self, settings: ThrottleSettings) {
+        if let Ok(mut current) = self.settings.lock() {
+            *current = settings;
+        }
+    }
+
+    pub fn limits(&self) -> IoLimits {
+        self.settings.lock().map(|settings| settings.limits).unwrap_or_default()
+    }
+
+    /// The pause window in force now, if any
+    pub fn pause_window(&self) -> Option<PauseWindow> {
+        self.settings.lock().ok()?.window_at(local_minute())
+    }
+}
+
+fn local_minute() -> u16 {
+    unsafe {
+        let now = libc::time(std::ptr::null_mut());
+        let mut local: libc::tm = std::mem::zeroed();
+        libc::localtime_r(&now, &mut local);
+        (local.tm_hour * 60 + local.tm_min) as u16
+    }
+}
+
+/// Spaces one job's requests to stay within the limits in force as each is
+/// issued. Bursts are capped at a single request.
+pub(crate) struct Pacer {
+    bytes: f64,
+    requests: f64,
+    refilled: Instant,
+}
+
+impl Pacer {
+    pub fn new() -> Self {
+        Pacer { bytes: 0.0, requests: 0.0, refilled: Instant::now() }
+    }
+
+    /// Blocks until a request of `len` bytes fits within the limits
+    pub fn wait(&mut self, throttle: &Throttle, len: usize) {
+        let len = len as f64;
+        loop {
+            let limits = throttle.limits();
+            let elapsed = self.refilled.elapsed().as_secs_f64();
+            self.refilled = Instant::now();
+
+            let mut wait: f64 = 0.0;
+            match limits.bytes_per_second.filter(|&rate| rate > 0) {
+                Some(rate) => {
+                    self.bytes = (self.bytes + elapsed * rate as f64).min(len);
+                    wait = wait.max((len - self.bytes) / rate as f64);
+                }
+                None => self.bytes = len,
+            }
+            match limits.iops.filter(|&rate| rate > 0) {
+                Some(rate) => {
+                    self.requests = (self.requests + elapsed * rate as f64).min(1.0);
+                    wait = wait.max((1.0 - self.requests) / rate as f64);
+                }
+                None => self.requests = 1.0,
+            }
+
+            if wait <= 0.0 {
+                self.bytes -= len;
+                self.requests -= 1.0;
+                return;
+            }
+            thread::sleep(Duration::from_secs_f64(wait).min(RECHECK));
+        }
+    }
+}
@@ -0,0 +1,535 @@
+//! Drive health from the drive's own records
+//! Reads ATA SMART attributes, the NVMe SMART / Health log and SCSI log pages,
+//! and flags drives whose remapped sectors an overwrite cannot reach.
+
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    cert::HealthSummary,
+    erasure::{ata::AtaError, nvme::NvmeError},
+};
+
+#[derive(Debug)]
+pub enum HealthError {
+    IoError(std::io::Error),
+    Unsupported(String),
+    CommandFailed(String),
+}
+
+impl std::fmt::Display for HealthError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            HealthError::IoError(e) => write!(f, "I/O error: {}", e),
+            HealthError::Unsupported(e) => write!(f, "not supported: {}", e),
+            HealthError::CommandFailed(e) => write!(f, "health command failed: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for HealthError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            HealthError::IoError(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for HealthError {
+    fn from(e: std::io::Error) -> Self {
+        HealthError::IoError(e)
+    }
+}
+
+impl From<AtaError> for HealthError {
+    fn from(e: AtaError) -> Self {
+        match e {
+            AtaError::IoError(e) => HealthError::IoError(e),
+            other => HealthError::CommandFailed(other.to_string()),
+        }
+    }
+}
+
+impl From<NvmeError> for HealthError {
+    fn from(e: NvmeError) -> Self {
+        match e {
+            NvmeError::IoError(e) => HealthError::IoError(e),
+            NvmeError::Unsupported(e) => HealthError::Unsupported(e),
+            other => HealthError::CommandFailed(other.to_string()),
+        }
+    }
+}
+
+/// Where a report was read from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum HealthSource {
+    AtaSmart,
+    NvmeSmartLog,
+    ScsiLogPages,
+}
+
+impl std::fmt::Display for HealthSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            HealthSource::AtaSmart => write!(f, "ATA SMART"),
+            HealthSource::NvmeSmartLog => write!(f, "NVMe SMART/Health log"),
+            HealthSource::ScsiLogPages => write!(f, "SCSI log pages"),
+        }
+    }
+}
+
+/// One ATA SMART attribute with its threshold
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SmartAttribute {
+    pub id: u8,
+    pub name: String,
+    /// Normalized value, higher is better
+    pub current: u8,
+    pub worst: u8,
+    /// The attribute fails when `current` drops to this; 0 means never
+    pub threshold: u8,
+    pub raw: u64,
+}
+
+impl SmartAttribute {
+    pub fn failing(&self) -> bool {
+        self.threshold != 0 && self.current <= self.threshold
+    }
+}
+
+/// What a drive reports about its own condition. Values a drive does not
+/// report are `None`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HealthReport {
+    pub source: HealthSource,
+    /// The drive's overall self-assessment; false when it predicts failure
+    pub passed: Option<bool>,
+    /// Sectors remapped to spares (ATA), or grown defects (SCSI)
+    pub reallocated_sectors: Option<u64>,
+    /// Sectors waiting to be remapped on their next write
+    pub pending_sectors: Option<u64>,
+    /// Unrecovered read or write errors
+    pub media_errors: Option<u64>,
+    pub power_on_hours: Option<u64>,
+    /// Share of rated endurance used, may exceed 100
+    pub percent_used: Option<u8>,
+    /// Remaining spare capacity in percent (NVMe)
+    pub available_spare: Option<u8>,
+    pub temperature_celsius: Option<i16>,
+    /// ATA only
+    pub attributes: Vec<SmartAttribute>,
+}
+
+/// Reasons to look twice at a drive before trusting an erase of it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HealthWarning {
+    FailurePredicted,
+    Reallocated(u64),
+    SpareUsed(u8),
+    Pending(u64),
+    MediaErrors(u64),
+    WornOut(u8),
+    AttributeFailing { id: u8, name: String },
+}
+
+impl std::fmt::Display for HealthWarning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            HealthWarning::FailurePredicted => write!(f, "the drive predicts its own failure"),
+            HealthWarning::Reallocated(count) => write!(
+                f,
+                "{} reallocated sectors; an overwrite cannot reach the data they held",
+                count
+            ),
+            HealthWarning::SpareUsed(spare) => write!(
+                f,
+                "{}% spare capacity left, blocks were retired; an overwrite cannot reach them",
+                spare
+            ),
+            HealthWarning::Pending(count) => write!(f, "{} sectors pending reallocation", count),
+            HealthWarning::MediaErrors(count) => write!(f, "{} unrecovered media errors", count),
+            HealthWarning::WornOut(percent) => write!(f, "{}% of rated endurance used", percent),
+            HealthWarning::AttributeFailing { id, name } => {
+                write!(f, "SMART attribute {} ({}) is below its threshold", id, name)
+            }
+        }
+    }
+}
+
+impl HealthReport {
+    fn new(source: HealthSource) -> Self {
+        HealthReport {
+            source,
+            passed: None,
+            reallocated_sectors: None,
+            pending_sectors: None,
+            media_errors: None,
+            power_on_hours: None,
+            percent_used: None,
+            available_spare: None,
+            temperature_celsius: None,
+            attributes: Vec::new(),
+        }
+    }
+
+    /// Whether data may survive in sectors the drive retired, out of reach of
+    /// an overwrite. Only a firmware erase covers those.
+    pub fn has_remapped_sectors(&self) -> bool {
+        self.reallocated_sectors.is_some_and(|count| count > 0)
+            || self.available_spare.is_some_and(|spare| spare < 100)
+    }
+
+    pub fn summary(&self) -> HealthSummary {
+        HealthSummary {
+            source: self.source.to_string(),
+            passed: self.passed,
+            reallocated_sectors: self.reallocated_sectors,
+            pending_sectors: self.pending_sectors,
+            media_errors: self.media_errors,
+            power_on_hours: self.power_on_hours,
+            percent_used: self.percent_used,
+            temperature_celsius: self.temperature_celsius,
+            warnings: self.warnings().iter().map(|w| w.to_string()).collect(),
+        }
+    }
+
+    pub fn warnings(&self) -> Vec<HealthWarning> {
+        let mut warnings = Vec::new();
+        if self.passed == Some(false) {
+            warnings.push(HealthWarning::FailurePredicted);
+        }
+        if let Some(count) = self.reallocated_sectors.filter(|&count| count > 0) {
+            warnings.push(HealthWarning::Reallocated(count));
+        }
+        if let Some(spare) = self.available_spare.filter(|&spare| spare < 100) {
+            warnings.push(HealthWarning::SpareUsed(spare));
+        }
+        if let Some(count) = self.pending_sectors.filter(|&count| count > 0) {
+            warnings.push(HealthWarning::Pending(count));
+        }
+        if let Some(count) = self.media_errors.filter(|&count| count > 0) {
+            warnings.push(HealthWarning::MediaErrors(count));
+        }
+        if let Some(percent) = self.percent_used.filter(|&percent| percent >= 100) {
+            warnings.push(HealthWarning::WornOut(percent));
+        }
+        warnings.extend(
+            self.attributes
+                .iter()
+                .filter(|attribute| attribute.failing())
+                .map(|attribute| HealthWarning::AttributeFailing { id: attribute.id, name: attribute.name.clone() }),
+        );
+        warnings
+    }
+}
+
+fn attribute_name(id: u8) -> &'static str {
+    match id {
+        1 => "Raw Read Error Rate",
+        3 => "Spin Up Time",
+        4 => "Start Stop Count",
+        5 => "Reallocated Sector Count",
+        7 => "Seek Error Rate",
+        9 => "Power On Hours",
+        10 => "Spin Retry Count",
+        12 => "Power Cycle Count",
+        177 => "Wear Leveling Count",
+        183 => "Runtime Bad Block",
+        184 => "End-to-End Error",
+        187 => "Reported Uncorrectable",
+        188 => "Command Timeout",
+        190 => "Airflow Temperature",
+        194 => "Temperature",
+        196 => "Reallocation Event Count",
+        197 => "Current Pending Sector",
+        198 => "Offline Uncorrectable",
+        199 => "UDMA CRC Error Count",
+        231 => "SSD Life Left",
+        233 => "Media Wearout Indicator",
+        241 => "Total LBAs Written",
+        242 => "Total LBAs Read",
+        _ => "Unknown",
+    }
+}
+
+/// Decodes SMART READ DATA and SMART READ THRESHOLDS. `status` is the result
+/// of SMART RETURN STATUS where the drive answered it.
+pub fn parse_ata_smart(data: &[u8; 512], thresholds: &[u8; 512], status: Option<bool>) -> HealthReport {
+    let mut report = HealthReport::new(HealthSource::AtaSmart);
+    report.passed = status;
+
+    // 30 entries of 12 bytes after the two-byte revision, in both tables
+    for entry in data[2..362].chunks_exact(12).filter(|entry| entry[0] != 0) {
+        let id = entry[0];
+        let threshold = thresholds[2..362]
+            .chunks_exact(12)
+            .find(|t| t[0] == id)
+            .map_or(0, |t| t[1]);
+        let raw = entry[5..11].iter().rev().fold(0u64, |raw, &b| (raw << 8) | b as u64);
+        report.attributes.push(SmartAttribute {
+            id,
+            name: attribute_name(id).to_string(),
+            current: entry[3],
+            worst: entry[4],
+            threshold,
+            raw,
+        });
+    }
+
+    for attribute in &report.attributes {
+        match attribute.id {
+            5 => report.reallocated_sectors = Some(attribute.raw),
+            197 => report.pending_sectors = Some(attribute.raw),
+            198 => report.media_errors = Some(attribute.raw),
+            // Some drives keep minutes or milliseconds in the upper bytes
+            9 => report.power_on_hours = Some(attribute.raw & 0xffff_ffff),
+            194 => report.temperature_celsius = Some((attribute.raw & 0xff) as i16),
+            190 if report.temperature_celsius.is_none() => {
+                report.temperature_celsius = Some((attribute.raw & 0xff) as i16)
+            }
+            // Normalized value counts down from 100 as the flash wears
+            177 | 231 | 233 if attribute.current <= 100 => {
+                report.percent_used = Some(100 - attribute.current)
+            }
+            _ => {}
+        }
+    }
+    report
+}
+
+/// Decodes the NVMe SMART / Health Information log page (02h)
+pub fn parse_nvme_smart_log(data: &[u8]) -> Option<HealthReport> {
+    if data.len() < 512 {
+        return None;
+    }
+    // 128-bit counters; the upper half is zero for any real drive
+    let counter = |offset: usize| u64::from_le_bytes(data[offset..offset + 8].try_into().unwrap());
+    let kelvin = u16::from_le_bytes([data[1], data[2]]);
+
+    let mut report = HealthReport::new(HealthSource::NvmeSmartLog);
+    // Spare below threshold, reliability degraded or media read-only
+    report.passed = Some(data[0] & 0x0d == 0);
+    report.available_spare = Some(data[3]);
+    report.percent_used = Some(data[5]);
+    report.power_on_hours = Some(counter(128));
+    report.media_errors = Some(counter(160));
+    report.temperature_celsius = (kelvin != 0).then(|| kelvin as i16 - 273);
+    Some(report)
+}
+
+/// One parameter of a SCSI log page
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LogParameter {
+    pub code: u16,
+    pub value: Vec<u8>,
+}
+
+impl LogParameter {
+    fn number(&self) -> u64 {
+        self.value.iter().take(8).fold(0u64, |n, &b| (n << 8) | b as u64)
+    }
+}
+
+/// Splits a LOG SENSE response into its page code and parameters
+pub fn parse_log_page(page: &[u8]) -> Option<(u8, Vec<LogParameter>)> {
+    if page.len() < 4 {
+        return None;
+    }
+    let end = (4 + u16::from_be_bytes([page[2], page[3]]) as usize).min(page.len());
+    let mut parameters = Vec::new();
+    let mut offset = 4;
+    while offset + 4 <= end {
+        let length = page[offset + 3] as usize;
+        let value_end = (offset + 4 + length).min(end);
+        parameters.push(LogParameter {
+            code: u16::from_be_bytes([page[offset], page[offset + 1]]),
+            value: page[offset + 4..value_end].to_vec(),
+        });
+        offset += 4 + length;
+    }
+    Some((page[0] & 0x3f, parameters))
+}
+
+const LOG_READ_ERRORS: u8 = 0x03;
+const LOG_TEMPERATURE: u8 = 0x0d;
+const LOG_SOLID_STATE_MEDIA: u8 = 0x11;
+const LOG_BACKGROUND_SCAN: u8 = 0x15;
+const LOG_INFORMATIONAL_EXCEPTIONS: u8 = 0x2f;
+
+/// Builds a report from LOG SENSE responses and the length of the grown
+/// defect list. Pages the drive did not return are simply left out.
+pub fn parse_scsi_logs(pages: &[Vec<u8>], grown_defects: Option<u64>) -> HealthReport {
+    let mut report = HealthReport::new(HealthSource::ScsiLogPages);
+    report.reallocated_sectors = grown_defects;
+
+    for (code, parameters) in pages.iter().filter_map(|page| parse_log_page(page)) {
+        let parameter = |wanted: u16| parameters.iter().find(|p| p.code == wanted);
+        match code {
+            LOG_READ_ERRORS => {
+                // Total uncorrected errors
+                report.media_errors = parameter(0x0006).map(LogParameter::number);
+            }
+            LOG_TEMPERATURE => {
+                report.temperature_celsius = parameter(0x0000)
+                    .and_then(|p| p.value.get(1).copied())
+                    .filter(|&t| t != 0xff)
+                    .map(|t| t as i16);
+            }
+            LOG_SOLID_STATE_MEDIA => {
+                report.percent_used = parameter(0x0001).and_then(|p| p.value.get(3).copied());
+            }
+            LOG_BACKGROUND_SCAN => {
+                report.power_on_hours = parameter(0x0000)
+                    .filter(|p| p.value.len() >= 4)
+                    .map(|p| u32::from_be_bytes(p.value[..4].try_into().unwrap()) as u64 / 60);
+            }
+            LOG_INFORMATIONAL_EXCEPTIONS => {
+                // A non-zero additional sense code is a failure prediction
+                report.passed = parameter(0x0000).and_then(|p| p.value.first()).map(|&asc| asc == 0);
+            }
+            _ => {}
+        }
+    }
+    report
+}
+
+/// Reads the health of the drive at `path` without writing to it. NVMe drives
+/// are asked for their SMART log, other drives for ATA SMART through SCSI
+/// translation, and drives that do not pass ATA through for SCSI log pages.
+pub fn read_health(path: &Path) -> Result<HealthReport, HealthError> {
+    if !path.to_string_lossy().starts_with("/dev/") {
+        return Err(HealthError::Unsupported(format!("{} is not a drive", path.display())));
+    }
+
+    #[cfg(target_os = "linux")]
+    {
+        linux::read_health(path)
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    {
+        Err(HealthError::Unsupported("health reporting is only available on Linux".to_string()))
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod linux {
+    use std::{
+        fs::{File, OpenOptions},
+        os::{fd::AsRawFd, unix::fs::OpenOptionsExt},
+        path::Path,
+        time::Duration,
+    };
+
+    use super::*;
+    use crate::{
+        erasure::{
+            ata::{AtaData, AtaTaskfile},
+            nvme::NvmeController,
+        },
+        platform::linux::{ata_pass_through, scsi_read_command},
+    };
+
+    const ATA_SMART: u8 = 0xb0;
+    const SMART_READ_DATA: u16 = 0xd0;
+    const SMART_READ_THRESHOLDS: u16 = 0xd1;
+    const SMART_RETURN_STATUS: u16 = 0xda;
+    // LBA mid/high signature every SMART command carries, and the value
+    // RETURN STATUS puts there when a threshold is exceeded
+    const SMART_SIGNATURE: u64 = 0xc2_4f00;
+    const SMART_THRESHOLD_EXCEEDED: u64 = 0x2c_f400;
+    const NVME_LOG_SMART: u32 = 0x02;
+    const LOG_SENSE: u8 = 0x4d;
+    const READ_DEFECT_DATA_10: u8 = 0x37;
+    const TIMEOUT: Duration = Duration::from_secs(10);
+
+    pub(super) fn read_health(path: &Path) -> Result<HealthReport, HealthError> {
+        if path.file_name().is_some_and(|name| name.to_string_lossy().starts_with("nvme")) {
+            let controller = NvmeController::open_read_only(path)?;
+            let mut data = vec![0u8; 512];
+            controller.get_log_page(NVME_LOG_SMART, &mut data)?;
+            return parse_nvme_smart_log(&data)
+                .ok_or_else(|| HealthError::CommandFailed("short SMART log".to_string()));
+        }
+
+        // Read-only so that closing it does not make udev announce a change
+        let file = OpenOptions::new()
+            .read(true)
+            .custom_flags(libc::O_NONBLOCK | libc::O_CLOEXEC)
+            .open(path)?;
+        match ata_smart(&file) {
+            Ok(report) => Ok(report),
+            Err(ata_error) => scsi_logs(&file).map_err(|_| ata_error),
+        }
+    }
+
+    fn ata_smart(file: &File) -> Result<HealthReport, HealthError> {
+        let smart = |feature: u16, buf: &mut [u8; 512]| {
+            let taskfile = AtaTaskfile {
+                command: ATA_SMART,
+                feature,
+                count: 1,
+                lba: SMART_SIGNATURE,
+                ..Default::default()
+            };
+            ata_pass_through(file.as_raw_fd(), &taskfile, AtaData::In(buf), TIMEOUT)
+        };
+        let mut data = [0u8; 512];
+        let mut thresholds = [0u8; 512];
+        smart(SMART_READ_DATA, &mut data)?;
+        smart(SMART_READ_THRESHOLDS, &mut thresholds)?;
+
+        let taskfile = AtaTaskfile {
+            command: ATA_SMART,
+            feature: SMART_RETURN_STATUS,
+            lba: SMART_SIGNATURE,
+            ..Default::default()
+        };
+        let status = ata_pass_through(file.as_raw_fd(), &taskfile, AtaData::None, TIMEOUT)
+            .ok()
+            .and_then(|registers| match registers.lba & 0xff_ff00 {
+                SMART_SIGNATURE => Some(true),
+                SMART_THRESHOLD_EXCEEDED => Some(false),
+                _ => None,
+            });
+        Ok(parse_ata_smart(&data, &thresholds, status))
+    }
+
+    fn scsi_logs(file: &File) -> Result<HealthReport, HealthError> {
+        let pages: Vec<Vec<u8>> = [
+            super::LOG_READ_ERRORS,
+            super::LOG_TEMPERATURE,
+            super::LOG_SOLID_STATE_MEDIA,
+            super::LOG_BACKGROUND_SCAN,
+            super::LOG_INFORMATIONAL_EXCEPTIONS,
+        ]
+        .into_iter()
+        .filter_map(|page| log_sense(file, page).ok())
+        .collect();
+        if pages.is_empty() {
+            return Err(HealthError::Unsupported("the drive returned no log pages".to_string()));
+        }
+        Ok(parse_scsi_logs(&pages, grown_defects(file)))
+    }
+
+    fn log_sense(file: &File, page: u8) -> Result<Vec<u8>, HealthError> {
+        let mut data = vec![0u8; 1024];
+        let length = (data.len() as u16).to_be_bytes();
+        // Page control 01b: cumulative values
+        let cdb = [LOG_SENSE, 0, 0x40 | page, 0, 0, 0, 0, length[0], length[1], 0];
+        let read = scsi_read_command(file.as_raw_fd(), &cdb, &mut data, TIMEOUT)?;
+        data.truncate(read);
+        Ok(data)
+    }
+
+    // Length of the grown defect list in physical sector format, eight bytes
+    // per defect
+    fn grown_defects(file: &File) -> Option<u64> {
+        let mut header = [0u8; 4];
+        let cdb = [READ_DEFECT_DATA_10, 0, 0x08 | 0x05, 0, 0, 0, 0, 0, header.len() as u8, 0];
+        scsi_read_command(file.as_raw_fd(), &cdb, &mut header, TIMEOUT).ok()?;
+        Some(u16::from_be_bytes([header[2], header[3]]) as u64 / 8)
+    }
+}
@@ -0,0 +1,108 @@
+//! Secure erasure of storage devices.
+//!
+//! The library behind the `secure-erase` GUI and command line tool. It can be
+//! used on its own to detect devices, erase and verify them, and issue signed
+//! certificates and audit records for the result.
+//!
+//! - [`platform`] and [`libs`]: device detection and identification
+//! - [`analysis`]: read-only inspection of device contents such as partition tables
+//! - [`erasure`]: overwrite and firmware erase methods, and parallel batches
+//! - [`safety`]: in-use and system disk detection that erases are refused for
+//! - [`health`]: SMART and log page health reports, before and after an erase
+//! - [`verify`]: read-back verification of erased devices
+//! - [`cert`]: signed erasure certificates, PDF rendering and the certificate store
+//! - [`audit`]: the tamper-evident audit log
+//!
+//! Each module has its own error type; [`Error`] wraps all of them for
+//! callers that do not need to tell them apart.
+
+pub mod analysis;
+pub mod audit;
+pub mod cert;
+pub mod erasure;
+pub mod health;
+pub mod libs;
+pub mod platform;
+pub mod safety;
+pub mod verify;
+
+use crate::{
+    analysis::AnalysisError,
+    audit::AuditError,
+    cert::CertError,
+    erasure::{EraseError, ata::AtaError},
+    health::HealthError,
+};
+
+/// Any error returned by this crate
+#[derive(Debug)]
+pub enum Error {
+    Erase(EraseError),
+    Ata(AtaError),
+    Cert(CertError),
+    Audit(AuditError),
+    Analysis(AnalysisError),
+    Health(HealthError),
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Erase(e) => write!(f, "erase error: {}", e),
+            Error::Ata(e) => write!(f, "ATA error: {}", e),
+            Error::Cert(e) => write!(f, "certificate error: {}", e),
+            Error::Audit(e) => write!(f, "audit log error: {}", e),
+            Error::Analysis(e) => write!(f, "analysis error: {}", e),
+            Error::Health(e) => write!(f, "health error: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Erase(e) => Some(e),
+            Error::Ata(e) => Some(e),
+            Error::Cert(e) => Some(e),
+            Error::Audit(e) => Some(e),
+            Error::Analysis(e) => Some(e),
+            Error::Health(e) => Some(e),
+        }
+    }
+}
+
+impl From<EraseError> for Error {
+    fn from(e: EraseError) -> Self {
+        Error::Erase(e)
+    }
+}
+
+impl From<AtaError> for Error {
+    fn from(e: AtaError) -> Self {
+        Error::Ata(e)
+    }
+}
+
+impl From<CertError> for Error {
+    fn from(e: CertError) -> Self {
+        Error::Cert(e)
+    }
+}
+
+impl From<AuditError> for Error {
+    fn from(e: AuditError) -> Self {
+        Error::Audit(e)
+    }
+}
+
+impl From<AnalysisError> for Error {
+    fn from(e: AnalysisError) -> Self {
+        Error::Analysis(e)
+    }
+}
+
+impl From<HealthError> for Error {
+    fn from(e: HealthError) -> Self {
+        Error::Health(e)
+    }
+}
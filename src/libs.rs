@@ -1,6 +1,8 @@
-// Storage Driver - Cross-platform storage device interface
-use crate::platform::{StorageDevice, StorageInterface};
+//! Storage Driver - Cross-platform storage device interface
+use crate::platform::{BlockGeometry, DeviceIdentity, StorageDevice, StorageInterface};
+use crate::safety::DeviceUsage;
 
+/// Device detection for the platform the crate was built for
 pub struct StorageDriver;
 
 impl StorageDriver {
@@ -27,4 +29,106 @@ impl StorageDriver {
             format!("{} bytes", bytes)
         }
     }
-}
\ No newline at end of file
+
+    pub fn controller_id(device_path: &str) -> Option<String> {
+        #[cfg(target_os = "linux")]
+        {
+            crate::platform::linux::LinuxStorage::controller_id(device_path)
+        }
+
+        #[cfg(not(target_os = "linux"))]
+        {
+            let _ = device_path;
+            None
+        }
+    }
+
+    /// Reads the identity of `device_path` fresh from the system. Image
+    /// files are identified by their size only.
+    pub fn device_identity(device_path: &str) -> Option<DeviceIdentity> {
+        let metadata = std::fs::metadata(device_path).ok()?;
+        if metadata.is_file() {
+            return Some(DeviceIdentity { size: Some(metadata.len()), ..Default::default() });
+        }
+
+        #[cfg(target_os = "linux")]
+        {
+            crate::platform::linux::LinuxStorage::device_identity(device_path)
+        }
+
+        #[cfg(not(target_os = "linux"))]
+        {
+            None
+        }
+    }
+
+    /// Reads the identity of the device `file` was opened on. Unlike
+    /// [`StorageDriver::device_identity`] this cannot be redirected by another
+    /// drive appearing at the same path after the open.
+    pub fn opened_identity(file: &std::fs::File) -> Option<DeviceIdentity> {
+        let metadata = file.metadata().ok()?;
+        if metadata.is_file() {
+            return Some(DeviceIdentity { size: Some(metadata.len()), ..Default::default() });
+        }
+
+        #[cfg(target_os = "linux")]
+        {
+            crate::platform::linux::LinuxStorage::opened_identity(file)
+        }
+
+        #[cfg(not(target_os = "linux"))]
+        {
+            None
+        }
+    }
+
+    /// Block sizes and limits of `device_path`. Image files have none; callers
+    /// use [`BlockGeometry::default`] for them.
+    pub fn block_geometry(device_path: &str) -> Option<BlockGeometry> {
+        if std::fs::metadata(device_path).is_ok_and(|m| m.is_file()) {
+            return None;
+        }
+
+        #[cfg(target_os = "linux")]
+        {
+            crate::platform::linux::LinuxStorage::block_geometry(device_path)
+        }
+
+        #[cfg(not(target_os = "linux"))]
+        {
+            let _ = device_path;
+            None
+        }
+    }
+
+    /// Checks what still uses the disk at `device_path`. Image files are
+    /// never in use.
+    pub fn device_usage(device_path: &str) -> DeviceUsage {
+        if std::fs::metadata(device_path).is_ok_and(|m| m.is_file()) {
+            return DeviceUsage::default();
+        }
+
+        #[cfg(target_os = "linux")]
+        {
+            crate::platform::linux::LinuxStorage::device_usage(device_path)
+        }
+
+        #[cfg(not(target_os = "linux"))]
+        {
+            DeviceUsage::default()
+        }
+    }
+}
+
+/// Directory for keys, logs and other persistent application state
+pub fn data_dir() -> std::path::PathBuf {
+    use std::path::PathBuf;
+
+    if let Some(dir) = std::env::var_os("XDG_DATA_HOME") {
+        return PathBuf::from(dir).join("secure-erase");
+    }
+    if let Some(home) = std::env::var_os("HOME") {
+        return PathBuf::from(home).join(".local/share/secure-erase");
+    }
+    PathBuf::from("/var/lib/secure-erase")
+}
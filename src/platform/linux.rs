@@ -1,5 +1,21 @@
-use std::{fs, path::Path};
-use crate::{erasure::ata::{AtaDeviceInfo, AtaError, AtaStorageDriver}, platform::{DeviceStatus, DeviceType, StorageDevice, StorageInterface}};
+use std::{
+    collections::HashMap,
+    fs::{self, File, OpenOptions},
+    os::{fd::{AsRawFd, FromRawFd, OwnedFd, RawFd}, unix::fs::{MetadataExt, OpenOptionsExt}},
+    path::Path,
+    time::{Duration, Instant},
+};
+use crate::{
+    erasure::{
+        ata::{self, AtaData, AtaDeviceInfo, AtaError, AtaStatus, AtaStorageDriver, AtaTaskfile},
+        nvme::{NvmeCommand, NvmeError},
+    },
+    platform::{
+        BlockGeometry, DeviceAction, DeviceEvent, DeviceIdentity, DeviceStatus, DeviceType, StorageDevice,
+        StorageInterface, Transport,
+    },
+    safety::{DeviceUsage, UsageReason},
+};
 
 pub struct LinuxStorage;
 
@@ -11,10 +27,7 @@ impl StorageInterface for LinuxStorage {
         if let Ok(entries) = fs::read_dir("/sys/block") {
             for entry in entries.flatten() {
                 if let Some(device_name) = entry.file_name().to_str() {
-                    // Skip loop devices, ram disks, and other virtual devices
-                    if device_name.starts_with("loop") 
-                        || device_name.starts_with("ram")
-                        || device_name.starts_with("dm-") {
+                    if is_virtual(device_name) {
                         continue;
                     }
                     
@@ -52,33 +65,717 @@ impl StorageInterface for LinuxStorage {
         }
     }
 
+    // PCI address of the controller (HBA, AHCI or NVMe function) the disk sits
+    // behind, taken from the device's sysfs path
+    fn controller_id(device_path: &str) -> Option<String> {
+        let device_name = Path::new(device_path).file_name()?.to_str()?;
+        let sys_path = fs::canonicalize(format!("/sys/block/{}", device_name)).ok()?;
+        sys_path
+            .components()
+            .filter_map(|c| c.as_os_str().to_str())
+            .rfind(|c| is_pci_address(c))
+            .map(|c| c.to_string())
+    }
+
+    fn device_identity(device_path: &str) -> Option<DeviceIdentity> {
+        let device_name = Path::new(device_path).file_name()?.to_str()?;
+        if !Path::new("/sys/block").join(device_name).exists() {
+            return None;
+        }
+        Some(DeviceIdentity {
+            serial: get_device_serial(device_name),
+            wwn: get_device_wwn(device_name).or_else(|| identify_sata(device_name)?.wwn),
+            size: get_device_size(device_name),
+        })
+    }
+
+    // Looks the device up by the number the descriptor refers to, so a drive
+    // that took over the path after the open is not the one described
+    fn opened_identity(file: &File) -> Option<DeviceIdentity> {
+        let rdev = file.metadata().ok()?.rdev();
+        let sys_path = fs::canonicalize(format!("/sys/dev/block/{}", device_number(rdev))).ok()?;
+        let device_name = sys_path.file_name()?.to_str()?;
+        let mut bytes: u64 = 0;
+        let result = unsafe { libc::ioctl(file.as_raw_fd(), BLKGETSIZE64, &mut bytes) };
+        let wwn = match get_device_wwn(device_name) {
+            Some(wwn) => Some(wwn),
+            None if get_transport(device_name) == Transport::Sata => ata_identify(file.as_raw_fd()).ok()?.wwn,
+            None => None,
+        };
+        Some(DeviceIdentity {
+            serial: get_device_serial(device_name),
+            wwn,
+            size: (result == 0).then_some(bytes),
+        })
+    }
+
+    fn block_geometry(device_path: &str) -> Option<BlockGeometry> {
+        let name = block_name(device_path)?;
+        let device = Path::new("/sys/class/block").join(&name);
+        // Partitions have no queue of their own and share the disk's limits
+        let queue = match device.join("queue") {
+            queue if queue.exists() => queue,
+            _ => fs::canonicalize(&device).ok()?.parent()?.join("queue"),
+        };
+        let read = |path: &Path| -> Option<u32> { fs::read_to_string(path).ok()?.trim().parse().ok() };
+        let logical_block_size = read(&queue.join("logical_block_size"))?;
+        Some(BlockGeometry {
+            logical_block_size,
+            physical_block_size: read(&queue.join("physical_block_size")).unwrap_or(logical_block_size),
+            optimal_io_size: read(&queue.join("optimal_io_size")).unwrap_or(0),
+            alignment_offset: read(&device.join("alignment_offset")).unwrap_or(0),
+            max_transfer: read(&queue.join("max_sectors_kb")).map_or(0, |kb| kb.saturating_mul(1024)),
+            discard_granularity: read(&queue.join("discard_granularity")).unwrap_or(0),
+        })
+    }
+
+    // Walks the partitions and holder tree of the disk and checks each device
+    // found against mounts, swap, md arrays and exclusive openers
+    fn device_usage(device_path: &str) -> DeviceUsage {
+        let mut usage = DeviceUsage::default();
+        let Some(disk) = block_name(device_path) else {
+            return usage;
+        };
+        let mut reasons = Vec::new();
+
+        let mut members = vec![disk.clone()];
+        members.extend(partitions(&disk));
+        let mut tree = members.clone();
+        let mut queue = members.clone();
+        while let Some(name) = queue.pop() {
+            for holder in sysfs_links(&name, "holders") {
+                let device = display_name(&name);
+                reasons.push(if holder.starts_with("md") {
+                    UsageReason::RaidMember { device, array: display_name(&holder) }
+                } else if dm_uuid(&holder).is_some_and(|uuid| uuid.starts_with("CRYPT-LUKS")) {
+                    UsageReason::LuksMapping { device, name: display_name(&holder) }
+                } else {
+                    UsageReason::Holder { device, holder: display_name(&holder) }
+                });
+                if !tree.contains(&holder) {
+                    tree.push(holder.clone());
+                    queue.push(holder);
+                }
+            }
+        }
+
+        let devices = device_numbers();
+        for mount in mountinfo() {
+            let name = devices.get(&mount.device).cloned().or_else(|| block_name(&mount.source));
+            if let Some(name) = name.filter(|name| tree.contains(name)) {
+                reasons.push(UsageReason::Mounted { device: display_name(&name), mountpoint: mount.mountpoint });
+            }
+        }
+
+        let swaps = fs::read_to_string("/proc/swaps").unwrap_or_default();
+        for line in swaps.lines().skip(1) {
+            if let Some(name) = line.split_whitespace().next().and_then(block_name)
+                && tree.contains(&name)
+            {
+                reasons.push(UsageReason::Swap { device: display_name(&name) });
+            }
+        }
+
+        // Arrays assembled from members whose holder links are missing, e.g.
+        // when md sits on a partition that was re-read
+        let mdstat = fs::read_to_string("/proc/mdstat").unwrap_or_default();
+        for line in mdstat.lines() {
+            let mut fields = line.split_whitespace();
+            let (Some(array), Some(":"), Some("active")) = (fields.next(), fields.next(), fields.next()) else {
+                continue;
+            };
+            for field in fields {
+                let member = field.split('[').next().unwrap_or(field);
+                if members.iter().any(|m| m == member) {
+                    reasons.push(UsageReason::RaidMember {
+                        device: display_name(member),
+                        array: display_name(array),
+                    });
+                }
+            }
+        }
+
+        // Anything the checks above missed, e.g. another erase tool or a
+        // filesystem mounted in a different mount namespace
+        if reasons.is_empty() {
+            for member in &members {
+                let open = OpenOptions::new()
+                    .read(true)
+                    .custom_flags(libc::O_EXCL)
+                    .open(format!("/dev/{}", member));
+                if open.is_err_and(|e| e.raw_os_error() == Some(libc::EBUSY)) {
+                    reasons.push(UsageReason::ExclusivelyOpen { device: display_name(member) });
+                }
+            }
+        }
+
+        for (system_disk, mountpoint) in system_disks(&devices) {
+            if system_disk == disk {
+                reasons.push(UsageReason::SystemDisk { mountpoint });
+            }
+        }
+
+        for reason in reasons {
+            if !usage.reasons.contains(&reason) {
+                usage.reasons.push(reason);
+            }
+        }
+        usage
+    }
 
     fn identify_ata_device(ata_storage_driver: &AtaStorageDriver) -> Result<AtaDeviceInfo, AtaError> {
-        println!("LinuxStorage::identify_ata_device called");
-        
-        todo!()
+        ata_identify(ata_storage_driver.fd())
+    }
+}
+
+fn ata_identify(fd: RawFd) -> Result<AtaDeviceInfo, AtaError> {
+    let mut data = [0u8; 512];
+    let taskfile = AtaTaskfile {
+        command: ata::ATA_IDENTIFY_DEVICE,
+        count: 1,
+        ..Default::default()
+    };
+    ata_pass_through(fd, &taskfile, AtaData::In(&mut data), Duration::from_secs(10))?;
+    Ok(AtaDeviceInfo::from_identify(&data))
+}
+
+const SG_IO: libc::c_ulong = 0x2285;
+const SG_DXFER_NONE: libc::c_int = -1;
+const SG_DXFER_TO_DEV: libc::c_int = -2;
+const SG_DXFER_FROM_DEV: libc::c_int = -3;
+const ATA_PASS_THROUGH_16: u8 = 0x85;
+const SCSI_CHECK_CONDITION: u8 = 0x02;
+
+// struct sg_io_hdr from <scsi/sg.h>
+#[repr(C)]
+struct SgIoHdr {
+    interface_id: libc::c_int,
+    dxfer_direction: libc::c_int,
+    cmd_len: u8,
+    mx_sb_len: u8,
+    iovec_count: u16,
+    dxfer_len: u32,
+    dxferp: *mut libc::c_void,
+    cmdp: *const u8,
+    sbp: *mut u8,
+    timeout: u32,
+    flags: u32,
+    pack_id: libc::c_int,
+    usr_ptr: *mut libc::c_void,
+    status: u8,
+    masked_status: u8,
+    msg_status: u8,
+    sb_len_wr: u8,
+    host_status: u16,
+    driver_status: u16,
+    resid: libc::c_int,
+    duration: u32,
+    info: u32,
+}
+
+/// Sends an ATA command through SCSI ATA PASS-THROUGH(16). The returned
+/// registers are requested with CK_COND so status commands can read them.
+pub fn ata_pass_through(
+    fd: RawFd,
+    taskfile: &AtaTaskfile,
+    data: AtaData,
+    timeout: Duration,
+) -> Result<AtaStatus, AtaError> {
+    // Protocol and transfer flags: PIO data-in/out transfer `count` sectors.
+    // CK_COND is only set for non-data commands, some bridges mishandle it
+    // together with a data phase.
+    let (protocol, flags, direction, buffer, length) = match data {
+        AtaData::None => (3u8, 0x20u8, SG_DXFER_NONE, std::ptr::null_mut(), 0),
+        AtaData::In(buf) => (4, 0x0e, SG_DXFER_FROM_DEV, buf.as_mut_ptr().cast(), buf.len()),
+        AtaData::Out(buf) => (5, 0x06, SG_DXFER_TO_DEV, buf.as_ptr() as *mut libc::c_void, buf.len()),
+    };
+    let lba = taskfile.lba.to_le_bytes();
+    let cdb: [u8; 16] = [
+        ATA_PASS_THROUGH_16,
+        (protocol << 1) | taskfile.extended as u8,
+        flags,
+        (taskfile.feature >> 8) as u8,
+        taskfile.feature as u8,
+        (taskfile.count >> 8) as u8,
+        taskfile.count as u8,
+        lba[3],
+        lba[0],
+        lba[4],
+        lba[1],
+        lba[5],
+        lba[2],
+        taskfile.device,
+        taskfile.command,
+        0,
+    ];
+    let mut sense = [0u8; 32];
+    let mut header = SgIoHdr {
+        interface_id: b'S' as libc::c_int,
+        dxfer_direction: direction,
+        cmd_len: cdb.len() as u8,
+        mx_sb_len: sense.len() as u8,
+        iovec_count: 0,
+        dxfer_len: length as u32,
+        dxferp: buffer,
+        cmdp: cdb.as_ptr(),
+        sbp: sense.as_mut_ptr(),
+        timeout: timeout.as_millis().min(u32::MAX as u128) as u32,
+        flags: 0,
+        pack_id: 0,
+        usr_ptr: std::ptr::null_mut(),
+        status: 0,
+        masked_status: 0,
+        msg_status: 0,
+        sb_len_wr: 0,
+        host_status: 0,
+        driver_status: 0,
+        resid: 0,
+        duration: 0,
+        info: 0,
+    };
+
+    // SAFETY: the header points at buffers that outlive the call
+    if unsafe { libc::ioctl(fd, SG_IO, &mut header) } < 0 {
+        return Err(std::io::Error::last_os_error().into());
+    }
+    if header.host_status != 0 {
+        return Err(AtaError::CommandFailed(format!("host status 0x{:02x}", header.host_status)));
+    }
+
+    let sense = &sense[..header.sb_len_wr as usize];
+    let returned = parse_ata_sense(sense);
+    match returned {
+        Some(status) if status.status & 0x21 != 0 => Err(AtaError::CommandFailed(format!(
+            "command 0x{:02x}: status 0x{:02x} error 0x{:02x}",
+            taskfile.command, status.status, status.error
+        ))),
+        Some(status) => Ok(status),
+        None if header.status == 0 => Ok(AtaStatus::default()),
+        None if header.status == SCSI_CHECK_CONDITION && sense.len() > 2 => {
+            // Without an ATA status return the command was rejected by the
+            // SCSI layer, usually because the bridge does not pass it through
+            let key = if sense[0] & 0x7f >= 0x72 { sense[1] } else { sense[2] } & 0x0f;
+            Err(AtaError::CommandFailed(format!("sense key 0x{:x}", key)))
+        }
+        None => Err(AtaError::CommandFailed(format!("SCSI status 0x{:02x}", header.status))),
+    }
+}
+
+/// Sends a SCSI command that reads into `data` and returns the number of
+/// bytes the device transferred
+pub fn scsi_read_command(fd: RawFd, cdb: &[u8], data: &mut [u8], timeout: Duration) -> std::io::Result<usize> {
+    let mut sense = [0u8; 32];
+    let mut header = SgIoHdr {
+        interface_id: b'S' as libc::c_int,
+        dxfer_direction: SG_DXFER_FROM_DEV,
+        cmd_len: cdb.len() as u8,
+        mx_sb_len: sense.len() as u8,
+        iovec_count: 0,
+        dxfer_len: data.len() as u32,
+        dxferp: data.as_mut_ptr().cast(),
+        cmdp: cdb.as_ptr(),
+        sbp: sense.as_mut_ptr(),
+        timeout: timeout.as_millis().min(u32::MAX as u128) as u32,
+        flags: 0,
+        pack_id: 0,
+        usr_ptr: std::ptr::null_mut(),
+        status: 0,
+        masked_status: 0,
+        msg_status: 0,
+        sb_len_wr: 0,
+        host_status: 0,
+        driver_status: 0,
+        resid: 0,
+        duration: 0,
+        info: 0,
+    };
+
+    // SAFETY: the header points at buffers that outlive the call
+    if unsafe { libc::ioctl(fd, SG_IO, &mut header) } < 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    if header.host_status != 0 || header.status != 0 {
+        return Err(std::io::Error::other(format!(
+            "SCSI command 0x{:02x} failed: status 0x{:02x} host status 0x{:02x}",
+            cdb[0], header.status, header.host_status
+        )));
+    }
+    Ok(data.len().saturating_sub(header.resid.max(0) as usize))
+}
+
+// Reads the ATA return registers from descriptor or fixed format sense data
+fn parse_ata_sense(sense: &[u8]) -> Option<AtaStatus> {
+    match sense.first()? & 0x7f {
+        0x72 | 0x73 => {
+            let mut descriptors = sense.get(8..)?;
+            while descriptors.len() >= 2 {
+                let length = descriptors[1] as usize + 2;
+                if descriptors[0] == 0x09 && descriptors.len() >= 14 {
+                    let d = descriptors;
+                    return Some(AtaStatus {
+                        error: d[3],
+                        count: u16::from_be_bytes([d[4], d[5]]),
+                        lba: u64::from_le_bytes([d[7], d[9], d[11], d[6], d[8], d[10], 0, 0]),
+                        status: d[13],
+                    });
+                }
+                descriptors = descriptors.get(length..)?;
+            }
+            None
+        }
+        0x70 | 0x71 if sense.len() >= 12 && sense[2] & 0x0f == 0x01 => Some(AtaStatus {
+            error: sense[3],
+            status: sense[4],
+            count: sense[6] as u16,
+            lba: u64::from_le_bytes([sense[9], sense[10], sense[11], 0, 0, 0, 0, 0]),
+        }),
+        _ => None,
+    }
+}
+
+const NVME_IOCTL_ADMIN_CMD: libc::c_ulong = 0xC048_4E41;
+
+// struct nvme_passthru_cmd from <linux/nvme_ioctl.h>
+#[repr(C)]
+#[derive(Default)]
+struct NvmePassthruCmd {
+    opcode: u8,
+    flags: u8,
+    rsvd1: u16,
+    nsid: u32,
+    cdw2: u32,
+    cdw3: u32,
+    metadata: u64,
+    addr: u64,
+    metadata_len: u32,
+    data_len: u32,
+    cdw10: u32,
+    cdw11: u32,
+    cdw12: u32,
+    cdw13: u32,
+    cdw14: u32,
+    cdw15: u32,
+    timeout_ms: u32,
+    result: u32,
+}
+
+/// Issues an NVMe admin command and returns completion dword 0
+pub fn nvme_admin_command(
+    fd: RawFd,
+    command: &NvmeCommand,
+    data: Option<&mut [u8]>,
+) -> Result<u32, NvmeError> {
+    let (addr, data_len) = match data {
+        Some(buf) => (buf.as_mut_ptr() as u64, buf.len() as u32),
+        None => (0, 0),
+    };
+    let mut cmd = NvmePassthruCmd {
+        opcode: command.opcode,
+        nsid: command.nsid,
+        addr,
+        data_len,
+        cdw10: command.cdw10,
+        cdw11: command.cdw11,
+        timeout_ms: 10_000,
+        ..Default::default()
+    };
+
+    // SAFETY: `addr` points at a buffer of `data_len` bytes for the duration of the call
+    let result = unsafe { libc::ioctl(fd, NVME_IOCTL_ADMIN_CMD, &mut cmd) };
+    match result {
+        0 => Ok(cmd.result),
+        r if r < 0 => Err(std::io::Error::last_os_error().into()),
+        status => Err(NvmeError::CommandFailed(status as u16)),
+    }
+}
+
+/// Opens a device read-only with O_DIRECT, so reads bypass the page cache.
+/// Buffers, offsets and lengths must then be aligned to the logical block size.
+pub fn open_direct(path: &Path) -> std::io::Result<File> {
+    OpenOptions::new().read(true).custom_flags(libc::O_DIRECT).open(path)
+}
+
+/// Kernel uevents for block devices, read from a NETLINK_KOBJECT_UEVENT socket
+pub struct UeventMonitor {
+    socket: OwnedFd,
+}
+
+impl UeventMonitor {
+    pub fn open() -> std::io::Result<Self> {
+        // SAFETY: creates a new descriptor, owned from here on
+        let fd = unsafe {
+            libc::socket(libc::AF_NETLINK, libc::SOCK_DGRAM | libc::SOCK_CLOEXEC, libc::NETLINK_KOBJECT_UEVENT)
+        };
+        if fd < 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        // SAFETY: `fd` was just created and nothing else owns it
+        let socket = unsafe { OwnedFd::from_raw_fd(fd) };
+
+        // Group 1 carries the kernel's own events; udev rebroadcasts on group 2
+        // SAFETY: sockaddr_nl is plain data and valid when zeroed
+        let mut address: libc::sockaddr_nl = unsafe { std::mem::zeroed() };
+        address.nl_family = libc::AF_NETLINK as libc::sa_family_t;
+        address.nl_groups = 1;
+        // SAFETY: `address` outlives the call and its size is passed along
+        let result = unsafe {
+            libc::bind(
+                fd,
+                &address as *const libc::sockaddr_nl as *const libc::sockaddr,
+                std::mem::size_of::<libc::sockaddr_nl>() as libc::socklen_t,
+            )
+        };
+        if result < 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        Ok(UeventMonitor { socket })
+    }
+
+    /// Waits for the next event about a physical block device, or until
+    /// `timeout` passes (None when it does). ENOBUFS means events were lost.
+    pub fn next_event(&self, timeout: Option<Duration>) -> std::io::Result<Option<DeviceEvent>> {
+        let deadline = timeout.map(|timeout| Instant::now() + timeout);
+        let mut buffer = [0u8; 8192];
+        loop {
+            let wait = match deadline {
+                Some(deadline) => {
+                    let left = deadline.saturating_duration_since(Instant::now());
+                    left.as_millis().min(i32::MAX as u128) as i32
+                }
+                None => -1,
+            };
+            let mut poll = libc::pollfd { fd: self.socket.as_raw_fd(), events: libc::POLLIN, revents: 0 };
+            // SAFETY: one valid pollfd
+            match unsafe { libc::poll(&mut poll, 1, wait) } {
+                0 => return Ok(None),
+                r if r < 0 => {
+                    let e = std::io::Error::last_os_error();
+                    if e.kind() == std::io::ErrorKind::Interrupted {
+                        continue;
+                    }
+                    return Err(e);
+                }
+                _ => {}
+            }
+
+            // SAFETY: sockaddr_nl is plain data and valid when zeroed
+            let mut sender: libc::sockaddr_nl = unsafe { std::mem::zeroed() };
+            let mut sender_len = std::mem::size_of::<libc::sockaddr_nl>() as libc::socklen_t;
+            // SAFETY: the buffer and address outlive the call, sizes match them
+            let len = unsafe {
+                libc::recvfrom(
+                    self.socket.as_raw_fd(),
+                    buffer.as_mut_ptr() as *mut libc::c_void,
+                    buffer.len(),
+                    0,
+                    &mut sender as *mut libc::sockaddr_nl as *mut libc::sockaddr,
+                    &mut sender_len,
+                )
+            };
+            if len < 0 {
+                let e = std::io::Error::last_os_error();
+                if e.kind() == std::io::ErrorKind::Interrupted {
+                    continue;
+                }
+                return Err(e);
+            }
+            // Only the kernel (port 0) is trusted; anything else could be spoofed
+            if sender.nl_pid != 0 {
+                continue;
+            }
+            if let Some(event) = parse_uevent(&buffer[..len as usize])
+                && !is_virtual(event.path.trim_start_matches("/dev/"))
+            {
+                return Ok(Some(event));
+            }
+        }
+    }
+}
+
+/// Parses a kernel uevent: an "action@devpath" header followed by
+/// NUL-separated KEY=VALUE pairs. None for anything but block devices
+/// being added, removed or changed.
+pub fn parse_uevent(message: &[u8]) -> Option<DeviceEvent> {
+    let fields = message.split(|&b| b == 0).skip(1).filter_map(|field| {
+        let field = std::str::from_utf8(field).ok()?;
+        field.split_once('=')
+    });
+    let (mut action, mut subsystem, mut name, mut device_type) = (None, None, None, None);
+    for (key, value) in fields {
+        match key {
+            "ACTION" => action = Some(value),
+            "SUBSYSTEM" => subsystem = Some(value),
+            "DEVNAME" => name = Some(value),
+            "DEVTYPE" => device_type = Some(value),
+            _ => {}
+        }
+    }
+    if subsystem != Some("block") {
+        return None;
+    }
+    let action = match action? {
+        "add" => DeviceAction::Added,
+        "remove" => DeviceAction::Removed,
+        "change" => DeviceAction::Changed,
+        _ => return None,
+    };
+    // DEVNAME is relative to /dev, e.g. "sdb" or "nvme0n1p1"
+    let name = name?.trim_start_matches("/dev/");
+    Some(DeviceEvent {
+        action,
+        path: format!("/dev/{}", name),
+        partition: device_type == Some("partition"),
+    })
+}
+
+// Loop devices, ram disks and device-mapper targets are not physical disks
+fn is_virtual(name: &str) -> bool {
+    name.starts_with("loop") || name.starts_with("ram") || name.starts_with("dm-")
+}
+
+// Matches domain:bus:device.function, e.g. 0000:00:17.0
+fn is_pci_address(component: &str) -> bool {
+    let bytes = component.as_bytes();
+    bytes.len() == 12
+        && bytes[4] == b':'
+        && bytes[7] == b':'
+        && bytes[10] == b'.'
+        && component
+            .chars()
+            .enumerate()
+            .all(|(i, c)| matches!(i, 4 | 7 | 10) || c.is_ascii_hexdigit())
+}
+
+/// Bus a disk is attached over, judged from its resolved sysfs path such as
+/// `/sys/devices/pci0000:00/0000:00:17.0/ata3/host2/target2:0:0/2:0:0:0/block/sda`
+pub fn transport_from_sys_path(sys_path: &str) -> Transport {
+    let components: Vec<&str> = sys_path.split('/').collect();
+    let has = |prefix: &str, rest: fn(&str) -> bool| {
+        components.iter().any(|c| c.strip_prefix(prefix).is_some_and(rest))
+    };
+    let digits = |rest: &str| !rest.is_empty() && rest.bytes().all(|b| b.is_ascii_digit());
+    let any = |_: &str| true;
+
+    if has("nvme", any) {
+        Transport::NVMe
+    } else if has("usb", digits) {
+        Transport::Usb
+    } else if has("mmc", digits) || has("mmcblk", any) {
+        Transport::Mmc
+    } else if has("virtio", digits) {
+        Transport::Virtio
+    } else if has("ata", digits) {
+        Transport::Sata
+    } else if has("end_device-", any) || has("expander-", any) {
+        Transport::Sas
+    } else if has("target", any) {
+        Transport::Scsi
+    } else {
+        Transport::Unknown
     }
 }
 
 fn create_storage_device(device_path: &str, device_name: &str) -> StorageDevice {
-    let model = get_device_model(device_name);
-    let name = model.clone().unwrap_or_else(|| device_name.to_string());
+    let mut model = get_device_model(device_name);
     let size = get_device_size(device_name);
+    let geometry = LinuxStorage::block_geometry(device_path);
     let device_type = determine_device_type(device_name, device_path);
     let serial_number = get_device_serial(device_name);
     let vendor = get_device_vendor(device_name);
-    let status = get_device_status(device_name);
+    let mut firmware = get_device_firmware(device_name);
+    let mut wwn = get_device_wwn(device_name);
+    let transport = get_transport(device_name);
+
+    // SCSI translation truncates model and firmware revision; IDENTIFY has
+    // all of them when we may send it
+    if let Some(info) = identify_sata(device_name) {
+        model = Some(info.model).filter(|m| !m.is_empty()).or(model);
+        firmware = Some(info.firmware).filter(|f| !f.is_empty()).or(firmware);
+        wwn = wwn.or(info.wwn);
+    }
+
+    let name = model.clone().unwrap_or_else(|| device_name.to_string());
+    let usage = LinuxStorage::device_usage(device_path);
+    let status = get_device_status(device_name, &usage);
     
     StorageDevice {
         path: device_path.to_string(),
         name,
         size,
+        geometry,
         device_type,
         serial_number,
         model,
         vendor,
+        firmware,
+        wwn,
+        transport,
+        host_controller: get_host_controller(device_path),
+        by_id: disk_links("/dev/disk/by-id", device_name),
+        by_path: disk_links("/dev/disk/by-path", device_name).into_iter().next(),
         status,
+        usage,
+    }
+}
+
+fn get_transport(device_name: &str) -> Transport {
+    fs::canonicalize(format!("/sys/block/{}", device_name))
+        .map_or(Transport::Unknown, |path| transport_from_sys_path(&path.to_string_lossy()))
+}
+
+// A read-only descriptor is enough for SG_IO with CAP_SYS_RAWIO, and unlike a
+// writable one closing it does not make udev announce a change
+fn identify_sata(device_name: &str) -> Option<AtaDeviceInfo> {
+    if get_transport(device_name) != Transport::Sata {
+        return None;
     }
+    let device_path = format!("/dev/{}", device_name);
+    let file = OpenOptions::new()
+        .read(true)
+        .custom_flags(libc::O_NONBLOCK | libc::O_CLOEXEC)
+        .open(&device_path)
+        .ok()?;
+    ata_identify(file.as_raw_fd()).ok()
+}
+
+fn get_device_firmware(device_name: &str) -> Option<String> {
+    // NVMe controllers, SCSI disks and MMC cards respectively
+    let firmware_paths = [
+        format!("/sys/block/{}/device/firmware_rev", device_name),
+        format!("/sys/block/{}/device/rev", device_name),
+        format!("/sys/block/{}/device/fwrev", device_name),
+    ];
+
+    firmware_paths
+        .iter()
+        .filter_map(|path| fs::read_to_string(path).ok())
+        .map(|content| content.trim().to_string())
+        .find(|firmware| !firmware.is_empty())
+}
+
+fn get_host_controller(device_path: &str) -> Option<String> {
+    let address = LinuxStorage::controller_id(device_path)?;
+    let driver = fs::read_link(format!("/sys/bus/pci/devices/{}/driver", address)).ok();
+    match driver.as_deref().and_then(Path::file_name) {
+        Some(driver) => Some(format!("{} {}", driver.to_string_lossy(), address)),
+        None => Some(address),
+    }
+}
+
+// Names in a /dev/disk/by-* directory that resolve to the disk itself, not to
+// one of its partitions
+fn disk_links(dir: &str, device_name: &str) -> Vec<String> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let target = Path::new("/dev").join(device_name);
+    let mut names: Vec<String> = entries
+        .flatten()
+        .filter(|entry| fs::canonicalize(entry.path()).is_ok_and(|path| path == target))
+        .filter_map(|entry| entry.file_name().to_str().map(str::to_string))
+        .collect();
+    names.sort();
+    names
 }
 
 fn get_device_model(device_name: &str) -> Option<String> {
@@ -102,16 +799,29 @@ fn get_device_model(device_name: &str) -> Option<String> {
 }
 
 fn get_device_size(device_name: &str) -> Option<u64> {
-    let size_path = format!("/sys/block/{}/size", device_name);
-    
-    if let Ok(content) = fs::read_to_string(&size_path) {
-        if let Ok(sectors) = content.trim().parse::<u64>() {
-            // Convert sectors to bytes (assuming 512 bytes per sector)
-            return Some(sectors * 512);
-        }
+    if let Some(bytes) = ioctl_size(&format!("/dev/{}", device_name)) {
+        return Some(bytes);
     }
-    
-    None
+
+    // sysfs counts in 512-byte units whatever the logical block size is
+    let size_path = format!("/sys/block/{}/size", device_name);
+    let sectors = fs::read_to_string(&size_path).ok()?.trim().parse::<u64>().ok()?;
+    Some(sectors * 512)
+}
+
+const BLKGETSIZE64: libc::c_ulong = 0x8008_1272;
+
+// Capacity in bytes straight from the block layer. Opening needs read access
+// to the node, so callers fall back to sysfs without it.
+fn ioctl_size(device_path: &str) -> Option<u64> {
+    let file = OpenOptions::new()
+        .read(true)
+        .custom_flags(libc::O_NONBLOCK | libc::O_CLOEXEC)
+        .open(device_path)
+        .ok()?;
+    let mut bytes: u64 = 0;
+    let result = unsafe { libc::ioctl(file.as_raw_fd(), BLKGETSIZE64, &mut bytes) };
+    (result == 0).then_some(bytes)
 }
 
 fn determine_device_type(device_name: &str, _device_path: &str) -> DeviceType {
@@ -132,10 +842,10 @@ fn determine_device_type(device_name: &str, _device_path: &str) -> DeviceType {
     
     // Check if it's a USB device
     let device_path_sys = format!("/sys/block/{}/device", device_name);
-    if let Ok(target) = fs::read_link(&device_path_sys) {
-        if target.to_string_lossy().contains("usb") {
-            return DeviceType::USB;
-        }
+    if let Ok(target) = fs::read_link(&device_path_sys)
+        && target.to_string_lossy().contains("usb")
+    {
+        return DeviceType::USB;
     }
     
     DeviceType::Unknown
@@ -160,6 +870,20 @@ fn get_device_serial(device_name: &str) -> Option<String> {
     None
 }
 
+fn get_device_wwn(device_name: &str) -> Option<String> {
+    // SCSI and ATA disks expose it below device/, NVMe namespaces directly
+    let wwn_paths = [
+        format!("/sys/block/{}/device/wwid", device_name),
+        format!("/sys/block/{}/wwid", device_name),
+    ];
+
+    wwn_paths
+        .iter()
+        .filter_map(|path| fs::read_to_string(path).ok())
+        .map(|content| content.trim().to_string())
+        .find(|wwn| !wwn.is_empty())
+}
+
 fn get_device_vendor(device_name: &str) -> Option<String> {
     // Try to read vendor information from sysfs
     let vendor_paths = [
@@ -179,12 +903,12 @@ fn get_device_vendor(device_name: &str) -> Option<String> {
     None
 }
 
-fn get_device_status(device_name: &str) -> DeviceStatus {
-    // Check if device is mounted
-    if let Ok(mounts) = fs::read_to_string("/proc/mounts") {
-        if mounts.contains(&format!("/dev/{}", device_name)) {
-            return DeviceStatus::Mounted;
-        }
+fn get_device_status(device_name: &str, usage: &DeviceUsage) -> DeviceStatus {
+    if usage.is_mounted() {
+        return DeviceStatus::Mounted;
+    }
+    if usage.is_in_use() {
+        return DeviceStatus::InUse;
     }
     
     // Check if device is available/active
@@ -197,4 +921,166 @@ fn get_device_status(device_name: &str) -> DeviceStatus {
     }
     
     DeviceStatus::Available
-}
\ No newline at end of file
+}
+
+struct MountEntry {
+    // major:minor of the mounted device
+    device: String,
+    mountpoint: String,
+    source: String,
+}
+
+fn mountinfo() -> Vec<MountEntry> {
+    let content = fs::read_to_string("/proc/self/mountinfo").unwrap_or_default();
+    content
+        .lines()
+        .filter_map(|line| {
+            let (left, right) = line.split_once(" - ")?;
+            let left: Vec<&str> = left.split_whitespace().collect();
+            let source = right.split_whitespace().nth(1)?;
+            Some(MountEntry {
+                device: left.get(2)?.to_string(),
+                mountpoint: unescape_mount_path(left.get(4)?),
+                source: source.to_string(),
+            })
+        })
+        .collect()
+}
+
+// Mount paths escape space, tab, newline and backslash as \ooo
+fn unescape_mount_path(path: &str) -> String {
+    let bytes = path.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'\\'
+            && let Some(code) = path.get(i + 1..i + 4).and_then(|o| u8::from_str_radix(o, 8).ok())
+        {
+            out.push(code);
+            i += 4;
+            continue;
+        }
+        out.push(bytes[i]);
+        i += 1;
+    }
+    String::from_utf8_lossy(&out).to_string()
+}
+
+// Kernel name (sda, sda1, dm-0) of a /dev path, following symlinks such as
+// /dev/mapper/* and /dev/disk/by-id/*
+fn block_name(path: &str) -> Option<String> {
+    if !path.starts_with('/') {
+        return None;
+    }
+    let resolved = fs::canonicalize(path).ok()?;
+    let name = resolved.file_name()?.to_str()?.to_string();
+    Path::new("/sys/class/block").join(&name).exists().then_some(name)
+}
+
+// dm-N devices are shown by their mapper name
+fn display_name(name: &str) -> String {
+    match fs::read_to_string(format!("/sys/class/block/{}/dm/name", name)) {
+        Ok(mapper) => format!("/dev/mapper/{}", mapper.trim()),
+        Err(_) => format!("/dev/{}", name),
+    }
+}
+
+fn dm_uuid(name: &str) -> Option<String> {
+    fs::read_to_string(format!("/sys/class/block/{}/dm/uuid", name))
+        .ok()
+        .map(|uuid| uuid.trim().to_string())
+}
+
+fn partitions(disk: &str) -> Vec<String> {
+    let Ok(entries) = fs::read_dir(Path::new("/sys/block").join(disk)) else {
+        return Vec::new();
+    };
+    entries
+        .flatten()
+        .filter(|entry| entry.path().join("partition").exists())
+        .filter_map(|entry| entry.file_name().to_str().map(str::to_string))
+        .collect()
+}
+
+// Entries of /sys/class/block/<name>/holders or slaves
+fn sysfs_links(name: &str, kind: &str) -> Vec<String> {
+    let Ok(entries) = fs::read_dir(format!("/sys/class/block/{}/{}", name, kind)) else {
+        return Vec::new();
+    };
+    entries.flatten().filter_map(|entry| entry.file_name().to_str().map(str::to_string)).collect()
+}
+
+// major:minor to kernel name for every block device
+fn device_numbers() -> HashMap<String, String> {
+    let Ok(entries) = fs::read_dir("/sys/class/block") else {
+        return HashMap::new();
+    };
+    entries
+        .flatten()
+        .filter_map(|entry| {
+            let name = entry.file_name().to_str()?.to_string();
+            let number = fs::read_to_string(entry.path().join("dev")).ok()?;
+            Some((number.trim().to_string(), name))
+        })
+        .collect()
+}
+
+// Disks carrying the root and boot filesystems and the one this program was
+// started from, with the mountpoint that makes them a system disk
+fn system_disks(devices: &HashMap<String, String>) -> Vec<(String, String)> {
+    let mounts = mountinfo();
+    let mut disks = Vec::new();
+    for mountpoint in ["/", "/boot", "/boot/efi"] {
+        // The last entry is the one on top of the mount stack
+        let Some(mount) = mounts.iter().rfind(|m| m.mountpoint == mountpoint) else {
+            continue;
+        };
+        let name = devices.get(&mount.device).cloned().or_else(|| block_name(&mount.source));
+        for disk in name.map(|name| physical_disks(&name, devices, 0)).unwrap_or_default() {
+            disks.push((disk, mountpoint.to_string()));
+        }
+    }
+
+    if let Ok(metadata) = fs::metadata("/proc/self/exe")
+        && let Some(name) = devices.get(&device_number(metadata.dev()))
+    {
+        for disk in physical_disks(name, devices, 0) {
+            disks.push((disk, "the filesystem this program runs from".to_string()));
+        }
+    }
+    disks
+}
+
+fn device_number(dev: u64) -> String {
+    format!("{}:{}", libc::major(dev), libc::minor(dev))
+}
+
+// Follows partitions, device-mapper/md slaves and loop backing files down
+// to the whole disks a block device is built on
+fn physical_disks(name: &str, devices: &HashMap<String, String>, depth: usize) -> Vec<String> {
+    if depth > 8 {
+        return Vec::new();
+    }
+    let sys_path = Path::new("/sys/class/block").join(name);
+    if sys_path.join("partition").exists() {
+        return fs::canonicalize(&sys_path)
+            .ok()
+            .and_then(|path| path.parent()?.file_name()?.to_str().map(str::to_string))
+            .into_iter()
+            .collect();
+    }
+    if name.starts_with("loop") {
+        let backing = fs::read_to_string(sys_path.join("loop/backing_file")).unwrap_or_default();
+        return fs::metadata(backing.trim())
+            .ok()
+            .and_then(|metadata| devices.get(&device_number(metadata.dev())))
+            .map(|name| physical_disks(name, devices, depth + 1))
+            .unwrap_or_default();
+    }
+    let slaves = sysfs_links(name, "slaves");
+    if slaves.is_empty() {
+        return vec![name.to_string()];
+    }
+    slaves.iter().flat_map(|slave| physical_disks(slave, devices, depth + 1)).collect()
+}
+
@@ -1,29 +1,227 @@
+use serde::{Deserialize, Serialize};
+
 use crate::erasure::ata::{AtaDeviceInfo, AtaError, AtaStorageDriver};
+use crate::safety::DeviceUsage;
 
 #[cfg(target_os = "linux")]
 pub mod linux;
 
+/// A whole-disk block device found during detection
 #[derive(Debug)]
 pub struct StorageDevice {
     pub path: String,
     pub name: String,
+    /// Capacity in bytes as the kernel reports it
     pub size: Option<u64>,
+    pub geometry: Option<BlockGeometry>,
     pub device_type: DeviceType,
     pub serial_number: Option<String>,
     pub model: Option<String>,
     pub vendor: Option<String>,
+    pub firmware: Option<String>,
+    /// World wide name; EUI-64 or NGUID for NVMe namespaces
+    pub wwn: Option<String>,
+    pub transport: Transport,
+    /// Driver and PCI address of the host controller
+    pub host_controller: Option<String>,
+    /// Names below `/dev/disk/by-id`, which survive reboots and re-enumeration
+    pub by_id: Vec<String>,
+    /// Name below `/dev/disk/by-path`, tied to the port the drive sits in
+    pub by_path: Option<String>,
     pub status: DeviceStatus,
+    /// Everything that still uses the disk, see [`crate::safety`]
+    pub usage: DeviceUsage,
+}
+
+impl StorageDevice {
+    /// The most descriptive `/dev/disk/by-id` name: one built from model and
+    /// serial where the kernel made one, the WWN name otherwise
+    pub fn stable_id(&self) -> Option<&str> {
+        self.by_id
+            .iter()
+            .find(|name| !name.starts_with("wwn-") && !name.starts_with("nvme-eui.") && !name.starts_with("nvme-nvme."))
+            .or_else(|| self.by_id.first())
+            .map(String::as_str)
+    }
+}
+
+/// Bus the drive is attached over
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum Transport {
+    Sata,
+    Sas,
+    Scsi,
+    NVMe,
+    Usb,
+    Mmc,
+    Virtio,
+    #[default]
+    Unknown,
+}
+
+impl std::fmt::Display for Transport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Transport::Sata => write!(f, "SATA"),
+            Transport::Sas => write!(f, "SAS"),
+            Transport::Scsi => write!(f, "SCSI"),
+            Transport::NVMe => write!(f, "NVMe"),
+            Transport::Usb => write!(f, "USB"),
+            Transport::Mmc => write!(f, "MMC"),
+            Transport::Virtio => write!(f, "virtio"),
+            Transport::Unknown => write!(f, "Unknown"),
+        }
+    }
+}
+
+/// Block sizes and limits of a device as the kernel reports them, in bytes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BlockGeometry {
+    /// Smallest unit the device can address
+    pub logical_block_size: u32,
+    /// Unit the device writes internally; smaller writes cost a read-modify-write
+    pub physical_block_size: u32,
+    /// Preferred request size, e.g. a RAID stripe; 0 when none is stated
+    pub optimal_io_size: u32,
+    /// Offset of the first logical block that starts a physical block
+    pub alignment_offset: u32,
+    /// Largest request the kernel passes to the device at once; 0 when unknown
+    pub max_transfer: u32,
+    /// Unit of discard (TRIM/UNMAP); 0 when discard is not supported
+    pub discard_granularity: u32,
+}
+
+impl Default for BlockGeometry {
+    fn default() -> Self {
+        BlockGeometry {
+            logical_block_size: 512,
+            physical_block_size: 512,
+            optimal_io_size: 0,
+            alignment_offset: 0,
+            max_transfer: 0,
+            discard_granularity: 0,
+        }
+    }
+}
+
+/// How logical sectors map onto physical ones
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SectorFormat {
+    /// 512-byte sectors throughout
+    Native512,
+    /// 512-byte logical sectors emulated on 4K physical ones
+    Emulated512,
+    /// 4K sectors throughout
+    Native4K,
+    Other { logical: u32, physical: u32 },
+}
+
+impl std::fmt::Display for SectorFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SectorFormat::Native512 => write!(f, "512n"),
+            SectorFormat::Emulated512 => write!(f, "512e"),
+            SectorFormat::Native4K => write!(f, "4Kn"),
+            SectorFormat::Other { logical, physical } => write!(f, "{}/{}", logical, physical),
+        }
+    }
+}
+
+impl BlockGeometry {
+    pub fn format(&self) -> SectorFormat {
+        match (self.logical_block_size, self.physical_block_size) {
+            (512, 512) => SectorFormat::Native512,
+            (512, 4096) => SectorFormat::Emulated512,
+            (4096, 4096) => SectorFormat::Native4K,
+            (logical, physical) => SectorFormat::Other { logical, physical },
+        }
+    }
+
+    /// The largest request up to `preferred` bytes that is a whole number of
+    /// physical blocks and of the optimal I/O size; never less than one such unit
+    pub fn write_size(&self, preferred: usize) -> usize {
+        let block = self.physical_block_size.max(self.logical_block_size).max(1) as usize;
+        let unit = match self.optimal_io_size as usize {
+            0 => block,
+            optimal => block / gcd(block, optimal) * optimal,
+        };
+        (preferred / unit).max(1) * unit
+    }
+}
+
+fn gcd(a: usize, b: usize) -> usize {
+    if b == 0 { a } else { gcd(b, a % b) }
+}
+
+/// What tells one physical drive from another. Captured when the operator
+/// confirms an erase and compared again right before the first write, so a
+/// hot-swap or re-enumeration cannot redirect the erase to another drive.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DeviceIdentity {
+    pub serial: Option<String>,
+    pub wwn: Option<String>,
+    pub size: Option<u64>,
+}
+
+impl From<&StorageDevice> for DeviceIdentity {
+    fn from(device: &StorageDevice) -> Self {
+        DeviceIdentity {
+            serial: device.serial_number.clone(),
+            wwn: device.wwn.clone(),
+            size: device.size,
+        }
+    }
+}
+
+impl DeviceIdentity {
+    /// Describes the first property of `self` that `current` does not match.
+    /// Properties that were unknown when `self` was captured are not compared.
+    pub fn mismatch(&self, current: &DeviceIdentity) -> Option<String> {
+        let differs = |expected: &Option<String>, found: &Option<String>| {
+            expected.is_some() && expected != found
+        };
+        if differs(&self.serial, &current.serial) {
+            return Some(format!("serial is {:?}, expected {:?}", current.serial, self.serial));
+        }
+        if differs(&self.wwn, &current.wwn) {
+            return Some(format!("WWN is {:?}, expected {:?}", current.wwn, self.wwn));
+        }
+        if self.size.is_some() && self.size != current.size {
+            return Some(format!("size is {:?}, expected {:?}", current.size, self.size));
+        }
+        None
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeviceAction {
+    Added,
+    Removed,
+    // New media, a resize or a re-read partition table
+    Changed,
+}
+
+/// A block device appearing, disappearing or changing while the app runs
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeviceEvent {
+    pub action: DeviceAction,
+    pub path: String,
+    /// A partition rather than a whole disk
+    pub partition: bool,
 }
 
 #[derive(Debug)]
 pub enum DeviceStatus {
     Active,
     Mounted,
+    // Held by swap, device-mapper, md or an exclusive opener
+    InUse,
     Available,
     // Unknown,
 }
 
 #[derive(Debug)]
+#[allow(clippy::upper_case_acronyms)]
 pub enum DeviceType {
     HDD,
     SSD,
@@ -59,9 +257,16 @@ impl WipeMethod {
     }
 }
 
+/// Implemented once per platform; use [`crate::libs::StorageDriver`] instead
+/// of calling implementations directly
 pub trait StorageInterface {
     fn detect_storage_devices() -> Vec<StorageDevice>;
     fn format_size(bytes: u64) -> String;
+    fn controller_id(device_path: &str) -> Option<String>;
+    fn device_identity(device_path: &str) -> Option<DeviceIdentity>;
+    fn opened_identity(file: &std::fs::File) -> Option<DeviceIdentity>;
+    fn block_geometry(device_path: &str) -> Option<BlockGeometry>;
+    fn device_usage(device_path: &str) -> DeviceUsage;
 
     fn identify_ata_device(ata_storage_driver: &AtaStorageDriver) -> Result<AtaDeviceInfo, AtaError>;
 }
\ No newline at end of file
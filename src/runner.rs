@@ -0,0 +1,331 @@
+// Background job runner for the GUI
+// The UI sends commands and drains events each frame; everything that touches
+// a device, the audit log or the certificate store runs on worker threads.
+// Every event wakes the UI with a repaint request, so the window only redraws
+// when something changed.
+
+use std::{
+    path::{Path, PathBuf},
+    sync::{
+        Arc, Mutex,
+        mpsc::{self, Receiver, Sender},
+    },
+    thread,
+    time::Duration,
+};
+
+use secure_erase::{
+    analysis::{
+        datamap::{DataMap, MapConfig, scan_data_map},
+        filesystem::{Volume, scan_filesystems},
+        forensics::{ForensicReport, scan_forensics},
+        partition::{PartitionLayout, read_partition_table},
+        sectors::{SectorPage, SectorReader},
+    },
+    audit::{AuditEvent, AuditLog, LogVerification},
+    cert::{CertificateSigner, cms::CmsSigner, default_key_path, store::CertificateStore},
+    erasure::{
+        DriveCapabilities,
+        batch::{BatchConfig, BatchControl, BatchDevice, BatchEvent, BatchServices, start_batch},
+        identify_drive,
+        throttle::ThrottleSettings,
+    },
+    health::{HealthReport, read_health},
+    libs::StorageDriver,
+    platform::{DeviceEvent, StorageDevice},
+};
+
+// Quiet time after hot-plug events before rescanning: a new disk sends a
+// burst of events, and udev creates its device nodes only after them
+#[cfg(target_os = "linux")]
+const HOTPLUG_SETTLE: Duration = Duration::from_millis(750);
+
+pub enum Command {
+    ScanDevices,
+    Identify { path: String },
+    ReadHealth { path: String },
+    AnalyzePartitions { path: String },
+    ReadSectors { path: String, sector_size: u64, lba: u64, count: u64 },
+    ScanDataMap { path: String },
+    ScanForensics { path: String },
+    VerifyAuditLog,
+    Audit(AuditEvent),
+    /// `cms` is an enterprise key and its certificate chain to also sign
+    /// the certificates with
+    StartBatch { devices: Vec<BatchDevice>, config: BatchConfig, cms: Option<(PathBuf, PathBuf)> },
+    Pause { job: usize },
+    Resume { job: usize },
+    Cancel { job: usize },
+    CancelAll,
+    SetThrottle(ThrottleSettings),
+}
+
+pub enum Event {
+    Devices(Vec<StorageDevice>),
+    Identified { path: String, result: Result<DriveCapabilities, String> },
+    Health { path: String, result: Result<HealthReport, String> },
+    Partitions { path: String, result: Result<PartitionLayout, String> },
+    Filesystems { path: String, result: Result<Vec<Volume>, String> },
+    Sectors { path: String, result: Result<SectorPage, String> },
+    DataMapProgress { path: String, done: usize, total: usize },
+    DataMap { path: String, result: Result<DataMap, String> },
+    ForensicsProgress { path: String, done: u64, total: u64 },
+    Forensics { path: String, result: Result<ForensicReport, String> },
+    AuditVerified(Result<LogVerification, String>),
+    AuditAppended(LogVerification),
+    Batch(BatchEvent),
+    Hotplug(DeviceEvent),
+    Log { message: String, error: bool },
+}
+
+pub struct JobRunner {
+    commands: Sender<Command>,
+    events: Receiver<Event>,
+}
+
+impl JobRunner {
+    pub fn spawn(ctx: egui::Context) -> Self {
+        let (commands, command_receiver) = mpsc::channel();
+        let (sender, events) = mpsc::channel();
+        let emitter = Emitter { sender, ctx };
+        #[cfg(target_os = "linux")]
+        {
+            let commands = commands.clone();
+            let emitter = emitter.clone();
+            thread::spawn(move || watch_devices(commands, emitter));
+        }
+        thread::spawn(move || dispatch(command_receiver, emitter));
+        JobRunner { commands, events }
+    }
+
+    pub fn send(&self, command: Command) {
+        let _ = self.commands.send(command);
+    }
+
+    pub fn poll(&self) -> Vec<Event> {
+        self.events.try_iter().collect()
+    }
+}
+
+#[derive(Clone)]
+struct Emitter {
+    sender: Sender<Event>,
+    ctx: egui::Context,
+}
+
+impl Emitter {
+    fn emit(&self, event: Event) {
+        if self.sender.send(event).is_ok() {
+            self.ctx.request_repaint();
+        }
+    }
+
+    fn log(&self, message: String, error: bool) {
+        self.emit(Event::Log { message, error });
+    }
+}
+
+// Passes hot-plug events on as they come and rescans once they settle
+#[cfg(target_os = "linux")]
+fn watch_devices(commands: Sender<Command>, emitter: Emitter) {
+    use secure_erase::platform::linux::UeventMonitor;
+
+    let monitor = match UeventMonitor::open() {
+        Ok(monitor) => monitor,
+        Err(e) => {
+            emitter.log(format!("Hot-plug monitoring unavailable, use Refresh instead: {}", e), true);
+            return;
+        }
+    };
+    let mut pending = false;
+    loop {
+        match monitor.next_event(pending.then_some(HOTPLUG_SETTLE)) {
+            Ok(Some(event)) => {
+                pending = true;
+                emitter.emit(Event::Hotplug(event));
+            }
+            Ok(None) => {
+                pending = false;
+                if commands.send(Command::ScanDevices).is_err() {
+                    return;
+                }
+            }
+            // The socket overflowed; what was missed shows up in the rescan
+            Err(e) if e.raw_os_error() == Some(libc::ENOBUFS) => pending = true,
+            Err(e) => {
+                emitter.log(format!("Hot-plug monitoring stopped: {}", e), true);
+                return;
+            }
+        }
+    }
+}
+
+// Runs on its own thread for the lifetime of the app. Anything that may block
+// is handed to a further thread so pause and cancel are never held up.
+fn dispatch(commands: Receiver<Command>, emitter: Emitter) {
+    let audit = match AuditLog::open_default() {
+        Ok(log) => Some(Arc::new(log)),
+        Err(e) => {
+            emitter.emit(Event::AuditVerified(Err(format!("Audit log unavailable: {}", e))));
+            None
+        }
+    };
+    // Appends are serialized on one thread so the chain head reported to the
+    // UI always matches the order of the entries
+    let (audit_sender, audit_receiver) = mpsc::channel::<AuditEvent>();
+    {
+        let audit = audit.clone();
+        let emitter = emitter.clone();
+        thread::spawn(move || {
+            for event in audit_receiver {
+                let Some(log) = &audit else { continue };
+                match log.append(event) {
+                    Ok(entry) => emitter.emit(Event::AuditAppended(LogVerification {
+                        entries: entry.seq + 1,
+                        last_hash: entry.hash,
+                    })),
+                    Err(e) => emitter.log(format!("Failed to write audit log: {}", e), true),
+                }
+            }
+        });
+    }
+    let mut batch: Option<BatchControl> = None;
+
+    for command in commands {
+        match command {
+            Command::ScanDevices => {
+                let emitter = emitter.clone();
+                let audit_sender = audit_sender.clone();
+                thread::spawn(move || {
+                    let devices = StorageDriver::detect_storage_devices();
+                    for device in &devices {
+                        let _ = audit_sender.send(AuditEvent::DeviceDetected {
+                            path: device.path.clone(),
+                            model: device.model.clone(),
+                            serial: device.serial_number.clone(),
+                        });
+                    }
+                    emitter.emit(Event::Devices(devices));
+                });
+            }
+            Command::Identify { path } => {
+                let emitter = emitter.clone();
+                thread::spawn(move || {
+                    let result = identify_drive(Path::new(&path)).map_err(|e| e.to_string());
+                    emitter.emit(Event::Identified { path, result });
+                });
+            }
+            Command::ReadHealth { path } => {
+                let emitter = emitter.clone();
+                thread::spawn(move || {
+                    let result = read_health(Path::new(&path)).map_err(|e| e.to_string());
+                    emitter.emit(Event::Health { path, result });
+                });
+            }
+            Command::AnalyzePartitions { path } => {
+                let emitter = emitter.clone();
+                thread::spawn(move || {
+                    let layout = read_partition_table(Path::new(&path));
+                    // What is on the partitions follows once the layout is known
+                    let filesystems = layout
+                        .as_ref()
+                        .ok()
+                        .map(|layout| scan_filesystems(Path::new(&path), layout).map_err(|e| e.to_string()));
+                    emitter.emit(Event::Partitions { path: path.clone(), result: layout.map_err(|e| e.to_string()) });
+                    if let Some(result) = filesystems {
+                        emitter.emit(Event::Filesystems { path, result });
+                    }
+                });
+            }
+            Command::ReadSectors { path, sector_size, lba, count } => {
+                let emitter = emitter.clone();
+                thread::spawn(move || {
+                    let result = SectorReader::open(Path::new(&path), sector_size)
+                        .and_then(|reader| reader.read_page(lba, count))
+                        .map_err(|e| e.to_string());
+                    emitter.emit(Event::Sectors { path, result });
+                });
+            }
+            Command::ScanDataMap { path } => {
+                let emitter = emitter.clone();
+                thread::spawn(move || {
+                    let mut progress = |done, total| {
+                        emitter.emit(Event::DataMapProgress { path: path.clone(), done, total });
+                    };
+                    let result = scan_data_map(Path::new(&path), MapConfig::default(), &mut progress)
+                        .map_err(|e| e.to_string());
+                    emitter.emit(Event::DataMap { path, result });
+                });
+            }
+            Command::ScanForensics { path } => {
+                let emitter = emitter.clone();
+                thread::spawn(move || {
+                    // Carving reports every megabyte; pass on whole permille steps
+                    let mut shown = None;
+                    let mut progress = |done: u64, total: u64| {
+                        let step = done.saturating_mul(1000) / total.max(1);
+                        if shown != Some(step) {
+                            shown = Some(step);
+                            emitter.emit(Event::ForensicsProgress { path: path.clone(), done, total });
+                        }
+                    };
+                    let result = scan_forensics(Path::new(&path), &mut progress).map_err(|e| e.to_string());
+                    emitter.emit(Event::Forensics { path, result });
+                });
+            }
+            Command::VerifyAuditLog => {
+                let Some(log) = audit.clone() else { continue };
+                let emitter = emitter.clone();
+                thread::spawn(move || {
+                    let result = log
+                        .verify()
+                        .map_err(|e| format!("Audit log integrity check failed: {}", e));
+                    emitter.emit(Event::AuditVerified(result));
+                });
+            }
+            Command::Audit(event) => {
+                let _ = audit_sender.send(event);
+            }
+            Command::StartBatch { devices, config, cms } => {
+                let emitter = emitter.clone();
+                let services = load_services(audit.clone(), cms, &emitter);
+                let handle = start_batch(devices, config, services);
+                batch = Some(handle.control());
+                thread::spawn(move || {
+                    while let Some(event) = handle.recv() {
+                        emitter.emit(Event::Batch(event));
+                    }
+                });
+            }
+            Command::Pause { job } => batch.iter().for_each(|b| b.pause(job)),
+            Command::Resume { job } => batch.iter().for_each(|b| b.resume(job)),
+            Command::Cancel { job } => batch.iter().for_each(|b| b.cancel(job)),
+            Command::CancelAll => batch.iter().for_each(|b| b.cancel_all()),
+            Command::SetThrottle(settings) => batch.iter().for_each(|b| b.set_throttle(settings.clone())),
+        }
+    }
+}
+
+// Certificates are still issued when only some services are available; the
+// batch reports what was skipped per job
+fn load_services(
+    audit: Option<Arc<AuditLog>>,
+    cms: Option<(PathBuf, PathBuf)>,
+    emitter: &Emitter,
+) -> BatchServices {
+    let signer = CertificateSigner::load_or_create(&default_key_path())
+        .map_err(|e| emitter.log(format!("Certificates disabled, signing key unavailable: {}", e), true))
+        .ok()
+        .map(Arc::new);
+    let mut store = CertificateStore::open_default()
+        .map_err(|e| emitter.log(format!("Certificate store unavailable: {}", e), true))
+        .ok();
+    if let (Some(store), Some((key, chain))) = (&mut store, cms) {
+        match CmsSigner::load(&key, &chain) {
+            Ok(cms) => store.set_cms_signer(cms),
+            Err(e) => emitter.log(format!("Certificates not CMS signed, enterprise key unavailable: {}", e), true),
+        }
+    }
+    let store = store.map(|store| Arc::new(Mutex::new(store)));
+    BatchServices { signer, store, audit }
+}
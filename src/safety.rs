@@ -0,0 +1,84 @@
+//! Safety checks run before a device is erased
+//! Finds everything that still uses a disk: mounted partitions, device-mapper
+//! and md holders, swap, exclusive openers, and whether the running system
+//! was booted from it.
+
+/// Why a disk must not be erased right now
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum UsageReason {
+    Mounted { device: String, mountpoint: String },
+    Swap { device: String },
+    // Device-mapper target stacked on the disk, e.g. LVM or multipath
+    Holder { device: String, holder: String },
+    LuksMapping { device: String, name: String },
+    RaidMember { device: String, array: String },
+    // Opened with O_EXCL by a process the other checks do not explain
+    ExclusivelyOpen { device: String },
+    // Carries /, /boot or the filesystem this program runs from
+    SystemDisk { mountpoint: String },
+}
+
+impl std::fmt::Display for UsageReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            UsageReason::Mounted { device, mountpoint } => write!(f, "{} is mounted at {}", device, mountpoint),
+            UsageReason::Swap { device } => write!(f, "{} is active swap", device),
+            UsageReason::Holder { device, holder } => write!(f, "{} is held by {}", device, holder),
+            UsageReason::LuksMapping { device, name } => {
+                write!(f, "{} is unlocked as LUKS mapping {}", device, name)
+            }
+            UsageReason::RaidMember { device, array } => {
+                write!(f, "{} is a member of active RAID array {}", device, array)
+            }
+            UsageReason::ExclusivelyOpen { device } => write!(f, "{} is opened exclusively", device),
+            UsageReason::SystemDisk { mountpoint } => {
+                write!(f, "system disk, carries {}", mountpoint)
+            }
+        }
+    }
+}
+
+/// Result of [`crate::libs::StorageDriver::device_usage`]
+#[derive(Debug, Clone, Default)]
+pub struct DeviceUsage {
+    pub reasons: Vec<UsageReason>,
+}
+
+impl DeviceUsage {
+    pub fn is_mounted(&self) -> bool {
+        self.reasons.iter().any(|r| matches!(r, UsageReason::Mounted { .. }))
+    }
+
+    pub fn is_system_disk(&self) -> bool {
+        self.reasons.iter().any(|r| matches!(r, UsageReason::SystemDisk { .. }))
+    }
+
+    /// Anything other than being the system disk. The system disk's own stack
+    /// counts towards that, not towards use: its mounts of /, /boot and
+    /// /boot/efi and the LVM, LUKS and swap devices a system install puts on it.
+    pub fn is_in_use(&self) -> bool {
+        let system_disk = self.is_system_disk();
+        self.reasons.iter().any(|r| match r {
+            UsageReason::SystemDisk { .. } => false,
+            UsageReason::Mounted { mountpoint, .. } => !self.carries(mountpoint),
+            UsageReason::Holder { .. } | UsageReason::LuksMapping { .. } | UsageReason::Swap { .. } => !system_disk,
+            _ => true,
+        })
+    }
+
+    fn carries(&self, mountpoint: &str) -> bool {
+        self.reasons
+            .iter()
+            .any(|r| matches!(r, UsageReason::SystemDisk { mountpoint: carried } if carried == mountpoint))
+    }
+
+    /// In-use disks are always refused. The system disk, including its system
+    /// mounts, is refused unless the operator explicitly allowed it.
+    pub fn blocks_erase(&self, allow_system_disk: bool) -> bool {
+        self.is_in_use() || (self.is_system_disk() && !allow_system_disk)
+    }
+
+    pub fn describe(&self) -> String {
+        self.reasons.iter().map(|r| r.to_string()).collect::<Vec<_>>().join("; ")
+    }
+}
@@ -1,33 +1,285 @@
+use std::{collections::{BTreeSet, HashMap}, path::PathBuf};
+
 use egui::{self, Align, Color32, Layout, RichText, Vec2};
 
-use crate::{platform::{StorageDevice, WipeMethod}};
-use crate::libs::StorageDriver;
+use secure_erase::analysis::{
+    datamap::{DataMap, RegionClass, RegionStats},
+    filesystem::Volume,
+    forensics::ForensicReport,
+    partition::{PartitionLayout, PartitionScheme, Region, RegionKind},
+    sectors::{Highlight, SectorPage, StructureKind, find_structures},
+};
+use secure_erase::platform::{DeviceAction, DeviceEvent, DeviceIdentity, StorageDevice, WipeMethod};
+use secure_erase::audit::{AuditEvent, LogVerification};
+use secure_erase::cert::DeviceRecord;
+use secure_erase::erasure::{
+    DriveCapabilities,
+    batch::{
+        BatchConfig, BatchDevice, BatchEvent, BatchSummary, JobOutcome, JobResult,
+        default_report_dir,
+    },
+    checkpoint::{Checkpoint, CheckpointStore, default_checkpoint_dir},
+    engine::IoConfig,
+    errormap::ErrorPolicy,
+    overwrite::OverwriteStandard,
+    progress::{ProgressTracker, format_duration, format_rate},
+    throttle::{IoPriority, PauseWindow, Throttle, ThrottleSettings, parse_windows},
+};
+use secure_erase::health::{HealthReport, HealthWarning};
+use secure_erase::libs::StorageDriver;
+use secure_erase::verify::VerifyMode;
+
+use crate::runner::{Command, Event, JobRunner};
 
 pub struct SecureWipeApp {
+    // Device scans, identifies, erases and audit writes run here
+    runner: JobRunner,
+
     // Storage device management
     available_devices: Vec<StorageDevice>,
     selected_device_index: Option<usize>,
+    scanning: bool,
+    // Identify results by path; None while the command is running
+    capabilities: HashMap<String, Option<Result<DriveCapabilities, String>>>,
+    // SMART or log page health by path, read again after an erase
+    health: HashMap<String, Option<Result<HealthReport, String>>>,
+    // Partition tables by path, read after every scan; None while reading
+    partitions: HashMap<String, Option<Result<PartitionLayout, String>>>,
+    // Filesystems found on each device's partitions
+    filesystems: HashMap<String, Result<Vec<Volume>, String>>,
+
+    // Devices ticked for a batch erase, by path
+    batch_selection: BTreeSet<String>,
 
     // Wipe method selection
     selected_wipe_method: WipeMethod,
+    selected_standard: OverwriteStandard,
+    selected_verify_mode: VerifyMode,
+    selected_error_policy: ErrorPolicy,
+    operator: String,
+    jobs_per_controller: usize,
+    io: IoConfig,
+    // Applied to the next batch, and to the running one as it changes
+    throttle: ThrottleSettings,
+    pause_windows: String,
+    allow_system_disk: bool,
+    // PEM key and chain that also sign certificates with CMS, when both are set
+    cms_key: String,
+    cms_chain: String,
+
+    // Read-only sector view of one device
+    hex_viewer: Option<HexViewer>,
+    // Data maps by path and the one shown
+    data_maps: HashMap<String, ScanView<DataMap>>,
+    data_map_open: Option<String>,
+    // Forensic previews by path and the one shown
+    forensics: HashMap<String, ScanView<ForensicReport>>,
+    forensics_open: Option<String>,
+    // Devices an erase job finished on this session
+    erased: BTreeSet<String>,
+
+    // Overwrites interrupted by a crash or power cut, offered for resuming
+    checkpoints: Vec<Checkpoint>,
+
+    // Running or finished batch
+    confirmation: Option<Confirmation>,
+    batch: Option<BatchView>,
+
+    // Audit trail
+    audit_summary: Option<LogVerification>,
+    audit_warning: Option<String>,
+
+    // Messages from background jobs, newest last
+    activity: Vec<(String, bool)>,
 }
 
 impl  SecureWipeApp {
     pub fn new(cc: &eframe::CreationContext<'_>) -> Self {
         egui_material_icons::initialize(&cc.egui_ctx);
+
         let mut app = Self {
+            runner: JobRunner::spawn(cc.egui_ctx.clone()),
             available_devices: Vec::new(),
             selected_device_index: None,
+            scanning: false,
+            capabilities: HashMap::new(),
+            health: HashMap::new(),
+            partitions: HashMap::new(),
+            filesystems: HashMap::new(),
+            batch_selection: BTreeSet::new(),
             selected_wipe_method: WipeMethod::Overwrite,
+            selected_standard: OverwriteStandard::Zero,
+            selected_verify_mode: VerifyMode::Sampled(10),
+            selected_error_policy: ErrorPolicy::Fail,
+            operator: std::env::var("USER").unwrap_or_default(),
+            jobs_per_controller: 2,
+            io: IoConfig::default(),
+            throttle: ThrottleSettings::default(),
+            pause_windows: String::new(),
+            allow_system_disk: false,
+            cms_key: String::new(),
+            cms_chain: String::new(),
+            hex_viewer: None,
+            data_maps: HashMap::new(),
+            data_map_open: None,
+            forensics: HashMap::new(),
+            forensics_open: None,
+            erased: BTreeSet::new(),
+            checkpoints: Vec::new(),
+            confirmation: None,
+            batch: None,
+            audit_summary: None,
+            audit_warning: None,
+            activity: Vec::new(),
         };
+        app.runner.send(Command::VerifyAuditLog);
         app.refresh_devices();
+        app.load_checkpoints();
         app
     }
     
 }
 
+// Snapshot of the devices the operator is confirming. Identities are taken
+// here, from what the operator sees, and re-checked before writing.
+struct Confirmation {
+    targets: Vec<ConfirmTarget>,
+    reviewed: bool,
+    // Carries on an interrupted overwrite with its own settings
+    resume: Option<Checkpoint>,
+}
+
+struct ConfirmTarget {
+    device: BatchDevice,
+    name: String,
+    status: String,
+    // Set when an override lets a system disk through
+    warning: Option<String>,
+    // Retired sectors an overwrite cannot reach, as far as health was read
+    health_warning: Option<String>,
+    code: String,
+    typed: String,
+}
+
+impl Confirmation {
+    fn new(
+        devices: Vec<&StorageDevice>,
+        health: &HashMap<String, Option<Result<HealthReport, String>>>,
+        method: &WipeMethod,
+    ) -> Self {
+        let targets = devices
+            .into_iter()
+            .map(|device| {
+                let record = DeviceRecord::from(device);
+                let remapped = match health.get(&device.path) {
+                    Some(Some(Ok(health))) if *method == WipeMethod::Overwrite => health
+                        .warnings()
+                        .into_iter()
+                        .find(|w| matches!(w, HealthWarning::Reallocated(_) | HealthWarning::SpareUsed(_))),
+                    _ => None,
+                };
+                ConfirmTarget {
+                    code: record.confirmation_code(),
+                    device: BatchDevice::new(record).with_identity(DeviceIdentity::from(device)),
+                    name: device.name.clone(),
+                    status: format!("{:?}", device.status),
+                    warning: (!device.usage.reasons.is_empty()).then(|| device.usage.describe()),
+                    health_warning: remapped.map(|w| format!("{}, consider a firmware erase", w)),
+                    typed: String::new(),
+                }
+            })
+            .collect();
+        Confirmation { targets, reviewed: false, resume: None }
+    }
+
+    fn resuming(mut self, checkpoint: Checkpoint) -> Self {
+        for target in &mut self.targets {
+            target.device.resume = Some(checkpoint.clone());
+        }
+        self.resume = Some(checkpoint);
+        self
+    }
+
+    fn confirmed(&self) -> bool {
+        self.targets.iter().all(|target| target.typed.trim() == target.code)
+    }
+}
+
+// Sectors shown per hex viewer page
+const HEX_PAGE_SECTORS: u64 = 1;
+
+struct HexViewer {
+    path: String,
+    lba: u64,
+    lba_input: String,
+    page: Option<Result<SectorPage, String>>,
+    // Page kept for side-by-side comparison, e.g. from before an erase
+    baseline: Option<SectorPage>,
+}
+
+// Results of a read-only scan of one device from before and after erasing it
+struct ScanView<T> {
+    before: Option<T>,
+    after: Option<T>,
+    // Fraction done while a scan runs
+    scanning: Option<f32>,
+    error: Option<String>,
+}
+
+impl<T> Default for ScanView<T> {
+    fn default() -> Self {
+        ScanView { before: None, after: None, scanning: None, error: None }
+    }
+}
+
+impl<T> ScanView<T> {
+    fn start(&mut self) {
+        self.scanning = Some(0.0);
+        self.error = None;
+    }
+
+    fn progress(&mut self, done: u64, total: u64) {
+        if self.scanning.is_some() && total > 0 {
+            self.scanning = Some(done as f32 / total as f32);
+        }
+    }
+
+    // Results from after an erase go next to the ones from before it
+    fn finish(&mut self, result: Result<T, String>, erased: bool) {
+        self.scanning = None;
+        match result {
+            Ok(found) if erased => self.after = Some(found),
+            Ok(found) => self.before = Some(found),
+            Err(e) => self.error = Some(e),
+        }
+    }
+
+    // Whether to scan again once the device is erased
+    fn wants_after(&self) -> bool {
+        self.before.is_some() && self.scanning.is_none()
+    }
+}
+
+struct BatchView {
+    jobs: Vec<JobView>,
+    summary: Option<Box<BatchSummary>>,
+    report: Option<Result<PathBuf, String>>,
+}
+
+struct JobView {
+    path: String,
+    running: bool,
+    paused: bool,
+    // Set while a pause window holds the job
+    pause_window: Option<PauseWindow>,
+    tracker: ProgressTracker,
+    result: Option<Box<JobResult>>,
+}
+
 impl eframe::App for SecureWipeApp {
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        self.handle_events();
+
         // Set white background
         let mut visuals = ctx.style().visuals.clone();
         visuals.window_fill = Color32::WHITE;
@@ -35,6 +287,7 @@ impl eframe::App for SecureWipeApp {
         visuals.extreme_bg_color = Color32::WHITE;
         ctx.set_visuals(visuals);
 
+        let mut open_viewer = None;
         egui::CentralPanel::default().show(ctx, |ui| {
             // Header section with shield icon and title
             ui.with_layout(Layout::top_down(Align::Center), |ui| {
@@ -64,9 +317,45 @@ impl eframe::App for SecureWipeApp {
                     );
                 });
 
+                if let Some(warning) = &self.audit_warning {
+                    ui.add_space(10.0);
+                    ui.label(RichText::new(format!("⚠ {}", warning)).color(Color32::RED));
+                } else if let Some(summary) = &self.audit_summary {
+                    ui.add_space(5.0);
+                    ui.label(
+                        RichText::new(format!(
+                            "🔒 Audit log verified: {} entries, head {}",
+                            summary.entries,
+                            &summary.last_hash[..12]
+                        ))
+                        .size(12.0)
+                        .color(Color32::GRAY),
+                    );
+                }
+
                 ui.add_space(30.0);
             });
 
+            if !self.activity.is_empty() {
+                egui::CollapsingHeader::new(format!("Activity ({})", self.activity.len())).show(ui, |ui| {
+                    for (message, error) in self.activity.iter().rev().take(20) {
+                        let color = if *error { Color32::RED } else { Color32::DARK_GRAY };
+                        ui.label(RichText::new(message).color(color));
+                    }
+                });
+                ui.add_space(10.0);
+            }
+
+            // A running or finished batch replaces the device selection
+            if self.batch.is_some() {
+                self.show_batch(ui);
+                return;
+            }
+            if !self.checkpoints.is_empty() {
+                self.show_checkpoints(ui);
+                ui.add_space(10.0);
+            }
+
             // Main content area - two columns
             ui.horizontal(|ui| {
                 // Left side - Device list
@@ -86,7 +375,8 @@ impl eframe::App for SecureWipeApp {
                                             .strong(),
                                     );
                                     ui.with_layout(Layout::right_to_left(Align::Center), |ui| {
-                                        if ui.button("🔄 Refresh").clicked() {
+                                        let label = if self.scanning { "Scanning..." } else { "🔄 Refresh" };
+                                        if ui.add_enabled(!self.scanning, egui::Button::new(label)).clicked() {
                                             self.refresh_devices();
                                         }
                                     });
@@ -102,22 +392,40 @@ impl eframe::App for SecureWipeApp {
                                     for (index, device) in self.available_devices.iter().enumerate()
                                     {
                                         let is_selected = self.selected_device_index == Some(index);
+                                        let blocked = device.usage.blocks_erase(self.allow_system_disk);
 
-                                        let response = ui.selectable_label(
-                                            is_selected,
-                                            format!(
-                                                "💾 {}\n   {} | {:?}",
-                                                device.path,
-                                                device
-                                                    .size
-                                                    .map(|s| StorageDriver::format_size(s))
-                                                    .unwrap_or_else(|| "Unknown size".to_string()),
-                                                device.device_type
-                                            ),
-                                        );
+                                        ui.horizontal(|ui| {
+                                            let mut checked = self.batch_selection.contains(&device.path) && !blocked;
+                                            let checkbox = ui
+                                                .add_enabled(!blocked, egui::Checkbox::without_text(&mut checked))
+                                                .on_disabled_hover_text(device.usage.describe());
+                                            if checkbox.changed() {
+                                                if checked {
+                                                    self.batch_selection.insert(device.path.clone());
+                                                } else {
+                                                    self.batch_selection.remove(&device.path);
+                                                }
+                                            }
+
+                                            let response = ui.selectable_label(
+                                                is_selected,
+                                                format!(
+                                                    "💾 {}\n   {} | {:?}",
+                                                    device.path,
+                                                    device
+                                                        .size
+                                                        .map(StorageDriver::format_size)
+                                                        .unwrap_or_else(|| "Unknown size".to_string()),
+                                                    device.device_type
+                                                ),
+                                            );
 
-                                        if response.clicked() {
-                                            self.selected_device_index = Some(index);
+                                            if response.clicked() {
+                                                self.selected_device_index = Some(index);
+                                            }
+                                        });
+                                        if let Some(Some(Ok(layout))) = self.partitions.get(&device.path) {
+                                            partition_bar(ui, layout, 8.0);
                                         }
 
                                         ui.add_space(5.0);
@@ -160,10 +468,21 @@ impl eframe::App for SecureWipeApp {
                                             "💿 Size: {}",
                                             device
                                                 .size
-                                                .map(|s| StorageDriver::format_size(s))
+                                                .map(StorageDriver::format_size)
                                                 .unwrap_or_else(|| "Unknown".to_string())
                                         ));
+                                        if let Some(geometry) = &device.geometry {
+                                            ui.label(format!(
+                                                "🧱 Sectors: {} ({} B logical, {} B physical)",
+                                                geometry.format(),
+                                                geometry.logical_block_size,
+                                                geometry.physical_block_size
+                                            ));
+                                        }
                                         ui.label(format!("📊 Status: {:?}", device.status));
+                                        for reason in &device.usage.reasons {
+                                            ui.label(RichText::new(format!("⚠ {}", reason)).color(Color32::DARK_RED));
+                                        }
                                         ui.label(format!("🏷️ Path: {}", device.path));
                                         ui.label(format!(
                                             "🔢 Serial: {}",
@@ -179,19 +498,124 @@ impl eframe::App for SecureWipeApp {
                                                 .as_ref()
                                                 .unwrap_or(&"Unknown".to_string())
                                         ));
+                                        ui.label(format!(
+                                            "🧩 Firmware: {}",
+                                            device.firmware.as_deref().unwrap_or("Unknown")
+                                        ));
+                                        ui.label(format!("🌐 WWN: {}", device.wwn.as_deref().unwrap_or("Unknown")));
+                                        ui.label(format!(
+                                            "🔌 Transport: {} via {}",
+                                            device.transport,
+                                            device.host_controller.as_deref().unwrap_or("unknown controller")
+                                        ));
+                                        if let Some(by_id) = device.stable_id() {
+                                            ui.label(format!("🪪 ID: {}", by_id));
+                                        }
+                                        if let Some(by_path) = &device.by_path {
+                                            ui.label(format!("📍 Port: {}", by_path));
+                                        }
 
                                         ui.add_space(10.0);
                                         ui.separator();
                                         ui.add_space(10.0);
 
-                                        // ATA Secure Erase Support Status
+                                        // Firmware erase support, identified in the background
+                                        ui.label(
+                                            RichText::new("Firmware Erase Support")
+                                                .size(16.0)
+                                                .color(Color32::BLACK)
+                                                .strong(),
+                                        );
+                                        ui.add_space(5.0);
+                                        match self.capabilities.get(&device.path) {
+                                            None => {
+                                                self.capabilities.insert(device.path.clone(), None);
+                                                self.runner.send(Command::Identify { path: device.path.clone() });
+                                            }
+                                            Some(None) => {
+                                                ui.label("Identifying drive...");
+                                            }
+                                            Some(Some(Err(e))) => {
+                                                ui.label(RichText::new(format!("Unavailable: {}", e)).color(Color32::GRAY));
+                                            }
+                                            Some(Some(Ok(caps))) => {
+                                                let mark = |supported: bool| if supported { "✔" } else { "✖" };
+                                                ui.label(format!("🔩 Firmware: {}", caps.firmware));
+                                                ui.label(format!("{} Block erase (sanitize)", mark(caps.block_erase)));
+                                                ui.label(format!("{} Crypto erase", mark(caps.crypto_erase)));
+                                                if caps.security_erase {
+                                                    ui.label(format!(
+                                                        "{} ATA security erase{}",
+                                                        mark(!caps.security_frozen),
+                                                        if caps.security_frozen { " (frozen)" } else { "" }
+                                                    ));
+                                                }
+                                                if let Some(time) = caps.erase_time {
+                                                    ui.label(format!("⏱ Drive estimate: {}", format_duration(time)));
+                                                }
+                                            }
+                                        }
+
+                                        ui.add_space(10.0);
+                                        ui.label(
+                                            RichText::new("Drive Health")
+                                                .size(16.0)
+                                                .color(Color32::BLACK)
+                                                .strong(),
+                                        );
+                                        ui.add_space(5.0);
+                                        match self.health.get(&device.path) {
+                                            None => {
+                                                self.health.insert(device.path.clone(), None);
+                                                self.runner.send(Command::ReadHealth { path: device.path.clone() });
+                                            }
+                                            Some(None) => {
+                                                ui.label("Reading health...");
+                                            }
+                                            Some(Some(Err(e))) => {
+                                                ui.label(RichText::new(format!("Unavailable: {}", e)).color(Color32::GRAY));
+                                            }
+                                            Some(Some(Ok(health))) => show_health(ui, health),
+                                        }
+
+                                        ui.add_space(10.0);
                                         ui.label(
-                                            RichText::new("ATA Secure Erase Support")
+                                            RichText::new("Partition Layout")
                                                 .size(16.0)
                                                 .color(Color32::BLACK)
                                                 .strong(),
                                         );
                                         ui.add_space(5.0);
+                                        match self.partitions.get(&device.path) {
+                                            None | Some(None) => {
+                                                ui.label("Reading partition table...");
+                                            }
+                                            Some(Some(Err(e))) => {
+                                                ui.label(RichText::new(format!("Unavailable: {}", e)).color(Color32::GRAY));
+                                            }
+                                            Some(Some(Ok(layout))) => show_partitions(ui, layout),
+                                        }
+                                        match self.filesystems.get(&device.path) {
+                                            Some(Ok(volumes)) => show_volumes(ui, volumes),
+                                            Some(Err(e)) => {
+                                                ui.label(
+                                                    RichText::new(format!("Filesystems unavailable: {}", e))
+                                                        .color(Color32::GRAY),
+                                                );
+                                            }
+                                            None => {}
+                                        }
+                                        ui.horizontal(|ui| {
+                                            if ui.button("🔍 Hex Viewer").clicked() {
+                                                open_viewer = Some(device.path.clone());
+                                            }
+                                            if ui.button("🗺 Data Map").clicked() {
+                                                self.data_map_open = Some(device.path.clone());
+                                            }
+                                            if ui.button("🕵 Forensic Preview").clicked() {
+                                                self.forensics_open = Some(device.path.clone());
+                                            }
+                                        });
 
 
                                         ui.add_space(20.0);
@@ -205,17 +629,115 @@ impl eframe::App for SecureWipeApp {
                                         );
                                         ui.add_space(5.0);
 
+                                        let mut method_changed = false;
                                         egui::ComboBox::from_id_salt("wipe_method_selector")
                                             .selected_text(self.selected_wipe_method.to_string())
                                             .show_ui(ui, |ui| {
                                                 for method in WipeMethod::get_all_methods() {
+                                                    method_changed |= ui
+                                                        .selectable_value(
+                                                            &mut self.selected_wipe_method,
+                                                            method.clone(),
+                                                            method.to_string(),
+                                                        )
+                                                        .changed();
+                                                }
+                                            });
+                                        if method_changed {
+                                            let event = AuditEvent::MethodChosen {
+                                                path: device.path.clone(),
+                                                method: self.selected_wipe_method.to_string(),
+                                            };
+                                            self.record_audit(event);
+                                        }
+
+                                        if self.selected_wipe_method == WipeMethod::Overwrite {
+                                            ui.add_space(5.0);
+                                            egui::ComboBox::from_id_salt("overwrite_standard_selector")
+                                                .selected_text(self.selected_standard.to_string())
+                                                .show_ui(ui, |ui| {
+                                                    for standard in OverwriteStandard::get_all_standards() {
+                                                        ui.selectable_value(
+                                                            &mut self.selected_standard,
+                                                            standard,
+                                                            standard.to_string(),
+                                                        );
+                                                    }
+                                                });
+                                        }
+
+                                        ui.add_space(5.0);
+                                        egui::ComboBox::from_id_salt("verify_mode_selector")
+                                            .selected_text(self.selected_verify_mode.to_string())
+                                            .show_ui(ui, |ui| {
+                                                for mode in VerifyMode::get_all_modes() {
                                                     ui.selectable_value(
-                                                        &mut self.selected_wipe_method,
-                                                        method.clone(),
-                                                        method.to_string(),
+                                                        &mut self.selected_verify_mode,
+                                                        mode,
+                                                        mode.to_string(),
                                                     );
                                                 }
                                             });
+
+                                        if self.selected_wipe_method == WipeMethod::Overwrite {
+                                            ui.add_space(5.0);
+                                            egui::ComboBox::from_id_salt("error_policy_selector")
+                                                .selected_text(self.selected_error_policy.to_string())
+                                                .show_ui(ui, |ui| {
+                                                    for policy in ErrorPolicy::get_all_policies() {
+                                                        ui.selectable_value(
+                                                            &mut self.selected_error_policy,
+                                                            policy,
+                                                            policy.to_string(),
+                                                        );
+                                                    }
+                                                });
+                                        }
+
+                                        ui.add_space(10.0);
+                                        ui.horizontal(|ui| {
+                                            ui.label("Operator:");
+                                            ui.text_edit_singleline(&mut self.operator);
+                                        });
+                                        ui.horizontal(|ui| {
+                                            ui.label("Parallel jobs per controller:");
+                                            ui.add(egui::DragValue::new(&mut self.jobs_per_controller).range(1..=16));
+                                        });
+                                        ui.horizontal(|ui| {
+                                            ui.label("Queue depth:");
+                                            ui.add(egui::DragValue::new(&mut self.io.queue_depth).range(1..=64));
+                                            ui.label("Block size:");
+                                            egui::ComboBox::from_id_salt("block_size_selector")
+                                                .selected_text(StorageDriver::format_size(self.io.block_size as u64))
+                                                .show_ui(ui, |ui| {
+                                                    for size in [64 << 10, 256 << 10, 1 << 20, 4 << 20, 16 << 20] {
+                                                        ui.selectable_value(
+                                                            &mut self.io.block_size,
+                                                            size,
+                                                            StorageDriver::format_size(size as u64),
+                                                        );
+                                                    }
+                                                });
+                                        });
+                                        ui.horizontal(|ui| {
+                                            ui.label("CMS key:");
+                                            ui.text_edit_singleline(&mut self.cms_key)
+                                                .on_hover_text("PEM private key, RSA or P-256, that also signs certificates");
+                                        });
+                                        ui.horizontal(|ui| {
+                                            ui.label("CMS chain:");
+                                            ui.text_edit_singleline(&mut self.cms_chain)
+                                                .on_hover_text("PEM certificate chain of the key, its own certificate first");
+                                        });
+                                        if show_throttle(ui, "settings", &mut self.throttle, &mut self.pause_windows) {
+                                            self.runner.send(Command::SetThrottle(self.throttle.clone()));
+                                        }
+                                        if device.usage.is_system_disk() {
+                                            ui.checkbox(
+                                                &mut self.allow_system_disk,
+                                                RichText::new("Allow erasing the system disk").color(Color32::RED),
+                                            );
+                                        }
                                     }
                                 } else {
                                     ui.label("Select a device to show device information");
@@ -230,41 +752,1328 @@ impl eframe::App for SecureWipeApp {
 
             // Start Erase button (centered at bottom)
             ui.with_layout(Layout::top_down(Align::Center), |ui| {
-                let start_button_enabled = self.selected_device_index.is_some();
+                let target_count = self.erase_targets().len();
+                let start_button_enabled = target_count > 0 && !self.operator.trim().is_empty();
                 ui.add_enabled_ui(start_button_enabled, |ui| {
-                    if ui
-                        .button(RichText::new("🗑️ Start Erase").size(18.0))
-                        .clicked()
+                    let label = match target_count {
+                        0 | 1 => "🗑️ Start Erase".to_string(),
+                        count => format!("🗑️ Start Erase ({} devices)", count),
+                    };
+                    if ui.button(RichText::new(label).size(18.0)).clicked() {
+                        self.confirmation = Some(Confirmation::new(
+                            self.erase_targets(),
+                            &self.health,
+                            &self.selected_wipe_method,
+                        ));
+                    }
+                });
+            });
+        });
+
+        if let Some(path) = open_viewer {
+            self.open_hex_viewer(path);
+        }
+        if self.hex_viewer.is_some() {
+            self.show_hex_viewer(ctx);
+        }
+        if self.data_map_open.is_some() {
+            self.show_data_map(ctx);
+        }
+        if self.forensics_open.is_some() {
+            self.show_forensics(ctx);
+        }
+        if self.confirmation.is_some() {
+            self.show_confirmation(ctx);
+        }
+    }
+}
+
+impl SecureWipeApp {
+    fn refresh_devices(&mut self) {
+        self.scanning = true;
+        self.runner.send(Command::ScanDevices);
+    }
+
+    fn handle_events(&mut self) {
+        for event in self.runner.poll() {
+            match event {
+                Event::Devices(devices) => {
+                    self.scanning = false;
+                    // Selections follow the drive, not the path: a drive that
+                    // comes back under another name stays selected, and a
+                    // different drive at the same path never inherits them
+                    let selected_path = self
+                        .selected_device_index
+                        .and_then(|index| self.available_devices.get(index))
+                        .map(|device| device.path.clone());
+                    let old: HashMap<String, DeviceIdentity> = self
+                        .available_devices
+                        .iter()
+                        .map(|device| (device.path.clone(), DeviceIdentity::from(device)))
+                        .collect();
+                    // Path now used by the drive that was at `path`. Drives
+                    // without a serial or WWN can only be followed by path.
+                    let moved = |path: &String| -> Option<String> {
+                        let identity = old.get(path)?;
+                        let same = |device: &&StorageDevice| &DeviceIdentity::from(*device) == identity;
+                        if identity.serial.is_some() || identity.wwn.is_some() {
+                            devices.iter().find(same).map(|device| device.path.clone())
+                        } else {
+                            devices.iter().filter(same).find(|device| &device.path == path).map(|_| path.clone())
+                        }
+                    };
+                    let selected_path = selected_path.and_then(|path| moved(&path));
+                    self.batch_selection = self.batch_selection.iter().filter_map(&moved).collect();
+                    self.data_maps = self.data_maps.drain().filter_map(|(path, view)| Some((moved(&path)?, view))).collect();
+                    self.forensics = self.forensics.drain().filter_map(|(path, view)| Some((moved(&path)?, view))).collect();
+
+                    self.available_devices = devices;
+                    self.capabilities.clear();
+                    self.health.clear();
+                    self.partitions.clear();
+                    self.filesystems.clear();
+                    for device in &self.available_devices {
+                        self.partitions.insert(device.path.clone(), None);
+                        self.runner.send(Command::AnalyzePartitions { path: device.path.clone() });
+                    }
+                    self.selected_device_index =
+                        self.available_devices.iter().position(|device| Some(&device.path) == selected_path.as_ref());
+                }
+                Event::Identified { path, result } => {
+                    self.capabilities.insert(path, Some(result));
+                }
+                Event::Health { path, result } => {
+                    self.health.insert(path, Some(result));
+                }
+                Event::Partitions { path, result } => {
+                    self.partitions.insert(path, Some(result));
+                }
+                Event::Filesystems { path, result } => {
+                    self.filesystems.insert(path, result);
+                }
+                Event::DataMapProgress { path, done, total } => {
+                    if let Some(view) = self.data_maps.get_mut(&path) {
+                        view.progress(done as u64, total as u64);
+                    }
+                }
+                Event::DataMap { path, result } => {
+                    let erased = self.erased.contains(&path);
+                    self.data_maps.entry(path).or_default().finish(result, erased);
+                }
+                Event::ForensicsProgress { path, done, total } => {
+                    if let Some(view) = self.forensics.get_mut(&path) {
+                        view.progress(done, total);
+                    }
+                }
+                Event::Forensics { path, result } => {
+                    let erased = self.erased.contains(&path);
+                    self.forensics.entry(path).or_default().finish(result, erased);
+                }
+                Event::Sectors { path, result } => {
+                    // Replies to pages the viewer has moved on from are dropped
+                    if let Some(viewer) = &mut self.hex_viewer
+                        && viewer.path == path
+                    {
+                        let current = match &result {
+                            Ok(page) => page.lba == viewer.lba,
+                            Err(_) => true,
+                        };
+                        if current {
+                            viewer.page = Some(result);
+                        }
+                    }
+                }
+                Event::AuditVerified(Ok(summary)) => {
+                    self.audit_summary = Some(summary);
+                    self.audit_warning = None;
+                }
+                Event::AuditVerified(Err(e)) => self.audit_warning = Some(e),
+                Event::AuditAppended(head) => {
+                    if let Some(summary) = &mut self.audit_summary {
+                        *summary = head;
+                    }
+                }
+                Event::Batch(event) => self.handle_batch_event(event),
+                Event::Hotplug(event) => self.handle_hotplug(event),
+                Event::Log { message, error } => self.activity.push((message, error)),
+            }
+        }
+    }
+
+    // The device list itself is rescanned once events settle
+    fn handle_hotplug(&mut self, event: DeviceEvent) {
+        if event.partition {
+            return;
+        }
+        match event.action {
+            DeviceAction::Added => self.activity.push((format!("{} connected", event.path), false)),
+            DeviceAction::Removed => {
+                self.activity.push((format!("{} removed", event.path), false));
+                let Some(batch) = &self.batch else {
+                    return;
+                };
+                // Queued jobs too: the path may belong to another drive by then
+                for (index, job) in batch.jobs.iter().enumerate() {
+                    if job.path == event.path && job.result.is_none() {
+                        self.runner.send(Command::Cancel { job: index });
+                        self.activity.push((format!("Cancelled erase of {}: device was removed", job.path), true));
+                    }
+                }
+            }
+            DeviceAction::Changed => {}
+        }
+    }
+
+    fn handle_batch_event(&mut self, event: BatchEvent) {
+        let Some(batch) = &mut self.batch else {
+            return;
+        };
+        match event {
+            BatchEvent::JobStarted { job } => {
+                batch.jobs[job].running = true;
+                batch.jobs[job].tracker = ProgressTracker::new();
+            }
+            BatchEvent::Progress { job, progress } => batch.jobs[job].tracker.update(progress),
+            BatchEvent::JobPaused { job, window } => {
+                batch.jobs[job].paused = true;
+                batch.jobs[job].pause_window = window;
+            }
+            BatchEvent::JobResumed { job } => {
+                batch.jobs[job].paused = false;
+                batch.jobs[job].pause_window = None;
+            }
+            BatchEvent::JobFinished { job, result } => {
+                let path = batch.jobs[job].path.clone();
+                // Show the erased sectors next to the ones captured before
+                if let Some(viewer) = &self.hex_viewer
+                    && viewer.path == path
+                {
+                    let lba = viewer.lba;
+                    self.read_sectors(lba);
+                }
+                // Health is read again to compare with the state before
+                self.health.remove(&path);
+                // A map from before the erase gets its counterpart
+                self.erased.insert(path.clone());
+                if self.data_maps.get(&path).is_some_and(ScanView::wants_after) {
+                    self.scan_data_map(path.clone());
+                }
+                if self.forensics.get(&path).is_some_and(ScanView::wants_after) {
+                    self.scan_forensics(path);
+                }
+                let Some(batch) = &mut self.batch else {
+                    return;
+                };
+                batch.jobs[job].running = false;
+                batch.jobs[job].paused = false;
+                batch.jobs[job].pause_window = None;
+                batch.jobs[job].tracker.finish();
+                batch.jobs[job].result = Some(result);
+            }
+            BatchEvent::BatchFinished { summary, report } => {
+                batch.summary = Some(summary);
+                batch.report = Some(report);
+                // Finished jobs drop their checkpoints, interrupted ones keep them
+                self.load_checkpoints();
+                // Workers append to the audit log themselves, so re-read its head
+                self.runner.send(Command::VerifyAuditLog);
+            }
+        }
+    }
+
+    // Ticked devices, or the highlighted one when nothing is ticked. Devices
+    // in use, and the system disk unless allowed, are never included.
+    fn erase_targets(&self) -> Vec<&StorageDevice> {
+        let erasable = |device: &&StorageDevice| !device.usage.blocks_erase(self.allow_system_disk);
+        if self.batch_selection.is_empty() {
+            return self
+                .selected_device_index
+                .and_then(|index| self.available_devices.get(index))
+                .filter(erasable)
+                .into_iter()
+                .collect();
+        }
+        self.available_devices
+            .iter()
+            .filter(|device| self.batch_selection.contains(&device.path))
+            .filter(erasable)
+            .collect()
+    }
+
+    // Two steps: review what will be destroyed, then type each device's
+    // confirmation code
+    fn show_confirmation(&mut self, ctx: &egui::Context) {
+        let Some(confirmation) = &mut self.confirmation else {
+            return;
+        };
+        let mut start = false;
+        let mut cancel = confirmation.targets.is_empty();
+        egui::Window::new("Confirm Erase")
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, Vec2::ZERO)
+            .show(ctx, |ui| {
+                ui.label(
+                    RichText::new("All data on these devices will be destroyed:")
+                        .color(Color32::RED)
+                        .strong(),
+                );
+                if let Some(checkpoint) = &confirmation.resume {
+                    ui.label(format!(
+                        "Resuming {} at {:.1}%, started {}",
+                        checkpoint.standard,
+                        checkpoint.fraction() * 100.0,
+                        checkpoint.started_at
+                    ));
+                }
+                ui.add_space(5.0);
+                for (index, target) in confirmation.targets.iter_mut().enumerate() {
+                    let record = &target.device.record;
+                    ui.group(|ui| {
+                        ui.label(RichText::new(format!("💾 {} ({})", record.path, target.name)).strong());
+                        egui::Grid::new(("confirm_target", index)).num_columns(2).show(ui, |ui| {
+                            ui.label("Model:");
+                            ui.label(record.model.as_deref().unwrap_or("Unknown"));
+                            ui.end_row();
+                            ui.label("Serial:");
+                            ui.label(record.serial_number.as_deref().unwrap_or("Unknown"));
+                            ui.end_row();
+                            if let Some(wwn) = &record.wwn {
+                                ui.label("WWN:");
+                                ui.label(wwn);
+                                ui.end_row();
+                            }
+                            if let Some(by_id) = &record.by_id {
+                                ui.label("ID:");
+                                ui.label(by_id);
+                                ui.end_row();
+                            }
+                            ui.label("Size:");
+                            ui.label(
+                                record.size.map(StorageDriver::format_size).unwrap_or_else(|| "Unknown".to_string()),
+                            );
+                            ui.end_row();
+                            ui.label("Status:");
+                            ui.label(&target.status);
+                            ui.end_row();
+                        });
+                        if let Some(warning) = &target.warning {
+                            ui.label(RichText::new(format!("⚠ {}", warning)).color(Color32::RED).strong());
+                        }
+                        if let Some(warning) = &target.health_warning {
+                            ui.label(RichText::new(format!("⚠ {}", warning)).color(Color32::DARK_RED));
+                        }
+
+                        if confirmation.reviewed {
+                            let what = if record.serial_number.is_some() {
+                                format!("last {} characters of the serial", target.code.chars().count())
+                            } else {
+                                "device name".to_string()
+                            };
+                            ui.horizontal(|ui| {
+                                ui.label(format!("Type the {}:", what));
+                                ui.add(egui::TextEdit::singleline(&mut target.typed).desired_width(80.0));
+                                if target.typed.trim() == target.code {
+                                    ui.label(RichText::new("✔").color(Color32::DARK_GREEN));
+                                }
+                            });
+                        }
+                    });
+                }
+
+                ui.add_space(10.0);
+                ui.horizontal(|ui| {
+                    cancel |= ui.button("Cancel").clicked();
+                    if !confirmation.reviewed {
+                        confirmation.reviewed = ui.button("Continue").clicked();
+                    } else {
+                        let erase = egui::Button::new(RichText::new("Erase").color(Color32::RED));
+                        start = ui.add_enabled(confirmation.confirmed(), erase).clicked();
+                    }
+                });
+            });
+
+        if start && let Some(confirmation) = self.confirmation.take() {
+            self.start_erase(confirmation);
+        }
+        if cancel {
+            self.confirmation = None;
+        }
+    }
+
+    fn start_erase(&mut self, confirmation: Confirmation) {
+        let resume = confirmation.resume;
+        let devices: Vec<BatchDevice> =
+            confirmation.targets.into_iter().map(|target| target.device).collect();
+        let jobs = devices
+            .iter()
+            .map(|device| JobView {
+                path: device.record.path.clone(),
+                running: false,
+                paused: false,
+                pause_window: None,
+                tracker: ProgressTracker::new(),
+                result: None,
+            })
+            .collect();
+
+        let mut config = BatchConfig {
+            operator: self.operator.trim().to_string(),
+            method: self.selected_wipe_method.clone(),
+            standard: self.selected_standard,
+            verify: self.selected_verify_mode,
+            error_policy: self.selected_error_policy,
+            max_jobs_per_controller: self.jobs_per_controller,
+            allow_system_disk: self.allow_system_disk,
+            report_dir: default_report_dir(),
+            checkpoint_dir: Some(default_checkpoint_dir()),
+            io: IoConfig { throttle: Throttle::new(self.throttle.clone()), ..self.io.clone() },
+        };
+        if let Some(checkpoint) = &resume {
+            config.method = WipeMethod::Overwrite;
+            config.standard = checkpoint.standard;
+            config.verify = checkpoint.verify;
+            config.error_policy = checkpoint.error_policy;
+        }
+
+        // Keep what the hex viewer shows to compare with after the erase
+        if let Some(viewer) = &mut self.hex_viewer
+            && viewer.baseline.is_none()
+            && devices.iter().any(|device| device.record.path == viewer.path)
+            && let Some(Ok(page)) = &viewer.page
+        {
+            viewer.baseline = Some(page.clone());
+        }
+
+        let cms = match (self.cms_key.trim(), self.cms_chain.trim()) {
+            ("", _) | (_, "") => None,
+            (key, chain) => Some((PathBuf::from(key), PathBuf::from(chain))),
+        };
+        self.runner.send(Command::StartBatch { devices, config, cms });
+        self.batch = Some(BatchView {
+            jobs,
+            summary: None,
+            report: None,
+        });
+    }
+
+    fn load_checkpoints(&mut self) {
+        self.checkpoints = CheckpointStore::open_default().map(|store| store.list()).unwrap_or_default();
+    }
+
+    // Offers each interrupted overwrite for resuming on the drive it was
+    // taken on, wherever that drive is now
+    fn show_checkpoints(&mut self, ui: &mut egui::Ui) {
+        let mut resume = None;
+        let mut discard = None;
+        ui.group(|ui| {
+            ui.label(RichText::new("Interrupted Erases").strong());
+            for (index, checkpoint) in self.checkpoints.iter().enumerate() {
+                let device = self.available_devices.iter().find(|device| {
+                    checkpoint.matches(&device.path, &DeviceIdentity::from(*device))
+                });
+                ui.horizontal(|ui| {
+                    ui.label(format!(
+                        "{} ({}): {}, {:.1}% written, saved {}",
+                        device.map_or(&checkpoint.path, |device| &device.path),
+                        checkpoint.identity.serial.as_deref().unwrap_or("no serial"),
+                        checkpoint.standard,
+                        checkpoint.fraction() * 100.0,
+                        checkpoint.saved_at
+                    ));
+                    match device {
+                        Some(device) if !device.usage.blocks_erase(self.allow_system_disk) => {
+                            if ui.button("Resume").clicked() {
+                                resume = Some((index, device.path.clone()));
+                            }
+                        }
+                        Some(device) => {
+                            ui.label(RichText::new(device.usage.describe()).color(Color32::DARK_RED));
+                        }
+                        None => {
+                            ui.label(RichText::new("drive not connected").color(Color32::GRAY));
+                        }
+                    }
+                    if ui.button("Discard").clicked() {
+                        discard = Some(index);
+                    }
+                });
+            }
+        });
+
+        if let Some((index, path)) = resume {
+            let checkpoint = self.checkpoints[index].clone();
+            let devices = self.available_devices.iter().filter(|device| device.path == path).collect();
+            let confirmation = Confirmation::new(devices, &self.health, &WipeMethod::Overwrite);
+            self.confirmation = Some(confirmation.resuming(checkpoint));
+        }
+        if let Some(index) = discard {
+            let checkpoint = self.checkpoints.remove(index);
+            if let Err(e) = CheckpointStore::open_default().and_then(|store| store.remove(&checkpoint)) {
+                self.activity.push((format!("Discarding checkpoint of {}: {}", checkpoint.path, e), true));
+            }
+        }
+    }
+
+    fn show_batch(&mut self, ui: &mut egui::Ui) {
+        let Some(batch) = &self.batch else {
+            return;
+        };
+
+        let mut close = false;
+        ui.group(|ui| {
+            ui.horizontal(|ui| {
+                ui.label(RichText::new("Erase Session").size(18.0).color(Color32::BLACK).strong());
+                ui.with_layout(Layout::right_to_left(Align::Center), |ui| {
+                    if batch.summary.is_some() {
+                        close = ui.button("Close").clicked();
+                    } else if ui.button("Cancel All").clicked() {
+                        self.runner.send(Command::CancelAll);
+                    }
+                });
+            });
+            if batch.summary.is_none() {
+                egui::CollapsingHeader::new("Throttling").id_salt("batch_throttle").show(ui, |ui| {
+                    if show_throttle(ui, "batch", &mut self.throttle, &mut self.pause_windows) {
+                        self.runner.send(Command::SetThrottle(self.throttle.clone()));
+                    }
+                });
+            }
+            ui.separator();
+
+            egui::ScrollArea::vertical().max_height(ui.available_height() - 80.0).show(ui, |ui| {
+                for (index, job) in batch.jobs.iter().enumerate() {
+                    ui.add_space(5.0);
+                    if let Some(command) = show_job(ui, index, job) {
+                        self.runner.send(command);
+                    }
+                }
+            });
+
+            if let Some(summary) = &batch.summary {
+                ui.add_space(5.0);
+                ui.label(format!(
+                    "Batch {} finished: {} passed, {} failed, {} cancelled",
+                    summary.batch_id,
+                    summary.count(JobOutcome::Passed),
+                    summary.count(JobOutcome::Failed),
+                    summary.count(JobOutcome::Cancelled)
+                ));
+                match &batch.report {
+                    Some(Ok(path)) => {
+                        ui.label(format!("Report saved to {}", path.display()));
+                    }
+                    Some(Err(e)) => {
+                        ui.label(RichText::new(format!("Failed to save report: {}", e)).color(Color32::RED));
+                    }
+                    None => {}
+                }
+            }
+        });
+
+        if close {
+            self.batch = None;
+        }
+    }
+
+    fn open_hex_viewer(&mut self, path: String) {
+        self.hex_viewer = Some(HexViewer {
+            path,
+            lba: 0,
+            lba_input: "0".to_string(),
+            page: None,
+            baseline: None,
+        });
+        self.read_sectors(0);
+    }
+
+    fn read_sectors(&mut self, lba: u64) {
+        let Some(viewer) = &mut self.hex_viewer else {
+            return;
+        };
+        let sector_size = match self.partitions.get(&viewer.path) {
+            Some(Some(Ok(layout))) => layout.sector_size,
+            _ => self
+                .available_devices
+                .iter()
+                .find(|device| device.path == viewer.path)
+                .and_then(|device| device.geometry)
+                .map_or(512, |geometry| geometry.logical_block_size as u64),
+        };
+        viewer.lba = lba;
+        viewer.lba_input = lba.to_string();
+        viewer.page = None;
+        self.runner.send(Command::ReadSectors {
+            path: viewer.path.clone(),
+            sector_size,
+            lba,
+            count: HEX_PAGE_SECTORS,
+        });
+    }
+
+    fn show_hex_viewer(&mut self, ctx: &egui::Context) {
+        let Some(viewer) = &mut self.hex_viewer else {
+            return;
+        };
+        let layout = match self.partitions.get(&viewer.path) {
+            Some(Some(Ok(layout))) => Some(layout),
+            _ => None,
+        };
+        let last_lba = match &viewer.page {
+            Some(Ok(page)) => page.total_sectors.saturating_sub(1),
+            _ => layout.map(|layout| layout.total_sectors - 1).unwrap_or(0),
+        };
+
+        let mut open = true;
+        let mut goto = None;
+        egui::Window::new(format!("Hex Viewer: {}", viewer.path))
+            .open(&mut open)
+            .default_width(1000.0)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    let lba = viewer.lba;
+                    if ui.add_enabled(lba > 0, egui::Button::new("⏮")).clicked() {
+                        goto = Some(0);
+                    }
+                    if ui.add_enabled(lba > 0, egui::Button::new("◀ Prev")).clicked() {
+                        goto = Some(lba.saturating_sub(HEX_PAGE_SECTORS));
+                    }
+                    if ui.add_enabled(lba < last_lba, egui::Button::new("Next ▶")).clicked() {
+                        goto = Some((lba + HEX_PAGE_SECTORS).min(last_lba));
+                    }
+                    if ui.add_enabled(lba < last_lba, egui::Button::new("⏭")).clicked() {
+                        goto = Some(last_lba);
+                    }
+                    ui.separator();
+                    ui.label("LBA:");
+                    let input = ui.add(egui::TextEdit::singleline(&mut viewer.lba_input).desired_width(110.0));
+                    let entered = input.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+                    if (ui.button("Go").clicked() || entered)
+                        && let Ok(lba) = viewer.lba_input.trim().parse::<u64>()
                     {
-                        // TODO: Implement erase logic
-                        if let Some(selected_index) = self.selected_device_index {
-                            if let Some(device) = self.available_devices.get(selected_index) {
-                                println!(
-                                    "Start Erase clicked for device: {} with method: {}",
-                                    device.name, self.selected_wipe_method
+                        goto = Some(lba.min(last_lba));
+                    }
+                    if let Some(layout) = layout {
+                        egui::ComboBox::from_id_salt("hex_jump")
+                            .selected_text("Jump to...")
+                            .show_ui(ui, |ui| {
+                                for (label, lba) in jump_targets(layout) {
+                                    if ui.selectable_label(false, label).clicked() {
+                                        goto = Some(lba);
+                                    }
+                                }
+                            });
+                    }
+                });
+
+                ui.horizontal(|ui| {
+                    let page = viewer.page.as_ref().and_then(|page| page.as_ref().ok());
+                    if ui.add_enabled(page.is_some(), egui::Button::new("📌 Capture for comparison")).clicked() {
+                        viewer.baseline = page.cloned();
+                    }
+                    if let Some(baseline) = &viewer.baseline {
+                        if baseline.lba != viewer.lba && ui.button(format!("Go to captured LBA {}", baseline.lba)).clicked() {
+                            goto = Some(baseline.lba);
+                        }
+                        if ui.button("Clear comparison").clicked() {
+                            viewer.baseline = None;
+                        }
+                    }
+                    if let Some(page) = page {
+                        let source = if page.direct { "direct read" } else { "buffered read" };
+                        ui.label(
+                            RichText::new(format!("LBA {} of {}, {}", page.lba, last_lba, source)).color(Color32::GRAY),
+                        );
+                    }
+                });
+                ui.separator();
+
+                match &viewer.page {
+                    None => {
+                        ui.label("Reading...");
+                    }
+                    Some(Err(e)) => {
+                        ui.label(RichText::new(format!("Read failed: {}", e)).color(Color32::RED));
+                    }
+                    Some(Ok(page)) => {
+                        let highlights = find_structures(page, layout);
+                        ui.horizontal_wrapped(|ui| {
+                            for highlight in &highlights {
+                                ui.label(
+                                    RichText::new(&highlight.label)
+                                        .background_color(structure_color(highlight.kind))
+                                        .color(Color32::BLACK),
                                 );
                             }
+                        });
+                        egui::ScrollArea::vertical().max_height(560.0).show(ui, |ui| {
+                            ui.horizontal_top(|ui| match &viewer.baseline {
+                                Some(baseline) => {
+                                    let compared = baseline.lba == page.lba;
+                                    ui.vertical(|ui| {
+                                        ui.label(RichText::new(format!("Captured, LBA {}", baseline.lba)).strong());
+                                        hex_dump(ui, baseline, &find_structures(baseline, layout), None);
+                                    });
+                                    ui.add_space(20.0);
+                                    ui.vertical(|ui| {
+                                        ui.label(RichText::new(format!("Current, LBA {}", page.lba)).strong());
+                                        let other = compared.then_some(baseline.data.as_slice());
+                                        hex_dump(ui, page, &highlights, other);
+                                    });
+                                }
+                                None => {
+                                    ui.vertical(|ui| hex_dump(ui, page, &highlights, None));
+                                }
+                            });
+                        });
+                    }
+                }
+            });
+
+        if !open {
+            self.hex_viewer = None;
+        } else if let Some(lba) = goto {
+            self.read_sectors(lba);
+        }
+    }
+
+    fn scan_data_map(&mut self, path: String) {
+        self.data_maps.entry(path.clone()).or_default().start();
+        self.runner.send(Command::ScanDataMap { path });
+    }
+
+    fn show_data_map(&mut self, ctx: &egui::Context) {
+        let Some(path) = self.data_map_open.clone() else {
+            return;
+        };
+        let erased = self.erased.contains(&path);
+        let view = self.data_maps.entry(path.clone()).or_default();
+
+        let mut open = true;
+        let mut scan = false;
+        egui::Window::new(format!("Data Map: {}", path))
+            .open(&mut open)
+            .default_width(620.0)
+            .show(ctx, |ui| {
+                ui.label(
+                    RichText::new(
+                        "Samples every region of the disk. Take a map before erasing; \
+                         one is taken again automatically when the erase finishes.",
+                    )
+                    .color(Color32::GRAY),
+                );
+                ui.horizontal(|ui| {
+                    let label = if erased { "Scan after erase" } else { "Scan before erase" };
+                    if ui.add_enabled(view.scanning.is_none(), egui::Button::new(label)).clicked() {
+                        scan = true;
+                    }
+                    if let Some(fraction) = view.scanning {
+                        ui.add(egui::ProgressBar::new(fraction).show_percentage().desired_width(200.0));
+                    }
+                });
+                if let Some(e) = &view.error {
+                    ui.label(RichText::new(format!("Scan failed: {}", e)).color(Color32::RED));
+                }
+                for (title, map) in [("Before erase", &view.before), ("After erase", &view.after)] {
+                    if let Some(map) = map {
+                        ui.add_space(10.0);
+                        ui.label(RichText::new(title).size(16.0).color(Color32::BLACK).strong());
+                        show_heat_map(ui, map);
+                    }
+                }
+                ui.add_space(10.0);
+                ui.horizontal_wrapped(|ui| {
+                    for class in [
+                        RegionClass::Zero,
+                        RegionClass::Uniform,
+                        RegionClass::LowEntropy,
+                        RegionClass::Data,
+                        RegionClass::HighEntropy,
+                    ] {
+                        ui.label(RichText::new("■").color(class_color(class)));
+                        ui.label(class.to_string());
+                    }
+                    ui.label("● file signature");
+                });
+            });
+
+        if !open {
+            self.data_map_open = None;
+        } else if scan {
+            self.scan_data_map(path);
+        }
+    }
+
+    fn scan_forensics(&mut self, path: String) {
+        self.forensics.entry(path.clone()).or_default().start();
+        self.runner.send(Command::ScanForensics { path });
+    }
+
+    fn show_forensics(&mut self, ctx: &egui::Context) {
+        let Some(path) = self.forensics_open.clone() else {
+            return;
+        };
+        let erased = self.erased.contains(&path);
+        let view = self.forensics.entry(path.clone()).or_default();
+
+        let mut open = true;
+        let mut scan = false;
+        egui::Window::new(format!("Forensic Preview: {}", path))
+            .open(&mut open)
+            .default_width(620.0)
+            .show(ctx, |ui| {
+                ui.label(
+                    RichText::new(
+                        "Looks for what could still be recovered: deleted files in ext and NTFS \
+                         filesystems, and files carved from space outside any filesystem. \
+                         Nothing is written to the device.",
+                    )
+                    .color(Color32::GRAY),
+                );
+                ui.horizontal(|ui| {
+                    let label = if erased { "Scan after erase" } else { "Scan before erase" };
+                    if ui.add_enabled(view.scanning.is_none(), egui::Button::new(label)).clicked() {
+                        scan = true;
+                    }
+                    if let Some(fraction) = view.scanning {
+                        ui.add(egui::ProgressBar::new(fraction).show_percentage().desired_width(200.0));
+                    }
+                });
+                if let Some(e) = &view.error {
+                    ui.label(RichText::new(format!("Scan failed: {}", e)).color(Color32::RED));
+                }
+                egui::ScrollArea::vertical().max_height(480.0).show(ui, |ui| {
+                    for (title, report) in [("Before erase", &view.before), ("After erase", &view.after)] {
+                        if let Some(report) = report {
+                            ui.add_space(10.0);
+                            ui.label(RichText::new(title).size(16.0).color(Color32::BLACK).strong());
+                            show_forensic_report(ui, report);
                         }
                     }
                 });
             });
+
+        if !open {
+            self.forensics_open = None;
+        } else if scan {
+            self.scan_forensics(path);
+        }
+    }
+
+    fn record_audit(&self, event: AuditEvent) {
+        self.runner.send(Command::Audit(event));
+    }
+}
+
+// Rate limits, I/O priority and pause windows; returns whether the operator
+// changed them. `windows` is the text being edited, applied once it parses.
+fn show_throttle(ui: &mut egui::Ui, id: &str, settings: &mut ThrottleSettings, windows: &mut String) -> bool {
+    let mut changed = false;
+    let limits = &mut settings.limits;
+    ui.horizontal(|ui| {
+        ui.label("Max MB/s:");
+        let mut rate = limits.bytes_per_second.map_or(0, |rate| rate >> 20);
+        if ui.add(egui::DragValue::new(&mut rate).range(0..=100_000)).on_hover_text("0 is unlimited").changed() {
+            limits.bytes_per_second = (rate > 0).then_some(rate << 20);
+            changed = true;
+        }
+        ui.label("Max IOPS:");
+        let mut iops = limits.iops.unwrap_or(0);
+        if ui.add(egui::DragValue::new(&mut iops).range(0..=1_000_000)).on_hover_text("0 is unlimited").changed() {
+            limits.iops = (iops > 0).then_some(iops);
+            changed = true;
+        }
+    });
+    ui.horizontal(|ui| {
+        ui.label("I/O priority:");
+        egui::ComboBox::from_id_salt(format!("{}_io_priority", id))
+            .selected_text(limits.priority.to_string())
+            .show_ui(ui, |ui| {
+                for priority in IoPriority::get_all_priorities() {
+                    changed |= ui.selectable_value(&mut limits.priority, priority, priority.to_string()).changed();
+                }
+            });
+    });
+    ui.horizontal(|ui| {
+        ui.label("Pause during:");
+        let edit = ui.text_edit_singleline(windows).on_hover_text("Daily local times, e.g. 08:00-18:00, 20:00-22:00");
+        match parse_windows(windows) {
+            Ok(parsed) if edit.changed() => {
+                settings.pause_windows = parsed;
+                changed = true;
+            }
+            Ok(_) => {}
+            Err(e) => {
+                ui.label(RichText::new(e).color(Color32::RED));
+            }
+        }
+    });
+    changed
+}
+
+// One card of the erase session; returns the command of a clicked button
+fn show_job(ui: &mut egui::Ui, index: usize, job: &JobView) -> Option<Command> {
+    let mut command = None;
+    ui.group(|ui| {
+        ui.set_min_width(ui.available_width());
+        ui.horizontal(|ui| {
+            ui.label(RichText::new(format!("💾 {}", job.path)).strong());
+            match job.pause_window {
+                Some(window) => {
+                    let until = window.to_string();
+                    let until = until.split('-').next_back().unwrap_or_default();
+                    ui.label(RichText::new(format!("Paused by schedule until {}", until)).color(Color32::DARK_BLUE));
+                }
+                None if job.paused => {
+                    ui.label(RichText::new("Paused").color(Color32::DARK_BLUE));
+                }
+                None => {}
+            }
+            ui.with_layout(Layout::right_to_left(Align::Center), |ui| {
+                if job.result.is_some() {
+                    return;
+                }
+                if ui.button("Cancel").clicked() {
+                    command = Some(Command::Cancel { job: index });
+                }
+                // Firmware erases run inside the drive and cannot be held
+                let firmware = job.tracker.latest().is_some_and(|p| p.firmware.is_some());
+                if job.running && !firmware && job.pause_window.is_none() {
+                    if job.paused {
+                        if ui.button("Resume").clicked() {
+                            command = Some(Command::Resume { job: index });
+                        }
+                    } else if ui.button("Pause").clicked() {
+                        command = Some(Command::Pause { job: index });
+                    }
+                }
+            });
+        });
+
+        if let Some(result) = &job.result {
+            let color = match result.outcome {
+                JobOutcome::Passed => Color32::DARK_GREEN,
+                _ => Color32::RED,
+            };
+            let mut text = result.outcome.to_string();
+            if let Some(error) = &result.error {
+                text += &format!(": {}", error);
+            }
+            if let Some(id) = &result.certificate_id {
+                text += &format!(" (certificate {})", id);
+            }
+            ui.label(RichText::new(text).color(color));
+        }
+
+        let Some(progress) = job.tracker.latest() else {
+            ui.label(if job.running { "Starting..." } else { "Queued (waiting for controller)" });
+            return;
+        };
+        let stats = job.tracker.stats();
+        let eta = stats.eta.map(format_duration).unwrap_or_else(|| "--".to_string());
+
+        let bar_text = match &progress.firmware {
+            Some(firmware) => match firmware.reported {
+                Some(done) => format!("{} {:.1}%", firmware.operation, done * 100.0),
+                None => format!("{} (drive does not report progress)", firmware.operation),
+            },
+            None => format!("{:.1}%", progress.overall_fraction() * 100.0),
+        };
+        // Without drive-reported progress, fill the bar from the estimate
+        let fraction = match (&progress.firmware, stats.eta) {
+            (Some(firmware), Some(eta)) if firmware.reported.is_none() => {
+                let total = stats.elapsed + eta;
+                stats.elapsed.as_secs_f32() / total.as_secs_f32().max(1.0)
+            }
+            _ => progress.overall_fraction(),
+        };
+        ui.add(egui::ProgressBar::new(fraction).text(bar_text));
+
+        egui::Grid::new(("job_stats", index)).num_columns(4).spacing([20.0, 4.0]).show(ui, |ui| {
+            if let Some(firmware) = &progress.firmware {
+                ui.label("Operation:");
+                ui.label(&firmware.operation);
+                ui.label("Drive estimate:");
+                ui.label(firmware.estimated.map(format_duration).unwrap_or_else(|| "not reported".to_string()));
+                ui.end_row();
+            } else {
+                ui.label(format!("{}:", progress.phase));
+                ui.label(format!(
+                    "pass {}/{} ({}) {:.0}%",
+                    progress.pass,
+                    progress.passes,
+                    progress.pattern,
+                    progress.fraction() * 100.0
+                ));
+                ui.label("Written / verified:");
+                ui.label(format!(
+                    "{} / {}",
+                    StorageDriver::format_size(progress.bytes_written),
+                    StorageDriver::format_size(progress.bytes_verified)
+                ));
+                ui.end_row();
+
+                ui.label("Speed:");
+                ui.label(format_rate(stats.current_rate));
+                ui.label("Average:");
+                ui.label(format_rate(stats.average_rate));
+                ui.end_row();
+            }
+
+            ui.label("Elapsed:");
+            ui.label(format_duration(stats.elapsed));
+            ui.label("ETA:");
+            ui.label(eta);
+            ui.end_row();
         });
+    });
+    command
+}
 
-        // request a redraw for UI updates
-        ctx.request_repaint();
+fn show_health(ui: &mut egui::Ui, health: &HealthReport) {
+    let count = |value: Option<u64>| value.map_or_else(|| "-".to_string(), |v| v.to_string());
+    match health.passed {
+        Some(true) => ui.label(RichText::new(format!("✔ {}: passed", health.source)).color(Color32::DARK_GREEN)),
+        Some(false) => ui.label(RichText::new(format!("✖ {}: failure predicted", health.source)).color(Color32::RED)),
+        None => ui.label(format!("{}: no overall assessment", health.source)),
+    };
+    egui::Grid::new("health").num_columns(2).show(ui, |ui| {
+        ui.label("Reallocated:");
+        ui.label(count(health.reallocated_sectors));
+        ui.end_row();
+        ui.label("Pending:");
+        ui.label(count(health.pending_sectors));
+        ui.end_row();
+        ui.label("Media errors:");
+        ui.label(count(health.media_errors));
+        ui.end_row();
+        ui.label("Power-on hours:");
+        ui.label(count(health.power_on_hours));
+        ui.end_row();
+        if let Some(percent) = health.percent_used {
+            ui.label("Wear:");
+            ui.label(format!("{}% used", percent));
+            ui.end_row();
+        }
+        if let Some(temperature) = health.temperature_celsius {
+            ui.label("Temperature:");
+            ui.label(format!("{} °C", temperature));
+            ui.end_row();
+        }
+    });
+    for warning in health.warnings() {
+        ui.label(RichText::new(format!("⚠ {}", warning)).color(Color32::DARK_RED));
     }
 }
 
-impl SecureWipeApp {
-    fn refresh_devices(&mut self) {
-        // Use actual device detection
-        self.available_devices = StorageDriver::detect_storage_devices();
-
-        // Reset selection to first device if available
-        if !self.available_devices.is_empty() {
-            self.selected_device_index = Some(0);
-        } else {
-            self.selected_device_index = None;
+fn show_partitions(ui: &mut egui::Ui, layout: &PartitionLayout) {
+    ui.label(format!("🗂 {}, {} byte sectors", layout.scheme, layout.sector_size));
+    partition_bar(ui, layout, 24.0);
+    for index in 0..layout.partitions.len() {
+        let label = format!("■ {}", partition_label(layout, index));
+        ui.label(RichText::new(label).color(region_color(RegionKind::Partition(index))));
+    }
+    let free: u64 = layout.gaps().map(|region| region.sectors()).sum();
+    ui.label(format!("Unallocated: {}", StorageDriver::format_size(free * layout.sector_size)));
+    for problem in &layout.problems {
+        ui.label(RichText::new(format!("⚠ {}", problem)).color(Color32::DARK_RED));
+    }
+}
+
+// What each partition holds, so the operator sees what an erase destroys
+fn show_volumes(ui: &mut egui::Ui, volumes: &[Volume]) {
+    ui.add_space(10.0);
+    ui.label(RichText::new("Contents").size(16.0).color(Color32::BLACK).strong());
+    ui.add_space(5.0);
+    for volume in volumes {
+        let place = match volume.partition {
+            Some(number) => format!("#{}", number),
+            None => "Whole disk".to_string(),
+        };
+        let Some(fs) = &volume.filesystem else {
+            ui.label(RichText::new(format!("{}: no known filesystem", place)).color(Color32::GRAY));
+            continue;
+        };
+        let label = fs.label.as_ref().map(|label| format!(" \"{}\"", label)).unwrap_or_default();
+        ui.label(RichText::new(format!("📁 {}: {}{}", place, fs.kind, label)).strong());
+        ui.indent(("volume", volume.offset), |ui| {
+            if let Some(uuid) = &fs.uuid {
+                ui.label(format!("UUID: {}", uuid));
+            }
+            match (fs.used, fs.size) {
+                (Some(used), Some(size)) => {
+                    ui.label(format!(
+                        "Used: {} of {}",
+                        StorageDriver::format_size(used),
+                        StorageDriver::format_size(size)
+                    ));
+                }
+                (None, Some(size)) => {
+                    ui.label(format!("Size: {}", StorageDriver::format_size(size)));
+                }
+                _ => {}
+            }
+            if let Some(mounted) = fs.last_mounted {
+                let format = time::macros::format_description!("[year]-[month]-[day] [hour]:[minute] UTC");
+                let at = fs.last_mount_point.as_ref().map(|point| format!(" at {}", point)).unwrap_or_default();
+                ui.label(format!("Last mounted: {}{}", mounted.format(&format).unwrap_or_default(), at));
+            }
+        });
+    }
+}
+
+// Regions drawn in proportion to their size, at least a pixel wide so small
+// partitions stay visible. The outline turns red when partitions overlap.
+fn partition_bar(ui: &mut egui::Ui, layout: &PartitionLayout, height: f32) {
+    let (rect, response) = ui.allocate_exact_size(Vec2::new(ui.available_width(), height), egui::Sense::hover());
+    let painter = ui.painter_at(rect);
+    let total = layout.total_sectors as f32;
+    let mut hovered = None;
+    for region in &layout.regions {
+        let left = rect.left() + rect.width() * region.first_lba as f32 / total;
+        let right = rect.left() + rect.width() * (region.last_lba + 1) as f32 / total;
+        let segment = egui::Rect::from_x_y_ranges(left..=right.max(left + 1.0), rect.y_range());
+        painter.rect_filled(segment, 0.0, region_color(region.kind));
+        if response.hover_pos().is_some_and(|pos| segment.contains(pos)) {
+            hovered = Some(region);
+        }
+    }
+    let outline = if layout.overlaps.is_empty() { Color32::GRAY } else { Color32::RED };
+    painter.rect_stroke(rect, 0.0, egui::Stroke::new(1.0, outline), egui::StrokeKind::Inside);
+    if let Some(region) = hovered {
+        response.on_hover_text(region_label(layout, region));
+    }
+}
+
+fn region_color(kind: RegionKind) -> Color32 {
+    const PALETTE: [Color32; 6] = [
+        Color32::from_rgb(66, 133, 244),
+        Color32::from_rgb(52, 168, 83),
+        Color32::from_rgb(244, 160, 0),
+        Color32::from_rgb(171, 71, 188),
+        Color32::from_rgb(0, 172, 193),
+        Color32::from_rgb(255, 112, 67),
+    ];
+    match kind {
+        RegionKind::Metadata => Color32::DARK_GRAY,
+        RegionKind::Free => Color32::from_gray(225),
+        RegionKind::Partition(index) => PALETTE[index % PALETTE.len()],
+    }
+}
+
+fn region_label(layout: &PartitionLayout, region: &Region) -> String {
+    let size = |sectors: u64| StorageDriver::format_size(sectors * layout.sector_size);
+    match region.kind {
+        RegionKind::Metadata => format!("Partition table, {}", size(region.sectors())),
+        RegionKind::Free => format!("Unallocated, {}", size(region.sectors())),
+        RegionKind::Partition(index) => partition_label(layout, index),
+    }
+}
+
+fn partition_label(layout: &PartitionLayout, index: usize) -> String {
+    let partition = &layout.partitions[index];
+    let name = partition.name.as_ref().map(|name| format!(" \"{}\"", name)).unwrap_or_default();
+    format!(
+        "#{} {}{}, {} ({:?})",
+        partition.number,
+        partition.kind.description(),
+        name,
+        StorageDriver::format_size(partition.sectors() * layout.sector_size),
+        partition.role
+    )
+}
+
+// Offset, hex and ASCII columns, 16 bytes a row. Bytes that differ from
+// `compare` are marked red, otherwise known structures are shaded.
+fn hex_dump(ui: &mut egui::Ui, page: &SectorPage, highlights: &[Highlight], compare: Option<&[u8]>) {
+    let font = egui::FontId::monospace(12.0);
+    let plain = egui::TextFormat { font_id: font.clone(), color: Color32::BLACK, ..Default::default() };
+    ui.spacing_mut().item_spacing.y = 0.0;
+    for (row, bytes) in page.data.chunks(16).enumerate() {
+        let row_offset = page.offset() + row as u64 * 16;
+        let background = |index: usize| {
+            let offset = row * 16 + index;
+            if compare.is_some_and(|other| other.get(offset) != Some(&bytes[index])) {
+                Color32::from_rgb(255, 138, 128)
+            } else {
+                highlights
+                    .iter()
+                    .find(|highlight| highlight.contains(row_offset + index as u64))
+                    .map(|highlight| structure_color(highlight.kind))
+                    .unwrap_or(Color32::TRANSPARENT)
+            }
+        };
+        let shaded = |index: usize| egui::TextFormat { background: background(index), ..plain.clone() };
+
+        let mut job = egui::text::LayoutJob::default();
+        job.append(
+            &format!("{:010X}  ", row_offset),
+            0.0,
+            egui::TextFormat { color: Color32::GRAY, ..plain.clone() },
+        );
+        for (index, byte) in bytes.iter().enumerate() {
+            job.append(&format!("{:02X}", byte), 0.0, shaded(index));
+            job.append(if index == 7 { "  " } else { " " }, 0.0, plain.clone());
+        }
+        job.append(" ", 0.0, plain.clone());
+        for (index, byte) in bytes.iter().enumerate() {
+            let c = if byte.is_ascii_graphic() || *byte == b' ' { *byte as char } else { '.' };
+            job.append(&c.to_string(), 0.0, shaded(index));
+        }
+        ui.label(job);
+    }
+}
+
+fn structure_color(kind: StructureKind) -> Color32 {
+    match kind {
+        StructureKind::PartitionTable => Color32::from_rgb(255, 224, 178),
+        StructureKind::BootSignature => Color32::from_rgb(255, 183, 77),
+        StructureKind::GptHeader => Color32::from_rgb(179, 229, 252),
+        StructureKind::GptEntries => Color32::from_rgb(225, 245, 254),
+        StructureKind::Filesystem => Color32::from_rgb(200, 230, 201),
+    }
+}
+
+// Places worth jumping to in the hex viewer
+fn jump_targets(layout: &PartitionLayout) -> Vec<(String, u64)> {
+    let mut targets = vec![("Partition table (LBA 0)".to_string(), 0)];
+    if let PartitionScheme::Gpt { primary, backup, .. } = &layout.scheme {
+        for (label, header) in [("Primary", primary), ("Backup", backup)] {
+            if let Some(header) = header {
+                targets.push((format!("{} GPT header", label), header.lba));
+                targets.push((format!("{} GPT entries", label), header.entries_lba));
+            }
+        }
+    }
+    for index in 0..layout.partitions.len() {
+        targets.push((partition_label(layout, index), layout.partitions[index].first_lba));
+    }
+    targets
+}
+
+// One cell per region, left to right and top to bottom from the start of the
+// disk; hover a cell for its numbers
+fn show_heat_map(ui: &mut egui::Ui, map: &DataMap) {
+    const COLUMNS: usize = 32;
+    const CELL: f32 = 16.0;
+    let rows = map.regions.len().div_ceil(COLUMNS);
+    let (rect, response) =
+        ui.allocate_exact_size(Vec2::new(COLUMNS as f32 * CELL, rows as f32 * CELL), egui::Sense::hover());
+    let painter = ui.painter_at(rect);
+    let mut hovered = None;
+    for (index, region) in map.regions.iter().enumerate() {
+        let min = rect.min + Vec2::new((index % COLUMNS) as f32 * CELL, (index / COLUMNS) as f32 * CELL);
+        let cell = egui::Rect::from_min_size(min, Vec2::splat(CELL)).shrink(1.0);
+        painter.rect_filled(cell, 2.0, region_heat(region));
+        if !region.signatures.is_empty() {
+            painter.circle_filled(cell.center(), 3.0, Color32::WHITE);
+        }
+        if response.hover_pos().is_some_and(|pos| cell.contains(pos)) {
+            hovered = Some(region);
+        }
+    }
+    if let Some(region) = hovered {
+        let mut text = format!(
+            "{} - {}\n{}, entropy {:.2} bits/byte, {:.1}% zero",
+            StorageDriver::format_size(region.offset),
+            StorageDriver::format_size(region.offset + region.len),
+            region.class(),
+            region.entropy,
+            region.zero_ratio * 100.0
+        );
+        if let Some(fill) = region.fill {
+            text.push_str(&format!("\nFilled with 0x{:02X}", fill));
+        }
+        if !region.signatures.is_empty() {
+            text.push_str(&format!("\nFile signatures: {}", region.signatures.join(", ")));
+        }
+        response.on_hover_text(text);
+    }
+
+    let regions = map.regions.len();
+    let erased = map.count(RegionClass::Zero) + map.count(RegionClass::Uniform);
+    ui.label(format!(
+        "{} regions of {}, {} sampled: {} zero or uniform, {} high entropy, {} with file signatures",
+        regions,
+        StorageDriver::format_size(map.size / regions.max(1) as u64),
+        StorageDriver::format_size(map.bytes_sampled()),
+        erased,
+        map.count(RegionClass::HighEntropy),
+        map.regions_with_signatures()
+    ));
+}
+
+fn class_color(class: RegionClass) -> Color32 {
+    match class {
+        RegionClass::Zero => Color32::from_rgb(30, 60, 140),
+        RegionClass::Uniform => Color32::from_rgb(90, 150, 220),
+        RegionClass::LowEntropy => Color32::from_rgb(255, 235, 59),
+        RegionClass::Data => Color32::from_rgb(251, 140, 0),
+        RegionClass::HighEntropy => Color32::from_rgb(211, 47, 47),
+    }
+}
+
+// Filled regions are cold colours; data runs from yellow to red with entropy
+fn region_heat(region: &RegionStats) -> Color32 {
+    match region.class() {
+        class @ (RegionClass::Zero | RegionClass::Uniform) => class_color(class),
+        _ => {
+            let low = class_color(RegionClass::LowEntropy);
+            let high = class_color(RegionClass::HighEntropy);
+            low.lerp_to_gamma(high, region.entropy / 8.0)
+        }
+    }
+}
+
+fn show_forensic_report(ui: &mut egui::Ui, report: &ForensicReport) {
+    if report.is_clean() {
+        ui.label(RichText::new("✅ Nothing recoverable found").color(Color32::DARK_GREEN).strong());
+    }
+
+    ui.label(format!(
+        "Carved from {} outside any filesystem: {} files",
+        StorageDriver::format_size(report.unallocated_bytes),
+        report.carved_count()
+    ));
+    if !report.carved_by_type.is_empty() {
+        let types: Vec<String> =
+            report.carved_by_type.iter().map(|(kind, count)| format!("{} {}", count, kind)).collect();
+        ui.label(RichText::new(types.join(", ")).color(Color32::from_rgb(211, 47, 47)));
+        ui.collapsing(format!("Carved files ({} listed)", report.carved.len()), |ui| {
+            egui::Grid::new(ui.next_auto_id()).striped(true).show(ui, |ui| {
+                for file in &report.carved {
+                    ui.label(file.kind);
+                    ui.label(format!("at {}", StorageDriver::format_size(file.offset)));
+                    ui.label(StorageDriver::format_size(file.len));
+                    ui.label(if file.complete { "complete" } else { "end not found" });
+                    ui.end_row();
+                }
+            });
+        });
+    }
+
+    for deleted in &report.deleted {
+        ui.add_space(6.0);
+        let volume = match deleted.partition {
+            Some(number) => format!("Partition {}", number),
+            None => "Whole disk".to_string(),
+        };
+        ui.label(
+            RichText::new(format!(
+                "{} ({}): {} deleted files, {} deleted directories, {} names recoverable",
+                volume, deleted.filesystem, deleted.files, deleted.directories, deleted.names
+            ))
+            .color(if deleted.is_empty() { Color32::DARK_GREEN } else { Color32::from_rgb(211, 47, 47) }),
+        );
+        if !deleted.by_type.is_empty() {
+            let types: Vec<String> =
+                deleted.by_type.iter().map(|(extension, count)| format!("{} {}", count, extension)).collect();
+            ui.label(RichText::new(types.join(", ")).color(Color32::GRAY));
+        }
+        for entry in &deleted.samples {
+            let size = entry.size.map(StorageDriver::format_size).unwrap_or_default();
+            let icon = if entry.directory { "📁" } else { "📄" };
+            ui.label(RichText::new(format!("  {} {} {}", icon, entry.name, size)).monospace());
         }
     }
 }
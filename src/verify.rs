@@ -1,15 +1,225 @@
-// Verification module for data erasure validation
-// Handles hashing, scanning, and verification of erased data
+//! Verification module for data erasure validation
+//! Handles hashing, scanning, and verification of erased data
+
+use std::{
+    collections::BTreeSet,
+    fs::File,
+    os::{fd::AsRawFd, unix::fs::FileExt},
+    path::Path,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    cert::VerificationSummary,
+    erasure::{
+        EraseError, ErasePhase, EraseProgress,
+        engine::{IoConfig, IoEngine, IoOp},
+        errormap::{ErrorMap, is_media_error},
+        overwrite::PatternGenerator,
+    },
+};
+
+/// Mismatches are counted per sector of this size
+pub const SECTOR_SIZE: usize = 512;
+const SAMPLE_SIZE: u64 = 1024 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum VerifyMode {
+    None,
+    // Reads this percentage of the device in randomly placed regions
+    Sampled(u8),
+    Full,
+}
+
+impl std::fmt::Display for VerifyMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            VerifyMode::None => write!(f, "No Verification"),
+            VerifyMode::Sampled(percent) => write!(f, "Sampled ({}%)", percent),
+            VerifyMode::Full => write!(f, "Full Read-Back"),
+        }
+    }
+}
+
+impl VerifyMode {
+    pub fn get_all_modes() -> Vec<VerifyMode> {
+        vec![VerifyMode::None, VerifyMode::Sampled(10), VerifyMode::Full]
+    }
+
+    /// Approximate number of bytes read back when verifying `size` bytes
+    pub fn estimated_bytes(&self, size: u64) -> u64 {
+        match self {
+            VerifyMode::None => 0,
+            VerifyMode::Sampled(percent) if *percent >= 100 => size,
+            VerifyMode::Sampled(percent) => size / 100 * *percent as u64,
+            VerifyMode::Full => size,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VerificationReport {
+    pub mode: VerifyMode,
+    pub bytes_verified: u64,
+    pub mismatched_sectors: u64,
+    pub first_mismatch: Option<u64>,
+    /// Sectors that could not be read back; not counted as mismatches
+    pub unreadable: ErrorMap,
+    /// Every sector that could be read matched
+    pub passed: bool,
+}
+
+impl VerificationReport {
+    pub fn summary(&self) -> VerificationSummary {
+        VerificationSummary {
+            method: self.mode.to_string(),
+            bytes_verified: self.bytes_verified,
+            mismatches: self.mismatched_sectors,
+            passed: self.passed,
+            read_errors: self.unreadable.clone(),
+            ..Default::default()
+        }
+    }
+}
+
+/// Reads the device back and compares it with what the final pass wrote.
+/// Reads go through an [`IoEngine`] configured by `io`.
+pub fn verify_pattern(
+    path: &Path,
+    generator: &PatternGenerator,
+    size: u64,
+    mode: VerifyMode,
+    io: &IoConfig,
+    progress: &mut dyn FnMut(EraseProgress),
+    cancel: &AtomicBool,
+) -> Result<VerificationReport, EraseError> {
+    if size == 0 {
+        return Err(EraseError::InvalidTarget(format!("{} has zero size", path.display())));
+    }
+    let mut engine = IoEngine::open(path, IoOp::Read, io)?;
+    // Written data may still sit in the page cache; drop it so the reads
+    // below come from the device
+    unsafe {
+        libc::posix_fadvise(engine.file().as_raw_fd(), 0, 0, libc::POSIX_FADV_DONTNEED);
+    }
+    // Failed reads are retried a sector at a time, which O_DIRECT would
+    // refuse on drives with larger logical blocks
+    let retries = File::open(path)?;
+
+    let regions = match mode {
+        VerifyMode::None => Vec::new(),
+        VerifyMode::Full => vec![(0, size)],
+        VerifyMode::Sampled(percent) => sample_regions(size, percent)?,
+    };
+    let total: u64 = regions.iter().map(|(_, length)| length).sum();
+
+    let mut report = VerificationReport {
+        mode,
+        bytes_verified: 0,
+        mismatched_sectors: 0,
+        first_mismatch: None,
+        unreadable: ErrorMap::new(SECTOR_SIZE as u64),
+        passed: false,
+    };
+    let mut expected = vec![0u8; engine.config().block_size];
+
+    let finished = engine.run(IoOp::Read, &regions, &mut |_, _| {}, &mut |completion| {
+        let offset = completion.offset;
+        let actual = completion.data;
+        let chunk = actual.len();
+        let readable = match completion.result {
+            Ok(()) => vec![true; chunk.div_ceil(SECTOR_SIZE)],
+            Err(e) if !is_media_error(&e) => return Err(e.into()),
+            Err(_) => read_sectors(&retries, actual, offset, &mut report.unreadable)?,
+        };
+        generator.fill(offset, &mut expected[..chunk]);
+
+        for (index, (a, e)) in actual.chunks(SECTOR_SIZE).zip(expected[..chunk].chunks(SECTOR_SIZE)).enumerate() {
+            if readable[index] && a != e {
+                report.mismatched_sectors += 1;
+                report
+                    .first_mismatch
+                    .get_or_insert(offset + (index * SECTOR_SIZE) as u64);
+            }
+        }
+
+        report.bytes_verified += chunk as u64;
+        progress(EraseProgress {
+            phase: ErasePhase::Verifying,
+            pass: 1,
+            passes: 1,
+            pattern: mode.to_string(),
+            bytes_done: report.bytes_verified,
+            bytes_total: total,
+            ..Default::default()
+        });
+        Ok(!cancel.load(Ordering::Relaxed))
+    })?;
+    if !finished {
+        return Err(EraseError::Cancelled);
+    }
+
+    report.passed = report.bytes_verified > 0 && report.mismatched_sectors == 0;
+    Ok(report)
+}
+
+// Reads `buf` at `offset`, falling back to one sector at a time when the
+// medium fails the read. Returns which sectors of `buf` were read.
+fn read_sectors(file: &File, buf: &mut [u8], offset: u64, unreadable: &mut ErrorMap) -> Result<Vec<bool>, EraseError> {
+    let sectors = buf.len().div_ceil(SECTOR_SIZE);
+    match file.read_exact_at(buf, offset) {
+        Ok(()) => return Ok(vec![true; sectors]),
+        Err(e) if !is_media_error(&e) => return Err(e.into()),
+        Err(_) => {}
+    }
+    let mut readable = Vec::with_capacity(sectors);
+    for (index, sector) in buf.chunks_mut(SECTOR_SIZE).enumerate() {
+        let position = offset + (index * SECTOR_SIZE) as u64;
+        match file.read_exact_at(sector, position) {
+            Ok(()) => readable.push(true),
+            Err(e) if is_media_error(&e) => {
+                unreadable.add(position, sector.len() as u64);
+                readable.push(false);
+            }
+            Err(e) => return Err(e.into()),
+        }
+    }
+    Ok(readable)
+}
+
+// Sorted, non-overlapping regions covering roughly `percent` of the device.
+// The first and last regions are always included.
+fn sample_regions(size: u64, percent: u8) -> Result<Vec<(u64, u64)>, EraseError> {
+    let slots = size.div_ceil(SAMPLE_SIZE);
+    if percent >= 100 {
+        return Ok(vec![(0, size)]);
+    }
+    let wanted = (slots * percent as u64).div_ceil(100).clamp(slots.min(2), slots);
+
+    let mut picked = BTreeSet::from([0, slots - 1]);
+    let mut state = [0u8; 8];
+    getrandom::fill(&mut state)
+        .map_err(|e| EraseError::IoError(std::io::Error::other(e.to_string())))?;
+    let mut state = u64::from_le_bytes(state) | 1;
+    while (picked.len() as u64) < wanted {
+        // xorshift64
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        picked.insert(state % slots);
+    }
+
+    Ok(picked
+        .into_iter()
+        .map(|slot| {
+            let start = slot * SAMPLE_SIZE;
+            (start, SAMPLE_SIZE.min(size - start))
+        })
+        .collect())
+}
 
 // TODO: Add hash calculation functions (SHA-256, MD5)
-// TODO: Add sector-by-sector verification
-// TODO: Add pattern verification for overwrite methods
-// TODO: Add random sampling verification
-// TODO: Add full disk scanning
-// TODO: Add partial verification options
-// TODO: Add verification report generation
-// TODO: Add benchmark timing
-// TODO: Add progress tracking for verification
-// TODO: Add error detection and reporting
 // TODO: Add verification standards compliance
-// TODO: Add forensic-level verification options
\ No newline at end of file
+// TODO: Add forensic-level verification options
@@ -0,0 +1,571 @@
+mod common;
+
+use std::{fs, io::Cursor};
+
+use secure_erase::analysis::{
+    AnalysisError,
+    datamap::{MapConfig, RegionClass, scan_data_map},
+    filesystem::{FilesystemKind, detect_filesystem, scan_filesystems},
+    forensics::scan_forensics,
+    partition::{PartitionKind, PartitionRole, PartitionScheme, RegionKind, analyze, read_partition_table},
+    sectors::{SectorReader, StructureKind, find_structures},
+};
+
+const SECTOR: usize = 512;
+const LINUX_FS: &str = "0FC63DAF-8483-4772-8E79-3D69D8477DE4";
+const EFI_SYSTEM: &str = "C12A7328-F81F-11D2-BA4B-00A0C93EC93B";
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ 0xedb8_8320 } else { crc >> 1 };
+        }
+    }
+    !crc
+}
+
+// On-disk byte order of a GUID string
+fn guid_bytes(guid: &str) -> [u8; 16] {
+    let b = hex::decode(guid.replace('-', "")).unwrap();
+    let mut out = [0u8; 16];
+    out[..4].copy_from_slice(&[b[3], b[2], b[1], b[0]]);
+    out[4..8].copy_from_slice(&[b[5], b[4], b[7], b[6]]);
+    out[8..].copy_from_slice(&b[8..]);
+    out
+}
+
+fn put_mbr_entry(sector: &mut [u8], slot: usize, kind: u8, first: u32, sectors: u32) {
+    let entry = &mut sector[446 + slot * 16..462 + slot * 16];
+    entry[4] = kind;
+    entry[8..12].copy_from_slice(&first.to_le_bytes());
+    entry[12..16].copy_from_slice(&sectors.to_le_bytes());
+}
+
+fn boot_signature(sector: &mut [u8]) {
+    sector[510] = 0x55;
+    sector[511] = 0xaa;
+}
+
+// Primary 1 at 2048, extended 2 holding logicals 5 and 6, free space at the end
+fn mbr_image() -> Vec<u8> {
+    let mut disk = vec![0u8; 16384 * SECTOR];
+    put_mbr_entry(&mut disk[..SECTOR], 0, 0x83, 2048, 4096);
+    put_mbr_entry(&mut disk[..SECTOR], 1, 0x05, 6144, 8192);
+    disk[446] = 0x80;
+    boot_signature(&mut disk[..SECTOR]);
+
+    // First EBR at the start of the extended partition, second 4096 in
+    let ebr = &mut disk[6144 * SECTOR..6145 * SECTOR];
+    put_mbr_entry(ebr, 0, 0x83, 2048, 2048);
+    put_mbr_entry(ebr, 1, 0x05, 4096, 4096);
+    boot_signature(ebr);
+    let ebr = &mut disk[10240 * SECTOR..10241 * SECTOR];
+    put_mbr_entry(ebr, 0, 0x82, 2048, 2048);
+    boot_signature(ebr);
+    disk
+}
+
+fn gpt_header(total: u64, lba: u64, alternate: u64, entries_lba: u64, entries: &[u8]) -> Vec<u8> {
+    let mut header = vec![0u8; SECTOR];
+    header[0..8].copy_from_slice(b"EFI PART");
+    header[8..12].copy_from_slice(&0x0001_0000u32.to_le_bytes());
+    header[12..16].copy_from_slice(&92u32.to_le_bytes());
+    header[24..32].copy_from_slice(&lba.to_le_bytes());
+    header[32..40].copy_from_slice(&alternate.to_le_bytes());
+    header[40..48].copy_from_slice(&34u64.to_le_bytes());
+    header[48..56].copy_from_slice(&(total - 34).to_le_bytes());
+    header[56..72].copy_from_slice(&guid_bytes("01234567-89AB-CDEF-0123-456789ABCDEF"));
+    header[72..80].copy_from_slice(&entries_lba.to_le_bytes());
+    header[80..84].copy_from_slice(&128u32.to_le_bytes());
+    header[84..88].copy_from_slice(&128u32.to_le_bytes());
+    header[88..92].copy_from_slice(&crc32(entries).to_le_bytes());
+    let crc = crc32(&header[..92]);
+    header[16..20].copy_from_slice(&crc.to_le_bytes());
+    header
+}
+
+// EFI system partition and a Linux partition with a gap between them, both
+// GPT copies and a protective MBR
+fn gpt_image(total: u64) -> Vec<u8> {
+    let mut entries = vec![0u8; 128 * 128];
+    for (slot, (kind, first, last, name)) in
+        [(EFI_SYSTEM, 2048u64, 4095u64, "EFI"), (LINUX_FS, 8192, total - 2048, "root")].iter().enumerate()
+    {
+        let entry = &mut entries[slot * 128..(slot + 1) * 128];
+        entry[0..16].copy_from_slice(&guid_bytes(kind));
+        entry[16..32].copy_from_slice(&[slot as u8 + 1; 16]);
+        entry[32..40].copy_from_slice(&first.to_le_bytes());
+        entry[40..48].copy_from_slice(&last.to_le_bytes());
+        for (i, unit) in name.encode_utf16().enumerate() {
+            entry[56 + i * 2..58 + i * 2].copy_from_slice(&unit.to_le_bytes());
+        }
+    }
+
+    let mut disk = vec![0u8; total as usize * SECTOR];
+    put_mbr_entry(&mut disk[..SECTOR], 0, 0xee, 1, (total - 1) as u32);
+    boot_signature(&mut disk[..SECTOR]);
+    let primary = gpt_header(total, 1, total - 1, 2, &entries);
+    let backup = gpt_header(total, total - 1, 1, total - 33, &entries);
+    disk[SECTOR..2 * SECTOR].copy_from_slice(&primary);
+    disk[2 * SECTOR..34 * SECTOR].copy_from_slice(&entries);
+    disk[(total as usize - 33) * SECTOR..(total as usize - 1) * SECTOR].copy_from_slice(&entries);
+    disk[(total as usize - 1) * SECTOR..].copy_from_slice(&backup);
+    disk
+}
+
+#[test]
+fn mbr_with_logical_partitions() {
+    let disk = mbr_image();
+    let layout = analyze(&mut Cursor::new(&disk), disk.len() as u64, SECTOR as u64).unwrap();
+
+    assert!(matches!(layout.scheme, PartitionScheme::Mbr { .. }));
+    assert!(layout.problems.is_empty(), "{:?}", layout.problems);
+    let found: Vec<(u32, u64, u64, PartitionRole)> =
+        layout.partitions.iter().map(|p| (p.number, p.first_lba, p.last_lba, p.role)).collect();
+    assert_eq!(
+        found,
+        [
+            (1, 2048, 6143, PartitionRole::Primary),
+            (2, 6144, 14335, PartitionRole::Extended),
+            (5, 8192, 10239, PartitionRole::Logical),
+            (6, 12288, 14335, PartitionRole::Logical),
+        ]
+    );
+    assert!(layout.partitions[0].bootable);
+    assert_eq!(layout.partitions[3].kind.description(), "Linux swap");
+
+    // Regions tile the disk; EBR space inside the extended partition stays
+    // with it and the tail is free
+    assert_eq!(layout.regions.first().unwrap().kind, RegionKind::Metadata);
+    assert_eq!(layout.regions.iter().map(|r| r.sectors()).sum::<u64>(), layout.total_sectors);
+    assert_eq!(layout.regions[2].kind, RegionKind::Partition(0));
+    assert_eq!(layout.regions[3].kind, RegionKind::Partition(1));
+    let gaps: Vec<(u64, u64)> = layout.gaps().map(|r| (r.first_lba, r.last_lba)).collect();
+    assert_eq!(gaps, [(1, 2047), (14336, 16383)]);
+}
+
+#[test]
+fn overlapping_and_looping_mbr_entries_are_reported() {
+    let mut disk = mbr_image();
+    put_mbr_entry(&mut disk[..SECTOR], 2, 0x07, 4096, 4096);
+    // Second EBR links back to the first
+    put_mbr_entry(&mut disk[10240 * SECTOR..10241 * SECTOR], 1, 0x05, 0, 4096);
+
+    let layout = analyze(&mut Cursor::new(&disk), disk.len() as u64, SECTOR as u64).unwrap();
+    let overlaps: Vec<(u32, u32)> = layout.overlaps.iter().map(|o| (o.first, o.second)).collect();
+    assert_eq!(overlaps, [(1, 3), (2, 3)]);
+    assert!(layout.problems.iter().any(|p| p.contains("loops back")));
+    assert_eq!(layout.partitions.iter().filter(|p| p.role == PartitionRole::Logical).count(), 2);
+}
+
+#[test]
+fn gpt_image_file_is_read_with_both_headers() {
+    let dir = common::temp_dir("gpt");
+    let path = dir.join("disk.img");
+    let total = 65536;
+    fs::write(&path, gpt_image(total)).unwrap();
+
+    let layout = read_partition_table(&path).unwrap();
+    assert!(layout.problems.is_empty(), "{:?}", layout.problems);
+    let PartitionScheme::Gpt { primary, backup, protective_mbr, disk_guid, .. } = &layout.scheme else {
+        panic!("expected GPT, got {}", layout.scheme);
+    };
+    assert!(protective_mbr);
+    assert!(primary.as_ref().unwrap().is_valid() && backup.as_ref().unwrap().is_valid());
+    assert_eq!(disk_guid.to_string(), "01234567-89AB-CDEF-0123-456789ABCDEF");
+
+    assert_eq!(layout.partitions.len(), 2);
+    assert_eq!(layout.partitions[0].kind.description(), "EFI System");
+    assert_eq!(layout.partitions[1].name.as_deref(), Some("root"));
+    let PartitionKind::Gpt(kind) = layout.partitions[1].kind else { panic!() };
+    assert_eq!(kind.to_string(), LINUX_FS);
+
+    let gaps: Vec<(u64, u64)> = layout.gaps().map(|r| (r.first_lba, r.last_lba)).collect();
+    assert_eq!(gaps, [(34, 2047), (4096, 8191), (total - 2047, total - 34)]);
+    assert_eq!(layout.regions.last().unwrap().kind, RegionKind::Metadata);
+}
+
+#[test]
+fn damaged_primary_gpt_falls_back_to_the_backup() {
+    let total = 65536;
+    let mut disk = gpt_image(total);
+    // Clobber the primary entry array, then the primary header too
+    disk[2 * SECTOR + 40] ^= 0xff;
+    let layout = analyze(&mut Cursor::new(&disk), disk.len() as u64, SECTOR as u64).unwrap();
+    assert_eq!(layout.problems, ["primary GPT partition entries are corrupt"]);
+    assert_eq!(layout.partitions[0].last_lba, 4095);
+
+    disk[SECTOR + 60] ^= 0xff;
+    let layout = analyze(&mut Cursor::new(&disk), disk.len() as u64, SECTOR as u64).unwrap();
+    assert_eq!(layout.problems, ["primary GPT header at LBA 1 is corrupt"]);
+    assert_eq!(layout.partitions.len(), 2);
+
+    // No protective MBR and no GPT left: nothing to report but free space
+    disk.fill(0);
+    let layout = analyze(&mut Cursor::new(&disk), disk.len() as u64, SECTOR as u64).unwrap();
+    assert!(matches!(layout.scheme, PartitionScheme::None));
+    assert_eq!(layout.gaps().count(), 1);
+}
+
+#[test]
+fn sector_pages_highlight_known_structures() {
+    let dir = common::temp_dir("sectors");
+    let path = dir.join("disk.img");
+    let total = 65536;
+    let mut disk = gpt_image(total);
+    // ext4 magic in the superblock of the second partition
+    disk[8192 * SECTOR + 1080..8192 * SECTOR + 1082].copy_from_slice(&[0x53, 0xef]);
+    fs::write(&path, &disk).unwrap();
+    let layout = read_partition_table(&path).unwrap();
+    let reader = SectorReader::open(&path, SECTOR as u64).unwrap();
+    assert_eq!(reader.total_sectors(), total);
+
+    let kinds = |lba: u64| -> Vec<(StructureKind, u64, u64)> {
+        let page = reader.read_page(lba, 1).unwrap();
+        assert_eq!(page.data, disk[lba as usize * SECTOR..(lba as usize + 1) * SECTOR]);
+        find_structures(&page, Some(&layout)).into_iter().map(|h| (h.kind, h.offset, h.len)).collect()
+    };
+    assert_eq!(
+        kinds(0),
+        [
+            (StructureKind::PartitionTable, 440, 4),
+            (StructureKind::PartitionTable, 446, 64),
+            (StructureKind::BootSignature, 510, 2),
+        ]
+    );
+    assert_eq!(kinds(1), [(StructureKind::GptHeader, 512, 92)]);
+    assert_eq!(kinds(2), [(StructureKind::GptEntries, 1024, 512)]);
+    assert_eq!(kinds(total - 1), [(StructureKind::GptHeader, (total - 1) * 512, 92)]);
+    assert_eq!(kinds(8194), [(StructureKind::Filesystem, 8194 * 512, 512)]);
+    assert!(kinds(8195).is_empty());
+
+    // Pages stop at the end of the device
+    assert_eq!(reader.read_page(total - 2, 8).unwrap().data.len(), 2 * SECTOR);
+    assert!(matches!(reader.read_page(total, 1), Err(AnalysisError::OutOfRange(_))));
+}
+
+#[test]
+fn filesystems_are_identified_from_their_superblocks() {
+    let mut disk = mbr_image();
+
+    // ext4 on partition 1: 1024 blocks of 4K, a quarter free
+    let sb = 2048 * SECTOR + 1024;
+    disk[sb + 0x04..sb + 0x08].copy_from_slice(&1024u32.to_le_bytes());
+    disk[sb + 0x0c..sb + 0x10].copy_from_slice(&256u32.to_le_bytes());
+    disk[sb + 0x18..sb + 0x1c].copy_from_slice(&2u32.to_le_bytes());
+    disk[sb + 0x2c..sb + 0x30].copy_from_slice(&1_700_000_000u32.to_le_bytes());
+    disk[sb + 0x38..sb + 0x3a].copy_from_slice(&0xef53u16.to_le_bytes());
+    disk[sb + 0x60..sb + 0x64].copy_from_slice(&0x40u32.to_le_bytes());
+    disk[sb + 0x68..sb + 0x78].copy_from_slice(&[0x11; 16]);
+    disk[sb + 0x78..sb + 0x7c].copy_from_slice(b"root");
+    disk[sb + 0x88..sb + 0x8d].copy_from_slice(b"/srv\0");
+
+    // FAT32 on logical partition 5 with an FSInfo sector
+    let boot = 8192 * SECTOR;
+    disk[boot + 0x0b..boot + 0x0d].copy_from_slice(&512u16.to_le_bytes());
+    disk[boot + 0x0d] = 8;
+    disk[boot + 0x20..boot + 0x24].copy_from_slice(&2048u32.to_le_bytes());
+    disk[boot + 0x30..boot + 0x32].copy_from_slice(&1u16.to_le_bytes());
+    disk[boot + 0x43..boot + 0x47].copy_from_slice(&0x1234_abcdu32.to_le_bytes());
+    disk[boot + 0x47..boot + 0x52].copy_from_slice(b"USBSTICK   ");
+    disk[boot + 82..boot + 90].copy_from_slice(b"FAT32   ");
+    boot_signature(&mut disk[boot..boot + SECTOR]);
+    let info = boot + SECTOR;
+    disk[info..info + 4].copy_from_slice(&0x4161_5252u32.to_le_bytes());
+    disk[info + 0x1e4..info + 0x1e8].copy_from_slice(&0x6141_7272u32.to_le_bytes());
+    disk[info + 0x1e8..info + 0x1ec].copy_from_slice(&100u32.to_le_bytes());
+
+    // Swap on logical partition 6
+    let swap = 12288 * SECTOR;
+    disk[swap + 1028..swap + 1032].copy_from_slice(&255u32.to_le_bytes());
+    disk[swap + 1052..swap + 1056].copy_from_slice(b"swap");
+    disk[swap + 4086..swap + 4096].copy_from_slice(b"SWAPSPACE2");
+
+    let dir = common::temp_dir("filesystems");
+    let path = dir.join("disk.img");
+    fs::write(&path, &disk).unwrap();
+    let layout = read_partition_table(&path).unwrap();
+    let volumes = scan_filesystems(&path, &layout).unwrap();
+    assert_eq!(volumes.iter().map(|v| v.partition).collect::<Vec<_>>(), [Some(1), Some(5), Some(6)]);
+
+    let ext = volumes[0].filesystem.as_ref().unwrap();
+    assert_eq!(ext.kind, FilesystemKind::Ext4);
+    assert_eq!(ext.label.as_deref(), Some("root"));
+    assert_eq!(ext.uuid.as_deref(), Some("11111111-1111-1111-1111-111111111111"));
+    assert_eq!((ext.size, ext.used, ext.free), (Some(4 << 20), Some(3 << 20), Some(1 << 20)));
+    assert_eq!(ext.last_mounted.unwrap().unix_timestamp(), 1_700_000_000);
+    assert_eq!(ext.last_mount_point.as_deref(), Some("/srv"));
+
+    let fat = volumes[1].filesystem.as_ref().unwrap();
+    assert_eq!(fat.kind, FilesystemKind::Fat32);
+    assert_eq!(fat.label.as_deref(), Some("USBSTICK"));
+    assert_eq!(fat.uuid.as_deref(), Some("1234-ABCD"));
+    assert_eq!(fat.free, Some(100 * 4096));
+
+    let swap = volumes[2].filesystem.as_ref().unwrap();
+    assert_eq!(swap.kind, FilesystemKind::Swap);
+    assert_eq!((swap.label.as_deref(), swap.size), (Some("swap"), Some(256 * 4096)));
+
+    // Containers and unknown contents
+    let mut luks = vec![0u8; 8192];
+    luks[..6].copy_from_slice(b"LUKS\xba\xbe");
+    luks[6..8].copy_from_slice(&2u16.to_be_bytes());
+    luks[24..29].copy_from_slice(b"vault");
+    let found = detect_filesystem(&mut Cursor::new(&luks), 0, luks.len() as u64).unwrap().unwrap();
+    assert_eq!((found.kind, found.label.as_deref()), (FilesystemKind::Luks, Some("vault")));
+    assert!(detect_filesystem(&mut Cursor::new(vec![0u8; 8192]), 0, 8192).unwrap().is_none());
+}
+
+
+#[test]
+fn data_map_classifies_regions_by_content() {
+    // Eight regions of 512K: zero, 0xFF fill, random, text, a PNG, then zero
+    let region = 512 * 1024;
+    let mut disk = vec![0u8; 8 * region];
+    disk[region..2 * region].fill(0xff);
+    let mut state = 0x2545_f491_4f6c_dd1du64;
+    for byte in &mut disk[2 * region..3 * region] {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        *byte = state as u8;
+    }
+    let text = b"The quick brown fox jumps over the lazy dog. ";
+    for (index, byte) in disk[3 * region..4 * region].iter_mut().enumerate() {
+        *byte = text[index % text.len()];
+    }
+    disk[4 * region..4 * region + 8].copy_from_slice(b"\x89PNG\r\n\x1a\n");
+
+    let dir = common::temp_dir("datamap");
+    let path = dir.join("disk.img");
+    fs::write(&path, &disk).unwrap();
+    let config = MapConfig { regions: 8, samples_per_region: 4, sample_size: 4096 };
+    let mut updates = Vec::new();
+    let map = scan_data_map(&path, config, &mut |done, total| updates.push((done, total))).unwrap();
+
+    assert_eq!(updates, (1..=8).map(|done| (done, 8)).collect::<Vec<_>>());
+    assert_eq!(map.size, disk.len() as u64);
+    assert_eq!(map.bytes_sampled(), 8 * 4 * 4096);
+    let classes: Vec<_> = map.regions.iter().map(|region| region.class()).collect();
+    assert_eq!(
+        classes,
+        [
+            RegionClass::Zero,
+            RegionClass::Uniform,
+            RegionClass::HighEntropy,
+            RegionClass::Data,
+            RegionClass::LowEntropy,
+            RegionClass::Zero,
+            RegionClass::Zero,
+            RegionClass::Zero,
+        ]
+    );
+    assert_eq!(map.regions[1].fill, Some(0xff));
+    assert!(map.regions[2].entropy > 7.9);
+    assert_eq!(map.regions[4].signatures, ["PNG"]);
+    assert_eq!(map.regions_with_signatures(), 1);
+    assert_eq!((map.regions[7].offset, map.regions[7].len), (7 * region as u64, region as u64));
+}
+
+#[test]
+fn files_are_carved_by_header_and_footer() {
+    let mut disk = vec![0u8; 2048 * SECTOR];
+    // JPEG whose footer straddles a sector boundary
+    let jpeg = 8 * SECTOR;
+    disk[jpeg..jpeg + 4].copy_from_slice(b"\xff\xd8\xff\xe0");
+    disk[jpeg + 1023] = 0xff;
+    disk[jpeg + 1024] = 0xd9;
+    // RIFF sized by its header; the PNG header inside it is not a new file
+    let riff = 16 * SECTOR;
+    disk[riff..riff + 4].copy_from_slice(b"RIFF");
+    disk[riff + 4..riff + 8].copy_from_slice(&1992u32.to_le_bytes());
+    disk[riff + SECTOR..riff + SECTOR + 8].copy_from_slice(b"\x89PNG\r\n\x1a\n");
+    // A ZIP without its end record is cut short by the next header
+    disk[64 * SECTOR..64 * SECTOR + 4].copy_from_slice(b"PK\x03\x04");
+    let gif = 80 * SECTOR;
+    disk[gif..gif + 6].copy_from_slice(b"GIF89a");
+    disk[gif + 100..gif + 102].copy_from_slice(b"\x00\x3b");
+    // A PDF running off the end of the disk
+    disk[1000 * SECTOR..1000 * SECTOR + 8].copy_from_slice(b"%PDF-1.7");
+
+    let dir = common::temp_dir("carving");
+    let path = dir.join("disk.img");
+    fs::write(&path, &disk).unwrap();
+    let mut last = (0, 0);
+    let report = scan_forensics(&path, &mut |done, total| last = (done, total)).unwrap();
+
+    assert_eq!(report.unallocated_bytes, disk.len() as u64);
+    assert_eq!(last, (disk.len() as u64, disk.len() as u64));
+    let carved: Vec<_> = report.carved.iter().map(|file| (file.kind, file.offset, file.len, file.complete)).collect();
+    assert_eq!(
+        carved,
+        [
+            ("JPEG", jpeg as u64, 1025, true),
+            ("RIFF (WAV/AVI)", riff as u64, 2000, true),
+            ("ZIP/Office", 64 * SECTOR as u64, 16 * SECTOR as u64, false),
+            ("GIF", gif as u64, 102, true),
+            ("PDF", 1000 * SECTOR as u64, 1048 * SECTOR as u64, false),
+        ]
+    );
+    assert_eq!(report.carved_count(), 5);
+    assert!(!report.is_clean());
+
+    fs::write(&path, vec![0u8; 2048 * SECTOR]).unwrap();
+    assert!(scan_forensics(&path, &mut |_, _| {}).unwrap().is_clean());
+}
+
+// ext2 with 1K blocks: inode table at block 5, root directory in block 20
+fn ext2_image(volume: &mut [u8]) {
+    let sb = 1024;
+    volume[sb..sb + 4].copy_from_slice(&32u32.to_le_bytes());
+    volume[sb + 0x04..sb + 0x08].copy_from_slice(&2048u32.to_le_bytes());
+    volume[sb + 0x14..sb + 0x18].copy_from_slice(&1u32.to_le_bytes());
+    volume[sb + 0x20..sb + 0x24].copy_from_slice(&8192u32.to_le_bytes());
+    volume[sb + 0x28..sb + 0x2c].copy_from_slice(&32u32.to_le_bytes());
+    volume[sb + 0x38..sb + 0x3a].copy_from_slice(&0xef53u16.to_le_bytes());
+    volume[sb + 0x4c..sb + 0x50].copy_from_slice(&1u32.to_le_bytes());
+    volume[sb + 0x58..sb + 0x5a].copy_from_slice(&128u16.to_le_bytes());
+    volume[2048 + 0x08..2048 + 0x0c].copy_from_slice(&5u32.to_le_bytes());
+
+    // (inode, mode, links, deleted, size, first block)
+    for (number, mode, links, deleted, size, block) in [
+        (2, 0x41ed, 3, false, 1024, 20),
+        (12, 0x81a4, 1, false, 10, 0),
+        (13, 0x81a4, 0, true, 4096, 0),
+        (14, 0x41ed, 2, false, 1024, 21),
+        (15, 0x41ed, 0, true, 1024, 0),
+        (16, 0x81a4, 0, true, 512, 0),
+    ] {
+        let inode = &mut volume[5 * 1024 + (number - 1) * 128..][..128];
+        inode[0..2].copy_from_slice(&(mode as u16).to_le_bytes());
+        inode[4..8].copy_from_slice(&(size as u32).to_le_bytes());
+        if deleted {
+            inode[0x14..0x18].copy_from_slice(&1_700_000_000u32.to_le_bytes());
+        }
+        inode[0x1a..0x1c].copy_from_slice(&(links as u16).to_le_bytes());
+        inode[0x28..0x2c].copy_from_slice(&(block as u32).to_le_bytes());
+    }
+
+    let mut dirent = |block: usize, at: usize, inode: u32, rec_len: u16, kind: u8, name: &str| {
+        let entry = &mut volume[block * 1024 + at..];
+        entry[0..4].copy_from_slice(&inode.to_le_bytes());
+        entry[4..6].copy_from_slice(&rec_len.to_le_bytes());
+        entry[6] = name.len() as u8;
+        entry[7] = kind;
+        entry[8..8 + name.len()].copy_from_slice(name.as_bytes());
+    };
+    // Deleting an entry folds it into the record length of the one before
+    dirent(20, 0, 2, 12, 2, ".");
+    dirent(20, 12, 2, 12, 2, "..");
+    dirent(20, 24, 12, 40, 1, "keep.txt");
+    dirent(20, 40, 13, 24, 1, "tax-2023.pdf");
+    dirent(20, 64, 14, 960, 2, "photos");
+    dirent(20, 80, 15, 20, 2, "old-backups");
+    dirent(21, 0, 14, 12, 2, ".");
+    dirent(21, 12, 2, 1012, 2, "..");
+}
+
+// NTFS with 4K clusters and 1K MFT records, the MFT at cluster 4
+fn ntfs_image(volume: &mut [u8]) {
+    volume[3..11].copy_from_slice(b"NTFS    ");
+    volume[0x0b..0x0d].copy_from_slice(&512u16.to_le_bytes());
+    volume[0x0d] = 8;
+    volume[0x28..0x30].copy_from_slice(&2048u64.to_le_bytes());
+    volume[0x30..0x38].copy_from_slice(&4u64.to_le_bytes());
+    volume[0x40] = 0xf6;
+
+    let mft = 4 * 4096;
+    let mut record = |number: usize, flags: u16, names: &[(&str, u8)], data: Option<u32>| {
+        let record = &mut volume[mft + number * 1024..][..1024];
+        record[0..4].copy_from_slice(b"FILE");
+        record[0x04..0x06].copy_from_slice(&0x30u16.to_le_bytes());
+        record[0x06..0x08].copy_from_slice(&3u16.to_le_bytes());
+        record[0x14..0x16].copy_from_slice(&0x38u16.to_le_bytes());
+        record[0x16..0x18].copy_from_slice(&flags.to_le_bytes());
+        let mut at = 0x38;
+        for (name, space) in names {
+            let units: Vec<u16> = name.encode_utf16().collect();
+            let len = (0x18 + 0x42 + units.len() * 2).next_multiple_of(8);
+            record[at..at + 4].copy_from_slice(&0x30u32.to_le_bytes());
+            record[at + 4..at + 8].copy_from_slice(&(len as u32).to_le_bytes());
+            record[at + 0x10..at + 0x14].copy_from_slice(&((0x42 + units.len() * 2) as u32).to_le_bytes());
+            record[at + 0x14..at + 0x16].copy_from_slice(&0x18u16.to_le_bytes());
+            record[at + 0x18 + 0x40] = units.len() as u8;
+            record[at + 0x18 + 0x41] = *space;
+            for (index, unit) in units.iter().enumerate() {
+                record[at + 0x18 + 0x42 + index * 2..][..2].copy_from_slice(&unit.to_le_bytes());
+            }
+            at += len;
+        }
+        match (number, data) {
+            // The MFT's own data: 8 clusters from cluster 4, 32 records
+            (0, _) => {
+                record[at..at + 4].copy_from_slice(&0x80u32.to_le_bytes());
+                record[at + 4..at + 8].copy_from_slice(&0x48u32.to_le_bytes());
+                record[at + 8] = 1;
+                record[at + 0x20..at + 0x22].copy_from_slice(&0x40u16.to_le_bytes());
+                record[at + 0x30..at + 0x38].copy_from_slice(&(32u64 * 1024).to_le_bytes());
+                record[at + 0x40..at + 0x43].copy_from_slice(&[0x11, 8, 4]);
+                at += 0x48;
+            }
+            (_, Some(size)) => {
+                record[at..at + 4].copy_from_slice(&0x80u32.to_le_bytes());
+                record[at + 4..at + 8].copy_from_slice(&0x18u32.to_le_bytes());
+                record[at + 0x10..at + 0x14].copy_from_slice(&size.to_le_bytes());
+                at += 0x18;
+            }
+            _ => {}
+        }
+        record[at..at + 4].copy_from_slice(&0xffff_ffffu32.to_le_bytes());
+        record[0x18..0x1c].copy_from_slice(&(at as u32 + 8).to_le_bytes());
+        // Update sequence: the last two bytes of each 512-byte stride
+        record[0x30..0x32].copy_from_slice(&7u16.to_le_bytes());
+        for stride in 1..3 {
+            let end = stride * 512;
+            record.copy_within(end - 2..end, 0x30 + stride * 2);
+            record[end - 2..end].copy_from_slice(&7u16.to_le_bytes());
+        }
+    };
+    record(0, 0x1, &[("$MFT", 3)], None);
+    record(16, 0x1, &[("keep.docx", 1)], Some(100));
+    record(17, 0x0, &[("budget.xlsx", 1)], Some(300));
+    record(18, 0x2, &[("Old Photos", 1)], None);
+    record(19, 0x0, &[("BUDGET~1.XLS", 2), ("budget-final.xlsx", 1)], Some(700));
+}
+
+#[test]
+fn deleted_entries_are_found_in_filesystem_metadata() {
+    let mut disk = mbr_image();
+    ext2_image(&mut disk[2048 * SECTOR..6144 * SECTOR]);
+    ntfs_image(&mut disk[8192 * SECTOR..10240 * SECTOR]);
+    // Inside the ext2 partition, so not carved; logical 6 holds nothing
+    // recognised and is carved like the free space at the end
+    disk[2048 * SECTOR + 100 * 1024..][..3].copy_from_slice(b"\xff\xd8\xff");
+    disk[12296 * SECTOR..][..3].copy_from_slice(b"\xff\xd8\xff");
+    disk[12296 * SECTOR + 600..][..2].copy_from_slice(b"\xff\xd9");
+    disk[14336 * SECTOR..][..8].copy_from_slice(b"\x89PNG\r\n\x1a\n");
+
+    let dir = common::temp_dir("deleted");
+    let path = dir.join("disk.img");
+    fs::write(&path, &disk).unwrap();
+    let report = scan_forensics(&path, &mut |_, _| {}).unwrap();
+
+    let ext = &report.deleted[0];
+    assert_eq!((ext.partition, ext.filesystem), (Some(1), FilesystemKind::Ext2));
+    assert_eq!((ext.files, ext.directories, ext.names), (2, 1, 2));
+    let names: Vec<_> = ext.samples.iter().map(|entry| (entry.name.as_str(), entry.directory, entry.size)).collect();
+    assert_eq!(names, [("tax-2023.pdf", false, Some(4096)), ("old-backups", true, Some(1024))]);
+    assert_eq!(ext.by_type.get("pdf"), Some(&1));
+
+    let ntfs = &report.deleted[1];
+    assert_eq!((ntfs.partition, ntfs.filesystem), (Some(5), FilesystemKind::Ntfs));
+    assert_eq!((ntfs.files, ntfs.directories, ntfs.names), (2, 1, 3));
+    let names: Vec<_> = ntfs.samples.iter().map(|entry| (entry.name.as_str(), entry.size)).collect();
+    assert_eq!(names, [("budget.xlsx", Some(300)), ("Old Photos", None), ("budget-final.xlsx", Some(700))]);
+    assert_eq!(ntfs.by_type.get("xlsx"), Some(&2));
+
+    let carved: Vec<_> = report.carved.iter().map(|file| (file.kind, file.offset, file.complete)).collect();
+    assert_eq!(carved, [("JPEG", 12296 * SECTOR as u64, true), ("PNG", 14336 * SECTOR as u64, false)]);
+}
@@ -0,0 +1,92 @@
+mod common;
+
+use std::fs;
+
+use secure_erase::audit::{AuditError, AuditEvent, AuditLog, GENESIS_HASH, verify_log};
+
+fn event(n: usize) -> AuditEvent {
+    AuditEvent::EraseStarted { path: format!("/dev/sd{}", n), method: "Overwrite".to_string() }
+}
+
+fn write_log(name: &str) -> std::path::PathBuf {
+    let path = common::temp_dir(name).join("audit.log");
+    let log = AuditLog::open(&path).unwrap();
+    for n in 0..4 {
+        log.append(event(n)).unwrap();
+    }
+    path
+}
+
+#[test]
+fn entries_are_chained() {
+    let path = common::temp_dir("chain").join("audit.log");
+    let log = AuditLog::open(&path).unwrap();
+    let first = log.append(event(0)).unwrap();
+    let second = log.append(event(1)).unwrap();
+
+    assert_eq!(first.seq, 0);
+    assert_eq!(first.prev_hash, GENESIS_HASH);
+    assert_eq!(second.prev_hash, first.hash);
+
+    let summary = log.verify().unwrap();
+    assert_eq!(summary.entries, 2);
+    assert_eq!(summary.last_hash, second.hash);
+}
+
+#[test]
+fn edited_entry_is_detected() {
+    let path = write_log("edited");
+    let contents = fs::read_to_string(&path).unwrap().replacen("/dev/sd2", "/dev/sd9", 1);
+    fs::write(&path, contents).unwrap();
+
+    assert!(matches!(verify_log(&path), Err(AuditError::Tampered { line: 3, .. })));
+}
+
+#[test]
+fn truncation_is_detected() {
+    let path = write_log("truncated");
+    let contents = fs::read_to_string(&path).unwrap();
+    let kept: Vec<&str> = contents.lines().take(2).collect();
+    fs::write(&path, kept.join("\n") + "\n").unwrap();
+
+    assert!(matches!(
+        verify_log(&path),
+        Err(AuditError::Truncated { expected_seq: 3, found_seq: 1 })
+    ));
+}
+
+#[test]
+fn head_one_entry_behind_is_accepted() {
+    // As if the process died between writing an entry and recording the head
+    let path = write_log("head-behind");
+    let head = path.with_extension("head");
+    let stale = fs::read(&head).unwrap();
+    let log = AuditLog::open(&path).unwrap();
+    log.append(event(4)).unwrap();
+    fs::write(&head, &stale).unwrap();
+    assert_eq!(verify_log(&path).unwrap().entries, 5);
+
+    // One entry behind is a crash; two is not
+    log.append(event(5)).unwrap();
+    fs::write(&head, &stale).unwrap();
+    assert!(matches!(verify_log(&path), Err(AuditError::Truncated { expected_seq: 3, found_seq: 5 })));
+
+    // The next append records the head again
+    log.append(event(6)).unwrap();
+    assert_eq!(verify_log(&path).unwrap().entries, 7);
+}
+
+#[test]
+fn missing_head_is_detected() {
+    let path = write_log("headless");
+    let contents = fs::read_to_string(&path).unwrap();
+    let kept: Vec<&str> = contents.lines().take(2).collect();
+    fs::write(&path, kept.join("\n") + "\n").unwrap();
+    fs::remove_file(path.with_extension("head")).unwrap();
+
+    assert!(matches!(verify_log(&path), Err(AuditError::Tampered { line: 2, .. })));
+
+    // A log nothing was written to yet has no head either
+    let empty = common::temp_dir("empty-log").join("audit.log");
+    assert_eq!(AuditLog::open(&empty).unwrap().verify().unwrap().entries, 0);
+}
@@ -0,0 +1,230 @@
+mod common;
+
+use std::{fs, path::PathBuf};
+
+use secure_erase::cert::{
+    CertError, Certificate, CertificateBody, CertificateResult, CertificateSigner, DeviceRecord,
+    cms::{CmsSigner, load_certificates, verify_detached, verify_pdf},
+    pdf::render_pdf,
+    store::{CertificateQuery, CertificateStore},
+    template::CertificateTemplate,
+    verify_compact,
+};
+use secure_erase::{audit::AuditLog, platform::BlockGeometry};
+
+fn body(serial: &str, operator: &str) -> CertificateBody {
+    let device = DeviceRecord {
+        path: "/dev/sdb".to_string(),
+        model: Some("Example SSD".to_string()),
+        serial_number: Some(serial.to_string()),
+        vendor: Some("ACME".to_string()),
+        size: Some(512 * 1024 * 1024 * 1024),
+        geometry: Some(BlockGeometry { physical_block_size: 4096, ..Default::default() }),
+        firmware: Some("RVT04B6Q".to_string()),
+        wwn: Some("naa.5002538e40a1b2c3".to_string()),
+        transport: Some("SATA".to_string()),
+        by_id: Some(format!("ata-Example_SSD_{}", serial)),
+        by_path: Some("pci-0000:00:17.0-ata-2".to_string()),
+        device_type: "SSD".to_string(),
+    };
+    let mut body = CertificateBody::new(operator, vec![device], "Overwrite (Multiple Pass)");
+    body.result = CertificateResult::Passed;
+    body
+}
+
+#[test]
+fn signed_certificate_round_trips_and_verifies() {
+    let signer = CertificateSigner::generate().unwrap();
+    let cert = signer.sign(body("SN-1", "alice")).unwrap();
+
+    let parsed = Certificate::from_json(&cert.to_json().unwrap()).unwrap();
+    parsed.verify(None).unwrap();
+    parsed.verify(Some(&signer.fingerprint())).unwrap();
+
+    let compact = &parsed.signature.as_ref().unwrap().compact;
+    let payload = verify_compact(compact, &signer.verifying_key()).unwrap();
+    assert_eq!(payload.id, cert.body.id);
+    assert_eq!(payload.sn, vec!["SN-1".to_string()]);
+    assert!(payload.ok);
+}
+
+#[test]
+fn tampering_and_untrusted_keys_are_rejected() {
+    let signer = CertificateSigner::generate().unwrap();
+    let mut cert = signer.sign(body("SN-2", "bob")).unwrap();
+
+    let other = CertificateSigner::generate().unwrap();
+    assert!(matches!(
+        cert.verify(Some(&other.fingerprint())),
+        Err(CertError::UntrustedSigner(_))
+    ));
+
+    cert.body.result = CertificateResult::Failed;
+    assert!(matches!(cert.verify(None), Err(CertError::InvalidSignature)));
+
+    let unsigned = Certificate::unsigned(body("SN-3", "bob"));
+    assert!(matches!(unsigned.verify(None), Err(CertError::Unsigned)));
+}
+
+#[test]
+fn signing_key_persists() {
+    let dir = common::temp_dir("signing-key");
+    let path = dir.join("key.pem");
+    let created = CertificateSigner::load_or_create(&path).unwrap();
+    let loaded = CertificateSigner::load_or_create(&path).unwrap();
+    assert_eq!(created.fingerprint(), loaded.fingerprint());
+}
+
+#[test]
+fn store_searches_and_revokes() {
+    let dir = common::temp_dir("store");
+    let signer = CertificateSigner::generate().unwrap();
+    let mut store = CertificateStore::open(&dir).unwrap();
+    store.set_audit_log(AuditLog::open(&dir.join("audit.log")).unwrap());
+    let first = signer.sign(body("SN-A", "alice")).unwrap();
+    let second = signer.sign(body("SN-B", "bob")).unwrap();
+    store.insert(&first).unwrap();
+    store.insert(&second).unwrap();
+    assert!(store.insert(&first).is_err());
+    let pdf = fs::read(store.pdf_path(&first.body.id).unwrap()).unwrap();
+    assert!(pdf.starts_with(b"%PDF-"));
+
+    let by_operator = CertificateQuery { operator: Some("ALI".to_string()), ..Default::default() };
+    let found = store.query(&by_operator);
+    assert_eq!(found.len(), 1);
+    assert_eq!(found[0].entry.id, first.body.id);
+
+    store.revoke(&first.body.id, "wrong disk").unwrap();
+    assert!(store.revoke(&first.body.id, "again").is_err());
+    assert!(store.query(&by_operator).is_empty());
+    let audit = fs::read_to_string(dir.join("audit.log")).unwrap();
+    assert_eq!(audit.lines().count(), 1);
+    assert!(audit.contains("certificate_revoked") && audit.contains(&first.body.id));
+
+    // Exports of a revoked certificate say so, others do not
+    let template = CertificateTemplate::default();
+    store.export_pdf(&first.body.id, &template, &dir.join("first.pdf")).unwrap();
+    store.export_pdf(&second.body.id, &template, &dir.join("second.pdf")).unwrap();
+    let revoked_marker = |name: &str| {
+        let pdf = fs::read(dir.join(name)).unwrap();
+        pdf.windows(7).any(|w| w == b"REVOKED")
+    };
+    assert!(revoked_marker("first.pdf"));
+    assert!(!revoked_marker("second.pdf"));
+
+    // Revocations survive reopening and rebuilding the index
+    let mut reopened = CertificateStore::open(&dir).unwrap();
+    reopened.rebuild_index().unwrap();
+    let revoked = reopened.status(&first.body.id).unwrap();
+    assert_eq!(revoked.revocation.as_ref().unwrap().reason, "wrong disk");
+    assert!(reopened.get("../../etc/passwd").is_err());
+}
+
+#[test]
+fn pdf_renders() {
+    let signer = CertificateSigner::generate().unwrap();
+    let cert = signer.sign(body("SN-PDF", "carol")).unwrap();
+    let pdf = render_pdf(&cert, &CertificateTemplate::default()).unwrap();
+    assert!(pdf.starts_with(b"%PDF-"));
+
+    // A passed erase that was never read back must not claim verification
+    let says = |pdf: &[u8], text: &str| pdf.windows(text.len()).any(|w| w == text.as_bytes());
+    assert!(says(&pdf, "ERASURE COMPLETED (NOT VERIFIED)"));
+    let mut verified = body("SN-PDF", "carol");
+    verified.verified = Some(true);
+    let pdf = render_pdf(&signer.sign(verified).unwrap(), &CertificateTemplate::default()).unwrap();
+    assert!(says(&pdf, "ERASURE COMPLETED AND VERIFIED"));
+
+    // Older certificates did not record it; a verification method implies it
+    let mut older = body("SN-PDF", "carol");
+    older.verified = None;
+    assert!(!older.is_verified());
+    older.verification.method = "Full".to_string();
+    assert!(older.is_verified());
+}
+
+// Keys and certificates issued by tests/fixtures/cms/ca.pem, valid until 2126
+fn fixture(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/cms").join(name)
+}
+
+fn cms_signers() -> Vec<(&'static str, CmsSigner)> {
+    ["rsa", "p256"]
+        .into_iter()
+        .map(|name| {
+            let key = fixture(&format!("{}.key", name));
+            let chain = fixture(&format!("{}.pem", name));
+            (name, CmsSigner::load(&key, &chain).unwrap())
+        })
+        .collect()
+}
+
+#[test]
+fn cms_detached_signatures_verify_and_detect_tampering() {
+    let trust = load_certificates(&fixture("ca.pem")).unwrap();
+    let untrusted = load_certificates(&fixture("other-ca.pem")).unwrap();
+    assert!(CmsSigner::load(&fixture("rsa.key"), &fixture("p256.pem")).is_err());
+
+    for (name, signer) in cms_signers() {
+        let data = Certificate::unsigned(body("SN-CMS", "dave")).to_json().unwrap();
+        let signature = signer.sign_detached(data.as_bytes()).unwrap();
+
+        let verified = verify_detached(&signature, data.as_bytes(), &trust).unwrap();
+        assert_eq!(verified.signer, signer.subject(), "{}", name);
+        assert_eq!(verified.chain.len(), 2, "{}", name);
+        assert!(verified.signing_time.is_some(), "{}", name);
+
+        let tampered = data.replace("dave", "eve!");
+        assert!(matches!(
+            verify_detached(&signature, tampered.as_bytes(), &trust),
+            Err(CertError::InvalidSignature)
+        ), "{}", name);
+        assert!(matches!(
+            verify_detached(&signature, data.as_bytes(), &untrusted),
+            Err(CertError::Chain(_))
+        ), "{}", name);
+    }
+}
+
+#[test]
+fn cms_signed_pdfs_verify_and_detect_tampering() {
+    let trust = load_certificates(&fixture("ca.pem")).unwrap();
+    let untrusted = load_certificates(&fixture("other-ca.pem")).unwrap();
+    // Certificate text is written before the signature dictionary and must
+    // not be mistaken for its placeholders
+    let operator = "frank /ByteRange [0 1 2 3] /Contents <00>";
+    let cert = CertificateSigner::generate().unwrap().sign(body("SN-PDF-CMS", operator)).unwrap();
+
+    for (name, signer) in cms_signers() {
+        let document = signer.sign_pdf(&cert, &CertificateTemplate::default()).unwrap();
+        let verified = verify_pdf(&document, &trust).unwrap();
+        assert_eq!(verified.signer, signer.subject(), "{}", name);
+        assert!(matches!(verify_pdf(&document, &untrusted), Err(CertError::Chain(_))), "{}", name);
+
+        let position = document.windows(5).position(|w| w == b"frank").unwrap();
+        let mut tampered = document.clone();
+        tampered[position] = b'F';
+        assert!(matches!(verify_pdf(&tampered, &trust), Err(CertError::InvalidSignature)), "{}", name);
+
+        let mut extended = document.clone();
+        extended.extend_from_slice(b"\n% appended\n");
+        assert!(verify_pdf(&extended, &trust).is_err(), "{}", name);
+    }
+}
+
+#[test]
+fn store_signs_with_cms_signer() {
+    let dir = common::temp_dir("store-cms");
+    let trust = load_certificates(&fixture("ca.pem")).unwrap();
+    let (_, cms) = cms_signers().remove(1);
+    let mut store = CertificateStore::open(&dir).unwrap();
+    store.set_cms_signer(cms);
+    let cert = CertificateSigner::generate().unwrap().sign(body("SN-STORE-CMS", "grace")).unwrap();
+    store.insert(&cert).unwrap();
+
+    let json = fs::read(store.json_path(&cert.body.id).unwrap()).unwrap();
+    let signature = fs::read(store.cms_signature_path(&cert.body.id).unwrap()).unwrap();
+    verify_detached(&signature, &json, &trust).unwrap();
+    let pdf = fs::read(store.pdf_path(&cert.body.id).unwrap()).unwrap();
+    verify_pdf(&pdf, &trust).unwrap();
+}
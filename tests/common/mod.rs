@@ -0,0 +1,11 @@
+// Helpers shared by the integration tests
+
+use std::{fs, path::PathBuf};
+
+// Fresh directory for one test, removed again when it is recreated
+pub fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("secure-erase-{}-{}", std::process::id(), name));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
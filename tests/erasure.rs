@@ -0,0 +1,580 @@
+mod common;
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicBool, Ordering},
+    },
+    time::{Duration, Instant},
+};
+
+use secure_erase::{
+    audit::{AuditLog, verify_log},
+    cert::{CertificateResult, CertificateSigner, DeviceRecord, store::{CertificateQuery, CertificateStore}},
+    erasure::{
+        EraseError, EraseProgress, EraseRequest,
+        batch::{BatchConfig, BatchDevice, BatchEvent, BatchServices, JobOutcome, start_batch},
+        checkpoint::CheckpointStore,
+        engine::{IoBackend, IoConfig, IoOp, benchmark},
+        erase_device,
+        errormap::{ErrorMap, ErrorPolicy, LbaRange},
+        overwrite::{CHUNK_SIZE, OverwriteOptions, OverwriteStandard, Pattern, PatternGenerator, Seed, overwrite_device},
+        throttle::{IoLimits, PauseWindow, Throttle, ThrottleSettings, parse_windows},
+    },
+    libs::StorageDriver,
+    platform::{DeviceIdentity, WipeMethod},
+    safety::{DeviceUsage, UsageReason},
+    verify::{VerifyMode, verify_pattern},
+};
+
+const SIZE: usize = 3 * 1024 * 1024 + 777;
+
+// Image file standing in for a disk, filled with non-zero data
+fn disk_image(dir: &Path, name: &str, size: usize) -> PathBuf {
+    let path = dir.join(name);
+    let data: Vec<u8> = (0..size).map(|i| (i % 251) as u8 | 1).collect();
+    fs::write(&path, data).unwrap();
+    path
+}
+
+fn image_record(path: &Path, serial: &str) -> DeviceRecord {
+    DeviceRecord {
+        path: path.display().to_string(),
+        model: Some("Test Disk".to_string()),
+        serial_number: Some(serial.to_string()),
+        vendor: None,
+        size: Some(SIZE as u64),
+        geometry: None,
+        firmware: None,
+        wwn: None,
+        transport: None,
+        by_id: None,
+        by_path: None,
+        device_type: "Image".to_string(),
+    }
+}
+
+#[test]
+fn every_standard_leaves_its_final_pattern() {
+    let dir = common::temp_dir("standards");
+    for standard in OverwriteStandard::get_all_standards() {
+        let image = disk_image(&dir, "disk.img", SIZE);
+        let cancel = AtomicBool::new(false);
+        let options = OverwriteOptions::default();
+        let report = overwrite_device(&image, standard, &options, None, &mut |_| {}, &cancel).unwrap();
+
+        assert_eq!(report.passes.len(), standard.passes().len());
+        assert_eq!(report.bytes_written, (SIZE * report.passes.len()) as u64);
+        let generator = report.final_pattern().unwrap();
+        let mut expected = vec![0u8; SIZE];
+        generator.fill(0, &mut expected);
+        assert_eq!(fs::read(&image).unwrap(), expected, "{}", standard);
+    }
+}
+
+#[test]
+fn io_backends_write_and_read_back_the_same_data() {
+    let dir = common::temp_dir("io-backends");
+    for backend in [IoBackend::Threads, IoBackend::Auto] {
+        let image = disk_image(&dir, "disk.img", SIZE);
+        let cancel = AtomicBool::new(false);
+        // Small blocks, so several requests are in flight and complete out of order
+        let io = IoConfig { backend, queue_depth: 3, block_size: 64 * 1024, ..Default::default() };
+        let options = OverwriteOptions { io: io.clone(), ..Default::default() };
+        let mut offsets = Vec::new();
+        let report = overwrite_device(
+            &image,
+            OverwriteStandard::Random,
+            &options,
+            None,
+            &mut |update| offsets.push(update.bytes_done),
+            &cancel,
+        )
+        .unwrap();
+
+        // Progress is reported in order, ending with the odd-sized tail
+        assert!(offsets.windows(2).all(|pair| pair[0] < pair[1]), "{}", backend);
+        assert_eq!(offsets.last(), Some(&(SIZE as u64)));
+        let generator = report.final_pattern().unwrap();
+        let mut expected = vec![0u8; SIZE];
+        generator.fill(0, &mut expected);
+        assert_eq!(fs::read(&image).unwrap(), expected, "{}", backend);
+
+        let verified = verify_pattern(&image, &generator, SIZE as u64, VerifyMode::Full, &io, &mut |_| {}, &cancel);
+        assert!(verified.unwrap().passed, "{}", backend);
+    }
+
+    let image = disk_image(&dir, "bench.img", SIZE);
+    let io = IoConfig { backend: IoBackend::Threads, queue_depth: 2, block_size: 1024 * 1024, ..Default::default() };
+    let report = benchmark(&image, IoOp::Read, u64::MAX, &io, None, &AtomicBool::new(false)).unwrap();
+    assert_eq!(report.backend, IoBackend::Threads);
+    assert_eq!(report.bytes, SIZE as u64);
+    assert!(report.throughput > 0.0);
+}
+
+#[test]
+fn erase_device_verifies_the_result() {
+    let dir = common::temp_dir("erase-verify");
+    let image = disk_image(&dir, "disk.img", SIZE);
+    let request = EraseRequest {
+        path: image.display().to_string(),
+        method: WipeMethod::Overwrite,
+        standard: OverwriteStandard::Random,
+        verify: VerifyMode::Full,
+        error_policy: ErrorPolicy::Fail,
+        expected: Some(DeviceIdentity { size: Some(SIZE as u64), ..Default::default() }),
+        allow_system_disk: false,
+        checkpoint_dir: None,
+        resume: None,
+        io: IoConfig::default(),
+    };
+
+    let report = erase_device(&request, &mut |_| {}, &AtomicBool::new(false)).unwrap();
+    let verification = report.verification.as_ref().unwrap();
+    assert!(report.passed());
+    assert_eq!(verification.bytes_verified, SIZE as u64);
+    assert_eq!(verification.mismatched_sectors, 0);
+}
+
+#[test]
+fn erase_refuses_a_device_whose_identity_changed() {
+    let dir = common::temp_dir("identity");
+    let image = disk_image(&dir, "disk.img", SIZE);
+    let before = fs::read(&image).unwrap();
+    let request = EraseRequest {
+        path: image.display().to_string(),
+        method: WipeMethod::Overwrite,
+        standard: OverwriteStandard::Zero,
+        verify: VerifyMode::None,
+        error_policy: ErrorPolicy::Fail,
+        // As if another, larger drive had been enumerated at the same path
+        expected: Some(DeviceIdentity { size: Some(2 * SIZE as u64), ..Default::default() }),
+        allow_system_disk: false,
+        checkpoint_dir: None,
+        resume: None,
+        io: IoConfig::default(),
+    };
+
+    let result = erase_device(&request, &mut |_| {}, &AtomicBool::new(false));
+    assert!(matches!(result, Err(EraseError::IdentityChanged(_))));
+    assert_eq!(fs::read(&image).unwrap(), before, "device was written to");
+
+    // The overwrite checks again on the device it opened
+    let options = OverwriteOptions { expected: request.expected.clone(), ..Default::default() };
+    let result = overwrite_device(&image, OverwriteStandard::Zero, &options, None, &mut |_| {}, &AtomicBool::new(false));
+    assert!(matches!(result, Err(EraseError::IdentityChanged(_))));
+    assert_eq!(fs::read(&image).unwrap(), before, "device was written to");
+}
+
+#[test]
+fn interrupted_overwrite_resumes_from_its_checkpoint() {
+    let dir = common::temp_dir("resume");
+    // Larger than one write, so the pass can stop halfway
+    let size = 2 * CHUNK_SIZE + 777;
+    let image = disk_image(&dir, "disk.img", size);
+    let other = disk_image(&dir, "other.img", size);
+    let store = CheckpointStore::open(&dir.join("checkpoints")).unwrap();
+    let mut request = EraseRequest {
+        path: image.display().to_string(),
+        method: WipeMethod::Overwrite,
+        standard: OverwriteStandard::Dod3Pass,
+        verify: VerifyMode::Full,
+        error_policy: ErrorPolicy::Fail,
+        expected: None,
+        allow_system_disk: false,
+        checkpoint_dir: Some(dir.join("checkpoints")),
+        resume: None,
+        io: IoConfig::default(),
+    };
+
+    // Power cut after the first write of the second pass
+    let cancel = AtomicBool::new(false);
+    let mut stop = |p: EraseProgress| {
+        if p.pass == 2 && p.bytes_done == CHUNK_SIZE as u64 {
+            cancel.store(true, Ordering::Relaxed);
+        }
+    };
+    let interrupted = erase_device(&request, &mut stop, &cancel);
+    assert!(matches!(interrupted, Err(EraseError::Cancelled)));
+    let identity = DeviceIdentity { size: Some(size as u64), ..Default::default() };
+    let checkpoint = store.find(&request.path, &identity).unwrap();
+    let state = checkpoint.overwrite.clone().unwrap();
+    assert_eq!((state.pass, state.offset, state.seeds.len()), (1, CHUNK_SIZE as u64, 2));
+
+    // Only the drive the checkpoint was taken on may be resumed
+    let before = fs::read(&other).unwrap();
+    let elsewhere = EraseRequest {
+        path: other.display().to_string(),
+        resume: Some(checkpoint.clone()),
+        ..request.clone()
+    };
+    let refused = erase_device(&elsewhere, &mut |_| {}, &AtomicBool::new(false));
+    assert!(matches!(refused, Err(EraseError::IdentityChanged(_))));
+    assert_eq!(fs::read(&other).unwrap(), before, "another device was written to");
+
+    request.resume = Some(checkpoint);
+    let mut updates: Vec<EraseProgress> = Vec::new();
+    let report = erase_device(&request, &mut |p| updates.push(p), &AtomicBool::new(false)).unwrap();
+    assert_eq!((updates[0].pass, updates[0].bytes_done), (2, 2 * CHUNK_SIZE as u64));
+    assert!(report.passed());
+    assert_eq!(report.verification.as_ref().unwrap().bytes_verified, size as u64);
+    let overwrite = report.overwrite.as_ref().unwrap();
+    assert_eq!(overwrite.passes[0].seed, state.seeds[0]);
+    assert_eq!(overwrite.passes[1].seed, state.seeds[1]);
+    assert_eq!(report.resumes.len(), 1);
+    assert_eq!((report.resumes[0].pass, report.resumes[0].offset), (2, CHUNK_SIZE as u64));
+    // A finished job leaves nothing to resume
+    assert!(store.list().is_empty());
+}
+
+#[test]
+fn verification_finds_leftover_data() {
+    let dir = common::temp_dir("leftover");
+    let image = disk_image(&dir, "disk.img", SIZE);
+    let zeros = PatternGenerator::new(Pattern::Fixed(vec![0]), Seed::default());
+
+    let cancel = AtomicBool::new(false);
+    let io = IoConfig::default();
+    let full = verify_pattern(&image, &zeros, SIZE as u64, VerifyMode::Full, &io, &mut |_| {}, &cancel).unwrap();
+    assert!(!full.passed);
+    assert_eq!(full.first_mismatch, Some(0));
+
+    let sampled =
+        verify_pattern(&image, &zeros, SIZE as u64, VerifyMode::Sampled(10), &io, &mut |_| {}, &cancel).unwrap();
+    assert!(!sampled.passed);
+    assert!(sampled.bytes_verified < SIZE as u64);
+
+    let empty = disk_image(&dir, "empty.img", 0);
+    let sampled = verify_pattern(&empty, &zeros, 0, VerifyMode::Sampled(10), &io, &mut |_| {}, &cancel);
+    assert!(matches!(sampled, Err(EraseError::InvalidTarget(_))));
+}
+
+#[test]
+fn random_passes_regenerate_from_their_seed() {
+    // RFC 8439 A.1 test vector 1: all-zero key, nonce and counter
+    let zero_key = PatternGenerator::new(Pattern::Random, Seed::default());
+    let mut block = [0u8; 16];
+    zero_key.fill(0, &mut block);
+    assert_eq!(hex::encode(block), "76b8e0ada0f13d90405d6ae55386bd28");
+
+    let seed = Seed::random().unwrap();
+    let generator = PatternGenerator::new(Pattern::Random, seed);
+    // Spans several parallel pieces and the nonce change at 1 GiB
+    let start = (1u64 << 30) - 3 * 256 * 1024 - 5;
+    let mut whole = vec![0u8; 1024 * 1024 + 77];
+    generator.fill(start, &mut whole);
+
+    let mut pieces = vec![0xaau8; whole.len()];
+    for (index, piece) in pieces.chunks_mut(4099).enumerate() {
+        generator.fill(start + (index * 4099) as u64, piece);
+    }
+    assert_eq!(pieces, whole);
+
+    let other = PatternGenerator::new(Pattern::Random, Seed::random().unwrap());
+    let mut different = vec![0u8; 4096];
+    other.fill(start, &mut different);
+    assert_ne!(different[..], whole[..4096]);
+
+    // Recorded seeds survive a checkpoint or report round trip
+    let json = serde_json::to_string(&seed).unwrap();
+    assert_eq!(serde_json::from_str::<Seed>(&json).unwrap(), seed);
+}
+
+#[test]
+fn error_map_merges_failed_sectors() {
+    let mut errors = ErrorMap::new(4096);
+    errors.add(4096 * 10, 4096);
+    errors.add(4096 * 12, 4096 * 2);
+    assert_eq!(errors.ranges, vec![LbaRange { start: 10, count: 1 }, LbaRange { start: 12, count: 2 }]);
+
+    // A write that fails part of a sector marks the whole sector, and
+    // ranges that touch are merged
+    errors.add(4096 * 11 + 100, 10);
+    assert_eq!(errors.ranges, vec![LbaRange { start: 10, count: 4 }]);
+    assert_eq!(errors.to_string(), "10-13");
+
+    // Unreadable 512-byte sectors, counted in the 4096-byte sectors above
+    let mut unreadable = ErrorMap::new(512);
+    unreadable.add(512 * 1000, 512);
+    let union = errors.union(&unreadable);
+    assert_eq!(union.sectors(), 5);
+    assert_eq!(union.to_string(), "10-13, 125");
+
+    assert!(!ErrorPolicy::Fail.accepts(1));
+    assert!(ErrorPolicy::Fail.accepts(0));
+    assert!(ErrorPolicy::Tolerate(5).accepts(union.sectors()));
+    assert!(!ErrorPolicy::Tolerate(4).accepts(union.sectors()));
+}
+
+#[test]
+fn cancelled_and_firmware_erases_of_images_fail() {
+    let dir = common::temp_dir("cancel");
+    let image = disk_image(&dir, "disk.img", SIZE);
+    let mut request = EraseRequest {
+        path: image.display().to_string(),
+        method: WipeMethod::Overwrite,
+        standard: OverwriteStandard::Zero,
+        verify: VerifyMode::None,
+        error_policy: ErrorPolicy::Fail,
+        expected: None,
+        allow_system_disk: false,
+        checkpoint_dir: None,
+        resume: None,
+        io: IoConfig::default(),
+    };
+
+    let cancelled = erase_device(&request, &mut |_| {}, &AtomicBool::new(true));
+    assert!(matches!(cancelled, Err(EraseError::Cancelled)));
+
+    // Firmware commands need a real drive
+    request.method = WipeMethod::CryptoErase;
+    let image_target = erase_device(&request, &mut |_| {}, &AtomicBool::new(false));
+    assert!(matches!(image_target, Err(EraseError::InvalidTarget(_))));
+}
+
+#[test]
+fn progress_totals_cover_every_pass_and_verification() {
+    let dir = common::temp_dir("progress");
+    let image = disk_image(&dir, "disk.img", SIZE);
+    let request = EraseRequest {
+        path: image.display().to_string(),
+        method: WipeMethod::Overwrite,
+        standard: OverwriteStandard::Dod3Pass,
+        verify: VerifyMode::Full,
+        error_policy: ErrorPolicy::Fail,
+        expected: None,
+        allow_system_disk: false,
+        checkpoint_dir: None,
+        resume: None,
+        io: IoConfig::default(),
+    };
+
+    let mut updates: Vec<EraseProgress> = Vec::new();
+    erase_device(&request, &mut |p| updates.push(p), &AtomicBool::new(false)).unwrap();
+
+    let last = updates.last().unwrap();
+    assert_eq!(last.bytes_written, 3 * SIZE as u64);
+    assert_eq!(last.bytes_verified, SIZE as u64);
+    assert_eq!(last.work_done, last.work_total);
+    assert!(updates.windows(2).all(|w| w[0].work_done <= w[1].work_done));
+    assert!(updates.iter().all(|p| p.work_total == 4 * SIZE as u64));
+}
+
+#[test]
+fn batch_issues_a_certificate_per_device() {
+    let dir = common::temp_dir("batch");
+    let devices: Vec<BatchDevice> = (0..3)
+        .map(|i| {
+            let image = disk_image(&dir, &format!("disk{}.img", i), SIZE);
+            BatchDevice { record: image_record(&image, &format!("SN{}", i)), controller: "hba0".to_string(), identity: None, resume: None }
+        })
+        .collect();
+
+    let store = Arc::new(Mutex::new(CertificateStore::open(&dir.join("store")).unwrap()));
+    let services = BatchServices {
+        signer: Some(Arc::new(CertificateSigner::generate().unwrap())),
+        store: Some(Arc::clone(&store)),
+        audit: Some(Arc::new(AuditLog::open(&dir.join("audit.log")).unwrap())),
+    };
+    let config = BatchConfig {
+        operator: "tester".to_string(),
+        method: WipeMethod::Overwrite,
+        standard: OverwriteStandard::Dod3Pass,
+        verify: VerifyMode::Full,
+        error_policy: ErrorPolicy::Fail,
+        max_jobs_per_controller: 1,
+        allow_system_disk: false,
+        report_dir: dir.join("reports"),
+        checkpoint_dir: None,
+        io: IoConfig::default(),
+    };
+
+    let handle = start_batch(devices, config, services);
+    let mut running = 0;
+    let mut most_running = 0;
+    let (summary, report) = loop {
+        match handle.recv().expect("batch ended without a summary") {
+            BatchEvent::JobStarted { .. } => {
+                running += 1;
+                most_running = most_running.max(running);
+            }
+            BatchEvent::JobFinished { .. } => running -= 1,
+            BatchEvent::Progress { .. } | BatchEvent::JobPaused { .. } | BatchEvent::JobResumed { .. } => {}
+            BatchEvent::BatchFinished { summary, report } => break (summary, report),
+        }
+    };
+
+    assert_eq!(most_running, 1, "controller limit was not respected");
+    assert_eq!(summary.count(JobOutcome::Passed), 3);
+    let report = report.unwrap();
+    assert!(report.exists());
+    assert!(report.with_extension("txt").exists());
+
+    let store = store.lock().unwrap();
+    for job in &summary.jobs {
+        let id = job.certificate_id.as_deref().unwrap();
+        let cert = store.get(id).unwrap();
+        cert.verify(None).unwrap();
+        assert_eq!(cert.body.result, CertificateResult::Passed);
+        assert!(cert.body.is_verified());
+        assert_eq!(cert.body.devices[0].serial_number, job.device.serial_number);
+    }
+    let query = CertificateQuery { serial: Some("SN1".to_string()), ..Default::default() };
+    assert_eq!(store.query(&query).len(), 1);
+
+    // started, finished, verification and certificate for each device
+    assert_eq!(verify_log(&dir.join("audit.log")).unwrap().entries, 12);
+}
+
+#[test]
+fn paused_jobs_hold_until_resumed() {
+    let dir = common::temp_dir("pause");
+    let image = disk_image(&dir, "disk.img", SIZE);
+    let device = BatchDevice { record: image_record(&image, "SN0"), controller: "hba0".to_string(), identity: None, resume: None };
+    let config = BatchConfig {
+        operator: "tester".to_string(),
+        method: WipeMethod::Overwrite,
+        standard: OverwriteStandard::Zero,
+        verify: VerifyMode::Full,
+        error_policy: ErrorPolicy::Fail,
+        max_jobs_per_controller: 1,
+        allow_system_disk: false,
+        report_dir: dir.join("reports"),
+        checkpoint_dir: None,
+        io: IoConfig::default(),
+    };
+    let services = BatchServices { signer: None, store: None, audit: None };
+
+    let handle = start_batch(vec![device], config, services);
+    let control = handle.control();
+    control.pause(0);
+    let mut paused = false;
+    let summary = loop {
+        match handle.recv().expect("batch ended without a summary") {
+            BatchEvent::JobPaused { job, .. } => {
+                paused = true;
+                control.resume(job);
+            }
+            BatchEvent::JobFinished { .. } => assert!(paused, "job finished without pausing"),
+            BatchEvent::BatchFinished { summary, .. } => break summary,
+            _ => {}
+        }
+    };
+    assert_eq!(summary.count(JobOutcome::Passed), 1);
+}
+
+#[test]
+fn pause_windows_and_rate_limits_apply_to_running_jobs() {
+    let window: PauseWindow = "22:30-06:00".parse().unwrap();
+    assert!(window.contains(23 * 60) && window.contains(0) && !window.contains(6 * 60));
+    assert_eq!(window.to_string(), "22:30-06:00");
+    assert!("08:00-08:00".parse::<PauseWindow>().is_err());
+    assert!(parse_windows("08:00-12:00, 13:00-18:00").unwrap().len() == 2);
+
+    let dir = common::temp_dir("throttle");
+    let image = disk_image(&dir, "disk.img", SIZE);
+    let device = BatchDevice::new(image_record(&image, "SN0"));
+    // Together the windows cover the whole day, so the job pauses at once
+    let all_day = parse_windows("00:00-12:00, 12:00-00:00").unwrap();
+    let io = IoConfig {
+        block_size: 256 * 1024,
+        throttle: Throttle::new(ThrottleSettings { pause_windows: all_day, ..Default::default() }),
+        ..Default::default()
+    };
+    let config = BatchConfig {
+        operator: "tester".to_string(),
+        method: WipeMethod::Overwrite,
+        standard: OverwriteStandard::Zero,
+        verify: VerifyMode::Full,
+        error_policy: ErrorPolicy::Fail,
+        max_jobs_per_controller: 1,
+        allow_system_disk: false,
+        report_dir: dir.join("reports"),
+        checkpoint_dir: None,
+        io,
+    };
+    let services = BatchServices { signer: None, store: None, audit: None };
+
+    let handle = start_batch(vec![device], config, services);
+    let control = handle.control();
+    let mut released = None;
+    let summary = loop {
+        match handle.recv().expect("batch ended without a summary") {
+            BatchEvent::JobPaused { window, .. } => {
+                assert!(window.is_some());
+                assert!(control.throttle().pause_windows.len() == 2);
+                // Lifting the windows releases the job, now held to 16 MiB/s
+                let limits = IoLimits { bytes_per_second: Some(16 << 20), ..Default::default() };
+                control.set_throttle(ThrottleSettings { limits, pause_windows: Vec::new() });
+                released = Some(Instant::now());
+            }
+            BatchEvent::BatchFinished { summary, .. } => break summary,
+            _ => {}
+        }
+    };
+    assert_eq!(summary.count(JobOutcome::Passed), 1);
+    // Written once and read back once
+    let minimum = Duration::from_secs_f64(2.0 * SIZE as f64 / (16 << 20) as f64 * 0.8);
+    assert!(released.expect("job never paused").elapsed() >= minimum);
+}
+
+#[test]
+fn system_disk_needs_an_override_but_in_use_disks_never_pass() {
+    let system = DeviceUsage {
+        reasons: vec![UsageReason::SystemDisk { mountpoint: "/boot".to_string() }],
+    };
+    assert!(system.blocks_erase(false));
+    assert!(!system.blocks_erase(true));
+
+    let mut mounted = system.clone();
+    mounted.reasons.push(UsageReason::Mounted {
+        device: "/dev/sda2".to_string(),
+        mountpoint: "/".to_string(),
+    });
+    assert!(mounted.blocks_erase(true));
+
+    // The disk carrying / is always also mounted there; only other mounts
+    // keep the override from applying
+    let root = DeviceUsage {
+        reasons: vec![
+            UsageReason::Mounted { device: "/dev/sda2".to_string(), mountpoint: "/".to_string() },
+            UsageReason::SystemDisk { mountpoint: "/".to_string() },
+        ],
+    };
+    assert!(root.blocks_erase(false));
+    assert!(!root.blocks_erase(true));
+
+    let mut home = root.clone();
+    home.reasons.push(UsageReason::Mounted {
+        device: "/dev/sda3".to_string(),
+        mountpoint: "/home".to_string(),
+    });
+    assert!(home.blocks_erase(true));
+
+    // Root on LVM inside LUKS, with swap in the same volume group
+    let device = |name: &str| name.to_string();
+    let lvm_root = DeviceUsage {
+        reasons: vec![
+            UsageReason::LuksMapping { device: device("/dev/sda3"), name: device("/dev/mapper/cryptroot") },
+            UsageReason::Holder { device: device("/dev/mapper/cryptroot"), holder: device("/dev/mapper/vg-root") },
+            UsageReason::Holder { device: device("/dev/mapper/cryptroot"), holder: device("/dev/mapper/vg-swap") },
+            UsageReason::Mounted { device: device("/dev/mapper/vg-root"), mountpoint: "/".to_string() },
+            UsageReason::Mounted { device: device("/dev/sda2"), mountpoint: "/boot".to_string() },
+            UsageReason::Swap { device: device("/dev/mapper/vg-swap") },
+            UsageReason::SystemDisk { mountpoint: "/".to_string() },
+            UsageReason::SystemDisk { mountpoint: "/boot".to_string() },
+        ],
+    };
+    assert!(lvm_root.blocks_erase(false));
+    assert!(!lvm_root.blocks_erase(true));
+
+    // The same stack on a disk that is not the system disk is in use
+    let mut data_disk = lvm_root.clone();
+    data_disk.reasons.retain(|r| !matches!(r, UsageReason::SystemDisk { .. } | UsageReason::Mounted { .. }));
+    assert!(data_disk.blocks_erase(true));
+
+    let image = disk_image(&common::temp_dir("usage"), "disk.img", SIZE);
+    assert!(!StorageDriver::device_usage(&image.display().to_string()).blocks_erase(false));
+}
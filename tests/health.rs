@@ -0,0 +1,129 @@
+use secure_erase::health::{
+    HealthSource, HealthWarning, parse_ata_smart, parse_log_page, parse_nvme_smart_log, parse_scsi_logs,
+};
+
+// SMART READ DATA / READ THRESHOLDS tables with the given entries
+fn smart_tables(entries: &[(u8, u8, u8, u64)]) -> ([u8; 512], [u8; 512]) {
+    let mut data = [0u8; 512];
+    let mut thresholds = [0u8; 512];
+    for (index, &(id, current, threshold, raw)) in entries.iter().enumerate() {
+        let offset = 2 + index * 12;
+        data[offset] = id;
+        data[offset + 3] = current;
+        data[offset + 4] = current;
+        data[offset + 5..offset + 11].copy_from_slice(&raw.to_le_bytes()[..6]);
+        thresholds[offset] = id;
+        thresholds[offset + 1] = threshold;
+    }
+    (data, thresholds)
+}
+
+#[test]
+fn ata_smart_attributes_are_decoded() {
+    let (data, thresholds) = smart_tables(&[
+        (5, 100, 10, 12),
+        (9, 95, 0, 0x0012_0000_2f1c),
+        (194, 64, 0, 0x0028_0014_0024),
+        (197, 100, 0, 3),
+        (177, 93, 5, 70),
+    ]);
+    let report = parse_ata_smart(&data, &thresholds, Some(true));
+
+    assert_eq!(report.source, HealthSource::AtaSmart);
+    assert_eq!(report.attributes.len(), 5);
+    assert_eq!(report.reallocated_sectors, Some(12));
+    assert_eq!(report.pending_sectors, Some(3));
+    assert_eq!(report.power_on_hours, Some(0x2f1c));
+    assert_eq!(report.temperature_celsius, Some(0x24));
+    assert_eq!(report.percent_used, Some(7));
+    // Remapped sectors keep their old data out of reach of an overwrite
+    assert!(report.has_remapped_sectors());
+    assert_eq!(report.warnings(), vec![HealthWarning::Reallocated(12), HealthWarning::Pending(3)]);
+}
+
+#[test]
+fn failing_ata_attributes_are_flagged() {
+    let (data, thresholds) = smart_tables(&[(5, 100, 10, 0), (1, 6, 6, 0)]);
+    let report = parse_ata_smart(&data, &thresholds, Some(false));
+
+    assert!(!report.has_remapped_sectors());
+    assert_eq!(
+        report.warnings(),
+        vec![
+            HealthWarning::FailurePredicted,
+            HealthWarning::AttributeFailing { id: 1, name: "Raw Read Error Rate".to_string() },
+        ]
+    );
+}
+
+#[test]
+fn nvme_smart_log_is_decoded() {
+    let mut log = vec![0u8; 512];
+    log[1..3].copy_from_slice(&310u16.to_le_bytes());
+    log[3] = 100;
+    log[5] = 4;
+    log[128..136].copy_from_slice(&1234u64.to_le_bytes());
+    log[160..168].copy_from_slice(&2u64.to_le_bytes());
+    let report = parse_nvme_smart_log(&log).unwrap();
+
+    assert_eq!(report.passed, Some(true));
+    assert_eq!(report.temperature_celsius, Some(37));
+    assert_eq!(report.percent_used, Some(4));
+    assert_eq!(report.power_on_hours, Some(1234));
+    assert_eq!(report.media_errors, Some(2));
+    assert!(!report.has_remapped_sectors());
+
+    // Spare below threshold, with retired blocks
+    log[0] = 0x01;
+    log[3] = 8;
+    let report = parse_nvme_smart_log(&log).unwrap();
+    assert_eq!(report.passed, Some(false));
+    assert!(report.has_remapped_sectors());
+    assert!(report.warnings().contains(&HealthWarning::SpareUsed(8)));
+
+    assert!(parse_nvme_smart_log(&log[..64]).is_none());
+}
+
+// A LOG SENSE response with (code, value) parameters
+fn log_page(page: u8, parameters: &[(u16, &[u8])]) -> Vec<u8> {
+    let mut body = Vec::new();
+    for (code, value) in parameters {
+        body.extend_from_slice(&code.to_be_bytes());
+        body.push(0x03);
+        body.push(value.len() as u8);
+        body.extend_from_slice(value);
+    }
+    let mut data = vec![page, 0];
+    data.extend_from_slice(&(body.len() as u16).to_be_bytes());
+    data.extend(body);
+    data
+}
+
+#[test]
+fn scsi_log_pages_are_decoded() {
+    let pages = [
+        log_page(0x03, &[(0x0000, &[0, 0, 0, 9]), (0x0006, &[0, 5])]),
+        log_page(0x0d, &[(0x0000, &[0, 41]), (0x0001, &[0, 60])]),
+        log_page(0x11, &[(0x0001, &[0, 0, 0, 12])]),
+        log_page(0x15, &[(0x0000, &[0, 0, 0x0e, 0x10, 0, 0, 0, 0])]),
+        log_page(0x2f, &[(0x0000, &[0, 0, 38])]),
+    ];
+    let (code, parameters) = parse_log_page(&pages[0]).unwrap();
+    assert_eq!(code, 0x03);
+    assert_eq!(parameters.len(), 2);
+
+    let report = parse_scsi_logs(&pages, Some(4));
+    assert_eq!(report.source, HealthSource::ScsiLogPages);
+    assert_eq!(report.passed, Some(true));
+    assert_eq!(report.media_errors, Some(5));
+    assert_eq!(report.temperature_celsius, Some(41));
+    assert_eq!(report.percent_used, Some(12));
+    assert_eq!(report.power_on_hours, Some(60));
+    assert_eq!(report.reallocated_sectors, Some(4));
+    assert!(report.has_remapped_sectors());
+
+    // Pages that were not returned are not guessed at
+    let report = parse_scsi_logs(&[], None);
+    assert_eq!(report.passed, None);
+    assert!(report.warnings().is_empty());
+}
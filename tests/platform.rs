@@ -0,0 +1,109 @@
+#![cfg(target_os = "linux")]
+
+use std::time::{Duration, Instant};
+
+use secure_erase::platform::{
+    BlockGeometry, DeviceAction, DeviceEvent, SectorFormat, Transport,
+    linux::{UeventMonitor, parse_uevent, transport_from_sys_path},
+};
+
+fn uevent(header: &str, fields: &[&str]) -> Vec<u8> {
+    let mut message = header.as_bytes().to_vec();
+    for field in fields {
+        message.push(0);
+        message.extend_from_slice(field.as_bytes());
+    }
+    message.push(0);
+    message
+}
+
+#[test]
+fn block_uevents_are_parsed() {
+    let added = uevent(
+        "add@/devices/pci0000:00/0000:00:17.0/ata3/host2/target2:0:0/2:0:0:0/block/sdb",
+        &[
+            "ACTION=add",
+            "DEVPATH=/devices/pci0000:00/0000:00:17.0/ata3/host2/target2:0:0/2:0:0:0/block/sdb",
+            "SUBSYSTEM=block",
+            "MAJOR=8",
+            "MINOR=16",
+            "DEVNAME=sdb",
+            "DEVTYPE=disk",
+            "SEQNUM=4711",
+        ],
+    );
+    assert_eq!(
+        parse_uevent(&added),
+        Some(DeviceEvent { action: DeviceAction::Added, path: "/dev/sdb".to_string(), partition: false })
+    );
+
+    let removed = uevent(
+        "remove@/devices/.../nvme0n1/nvme0n1p2",
+        &["ACTION=remove", "SUBSYSTEM=block", "DEVNAME=nvme0n1p2", "DEVTYPE=partition"],
+    );
+    let event = parse_uevent(&removed).unwrap();
+    assert_eq!((event.action, event.path.as_str(), event.partition), (DeviceAction::Removed, "/dev/nvme0n1p2", true));
+
+    // Other subsystems and actions are not device list changes
+    let usb = uevent("add@/devices/usb1/1-1", &["ACTION=add", "SUBSYSTEM=usb", "DEVNAME=bus/usb/001/002"]);
+    assert_eq!(parse_uevent(&usb), None);
+    let bind = uevent("bind@/devices/.../block/sdb", &["ACTION=bind", "SUBSYSTEM=block", "DEVNAME=sdb"]);
+    assert_eq!(parse_uevent(&bind), None);
+    assert_eq!(parse_uevent(b"libudev\0garbage"), None);
+}
+
+#[test]
+fn uevent_monitor_returns_when_the_timeout_passes() {
+    // Sandboxes without netlink access cannot run this part
+    let Ok(monitor) = UeventMonitor::open() else {
+        return;
+    };
+    let started = Instant::now();
+    // Whatever arrives meanwhile, the wait is bounded
+    monitor.next_event(Some(Duration::from_millis(20))).unwrap();
+    assert!(started.elapsed() < Duration::from_secs(5));
+}
+
+#[test]
+fn block_geometry_sizes_writes_to_whole_blocks() {
+    let native = BlockGeometry::default();
+    assert_eq!(native.format(), SectorFormat::Native512);
+    assert_eq!(native.write_size(4 * 1024 * 1024), 4 * 1024 * 1024);
+
+    let emulated = BlockGeometry { physical_block_size: 4096, ..Default::default() };
+    assert_eq!(emulated.format().to_string(), "512e");
+    // Never below one physical block, however small the request
+    assert_eq!(emulated.write_size(1000), 4096);
+
+    // A 3-disk RAID 5 stripe of 128 KiB chunks
+    let striped = BlockGeometry {
+        logical_block_size: 4096,
+        physical_block_size: 4096,
+        optimal_io_size: 3 * 128 * 1024,
+        ..Default::default()
+    };
+    assert_eq!(striped.format(), SectorFormat::Native4K);
+    assert_eq!(striped.write_size(4 * 1024 * 1024), 10 * 3 * 128 * 1024);
+}
+
+#[test]
+fn transport_is_read_from_the_sysfs_path() {
+    let cases = [
+        ("/sys/devices/pci0000:00/0000:00:17.0/ata3/host2/target2:0:0/2:0:0:0/block/sda", Transport::Sata),
+        ("/sys/devices/pci0000:00/0000:00:1d.0/0000:3d:00.0/nvme/nvme0/nvme0n1", Transport::NVMe),
+        (
+            "/sys/devices/pci0000:00/0000:00:14.0/usb2/2-1/2-1:1.0/host4/target4:0:0/4:0:0:0/block/sdb",
+            Transport::Usb,
+        ),
+        (
+            "/sys/devices/pci0000:00/0000:00:03.0/0000:02:00.0/host0/port-0:0/end_device-0:0/target0:0:0/0:0:0:0/block/sdc",
+            Transport::Sas,
+        ),
+        ("/sys/devices/platform/soc/fe340000.mmc/mmc_host/mmc0/mmc0:aaaa/block/mmcblk0", Transport::Mmc),
+        ("/sys/devices/pci0000:00/0000:00:04.0/virtio1/block/vda", Transport::Virtio),
+        ("/sys/devices/virtual/block/zram0", Transport::Unknown),
+    ];
+    for (path, expected) in cases {
+        assert_eq!(transport_from_sys_path(path), expected, "{}", path);
+    }
+}